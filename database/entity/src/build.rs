@@ -12,6 +12,15 @@ pub struct Model {
     pub commit_sha: String,
     pub status: String,
     pub created_at: DateTime,
+    pub pull_request: Option<i32>,
+    pub triggered_by: Option<String>,
+    pub completed_at: Option<DateTime>,
+    pub check_run_status: Option<String>,
+    pub checks_finished_at: Option<DateTime>,
+    pub deployment_id: Option<i64>,
+    pub auto_retries: i32,
+    pub selected_workflows: Option<String>,
+    pub deployment_status: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
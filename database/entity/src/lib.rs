@@ -2,6 +2,13 @@
 
 pub mod prelude;
 
+pub mod approval;
+pub mod audit_log;
 pub mod build;
+pub mod confirmation;
+pub mod delegation;
+pub mod failed_event;
+pub mod merge_group;
 pub mod pull_request;
+pub mod repository;
 pub mod workflow;
@@ -1,5 +1,12 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.11.3
 
+pub use super::approval::Entity as Approval;
+pub use super::audit_log::Entity as AuditLog;
 pub use super::build::Entity as Build;
+pub use super::confirmation::Entity as Confirmation;
+pub use super::delegation::Entity as Delegation;
+pub use super::failed_event::Entity as FailedEvent;
+pub use super::merge_group::Entity as MergeGroup;
 pub use super::pull_request::Entity as PullRequest;
+pub use super::repository::Entity as Repository;
 pub use super::workflow::Entity as Workflow;
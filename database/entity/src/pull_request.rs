@@ -11,10 +11,35 @@ pub struct Model {
     pub number: i32,
     pub try_build: Option<i32>,
     pub created_at: DateTime,
+    pub base_branch: Option<String>,
+    pub approved_sha: Option<String>,
+    pub auto_build: Option<i32>,
+    pub priority: i32,
+    pub rollup: Option<String>,
+    pub title: Option<String>,
+    pub approved_at: Option<DateTime>,
+    pub parked: bool,
+    pub tree_closed_notified: bool,
+    pub consecutive_failures: i32,
+    pub consecutive_auto_failures: i32,
+    pub not_before: Option<DateTime>,
+    pub rebase_notified_sha: Option<String>,
+    pub rollup_members: Option<String>,
+    pub merged_at: Option<DateTime>,
+    pub nag_interval: Option<i32>,
+    pub last_nag_at: Option<DateTime>,
+    pub muted: bool,
+    pub author: Option<String>,
+    pub status_comment_id: Option<i64>,
+    pub auto_build_started_at: Option<DateTime>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::approval::Entity")]
+    Approval,
+    #[sea_orm(has_many = "super::audit_log::Entity")]
+    AuditLog,
     #[sea_orm(
         belongs_to = "super::build::Entity",
         from = "Column::TryBuild",
@@ -23,6 +48,20 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Build,
+    #[sea_orm(has_many = "super::delegation::Entity")]
+    Delegation,
+}
+
+impl Related<super::approval::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Approval.def()
+    }
+}
+
+impl Related<super::audit_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditLog.def()
+    }
 }
 
 impl Related<super::build::Entity> for Entity {
@@ -31,4 +70,10 @@ impl Related<super::build::Entity> for Entity {
     }
 }
 
+impl Related<super::delegation::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Delegation.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -14,6 +14,7 @@ pub struct Model {
     pub status: String,
     pub r#type: String,
     pub created_at: DateTime,
+    pub workflow_id: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
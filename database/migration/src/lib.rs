@@ -3,6 +3,41 @@ pub use sea_orm_migration::prelude::*;
 mod m20230505_165859_create_build;
 mod m20230506_075859_create_pr;
 mod m20230506_102008_create_workflow;
+mod m20230521_091420_create_approval;
+mod m20230524_193015_add_pr_base_branch;
+mod m20230527_142310_create_delegation;
+mod m20230529_081145_add_build_history;
+mod m20230531_102314_add_merge_queue;
+mod m20230602_091532_add_queue_details;
+mod m20230602_094210_create_repository;
+mod m20230605_120914_add_pr_parked;
+mod m20230606_083127_add_pr_tree_closed_notified;
+mod m20230607_141205_add_pr_consecutive_failures;
+mod m20230608_103512_create_audit_log;
+mod m20230609_140822_add_pr_not_before;
+mod m20230610_091044_add_build_check_run_status;
+mod m20230611_083512_add_pr_rebase_notified_sha;
+mod m20230612_101433_add_pr_rollup_members;
+mod m20230613_152207_add_pr_nag;
+mod m20230614_093318_add_approval_author;
+mod m20230615_104127_add_pr_muted;
+mod m20230616_081945_add_pr_author;
+mod m20230617_090215_add_build_checks_finished_at;
+mod m20230618_101523_add_repository_tree_closed_details;
+mod m20230619_083411_add_build_deployment_id;
+mod m20230620_094512_create_failed_event;
+mod m20230621_102934_add_build_auto_retries;
+mod m20230622_091206_add_pr_status_comment_id;
+mod m20230623_084517_create_confirmation;
+mod m20230624_101847_add_repository_tree_reopen_at;
+mod m20230625_093012_add_workflow_workflow_id;
+mod m20230626_083154_add_build_selected_workflows;
+mod m20230627_091408_create_merge_group;
+mod m20230628_084233_add_pr_consecutive_auto_failures;
+mod m20230629_101529_add_build_deployment_status;
+mod m20230629_143052_add_approval_team;
+mod m20230630_082615_add_repository_suggested_rollup;
+mod m20230630_141907_add_pr_auto_build_started_at;
 
 pub struct Migrator;
 
@@ -13,6 +48,41 @@ impl MigratorTrait for Migrator {
             Box::new(m20230505_165859_create_build::Migration),
             Box::new(m20230506_075859_create_pr::Migration),
             Box::new(m20230506_102008_create_workflow::Migration),
+            Box::new(m20230521_091420_create_approval::Migration),
+            Box::new(m20230524_193015_add_pr_base_branch::Migration),
+            Box::new(m20230527_142310_create_delegation::Migration),
+            Box::new(m20230529_081145_add_build_history::Migration),
+            Box::new(m20230531_102314_add_merge_queue::Migration),
+            Box::new(m20230602_091532_add_queue_details::Migration),
+            Box::new(m20230602_094210_create_repository::Migration),
+            Box::new(m20230605_120914_add_pr_parked::Migration),
+            Box::new(m20230606_083127_add_pr_tree_closed_notified::Migration),
+            Box::new(m20230607_141205_add_pr_consecutive_failures::Migration),
+            Box::new(m20230608_103512_create_audit_log::Migration),
+            Box::new(m20230609_140822_add_pr_not_before::Migration),
+            Box::new(m20230610_091044_add_build_check_run_status::Migration),
+            Box::new(m20230611_083512_add_pr_rebase_notified_sha::Migration),
+            Box::new(m20230612_101433_add_pr_rollup_members::Migration),
+            Box::new(m20230613_152207_add_pr_nag::Migration),
+            Box::new(m20230614_093318_add_approval_author::Migration),
+            Box::new(m20230615_104127_add_pr_muted::Migration),
+            Box::new(m20230616_081945_add_pr_author::Migration),
+            Box::new(m20230617_090215_add_build_checks_finished_at::Migration),
+            Box::new(m20230618_101523_add_repository_tree_closed_details::Migration),
+            Box::new(m20230619_083411_add_build_deployment_id::Migration),
+            Box::new(m20230620_094512_create_failed_event::Migration),
+            Box::new(m20230621_102934_add_build_auto_retries::Migration),
+            Box::new(m20230622_091206_add_pr_status_comment_id::Migration),
+            Box::new(m20230623_084517_create_confirmation::Migration),
+            Box::new(m20230624_101847_add_repository_tree_reopen_at::Migration),
+            Box::new(m20230625_093012_add_workflow_workflow_id::Migration),
+            Box::new(m20230626_083154_add_build_selected_workflows::Migration),
+            Box::new(m20230627_091408_create_merge_group::Migration),
+            Box::new(m20230628_084233_add_pr_consecutive_auto_failures::Migration),
+            Box::new(m20230629_101529_add_build_deployment_status::Migration),
+            Box::new(m20230629_143052_add_approval_team::Migration),
+            Box::new(m20230630_082615_add_repository_suggested_rollup::Migration),
+            Box::new(m20230630_141907_add_pr_auto_build_started_at::Migration),
         ]
     }
 }
@@ -58,4 +58,13 @@ pub enum Build {
     CommitSha,
     Status,
     CreatedAt,
+    PullRequest,
+    TriggeredBy,
+    CompletedAt,
+    CheckRunStatus,
+    ChecksFinishedAt,
+    DeploymentId,
+    AutoRetries,
+    SelectedWorkflows,
+    DeploymentStatus,
 }
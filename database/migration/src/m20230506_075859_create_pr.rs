@@ -57,11 +57,32 @@ impl MigrationTrait for Migration {
 
 /// Learn more at https://docs.rs/sea-query#iden
 #[derive(Iden)]
-enum PullRequest {
+pub enum PullRequest {
     Table,
     Id,
     Repository,
     Number,
     TryBuild,
     CreatedAt,
+    BaseBranch,
+    ApprovedSha,
+    AutoBuild,
+    Priority,
+    Rollup,
+    Title,
+    ApprovedAt,
+    Parked,
+    TreeClosedNotified,
+    ConsecutiveFailures,
+    ConsecutiveAutoFailures,
+    NotBefore,
+    RebaseNotifiedSha,
+    RollupMembers,
+    MergedAt,
+    NagInterval,
+    LastNagAt,
+    Muted,
+    Author,
+    StatusCommentId,
+    AutoBuildStartedAt,
 }
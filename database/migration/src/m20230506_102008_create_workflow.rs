@@ -59,7 +59,8 @@ impl MigrationTrait for Migration {
 
 /// Learn more at https://docs.rs/sea-query#iden
 #[derive(Iden)]
-enum Workflow {
+#[allow(clippy::enum_variant_names)]
+pub enum Workflow {
     Table,
     Id,
     Build,
@@ -69,4 +70,5 @@ enum Workflow {
     Status,
     Type,
     CreatedAt,
+    WorkflowId,
 }
@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Approval::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Approval::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Approval::PullRequest).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-approval-pr")
+                            .from(Approval::Table, Approval::PullRequest)
+                            .to(PullRequest::Table, PullRequest::Id),
+                    )
+                    .col(ColumnDef::new(Approval::Approver).string().not_null())
+                    .col(ColumnDef::new(Approval::CommitSha).string().not_null())
+                    .col(
+                        ColumnDef::new(Approval::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .unique()
+                            .name("unique-approval-pr-approver")
+                            .col(Approval::PullRequest)
+                            .col(Approval::Approver),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Approval::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+pub enum Approval {
+    Table,
+    Id,
+    PullRequest,
+    Approver,
+    CommitSha,
+    CreatedAt,
+    Author,
+    Team,
+}
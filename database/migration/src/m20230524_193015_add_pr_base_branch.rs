@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::BaseBranch).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .drop_column(PullRequest::BaseBranch)
+                    .to_owned(),
+            )
+            .await
+    }
+}
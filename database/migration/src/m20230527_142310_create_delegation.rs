@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Delegation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Delegation::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Delegation::PullRequest).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-delegation-pr")
+                            .from(Delegation::Table, Delegation::PullRequest)
+                            .to(PullRequest::Table, PullRequest::Id),
+                    )
+                    .col(ColumnDef::new(Delegation::Delegatee).string().not_null())
+                    .col(ColumnDef::new(Delegation::Scope).string().not_null())
+                    .col(
+                        ColumnDef::new(Delegation::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .unique()
+                            .name("unique-delegation-pr-delegatee-scope")
+                            .col(Delegation::PullRequest)
+                            .col(Delegation::Delegatee)
+                            .col(Delegation::Scope),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Delegation::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Delegation {
+    Table,
+    Id,
+    PullRequest,
+    Delegatee,
+    Scope,
+    CreatedAt,
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230505_165859_create_build::Build;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// SQLite only supports adding a single column per `ALTER TABLE` statement.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Build::Table)
+                    .add_column(ColumnDef::new(Build::PullRequest).integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Build::Table)
+                    .add_column(ColumnDef::new(Build::TriggeredBy).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Build::Table)
+                    .add_column(ColumnDef::new(Build::CompletedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Build::PullRequest, Build::TriggeredBy, Build::CompletedAt] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Build::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
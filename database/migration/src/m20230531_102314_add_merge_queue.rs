@@ -0,0 +1,49 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// SQLite only supports adding a single column per `ALTER TABLE` statement, and it cannot add
+// foreign keys to existing tables, so `auto_build` is a plain column.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::ApprovedSha).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::AutoBuild).integer().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .drop_column(PullRequest::AutoBuild)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .drop_column(PullRequest::ApprovedSha)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,69 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// SQLite only supports adding a single column per `ALTER TABLE` statement.
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(
+                        ColumnDef::new(PullRequest::Priority)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::Rollup).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::Title).string().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PullRequest::Table)
+                    .add_column(ColumnDef::new(PullRequest::ApprovedAt).timestamp().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [
+            PullRequest::ApprovedAt,
+            PullRequest::Title,
+            PullRequest::Rollup,
+            PullRequest::Priority,
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(PullRequest::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+use crate::m20230506_075859_create_pr::PullRequest;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AuditLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuditLog::PullRequest).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-audit-log-pr")
+                            .from(AuditLog::Table, AuditLog::PullRequest)
+                            .to(PullRequest::Table, PullRequest::Id),
+                    )
+                    .col(ColumnDef::new(AuditLog::Author).string().not_null())
+                    .col(ColumnDef::new(AuditLog::Command).string().not_null())
+                    .col(
+                        ColumnDef::new(AuditLog::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum AuditLog {
+    Table,
+    Id,
+    PullRequest,
+    Author,
+    Command,
+    CreatedAt,
+}
@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230505_165859_create_build::Build;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Build::Table)
+                    .add_column(ColumnDef::new(Build::CheckRunStatus).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Build::Table)
+                    .drop_column(Build::CheckRunStatus)
+                    .to_owned(),
+            )
+            .await
+    }
+}
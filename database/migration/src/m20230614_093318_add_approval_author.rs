@@ -0,0 +1,31 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230521_091420_create_approval::Approval;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Approval::Table)
+                    .add_column(ColumnDef::new(Approval::Author).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Approval::Table)
+                    .drop_column(Approval::Author)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,51 @@
+use sea_orm_migration::prelude::*;
+
+use crate::m20230602_094210_create_repository::Repository;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Repository::Table)
+                    .add_column(
+                        ColumnDef::new(Repository::TreeClosedPriority)
+                            .integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Repository::Table)
+                    .add_column(ColumnDef::new(Repository::TreeClosedReason).string().null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Repository::Table)
+                    .drop_column(Repository::TreeClosedReason)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Repository::Table)
+                    .drop_column(Repository::TreeClosedPriority)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,53 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(FailedEvent::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FailedEvent::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(FailedEvent::Repository).string().not_null())
+                    .col(ColumnDef::new(FailedEvent::Event).text().not_null())
+                    .col(ColumnDef::new(FailedEvent::Error).text().not_null())
+                    .col(
+                        ColumnDef::new(FailedEvent::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FailedEvent::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum FailedEvent {
+    Table,
+    Id,
+    Repository,
+    Event,
+    Error,
+    CreatedAt,
+}
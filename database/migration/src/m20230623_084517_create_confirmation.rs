@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Confirmation::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Confirmation::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Confirmation::Repository).string().not_null())
+                    .col(
+                        ColumnDef::new(Confirmation::PrNumber)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Confirmation::Token).string().not_null())
+                    .col(ColumnDef::new(Confirmation::Action).string().not_null())
+                    .col(ColumnDef::new(Confirmation::Author).string().not_null())
+                    .col(
+                        ColumnDef::new(Confirmation::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Confirmation::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum Confirmation {
+    Table,
+    Id,
+    Repository,
+    PrNumber,
+    Token,
+    Action,
+    Author,
+    CreatedAt,
+}
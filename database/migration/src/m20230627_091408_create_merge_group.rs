@@ -0,0 +1,61 @@
+use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_query::types::Keyword;
+use sea_orm_migration::sea_query::SimpleExpr;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MergeGroup::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(MergeGroup::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(MergeGroup::Repository).string().not_null())
+                    .col(
+                        ColumnDef::new(MergeGroup::PrNumber)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(MergeGroup::Branch).string().not_null())
+                    .col(ColumnDef::new(MergeGroup::CommitSha).string().not_null())
+                    .col(ColumnDef::new(MergeGroup::Status).string().not_null())
+                    .col(
+                        ColumnDef::new(MergeGroup::CreatedAt)
+                            .timestamp()
+                            .default(SimpleExpr::Keyword(Keyword::CurrentTimestamp))
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(MergeGroup::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum MergeGroup {
+    Table,
+    Id,
+    Repository,
+    PrNumber,
+    Branch,
+    CommitSha,
+    Status,
+    CreatedAt,
+}
@@ -3,23 +3,28 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Context;
-use axum::routing::post;
+use axum::routing::{get, post};
 use axum::Router;
-use bors::bors::{BorsContext, CommandParser};
+use bors::bors::backoff::REFRESH_INTERVAL;
+use bors::bors::{BorsContext, CommandParser, ConcurrencyGuard};
 use clap::Parser;
 use sea_orm::Database;
+use tokio::signal::unix::SignalKind;
+use tokio::sync::oneshot;
 use tokio::task::LocalSet;
-use tower::limit::ConcurrencyLimitLayer;
+use tower::limit::GlobalConcurrencyLimitLayer;
 use tracing_subscriber::EnvFilter;
 
 use bors::bors::event::BorsEvent;
-use bors::database::SeaORMClient;
-use bors::github::server::{create_bors_process, github_webhook_handler, ServerState};
-use bors::github::{GithubAppState, WebhookSecret};
-use migration::{Migrator, MigratorTrait};
+use bors::database::{DbClient, SeaORMClient};
+use bors::github::server::{
+    create_bors_process, failed_events_handler, github_webhook_handler, metrics_handler,
+    queue_handler, rate_limit_handler, replay_failed_event_handler, ApiToken, ServerState,
+};
+use bors::github::{GithubAppState, GithubAuth, GithubRepoName, WebhookSecret};
 
-/// How often should the bot check DB state, e.g. for handling timeouts.
-const PERIODIC_REFRESH: Duration = Duration::from_secs(120);
+/// How long should the bot wait for the handling of already received events when shutting down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(clap::Parser)]
 struct Opts {
@@ -27,29 +32,72 @@ struct Opts {
     #[arg(long, env = "WEBHOOK_SECRET")]
     webhook_secret: String,
 
+    /// Token that authenticates requests to the endpoints of the server that are not webhooks, e.g.
+    /// the merge queue or the metrics, passed as a bearer token. The endpoints reject all requests
+    /// if it is not set.
+    #[arg(long, env = "API_TOKEN")]
+    api_token: Option<String>,
+
     /// Github App ID.
-    #[arg(long, env = "APP_ID")]
-    app_id: u64,
+    #[arg(long, env = "APP_ID", requires = "private_key")]
+    app_id: Option<u64>,
 
     /// Private key used to authenticate as a Github App.
-    #[arg(long, env = "PRIVATE_KEY")]
-    private_key: String,
+    #[arg(long, env = "PRIVATE_KEY", requires = "app_id")]
+    private_key: Option<String>,
+
+    /// Personal access token used to authenticate instead of a Github App.
+    #[arg(
+        long,
+        env = "GITHUB_TOKEN",
+        conflicts_with_all = ["app_id", "private_key"],
+        required_unless_present = "app_id"
+    )]
+    github_token: Option<String>,
+
+    /// Repositories managed with the personal access token, in the form `<owner>/<name>`.
+    #[arg(
+        long,
+        env = "REPOSITORIES",
+        value_delimiter = ',',
+        requires = "github_token"
+    )]
+    repositories: Vec<String>,
 
     /// Database connection string.
     #[arg(long, env = "DATABASE")]
     db: String,
 
+    /// Connection string of a read replica of the database, used by queries that serve reports.
+    #[arg(long, env = "DATABASE_REPLICA")]
+    db_replica: Option<String>,
+
     /// Prefix used for bot commands in PR comments.
     #[arg(long, env = "CMD_PREFIX", default_value = "@bors")]
     cmd_prefix: String,
+
+    /// Additional aliases of bot commands, in the form `<alias>=<command>`, e.g.
+    /// `please merge=r+`.
+    #[arg(long = "cmd-alias", env = "CMD_ALIASES", value_delimiter = ',')]
+    cmd_aliases: Vec<String>,
 }
 
-async fn server(state: ServerState) -> anyhow::Result<()> {
+async fn server(state: ServerState, guard: ConcurrencyGuard) -> anyhow::Result<()> {
     let state = Arc::new(state);
 
     let app = Router::new()
         .route("/github", post(github_webhook_handler))
-        .layer(ConcurrencyLimitLayer::new(100))
+        .route("/queue/:owner/:name", get(queue_handler))
+        .route("/failed-events/:owner/:name", get(failed_events_handler))
+        .route(
+            "/failed-events/:id/replay",
+            post(replay_failed_event_handler),
+        )
+        .route("/rate-limit", get(rate_limit_handler))
+        .route("/metrics", get(metrics_handler))
+        .layer(GlobalConcurrencyLimitLayer::with_semaphore(
+            guard.semaphore(),
+        ))
         .with_state(state);
     let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
 
@@ -59,10 +107,34 @@ async fn server(state: ServerState) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn initialize_db(connection_string: &str) -> anyhow::Result<SeaORMClient> {
-    let db = Database::connect(connection_string).await?;
-    Migrator::up(&db, None).await?;
-    Ok(SeaORMClient::new(db))
+async fn initialize_db(
+    connection_string: &str,
+    replica_connection_string: Option<&str>,
+) -> anyhow::Result<SeaORMClient> {
+    let mut db = SeaORMClient::new(Database::connect(connection_string).await?);
+    db.run_migrations()
+        .await
+        .context("Cannot migrate database schema")?;
+    let version = db.get_schema_version().await?;
+    tracing::info!("Database schema version: {}", version.unwrap_or_default());
+    if let Some(replica_connection_string) = replica_connection_string {
+        let replica = Database::connect(replica_connection_string)
+            .await
+            .context("Cannot connect to the read replica")?;
+        db = db.with_replica(replica);
+    }
+    Ok(db)
+}
+
+/// Resolves when the process receives SIGTERM or SIGINT.
+async fn shutdown_signal() -> anyhow::Result<()> {
+    let mut terminate = tokio::signal::unix::signal(SignalKind::terminate())
+        .context("Cannot install SIGTERM handler")?;
+    tokio::select! {
+        _ = terminate.recv() => {}
+        res = tokio::signal::ctrl_c() => res.context("Cannot listen for SIGINT")?,
+    }
+    Ok(())
 }
 
 fn try_main(opts: Opts) -> anyhow::Result<()> {
@@ -72,34 +144,97 @@ fn try_main(opts: Opts) -> anyhow::Result<()> {
         .context("Cannot build tokio runtime")?;
 
     let db = runtime
-        .block_on(initialize_db(&opts.db))
+        .block_on(initialize_db(&opts.db, opts.db_replica.as_deref()))
         .context("Cannot initialize database")?;
 
-    let state = runtime.block_on(GithubAppState::load(
-        opts.app_id.into(),
-        opts.private_key.into_bytes().into(),
-        db,
-    ))?;
-    let ctx = BorsContext::new(CommandParser::new(opts.cmd_prefix));
-    let (tx, gh_process) = create_bors_process(state, ctx);
+    // The server only uses the database for read-only endpoints
+    let server_db = db.read_only();
+    let auth = match (opts.github_token, opts.app_id, opts.private_key) {
+        (Some(token), _, _) => {
+            let repositories = opts
+                .repositories
+                .iter()
+                .map(|repository| match repository.split_once('/') {
+                    Some((owner, name)) => Ok(GithubRepoName::new(owner, name)),
+                    None => Err(anyhow::anyhow!(
+                        "Invalid repository `{repository}`, use `<owner>/<name>`"
+                    )),
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            if repositories.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "The repositories managed with the access token are required"
+                ));
+            }
+            GithubAuth::Token {
+                token: token.into(),
+                repositories,
+            }
+        }
+        (None, Some(app_id), Some(private_key)) => GithubAuth::App {
+            app_id: app_id.into(),
+            private_key: private_key.into_bytes().into(),
+        },
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Github App ID and private key are required"
+            ))
+        }
+    };
+    let state = runtime.block_on(GithubAppState::load(auth, db))?;
+    let rate_limits = state.rate_limits();
+    let mut parser = CommandParser::new(opts.cmd_prefix);
+    for alias in opts.cmd_aliases {
+        let Some((alias, command)) = alias.split_once('=') else {
+            return Err(anyhow::anyhow!(
+                "Invalid command alias `{alias}`, use `<alias>=<command>`"
+            ));
+        };
+        parser = parser.with_alias(alias, command)?;
+    }
+    let guard = ConcurrencyGuard::default();
+    let ctx = BorsContext::new(parser).with_concurrency_guard(guard.clone());
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let (tx, gh_process) = create_bors_process(state, ctx, async move {
+        // If the sender is dropped, the process is shutting down anyway
+        let _ = shutdown_rx.await;
+    });
 
     let refresh_tx = tx.clone();
     let refresh_process = async move {
+        refresh_tx.send(BorsEvent::Startup).await?;
         loop {
-            tokio::time::sleep(PERIODIC_REFRESH).await;
+            tokio::time::sleep(REFRESH_INTERVAL).await;
             refresh_tx.send(BorsEvent::Refresh).await?;
         }
     };
 
-    let state = ServerState::new(tx, WebhookSecret::new(opts.webhook_secret));
-    let server_process = server(state);
+    let state = ServerState::new(
+        tx,
+        WebhookSecret::new(opts.webhook_secret),
+        opts.api_token.map(ApiToken::new),
+        server_db,
+        rate_limits,
+    );
+    let server_process = server(state, guard);
 
     let fut = async move {
+        tokio::pin!(gh_process);
         tokio::select! {
-            () = gh_process => {
+            () = &mut gh_process => {
                 tracing::warn!("Github webhook process has ended");
                 Ok(())
             },
+            res = shutdown_signal() => {
+                tracing::info!("Received shutdown signal");
+                res?;
+                // Stop accepting new events and wait until the received ones are handled
+                let _ = shutdown_tx.send(());
+                if tokio::time::timeout(SHUTDOWN_TIMEOUT, gh_process).await.is_err() {
+                    tracing::warn!("Handling of received events has timed out");
+                }
+                Ok(())
+            }
             res = refresh_process => {
                 tracing::warn!("Refresh generator has ended");
                 res
@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+/// How often are the repositories refreshed, e.g. to handle timeouts.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Longest interval between refreshes of a repository whose refreshes keep failing.
+pub const MAX_REFRESH_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Decides when a repository is refreshed again after its refreshes have failed.
+///
+/// Each consecutive failed refresh doubles the interval, up to `max_interval`, so that e.g. an
+/// unavailable API is not hammered on every tick. A successful refresh resets the interval back to
+/// `base_interval`. Each repository has its own backoff, so that a failing repository does not
+/// delay the refreshes of the other ones.
+pub struct RefreshBackoff {
+    base_interval: Duration,
+    max_interval: Duration,
+    failures: u32,
+    /// Refreshes are skipped until this time, after a failed refresh.
+    retry_at: Option<DateTime<Utc>>,
+}
+
+impl RefreshBackoff {
+    pub fn new(base_interval: Duration, max_interval: Duration) -> Self {
+        Self {
+            base_interval,
+            max_interval: max_interval.max(base_interval),
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    /// How long to wait before the next refresh.
+    pub fn interval(&self) -> Duration {
+        self.interval_after(self.failures)
+    }
+
+    /// Should the repository be refreshed at `now`?
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.retry_at.is_none_or(|retry_at| now >= retry_at)
+    }
+
+    /// Records the result of a refresh at `now`, which changes the interval before the next one.
+    pub fn record(&mut self, success: bool, now: DateTime<Utc>) {
+        if success {
+            if self.failures > 0 {
+                tracing::info!(
+                    "Refresh succeeded after {} failure(s), resetting the interval to {:?}",
+                    self.failures,
+                    self.base_interval
+                );
+            }
+            self.failures = 0;
+            self.retry_at = None;
+        } else {
+            let previous = self.interval();
+            self.failures = self.failures.saturating_add(1);
+            let next = self.interval();
+            if next > previous {
+                tracing::warn!(
+                    "Refresh failed {} time(s) in a row, increasing the interval to {next:?}",
+                    self.failures
+                );
+            } else {
+                tracing::warn!(
+                    "Refresh failed {} time(s) in a row, the interval stays at {next:?}",
+                    self.failures
+                );
+            }
+            self.retry_at = chrono::Duration::from_std(next)
+                .ok()
+                .map(|interval| now + interval);
+        }
+    }
+
+    fn interval_after(&self, failures: u32) -> Duration {
+        self.base_interval
+            .saturating_mul(2u32.saturating_pow(failures))
+            .min(self.max_interval)
+    }
+}
+
+impl Default for RefreshBackoff {
+    fn default() -> Self {
+        Self::new(REFRESH_INTERVAL, MAX_REFRESH_INTERVAL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::bors::backoff::RefreshBackoff;
+
+    fn backoff() -> RefreshBackoff {
+        RefreshBackoff::new(Duration::from_secs(10), Duration::from_secs(60))
+    }
+
+    #[test]
+    fn interval_grows_with_failures() {
+        let mut backoff = backoff();
+        assert_eq!(backoff.interval(), Duration::from_secs(10));
+
+        let mut intervals = vec![];
+        for _ in 0..5 {
+            backoff.record(false, Utc::now());
+            intervals.push(backoff.interval().as_secs());
+        }
+        assert_eq!(intervals, vec![20, 40, 60, 60, 60]);
+    }
+
+    #[test]
+    fn interval_resets_on_success() {
+        let mut backoff = backoff();
+        backoff.record(false, Utc::now());
+        backoff.record(false, Utc::now());
+        backoff.record(true, Utc::now());
+        assert_eq!(backoff.interval(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn refresh_is_skipped_until_interval_elapses() {
+        let mut backoff = backoff();
+        let now = Utc::now();
+        assert!(backoff.is_due(now));
+
+        backoff.record(false, now);
+        assert!(!backoff.is_due(now + chrono::Duration::seconds(19)));
+        assert!(backoff.is_due(now + chrono::Duration::seconds(20)));
+
+        backoff.record(true, now);
+        assert!(backoff.is_due(now));
+    }
+}
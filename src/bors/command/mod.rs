@@ -1,17 +1,146 @@
 mod parser;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::database::{DelegationScope, RollupMode};
 use crate::github::CommitSha;
-pub use parser::{CommandParseError, CommandParser};
+pub use parser::{CommandParseError, CommandParser, FULL_SHA_LENGTH};
 
 /// Bors command specified by a user.
 #[derive(Debug, PartialEq)]
 pub enum BorsCommand {
     /// Ping the bot.
     Ping,
+    /// Approve a PR.
+    Approve {
+        /// Priority of the PR in the merge queue.
+        priority: Option<i32>,
+        /// Rollup mode of the PR.
+        rollup: Option<RollupMode>,
+        /// Team on whose behalf the PR is approved.
+        team: Option<String>,
+        /// How long the PR should wait before entering the active merge queue.
+        delay: Option<Duration>,
+    },
+    /// Approve a rollup PR, which batches the PRs listed in its description.
+    ApproveRollup,
     /// Perform a try build.
     Try {
         /// Parent commit which should be used as the merge base.
         parent: Option<CommitSha>,
+        /// Workflows that should be run by the build. If empty, all workflows are run.
+        workflows: Vec<String>,
+        /// Base the build on the latest commit of the base branch instead of the base commit
+        /// recorded by GitHub for the PR.
+        rebase: bool,
+    },
+    /// Describe what a try build would do, without starting it.
+    TryPlan {
+        /// Parent commit which would be used as the merge base.
+        parent: Option<CommitSha>,
+        /// Workflows that would be run by the build. If empty, all workflows would be run.
+        workflows: Vec<String>,
     },
     /// Cancel a try build.
     TryCancel,
+    /// Show the last builds of the PR.
+    History,
+    /// Retry a failed build.
+    Retry {
+        /// Failed workflow that should be run again. If not specified, the whole build is retried.
+        workflow: Option<String>,
+    },
+    /// Delegate permissions on the PR to a user.
+    Delegate {
+        /// Which permissions should be delegated.
+        scope: DelegationScope,
+        /// User that should receive the permissions. If not specified, the PR author is used.
+        delegatee: Option<String>,
+    },
+    /// Remove delegations of the PR author.
+    Undelegate {
+        /// Which delegation should be removed. If not specified, all delegations are removed.
+        scope: Option<DelegationScope>,
+    },
+    /// List the delegations of the PR.
+    Delegates,
+    /// Stop posting status comments to the PR.
+    Mute,
+    /// Post status comments to the PR again.
+    Unmute,
+    /// Close the tree of the repository, so that PRs are not merged.
+    TreeClosed {
+        /// PRs with at least this priority are still merged. If not specified, no PRs are merged.
+        priority: Option<i32>,
+        /// When is the tree opened again automatically. If not specified, it stays closed until
+        /// it is opened with `@bors treeopen`.
+        until: Option<DateTime<Utc>>,
+        /// Why the tree is closed.
+        reason: Option<String>,
+    },
+    /// Open the tree of the repository again.
+    TreeOpen,
+    /// Reconcile the state of the PR with GitHub.
+    Sync,
+    /// Show the events that were recently received for the repository.
+    Recent,
+    /// Show the permissions of the author of the command.
+    Whoami,
+    /// Explain what keeps the PR from being merged.
+    Why,
+    /// Delete the bors branches that are not used by any running build. Requires a confirmation.
+    Clean,
+    /// Confirm a destructive command, which was requested before.
+    Confirm {
+        /// Token from the reply of the bot to the destructive command.
+        token: String,
+    },
+    /// Change the merge queue priority of the PR.
+    SetPriority {
+        /// New priority of the PR. PRs with a higher priority are merged first.
+        priority: i32,
+    },
+    /// Remind the requested reviewers of the PR periodically, until it is approved.
+    Nag {
+        /// How often are the reviewers reminded.
+        interval: Duration,
+    },
+    /// Change the branch that bors merges the PR into.
+    SetBase {
+        /// Name of the new base branch.
+        branch: String,
+    },
+}
+
+impl BorsCommand {
+    /// Name of the command, as it is written in comments.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BorsCommand::Ping => "ping",
+            BorsCommand::Approve { .. } => "r+",
+            BorsCommand::ApproveRollup => "approve-rollup",
+            BorsCommand::Try { .. } => "try",
+            BorsCommand::TryPlan { .. } => "try?",
+            BorsCommand::TryCancel => "try cancel",
+            BorsCommand::History => "history",
+            BorsCommand::Retry { .. } => "retry",
+            BorsCommand::Delegate { .. } => "delegate",
+            BorsCommand::Undelegate { .. } => "delegate-",
+            BorsCommand::Delegates => "delegates",
+            BorsCommand::Mute => "mute",
+            BorsCommand::Unmute => "unmute",
+            BorsCommand::TreeClosed { .. } => "treeclosed",
+            BorsCommand::TreeOpen => "treeopen",
+            BorsCommand::Sync => "sync",
+            BorsCommand::Recent => "recent",
+            BorsCommand::Whoami => "whoami",
+            BorsCommand::Why => "why",
+            BorsCommand::Clean => "clean",
+            BorsCommand::Confirm { .. } => "confirm",
+            BorsCommand::SetPriority { .. } => "p",
+            BorsCommand::Nag { .. } => "nag",
+            BorsCommand::SetBase { .. } => "set-base",
+        }
+    }
 }
@@ -1,8 +1,13 @@
 //! Defines parsers for bors commands.
 
+use std::borrow::Cow;
 use std::collections::HashSet;
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
 
 use crate::bors::command::BorsCommand;
+use crate::database::{DelegationScope, RollupMode};
 use crate::github::CommitSha;
 
 #[derive(Debug, PartialEq)]
@@ -12,85 +17,308 @@ pub enum CommandParseError<'a> {
     MissingArgValue { arg: &'a str },
     UnknownArg(&'a str),
     DuplicateArg(&'a str),
+    UnterminatedQuote { arg: &'a str },
     ValidationError(String),
 }
 
-/// Part of a command, either a bare string like `try` or a key value like `parent=<sha>`.
+/// Part of a command, either a bare string like `try`, a key value like `parent=<sha>` or a
+/// mention of a user like `@user`.
+///
+/// Values can be quoted to contain spaces, like `workflows="Build and test"`. Quotes inside a
+/// quoted value are escaped as `\"`.
 #[derive(PartialEq)]
 enum CommandPart<'a> {
     Bare(&'a str),
-    KeyValue { key: &'a str, value: &'a str },
+    KeyValue {
+        key: &'a str,
+        value: Cow<'a, str>,
+    },
+    /// Name of the mentioned user, without the `@`. It is always the last part of a command.
+    Mention(&'a str),
+}
+
+/// Length of a full commit SHA.
+pub const FULL_SHA_LENGTH: usize = 40;
+
+/// Shortest abbreviation of a commit SHA that is accepted, the same as the default of git.
+const MIN_SHA_LENGTH: usize = 7;
+
+/// Names of the commands that can be the target of an alias.
+const COMMAND_NAMES: &[&str] = &[
+    "ping",
+    "r+",
+    "approve-rollup",
+    "try",
+    "try?",
+    "retry",
+    "history",
+    "delegate+",
+    "delegate-",
+    "delegates",
+    "sync",
+    "whoami",
+    "why",
+];
+
+/// Aliases that are always available, as pairs of the alias and the name of the aliased command.
+const DEFAULT_ALIASES: &[(&str, &str)] = &[("are you there", "ping"), ("undelegate", "delegate-")];
+
+/// Alternative phrasing of a command, e.g. `@bors are you there` for `@bors ping`.
+struct CommandAlias {
+    /// Words of the alias, in lowercase.
+    words: Vec<String>,
+    command: &'static str,
 }
 
 pub struct CommandParser {
     prefix: String,
+    aliases: Vec<CommandAlias>,
 }
 
 impl CommandParser {
     pub fn new(prefix: String) -> Self {
-        Self { prefix }
+        let mut parser = Self {
+            prefix,
+            aliases: vec![],
+        };
+        for (alias, command) in DEFAULT_ALIASES {
+            parser = parser
+                .with_alias(alias, command)
+                .expect("Invalid default alias");
+        }
+        parser
+    }
+
+    /// Adds an alias of `command`. The alias consists of one or more words, which are matched
+    /// case-insensitively. Arguments written after the alias are passed to the command.
+    pub fn with_alias(mut self, alias: &str, command: &str) -> anyhow::Result<Self> {
+        let Some(command) = COMMAND_NAMES.iter().find(|name| **name == command) else {
+            return Err(anyhow::anyhow!(
+                "Cannot create alias `{alias}` of unknown command `{command}`"
+            ));
+        };
+        let words = alias
+            .split_whitespace()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            return Err(anyhow::anyhow!("Alias of `{command}` must not be empty"));
+        }
+        self.aliases.push(CommandAlias { words, command });
+        Ok(self)
+    }
+
+    /// Replaces an alias at the start of the parts with the name of the aliased command.
+    /// The alias can be followed by a question mark, e.g. `@bors are you there?`.
+    fn resolve_alias<'a>(&self, parts: Vec<CommandPart<'a>>) -> Vec<CommandPart<'a>> {
+        let matches = |alias: &CommandAlias| {
+            alias.words.len() <= parts.len()
+                && alias
+                    .words
+                    .iter()
+                    .zip(&parts)
+                    .enumerate()
+                    .all(|(index, (word, part))| {
+                        let CommandPart::Bare(part) = part else {
+                            return false;
+                        };
+                        let part = match index == alias.words.len() - 1 {
+                            true => part.strip_suffix('?').unwrap_or(part),
+                            false => part,
+                        };
+                        part.eq_ignore_ascii_case(word)
+                    })
+        };
+        match self.aliases.iter().find(|alias| matches(alias)) {
+            Some(alias) => {
+                let mut resolved = vec![CommandPart::Bare(alias.command)];
+                resolved.extend(parts.into_iter().skip(alias.words.len()));
+                resolved
+            }
+            None => parts,
+        }
     }
 
     /// Parses bors commands from the given string.
     ///
-    /// Assumes that each command spands at most one line and that there are not more commands on
-    /// each line.
+    /// Assumes that each command spans at most one line. A single line can contain multiple
+    /// commands, such as `@bors r+ delegate+`, and the comment can mention the bot on multiple
+    /// lines. All commands are returned in the order in which they were written.
     pub fn parse_commands<'a>(
         &self,
         text: &'a str,
     ) -> Vec<Result<BorsCommand, CommandParseError<'a>>> {
         // The order of the parsers in the vector is important
-        let parsers: Vec<for<'b> fn(&'b str, &[CommandPart<'b>]) -> ParseResult<'b>> =
-            vec![parser_ping, parser_try_cancel, parser_try];
+        let parsers: Vec<ParserFn> = vec![
+            parser_ping,
+            parser_approve,
+            parser_approve_rollup,
+            parser_try_cancel,
+            parser_try,
+            parser_try_plan,
+            parser_retry,
+            parser_history,
+            parser_delegate,
+            parser_undelegate,
+            parser_delegates,
+            parser_mute,
+            parser_unmute,
+            parser_tree_closed,
+            parser_tree_open,
+            parser_sync,
+            parser_recent,
+            parser_whoami,
+            parser_why,
+            parser_clean,
+            parser_confirm,
+        ];
+        // Parsers of commands that start with a key-value pair, like `set-base=<branch>`
+        let key_value_parsers: Vec<KeyValueParserFn> = vec![
+            parser_approve_team,
+            parser_delay,
+            parser_set_base,
+            parser_delegate_scope,
+            parser_nag,
+            parser_tree_closed_priority,
+        ];
+        // Parsers of commands that look like an argument of another command, like `p=<priority>`.
+        // They do not start a new command when splitting a line, so that e.g. `@bors r+ p=1`
+        // remains a single command.
+        let argument_parsers: Vec<KeyValueParserFn> = vec![parser_set_priority];
+        let all_key_value_parsers = [key_value_parsers.as_slice(), &argument_parsers].concat();
 
         text.lines()
-            .filter_map(|line| match line.find(&self.prefix) {
-                Some(index) => {
-                    let command = &line[index + self.prefix.len()..];
-                    match parse_parts(command) {
-                        Ok(parts) => {
-                            if parts.is_empty() {
-                                Some(Err(CommandParseError::MissingCommand))
-                            } else {
-                                let (command, rest) = parts.split_at(1);
-                                match command[0] {
-                                    CommandPart::Bare(command) => {
-                                        for parser in &parsers {
-                                            if let Some(result) = parser(command, rest) {
-                                                return Some(result);
-                                            }
-                                        }
-                                        Some(Err(CommandParseError::UnknownCommand(command)))
-                                    }
-                                    CommandPart::KeyValue { .. } => {
-                                        Some(Err(CommandParseError::MissingCommand))
-                                    }
-                                }
-                            }
-                        }
-                        Err(error) => Some(Err(error)),
-                    }
-                }
-                None => None,
+            .filter_map(|line| {
+                line.find(&self.prefix)
+                    .map(|index| strip_markdown(&line[..index], &line[index + self.prefix.len()..]))
             })
+            .flat_map(
+                |command| match parse_parts(command).map(|parts| self.resolve_alias(parts)) {
+                    Ok(parts) if parts.is_empty() => vec![Err(CommandParseError::MissingCommand)],
+                    Ok(parts) => split_commands(&parts, &parsers, &key_value_parsers)
+                        .into_iter()
+                        .map(|parts| parse_command(parts, &parsers, &all_key_value_parsers))
+                        .collect(),
+                    Err(error) => vec![Err(error)],
+                },
+            )
             .collect()
     }
 }
 
+/// Removes inline markdown formatting (bold, italic or inline code) that wraps a command, such as
+/// `**@bors r+**`. `before` is the text of the line before the bot prefix and `command` is the text
+/// after it.
+///
+/// The command is only cut at the closing marker that matches the markers written right before
+/// the prefix, so that e.g. underscores in the arguments of unformatted commands are kept.
+fn strip_markdown<'a>(before: &str, command: &'a str) -> &'a str {
+    let opening = &before[before.trim_end_matches(['*', '_', '`']).len()..];
+    if opening.is_empty() {
+        return command;
+    }
+    let closing = opening.chars().rev().collect::<String>();
+    match command.find(&closing) {
+        Some(index) => &command[..index],
+        None => command,
+    }
+}
+
+/// Splits the parts of a line into separate commands. A new command starts with each part that
+/// is recognized as the name of a command.
+fn split_commands<'a, 'p>(
+    parts: &'p [CommandPart<'a>],
+    parsers: &[ParserFn],
+    key_value_parsers: &[KeyValueParserFn],
+) -> Vec<&'p [CommandPart<'a>]> {
+    let is_command = |part: &CommandPart<'a>| match part {
+        CommandPart::Bare(command) => parsers.iter().any(|parser| parser(command, &[]).is_some()),
+        CommandPart::KeyValue { key, value } => key_value_parsers
+            .iter()
+            .any(|parser| parser(key, value, &[]).is_some()),
+        CommandPart::Mention(_) => false,
+    };
+
+    let mut commands = vec![];
+    let mut start = 0;
+    for (index, part) in parts.iter().enumerate().skip(1) {
+        if is_command(part) {
+            commands.push(&parts[start..index]);
+            start = index;
+        }
+    }
+    commands.push(&parts[start..]);
+    commands
+}
+
+/// Parses a single command from its (non-empty) parts.
+fn parse_command<'a>(
+    parts: &[CommandPart<'a>],
+    parsers: &[ParserFn],
+    key_value_parsers: &[KeyValueParserFn],
+) -> Result<BorsCommand, CommandParseError<'a>> {
+    let (command, rest) = parts.split_at(1);
+    match &command[0] {
+        CommandPart::Bare(command) => {
+            for parser in parsers {
+                if let Some(result) = parser(command, rest) {
+                    return result;
+                }
+            }
+            Err(CommandParseError::UnknownCommand(command))
+        }
+        CommandPart::KeyValue { key, value } => {
+            for parser in key_value_parsers {
+                if let Some(result) = parser(key, value, rest) {
+                    return result;
+                }
+            }
+            Err(CommandParseError::UnknownCommand(key))
+        }
+        // The comment only mentions another user or bot
+        CommandPart::Mention(_) => Err(CommandParseError::MissingCommand),
+    }
+}
+
 type ParseResult<'a> = Option<Result<BorsCommand, CommandParseError<'a>>>;
 
-fn parse_parts(input: &str) -> Result<Vec<CommandPart>, CommandParseError> {
+/// Parses a command starting with a bare command name, receives the name and the rest of the parts.
+type ParserFn = for<'b> fn(&'b str, &[CommandPart<'b>]) -> ParseResult<'b>;
+
+/// Parses a command starting with a key-value pair, receives the key, the value and the rest of
+/// the parts.
+type KeyValueParserFn = for<'b> fn(&'b str, &str, &[CommandPart<'b>]) -> ParseResult<'b>;
+
+fn parse_parts(input: &str) -> Result<Vec<CommandPart<'_>>, CommandParseError<'_>> {
     let mut parts = vec![];
     let mut seen_keys = HashSet::new();
 
-    for item in input.split_whitespace() {
-        // Stop parsing, as this is a command for another bot, such as `@rust-timer queue`.
-        if item.starts_with('@') {
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let item = &rest[..end];
+        // Stop parsing, as this is either the argument of a command, such as
+        // `@bors delegate+ @user`, or a command for another bot, such as `@rust-timer queue`.
+        if let Some(user) = item.strip_prefix('@') {
+            parts.push(CommandPart::Mention(user));
             break;
         }
 
         match item.split_once('=') {
             Some((key, value)) => {
+                let value = match value.strip_prefix('"') {
+                    Some(_) => {
+                        let quoted = &rest[key.len() + 2..];
+                        let (value, length) = parse_quoted(quoted)
+                            .ok_or(CommandParseError::UnterminatedQuote { arg: key })?;
+                        rest = &quoted[length..];
+                        value
+                    }
+                    None => {
+                        rest = &rest[end..];
+                        Cow::Borrowed(value)
+                    }
+                };
                 if value.is_empty() {
                     return Err(CommandParseError::MissingArgValue { arg: key });
                 }
@@ -100,13 +328,43 @@ fn parse_parts(input: &str) -> Result<Vec<CommandPart>, CommandParseError> {
                 seen_keys.insert(key);
                 parts.push(CommandPart::KeyValue { key, value });
             }
-            None => parts.push(CommandPart::Bare(item)),
+            None => {
+                rest = &rest[end..];
+                parts.push(CommandPart::Bare(item));
+            }
         }
+        rest = rest.trim_start();
     }
     Ok(parts)
 }
 
-/// Parsers
+/// Parses a quoted value, starting after its opening quote. Returns the unescaped value and the
+/// length of the input up to and including the closing quote, or `None` if the quote is not
+/// closed.
+fn parse_quoted(input: &str) -> Option<(Cow<'_, str>, usize)> {
+    let mut value = String::new();
+    let mut escaped = false;
+    let mut chars = input.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => {
+                let value = match escaped {
+                    true => Cow::Owned(value),
+                    false => Cow::Borrowed(&input[..index]),
+                };
+                return Some((value, index + 1));
+            }
+            '\\' if matches!(chars.peek(), Some((_, '"' | '\\'))) => {
+                escaped = true;
+                value.extend(chars.next().map(|(_, c)| c));
+            }
+            c => value.push(c),
+        }
+    }
+    None
+}
+
+// Parsers
 
 /// Parses "@bors ping".
 fn parser_ping<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
@@ -117,58 +375,579 @@ fn parser_ping<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<
     }
 }
 
+/// Parses "@bors r+ <p=priority> <rollup=mode>".
+fn parser_approve<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "r+" {
+        return None;
+    }
+    Some(
+        parse_approve_args(parts).map(|(priority, rollup)| BorsCommand::Approve {
+            priority,
+            rollup,
+            team: None,
+            delay: None,
+        }),
+    )
+}
+
+/// Parses "@bors approve-rollup".
+fn parser_approve_rollup<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "approve-rollup" {
+        Some(Ok(BorsCommand::ApproveRollup))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors nag=<duration>".
+fn parser_nag<'a>(key: &'a str, value: &str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if key != "nag" {
+        return None;
+    }
+    Some(parse_duration(value).map(|interval| BorsCommand::Nag { interval }))
+}
+
+/// Parses "@bors r=team:<name> <p=priority> <rollup=mode>".
+fn parser_approve_team<'a>(
+    key: &'a str,
+    value: &str,
+    parts: &[CommandPart<'a>],
+) -> ParseResult<'a> {
+    if key != "r" {
+        return None;
+    }
+    let Some(team) = value.strip_prefix("team:").filter(|team| !team.is_empty()) else {
+        return Some(Err(CommandParseError::ValidationError(format!(
+            "Cannot approve on behalf of `{value}`, use `r=team:<name>` to approve on behalf of a team"
+        ))));
+    };
+    Some(
+        parse_approve_args(parts).map(|(priority, rollup)| BorsCommand::Approve {
+            priority,
+            rollup,
+            team: Some(team.to_string()),
+            delay: None,
+        }),
+    )
+}
+
+/// Parses "@bors delay=<duration> <p=priority> <rollup=mode>".
+fn parser_delay<'a>(key: &'a str, value: &str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if key != "delay" {
+        return None;
+    }
+    let delay = match parse_duration(value) {
+        Ok(delay) => delay,
+        Err(error) => return Some(Err(error)),
+    };
+    Some(
+        parse_approve_args(parts).map(|(priority, rollup)| BorsCommand::Approve {
+            priority,
+            rollup,
+            team: None,
+            delay: Some(delay),
+        }),
+    )
+}
+
+/// Parses a duration with a unit suffix, such as `30m`, `2h` or `1d`.
+fn parse_duration<'a>(input: &str) -> Result<Duration, CommandParseError<'a>> {
+    let invalid = || {
+        CommandParseError::ValidationError(format!(
+            "Invalid duration `{input}`, use a number followed by `s`, `m`, `h` or `d`, e.g. `2h`"
+        ))
+    };
+    let split = input.char_indices().last().map_or(0, |(index, _)| index);
+    let (amount, unit) = input.split_at(split);
+    let amount = amount.parse::<u64>().map_err(|_| invalid())?;
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    amount
+        .checked_mul(seconds)
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// Parses the arguments of an approval, returning its priority and rollup mode, if they were
+/// specified.
+fn parse_approve_args<'a>(
+    parts: &[CommandPart<'a>],
+) -> Result<(Option<i32>, Option<RollupMode>), CommandParseError<'a>> {
+    let mut priority = None;
+    let mut rollup = None;
+    for part in parts {
+        match part {
+            CommandPart::Bare(key) => {
+                return Err(CommandParseError::UnknownArg(key));
+            }
+            CommandPart::KeyValue { key: "p", value } => priority = Some(parse_priority(value)?),
+            CommandPart::KeyValue {
+                key: "rollup",
+                value,
+            } => rollup = Some(parse_rollup_mode(value)?),
+            CommandPart::KeyValue { key, .. } => {
+                return Err(CommandParseError::UnknownArg(key));
+            }
+            // Ignore commands for other bots
+            CommandPart::Mention(_) => break,
+        }
+    }
+    Ok((priority, rollup))
+}
+
+fn parse_priority<'a>(input: &str) -> Result<i32, CommandParseError<'a>> {
+    input.parse::<i32>().map_err(|_| {
+        CommandParseError::ValidationError(format!("Priority has to be a number, not `{input}`"))
+    })
+}
+
+fn parse_rollup_mode<'a>(input: &str) -> Result<RollupMode, CommandParseError<'a>> {
+    match input {
+        "always" => Ok(RollupMode::Always),
+        "maybe" => Ok(RollupMode::Maybe),
+        "iffy" => Ok(RollupMode::Iffy),
+        "never" => Ok(RollupMode::Never),
+        _ => Err(CommandParseError::ValidationError(format!(
+            "Unknown rollup mode `{input}`, use `always`, `maybe`, `iffy` or `never`"
+        ))),
+    }
+}
+
+/// Parses a full or abbreviated commit SHA. Abbreviated SHAs are resolved by the command handlers.
 fn parse_sha(input: &str) -> Result<CommitSha, String> {
-    if input.len() != 40 {
-        return Err("SHA must have exactly 40 characters".to_string());
+    if !(MIN_SHA_LENGTH..=FULL_SHA_LENGTH).contains(&input.len()) {
+        return Err(format!(
+            "SHA must have between {MIN_SHA_LENGTH} and {FULL_SHA_LENGTH} characters"
+        ));
+    }
+    if !input.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("SHA must consist of hexadecimal digits".to_string());
     }
     Ok(CommitSha(input.to_string()))
 }
 
-/// Parses "@bors try <parent=sha>".
+/// Parses a comma-separated list of values, such as `build,test,lint`.
+fn parse_list<'a>(key: &'a str, value: &str) -> Result<Vec<String>, CommandParseError<'a>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .map(|item| {
+            if item.is_empty() {
+                Err(CommandParseError::ValidationError(format!(
+                    "The list of `{key}` must not contain empty entries"
+                )))
+            } else {
+                Ok(item.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Parses "@bors try <rebase> <parent=sha> <workflows=a,b>".
 fn parser_try<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
     if command != "try" {
         return None;
     }
+    let (rebase, parts) = match parts.split_first() {
+        Some((CommandPart::Bare("rebase"), rest)) => (true, rest),
+        _ => (false, parts),
+    };
+    Some(parse_try_args(parts).and_then(|(parent, workflows)| {
+        if rebase && parent.is_some() {
+            return Err(CommandParseError::ValidationError(
+                "`rebase` cannot be combined with `parent`".to_string(),
+            ));
+        }
+        Ok(BorsCommand::Try {
+            parent,
+            workflows,
+            rebase,
+        })
+    }))
+}
+
+/// Parses "@bors try? <parent=sha> <workflows=a,b>".
+fn parser_try_plan<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "try?" {
+        return None;
+    }
+    Some(
+        parse_try_args(parts).map(|(parent, workflows)| BorsCommand::TryPlan { parent, workflows }),
+    )
+}
 
+/// Parses the arguments of a try build, returning the parent commit, if it was specified, and
+/// the selected workflows.
+fn parse_try_args<'a>(
+    parts: &[CommandPart<'a>],
+) -> Result<(Option<CommitSha>, Vec<String>), CommandParseError<'a>> {
     let mut parent = None;
+    let mut workflows = vec![];
 
     for part in parts {
         match part {
             CommandPart::Bare(key) => {
-                return Some(Err(CommandParseError::UnknownArg(key)));
+                return Err(CommandParseError::UnknownArg(key));
             }
+            // Ignore commands for other bots
+            CommandPart::Mention(_) => break,
             CommandPart::KeyValue { key, value } => {
                 if *key == "parent" {
                     parent = match parse_sha(value) {
                         Ok(sha) => Some(sha),
                         Err(error) => {
-                            return Some(Err(CommandParseError::ValidationError(format!(
+                            return Err(CommandParseError::ValidationError(format!(
                                 "Try parent has to be a valid commit SHA: {error}"
-                            ))));
+                            )));
                         }
                     };
+                } else if *key == "workflows" {
+                    workflows = parse_list(key, value)?;
                 } else {
-                    return Some(Err(CommandParseError::UnknownArg(key)));
+                    return Err(CommandParseError::UnknownArg(key));
                 }
             }
         }
     }
-    Some(Ok(BorsCommand::Try { parent }))
+    Ok((parent, workflows))
 }
 
 /// Parses "@bors try cancel".
 fn parser_try_cancel<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
-    if command == "try" && parts.get(0) == Some(&CommandPart::Bare("cancel")) {
+    if command == "try" && parts.first() == Some(&CommandPart::Bare("cancel")) {
         Some(Ok(BorsCommand::TryCancel))
     } else {
         None
     }
 }
 
+/// Parses "@bors retry <workflow>" and "@bors retry workflow=<workflow>".
+///
+/// A bare name of a command after `retry` starts a new command, e.g. `@bors retry r+` retries the
+/// build and approves the PR. Workflows named like a command are retried with `workflow=<name>`.
+fn parser_retry<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "retry" {
+        return None;
+    }
+    let workflow = match parts.first() {
+        None => None,
+        Some(CommandPart::Bare(workflow)) => Some(workflow.to_string()),
+        Some(CommandPart::KeyValue { key, value }) if *key == "workflow" => Some(value.to_string()),
+        Some(CommandPart::Mention(arg)) | Some(CommandPart::KeyValue { key: arg, .. }) => {
+            return Some(Err(CommandParseError::UnknownArg(arg)));
+        }
+    };
+    Some(Ok(BorsCommand::Retry { workflow }))
+}
+
+/// Parses "@bors history".
+fn parser_history<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "history" {
+        Some(Ok(BorsCommand::History))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors delegate+ <@user>".
+fn parser_delegate<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "delegate+" {
+        return None;
+    }
+    Some(
+        parse_delegatee(parts).map(|delegatee| BorsCommand::Delegate {
+            scope: DelegationScope::Review,
+            delegatee,
+        }),
+    )
+}
+
+/// Parses "@bors delegate=<try|review> <@user>" and "@bors delegate=<try|review>-".
+fn parser_delegate_scope<'a>(
+    key: &'a str,
+    value: &str,
+    parts: &[CommandPart<'a>],
+) -> ParseResult<'a> {
+    if key != "delegate" {
+        return None;
+    }
+    let (value, remove) = match value.strip_suffix('-') {
+        Some(value) => (value, true),
+        None => (value, false),
+    };
+    let scope = match value {
+        "try" => DelegationScope::Try,
+        "review" => DelegationScope::Review,
+        _ => {
+            return Some(Err(CommandParseError::ValidationError(format!(
+                "Unknown delegation scope `{value}`, use `try` or `review`"
+            ))));
+        }
+    };
+    if remove {
+        return Some(Ok(BorsCommand::Undelegate { scope: Some(scope) }));
+    }
+    Some(parse_delegatee(parts).map(|delegatee| BorsCommand::Delegate { scope, delegatee }))
+}
+
+/// Parses the optional user that should receive a delegation.
+fn parse_delegatee<'a>(parts: &[CommandPart<'a>]) -> Result<Option<String>, CommandParseError<'a>> {
+    match parts.first() {
+        None => Ok(None),
+        Some(CommandPart::Mention(user)) => Ok(Some(user.to_string())),
+        Some(CommandPart::Bare(arg)) | Some(CommandPart::KeyValue { key: arg, .. }) => {
+            Err(CommandParseError::UnknownArg(arg))
+        }
+    }
+}
+
+/// Parses "@bors delegates".
+fn parser_delegates<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "delegates" {
+        Some(Ok(BorsCommand::Delegates))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors mute".
+fn parser_mute<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "mute" {
+        Some(Ok(BorsCommand::Mute))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors unmute".
+fn parser_unmute<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "unmute" {
+        Some(Ok(BorsCommand::Unmute))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors treeclosed <until=time> <reason>".
+fn parser_tree_closed<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "treeclosed" {
+        return None;
+    }
+    Some(
+        parse_tree_closed_args(parts).map(|(until, reason)| BorsCommand::TreeClosed {
+            priority: None,
+            until,
+            reason,
+        }),
+    )
+}
+
+/// Parses "@bors treeclosed=<priority> <until=time> <reason>".
+fn parser_tree_closed_priority<'a>(
+    key: &'a str,
+    value: &str,
+    parts: &[CommandPart<'a>],
+) -> ParseResult<'a> {
+    if key != "treeclosed" {
+        return None;
+    }
+    Some(parse_priority(value).and_then(|priority| {
+        parse_tree_closed_args(parts).map(|(until, reason)| BorsCommand::TreeClosed {
+            priority: Some(priority),
+            until,
+            reason,
+        })
+    }))
+}
+
+/// Parses the arguments of closing the tree, returning the time when the tree should be opened
+/// again, if it was specified, and the reason.
+///
+/// The time can be specified anywhere among the arguments, the other arguments form the reason.
+fn parse_tree_closed_args<'a>(
+    parts: &[CommandPart<'a>],
+) -> Result<(Option<DateTime<Utc>>, Option<String>), CommandParseError<'a>> {
+    let mut until = None;
+    let mut reason = vec![];
+    for part in parts {
+        match part {
+            CommandPart::KeyValue {
+                key: "until",
+                value,
+            } => {
+                if until.is_some() {
+                    return Err(CommandParseError::DuplicateArg("until"));
+                }
+                until = Some(parse_time(value)?);
+            }
+            _ => reason.push(part),
+        }
+    }
+    Ok((until, parse_reason(reason)))
+}
+
+/// Parses a time in UTC, e.g. `2024-06-01T09:00Z`. Seconds and other time zones can be specified
+/// as in RFC 3339, e.g. `2024-06-01T11:00:00+02:00`.
+fn parse_time<'a>(input: &str) -> Result<DateTime<Utc>, CommandParseError<'a>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(input) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%MZ")
+        .map(|time| DateTime::from_utc(time, Utc))
+        .map_err(|_| {
+            CommandParseError::ValidationError(format!(
+                "Invalid time `{input}`, use a UTC time like `2024-06-01T09:00Z`"
+            ))
+        })
+}
+
+/// Joins the remaining parts of a command into a free-form reason.
+fn parse_reason<'b>(parts: impl IntoIterator<Item = &'b CommandPart<'b>>) -> Option<String> {
+    let words = parts
+        .into_iter()
+        .map(|part| match part {
+            CommandPart::Bare(word) => word.to_string(),
+            CommandPart::KeyValue { key, value } => format!("{key}={value}"),
+            CommandPart::Mention(user) => format!("@{user}"),
+        })
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Parses "@bors treeopen".
+fn parser_tree_open<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "treeopen" {
+        Some(Ok(BorsCommand::TreeOpen))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors sync".
+fn parser_sync<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "sync" {
+        Some(Ok(BorsCommand::Sync))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors recent".
+fn parser_recent<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "recent" {
+        Some(Ok(BorsCommand::Recent))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors whoami".
+fn parser_whoami<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "whoami" {
+        Some(Ok(BorsCommand::Whoami))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors why".
+fn parser_why<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "why" {
+        Some(Ok(BorsCommand::Why))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors clean".
+fn parser_clean<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "clean" {
+        Some(Ok(BorsCommand::Clean))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors confirm <token>".
+fn parser_confirm<'a>(command: &'a str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command != "confirm" {
+        return None;
+    }
+    match parts.first() {
+        Some(CommandPart::Bare(token)) => Some(Ok(BorsCommand::Confirm {
+            token: token.to_string(),
+        })),
+        Some(CommandPart::Mention(arg)) | Some(CommandPart::KeyValue { key: arg, .. }) => {
+            Some(Err(CommandParseError::UnknownArg(arg)))
+        }
+        None => Some(Err(CommandParseError::ValidationError(
+            "Missing confirmation token, use `@bors confirm <token>`".to_string(),
+        ))),
+    }
+}
+
+/// Parses "@bors delegate-".
+fn parser_undelegate<'a>(command: &'a str, _parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if command == "delegate-" {
+        Some(Ok(BorsCommand::Undelegate { scope: None }))
+    } else {
+        None
+    }
+}
+
+/// Parses "@bors set-base=<branch>".
+fn parser_set_base<'a>(key: &'a str, value: &str, parts: &[CommandPart<'a>]) -> ParseResult<'a> {
+    if key != "set-base" {
+        return None;
+    }
+    match parts.first() {
+        Some(CommandPart::Bare(arg)) | Some(CommandPart::KeyValue { key: arg, .. }) => {
+            return Some(Err(CommandParseError::UnknownArg(arg)));
+        }
+        Some(CommandPart::Mention(_)) | None => {}
+    }
+    Some(Ok(BorsCommand::SetBase {
+        branch: value.to_string(),
+    }))
+}
+
+/// Parses "@bors p=<priority>".
+fn parser_set_priority<'a>(
+    key: &'a str,
+    value: &str,
+    parts: &[CommandPart<'a>],
+) -> ParseResult<'a> {
+    if key != "p" {
+        return None;
+    }
+    match parts.first() {
+        Some(CommandPart::Bare(arg)) | Some(CommandPart::KeyValue { key: arg, .. }) => {
+            return Some(Err(CommandParseError::UnknownArg(arg)));
+        }
+        Some(CommandPart::Mention(_)) | None => {}
+    }
+    Some(parse_priority(value).map(|priority| BorsCommand::SetPriority { priority }))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
     use crate::bors::command::parser::{CommandParseError, CommandParser};
     use crate::bors::command::BorsCommand;
+    use crate::database::{DelegationScope, RollupMode};
     use crate::github::CommitSha;
 
     #[test]
@@ -226,62 +1005,288 @@ mod tests {
     }
 
     #[test]
-    fn parse_command_multiline() {
-        let cmds = parse_commands(
-            r#"
-line one
-@bors try
-line two
-"#,
+    fn parse_approve() {
+        let cmds = parse_commands("@bors r+");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                priority: None,
+                rollup: None,
+                team: None,
+                delay: None
+            })
         );
+    }
+
+    #[test]
+    fn parse_approve_priority_rollup() {
+        let cmds = parse_commands("@bors r+ p=5 rollup=never");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None })));
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                priority: Some(5),
+                rollup: Some(RollupMode::Never),
+                team: None,
+                delay: None
+            })
+        );
     }
 
     #[test]
-    fn parse_try() {
-        let cmds = parse_commands("@bors try");
+    fn parse_approve_team() {
+        let cmds = parse_commands("@bors r=team:reviewers p=1");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None })));
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                priority: Some(1),
+                rollup: None,
+                team: Some("reviewers".to_string()),
+                delay: None
+            })
+        );
     }
 
     #[test]
-    fn parse_try_parent() {
-        let cmds = parse_commands("@bors try parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+    fn parse_delay() {
+        let cmds = parse_commands("@bors delay=2h p=3");
         assert_eq!(cmds.len(), 1);
         assert_eq!(
             cmds[0],
-            Ok(BorsCommand::Try {
-                parent: Some(CommitSha(
-                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
-                ))
+            Ok(BorsCommand::Approve {
+                priority: Some(3),
+                rollup: None,
+                team: None,
+                delay: Some(Duration::from_secs(2 * 60 * 60))
             })
         );
     }
 
     #[test]
-    fn parse_try_parent_invalid() {
-        let cmds = parse_commands("@bors try parent=foo");
+    fn parse_delay_invalid() {
+        for delay in ["2", "h", "0m", "2w", "-1h", "1.5h"] {
+            let text = format!("@bors delay={delay}");
+            let cmds = parse_commands(&text);
+            assert_eq!(cmds.len(), 1);
+            assert!(
+                matches!(cmds[0], Err(CommandParseError::ValidationError(_))),
+                "{delay}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_approve_on_behalf_of_user() {
+        let cmds = parse_commands("@bors r=user");
         assert_eq!(cmds.len(), 1);
         insta::assert_debug_snapshot!(cmds[0], @r###"
         Err(
             ValidationError(
-                "Try parent has to be a valid commit SHA: SHA must have exactly 40 characters",
+                "Cannot approve on behalf of `user`, use `r=team:<name>` to approve on behalf of a team",
             ),
         )
         "###);
     }
 
     #[test]
-    fn parse_try_unknown_arg() {
-        let cmds = parse_commands("@bors try a");
+    fn parse_approve_invalid_priority() {
+        let cmds = parse_commands("@bors r+ p=high");
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Err(CommandParseError::UnknownArg("a"))));
-    }
-
-    #[test]
-    fn parse_try_unknown_kv_arg() {
-        let cmds = parse_commands("@bors try a=b");
+        insta::assert_debug_snapshot!(cmds[0], @r###"
+        Err(
+            ValidationError(
+                "Priority has to be a number, not `high`",
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn parse_command_multiline() {
+        let cmds = parse_commands(
+            r#"
+line one
+@bors try
+line two
+"#,
+        );
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None, .. })));
+    }
+
+    #[test]
+    fn parse_commands_on_multiple_lines() {
+        let cmds = parse_commands(
+            r#"
+@bors r+
+This PR fixes an important bug.
+@bors p=2
+"#,
+        );
+        assert_eq!(
+            cmds,
+            vec![
+                Ok(BorsCommand::Approve {
+                    priority: None,
+                    rollup: None,
+                    team: None,
+                    delay: None,
+                }),
+                Ok(BorsCommand::SetPriority { priority: 2 })
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_try() {
+        let cmds = parse_commands("@bors try");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None, .. })));
+    }
+
+    #[test]
+    fn parse_try_rebase() {
+        let cmds = parse_commands("@bors try rebase workflows=build");
+        assert_eq!(
+            cmds,
+            vec![Ok(BorsCommand::Try {
+                parent: None,
+                workflows: vec!["build".to_string()],
+                rebase: true
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_try_rebase_with_parent() {
+        let cmds =
+            parse_commands("@bors try rebase parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert!(matches!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn parse_try_parent() {
+        let cmds = parse_commands("@bors try parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Try {
+                parent: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+                workflows: vec![],
+                rebase: false
+            })
+        );
+    }
+
+    #[test]
+    fn parse_try_parent_invalid() {
+        let cmds = parse_commands("@bors try parent=foo");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r###"
+        Err(
+            ValidationError(
+                "Try parent has to be a valid commit SHA: SHA must have between 7 and 40 characters",
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn parse_try_parent_abbreviated() {
+        let cmds = parse_commands("@bors try parent=ea9c1b0");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            &cmds[0],
+            Ok(BorsCommand::Try { parent: Some(parent), .. }) if parent.0 == "ea9c1b0"
+        ));
+    }
+
+    #[test]
+    fn parse_try_parent_not_hex() {
+        let cmds = parse_commands("@bors try parent=master1");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r###"
+        Err(
+            ValidationError(
+                "Try parent has to be a valid commit SHA: SHA must consist of hexadecimal digits",
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn parse_try_workflows() {
+        let cmds = parse_commands("@bors try workflows=build,test,lint");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Try {
+                parent: None,
+                workflows: vec!["build".to_string(), "test".to_string(), "lint".to_string()],
+                rebase: false
+            })
+        );
+    }
+
+    #[test]
+    fn parse_try_quoted_workflows() {
+        let cmds = parse_commands(
+            r#"@bors try workflows="Build and test, Say \"hi\"" parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60"#,
+        );
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Try {
+                parent: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+                workflows: vec!["Build and test".to_string(), r#"Say "hi""#.to_string()],
+                rebase: false
+            })
+        );
+    }
+
+    #[test]
+    fn parse_try_workflows_unterminated_quote() {
+        let cmds = parse_commands(r#"@bors try workflows="Build and test"#);
+        assert_eq!(
+            cmds,
+            vec![Err(CommandParseError::UnterminatedQuote {
+                arg: "workflows"
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_try_workflows_empty_entry() {
+        let cmds = parse_commands("@bors try workflows=build,,lint");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r###"
+        Err(
+            ValidationError(
+                "The list of `workflows` must not contain empty entries",
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn parse_try_unknown_arg() {
+        let cmds = parse_commands("@bors try a");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(cmds[0], Err(CommandParseError::UnknownArg("a"))));
+    }
+
+    #[test]
+    fn parse_try_unknown_kv_arg() {
+        let cmds = parse_commands("@bors try a=b");
         assert_eq!(cmds.len(), 1);
         assert!(matches!(cmds[0], Err(CommandParseError::UnknownArg("a"))));
     }
@@ -294,7 +1299,32 @@ line two
 "#,
         );
         assert_eq!(cmds.len(), 1);
-        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None })));
+        assert!(matches!(cmds[0], Ok(BorsCommand::Try { parent: None, .. })));
+    }
+
+    #[test]
+    fn parse_try_plan() {
+        let cmds = parse_commands("@bors try?");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            cmds[0],
+            Ok(BorsCommand::TryPlan { parent: None, .. })
+        ));
+    }
+
+    #[test]
+    fn parse_try_plan_parent() {
+        let cmds = parse_commands("@bors try? parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TryPlan {
+                parent: Some(CommitSha(
+                    "ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string()
+                )),
+                workflows: vec![]
+            })
+        );
     }
 
     #[test]
@@ -304,7 +1334,511 @@ line two
         assert!(matches!(cmds[0], Ok(BorsCommand::TryCancel)));
     }
 
-    fn parse_commands(text: &str) -> Vec<Result<BorsCommand, CommandParseError>> {
+    #[test]
+    fn parse_set_base() {
+        let cmds = parse_commands("@bors set-base=release-1.2");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::SetBase {
+                branch: "release-1.2".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_set_base_unknown_arg() {
+        let cmds = parse_commands("@bors set-base=release-1.2 foo");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Err(CommandParseError::UnknownArg("foo")));
+    }
+
+    #[test]
+    fn parse_set_priority() {
+        let cmds = parse_commands("@bors p=5");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::SetPriority { priority: 5 }));
+    }
+
+    #[test]
+    fn parse_set_priority_invalid() {
+        let cmds = parse_commands("@bors p=high");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(
+                "Priority has to be a number, not `high`".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_approve_priority_is_not_split() {
+        let cmds = parse_commands("@bors r+ p=5");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Approve {
+                priority: Some(5),
+                rollup: None,
+                team: None,
+                delay: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_unknown_key_value_command() {
+        let cmds = parse_commands("@bors foo=bar");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Err(CommandParseError::UnknownCommand("foo")));
+    }
+
+    #[test]
+    fn parse_retry() {
+        let cmds = parse_commands("@bors retry");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Retry { workflow: None }));
+    }
+
+    #[test]
+    fn parse_retry_workflow() {
+        let cmds = parse_commands("@bors retry lint");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                workflow: Some("lint".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_retry_workflow_key() {
+        let cmds = parse_commands("@bors retry workflow=try");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Retry {
+                workflow: Some("try".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_retry_followed_by_command() {
+        let cmds = parse_commands("@bors retry r+");
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(cmds[0], Ok(BorsCommand::Retry { workflow: None }));
+        assert!(matches!(cmds[1], Ok(BorsCommand::Approve { .. })));
+    }
+
+    #[test]
+    fn parse_history() {
+        let cmds = parse_commands("@bors history");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::History));
+    }
+
+    #[test]
+    fn parse_delegate() {
+        let cmds = parse_commands("@bors delegate+");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Delegate {
+                scope: DelegationScope::Review,
+                delegatee: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_delegate_try() {
+        let cmds = parse_commands("@bors delegate=try");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Delegate {
+                scope: DelegationScope::Try,
+                delegatee: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_delegate_to_user() {
+        let cmds = parse_commands("@bors delegate+ @contributor");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Delegate {
+                scope: DelegationScope::Review,
+                delegatee: Some("contributor".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_delegate_try_to_user() {
+        let cmds = parse_commands("@bors delegate=try @contributor");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Delegate {
+                scope: DelegationScope::Try,
+                delegatee: Some("contributor".to_string())
+            })
+        );
+    }
+
+    #[test]
+    fn parse_delegate_unknown_arg() {
+        let cmds = parse_commands("@bors delegate+ contributor");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Err(CommandParseError::UnknownArg("contributor")));
+    }
+
+    #[test]
+    fn parse_delegate_unknown_scope() {
+        let cmds = parse_commands("@bors delegate=foo");
+        assert_eq!(cmds.len(), 1);
+        insta::assert_debug_snapshot!(cmds[0], @r###"
+        Err(
+            ValidationError(
+                "Unknown delegation scope `foo`, use `try` or `review`",
+            ),
+        )
+        "###);
+    }
+
+    #[test]
+    fn parse_delegates() {
+        let cmds = parse_commands("@bors delegates");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Delegates));
+    }
+
+    #[test]
+    fn parse_sync() {
+        let cmds = parse_commands("@bors sync");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Sync));
+    }
+
+    #[test]
+    fn parse_mute() {
+        let cmds = parse_commands("@bors mute");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Mute));
+        let cmds = parse_commands("@bors unmute");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Unmute));
+    }
+
+    #[test]
+    fn parse_tree_closed() {
+        let cmds = parse_commands("@bors treeclosed=5 deploying hotfix");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: Some(5),
+                until: None,
+                reason: Some("deploying hotfix".to_string())
+            })
+        );
+        let cmds = parse_commands("@bors treeclosed");
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: None,
+                until: None,
+                reason: None
+            })
+        );
+        let cmds = parse_commands("@bors treeopen");
+        assert_eq!(cmds[0], Ok(BorsCommand::TreeOpen));
+    }
+
+    #[test]
+    fn parse_tree_closed_until() {
+        let until = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        let cmds = parse_commands("@bors treeclosed=5 until=2024-06-01T09:00Z deploying hotfix");
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: Some(5),
+                until: Some(until),
+                reason: Some("deploying hotfix".to_string())
+            })
+        );
+        let cmds = parse_commands("@bors treeclosed until=2024-06-01T11:00:00+02:00");
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: None,
+                until: Some(until),
+                reason: None
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tree_closed_until_after_reason() {
+        let until = Utc.with_ymd_and_hms(2024, 6, 1, 9, 0, 0).unwrap();
+        let cmds = parse_commands("@bors treeclosed deploying until=2024-06-01T09:00Z hotfix");
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::TreeClosed {
+                priority: None,
+                until: Some(until),
+                reason: Some("deploying hotfix".to_string())
+            })
+        );
+        let cmds =
+            parse_commands("@bors treeclosed until=2024-06-01T09:00Z until=2024-06-02T09:00Z");
+        assert_eq!(cmds[0], Err(CommandParseError::DuplicateArg("until")));
+    }
+
+    #[test]
+    fn parse_tree_closed_invalid_until() {
+        let cmds = parse_commands("@bors treeclosed until=tomorrow");
+        assert_eq!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(
+                "Invalid time `tomorrow`, use a UTC time like `2024-06-01T09:00Z`".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_approve_rollup() {
+        let cmds = parse_commands("@bors approve-rollup");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::ApproveRollup));
+    }
+
+    #[test]
+    fn parse_nag() {
+        let cmds = parse_commands("@bors nag=24h");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Nag {
+                interval: Duration::from_secs(24 * 3600)
+            })
+        );
+    }
+
+    #[test]
+    fn parse_nag_invalid_interval() {
+        let cmds = parse_commands("@bors nag=often");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn parse_recent() {
+        let cmds = parse_commands("@bors recent");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Recent));
+    }
+
+    #[test]
+    fn parse_clean() {
+        let cmds = parse_commands("@bors clean");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Clean));
+    }
+
+    #[test]
+    fn parse_confirm() {
+        let cmds = parse_commands("@bors confirm 1a2b3c4d");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Confirm {
+                token: "1a2b3c4d".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn parse_confirm_missing_token() {
+        let cmds = parse_commands("@bors confirm");
+        assert_eq!(cmds.len(), 1);
+        assert!(matches!(
+            cmds[0],
+            Err(CommandParseError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn parse_bold_command() {
+        let cmds = parse_commands("**@bors ping**");
+        assert_eq!(cmds, vec![Ok(BorsCommand::Ping)]);
+    }
+
+    #[test]
+    fn parse_italic_command() {
+        let cmds = parse_commands("_@bors r+ p=1_");
+        assert_eq!(
+            cmds,
+            vec![Ok(BorsCommand::Approve {
+                priority: Some(1),
+                rollup: None,
+                team: None,
+                delay: None
+            })]
+        );
+        let cmds = parse_commands("*@bors ping*");
+        assert_eq!(cmds, vec![Ok(BorsCommand::Ping)]);
+    }
+
+    #[test]
+    fn parse_inline_code_command() {
+        let cmds = parse_commands("Please run `@bors try` once more.");
+        assert_eq!(
+            cmds,
+            vec![Ok(BorsCommand::Try {
+                parent: None,
+                workflows: vec![],
+                rebase: false
+            })]
+        );
+        let cmds = parse_commands("**`@bors ping`**");
+        assert_eq!(cmds, vec![Ok(BorsCommand::Ping)]);
+    }
+
+    #[test]
+    fn parse_unformatted_command_keeps_markers() {
+        let cmds = parse_commands("@bors set-base=release_1_");
+        assert_eq!(
+            cmds,
+            vec![Ok(BorsCommand::SetBase {
+                branch: "release_1_".to_string()
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_whoami() {
+        let cmds = parse_commands("@bors whoami");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Whoami));
+    }
+
+    #[test]
+    fn parse_why() {
+        let cmds = parse_commands("@bors why");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Why));
+    }
+
+    #[test]
+    fn parse_approve_and_delegate() {
+        let cmds = parse_commands("@bors r+ p=1 delegate+");
+        assert_eq!(
+            cmds,
+            vec![
+                Ok(BorsCommand::Approve {
+                    priority: Some(1),
+                    rollup: None,
+                    team: None,
+                    delay: None
+                }),
+                Ok(BorsCommand::Delegate {
+                    scope: DelegationScope::Review,
+                    delegatee: None
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_multiple_commands_unknown_arg() {
+        let cmds = parse_commands("@bors r+ foo delegate=try");
+        assert_eq!(
+            cmds,
+            vec![
+                Err(CommandParseError::UnknownArg("foo")),
+                Ok(BorsCommand::Delegate {
+                    scope: DelegationScope::Try,
+                    delegatee: None
+                })
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_ping_alias() {
+        let cmds = parse_commands("@bors are you there");
+        assert_eq!(cmds, vec![Ok(BorsCommand::Ping)]);
+        let cmds = parse_commands("@bors Are You there");
+        assert_eq!(cmds, vec![Ok(BorsCommand::Ping)]);
+    }
+
+    #[test]
+    fn parse_custom_alias() {
+        let parser = CommandParser::new("@bors".to_string())
+            .with_alias("please merge", "r+")
+            .unwrap();
+        let cmds = parser.parse_commands("@bors please merge p=2");
+        assert_eq!(
+            cmds,
+            vec![Ok(BorsCommand::Approve {
+                priority: Some(2),
+                rollup: None,
+                team: None,
+                delay: None
+            })]
+        );
+    }
+
+    #[test]
+    fn parse_alias_prefix_only() {
+        let cmds = parse_commands("@bors are you");
+        assert_eq!(cmds, vec![Err(CommandParseError::UnknownCommand("are"))]);
+    }
+
+    #[test]
+    fn alias_of_unknown_command() {
+        assert!(CommandParser::new("@bors".to_string())
+            .with_alias("go", "merge")
+            .is_err());
+    }
+
+    #[test]
+    fn parse_undelegate() {
+        let cmds = parse_commands("@bors delegate-");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Undelegate { scope: None }));
+    }
+
+    #[test]
+    fn parse_undelegate_alias() {
+        let cmds = parse_commands("@bors undelegate");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0], Ok(BorsCommand::Undelegate { scope: None }));
+    }
+
+    #[test]
+    fn parse_undelegate_scope() {
+        let cmds = parse_commands("@bors delegate=try-");
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(
+            cmds[0],
+            Ok(BorsCommand::Undelegate {
+                scope: Some(DelegationScope::Try)
+            })
+        );
+    }
+
+    fn parse_commands(text: &str) -> Vec<Result<BorsCommand, CommandParseError<'_>>> {
         CommandParser::new("@bors".to_string()).parse_commands(text)
     }
 }
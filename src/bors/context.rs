@@ -1,11 +1,80 @@
+use std::sync::Arc;
+
+use tokio::sync::Semaphore;
+
 use crate::bors::command::CommandParser;
 
+/// How many webhook requests can be handled by the server at the same time.
+pub const MAX_CONCURRENT_REQUESTS: usize = 100;
+
 pub struct BorsContext {
     pub parser: CommandParser,
+    pub concurrency_guard: ConcurrencyGuard,
 }
 
 impl BorsContext {
     pub fn new(parser: CommandParser) -> Self {
-        Self { parser }
+        Self {
+            parser,
+            concurrency_guard: ConcurrencyGuard::default(),
+        }
+    }
+
+    pub fn with_concurrency_guard(mut self, guard: ConcurrencyGuard) -> Self {
+        self.concurrency_guard = guard;
+        self
+    }
+}
+
+/// Limits how many requests are handled by the server at the same time, and reports how many of
+/// them are currently in flight.
+///
+/// The guard is cheaply cloneable and all clones share the same permits, so that the server can
+/// limit the requests with the same guard that the Bors process reads the load from.
+#[derive(Clone)]
+pub struct ConcurrencyGuard {
+    semaphore: Arc<Semaphore>,
+    max: usize,
+}
+
+impl ConcurrencyGuard {
+    pub fn new(max: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(max)),
+            max,
+        }
+    }
+
+    /// Semaphore whose permits are held by the requests in flight.
+    pub fn semaphore(&self) -> Arc<Semaphore> {
+        self.semaphore.clone()
+    }
+
+    /// Number of requests that are currently being handled.
+    pub fn in_flight(&self) -> usize {
+        self.max - self.semaphore.available_permits()
+    }
+}
+
+impl Default for ConcurrencyGuard {
+    fn default() -> Self {
+        Self::new(MAX_CONCURRENT_REQUESTS)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::context::ConcurrencyGuard;
+
+    #[tokio::test]
+    async fn test_concurrency_guard_in_flight() {
+        let guard = ConcurrencyGuard::new(10);
+        let semaphore = guard.semaphore();
+        let first = semaphore.clone().acquire_owned().await.unwrap();
+        let _second = semaphore.acquire_owned().await.unwrap();
+        assert_eq!(guard.in_flight(), 2);
+
+        drop(first);
+        assert_eq!(guard.in_flight(), 1);
     }
 }
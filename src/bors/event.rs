@@ -2,10 +2,12 @@ use crate::database::{WorkflowStatus, WorkflowType};
 use crate::github::{CommitSha, GithubRepoName, GithubUser, PullRequestNumber};
 use octocrab::models::RunId;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum BorsEvent {
     /// A comment was posted on a pull request.
     Comment(PullRequestComment),
+    /// A pull request was opened.
+    PullRequestOpened(PullRequestOpened),
     /// A workflow run on Github Actions or a check run from external CI system has been started.
     WorkflowStarted(WorkflowStarted),
     /// A workflow run on Github Actions or a check run from external CI system has been completed.
@@ -13,32 +15,67 @@ pub enum BorsEvent {
     /// A check suite has been completed, either as a workflow run on Github Actions, or as a
     /// workflow from some external CI system.
     CheckSuiteCompleted(CheckSuiteCompleted),
+    /// The native GitHub merge queue has created a merge group and requested its checks.
+    MergeGroupRequested(MergeGroupRequested),
+    /// A check suite of a merge group created by the native GitHub merge queue has been completed.
+    MergeGroup(MergeGroupCompleted),
+    /// The native GitHub merge queue has merged or removed a merge group.
+    MergeGroupDestroyed(MergeGroupDestroyed),
+    /// A legacy commit status has reached a final state.
+    CommitStatusChanged(CommitStatusChanged),
     /// The configuration of some repository has been changed for the bot's Github App.
     InstallationsChanged,
+    /// Some repositories have been removed from the bot's Github App.
+    RepositoriesRemoved(RepositoriesRemoved),
+    /// A repository has been renamed on GitHub.
+    RepositoryRenamed(RepositoryRenamed),
     /// Periodic event that serves for checking e.g. timeouts.
     Refresh,
+    /// Sent once after the bot has started, serves for cleaning up after a previous run of the bot.
+    Startup,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct PullRequestComment {
     pub repository: GithubRepoName,
     pub author: GithubUser,
     pub pr_number: PullRequestNumber,
     pub text: String,
+    /// ID of the comment on GitHub, if the text comes from a comment that can be edited.
+    pub comment_id: Option<u64>,
+    /// Set if the comment was edited, instead of newly posted.
+    pub edit: Option<CommentEdit>,
 }
 
-#[derive(Debug)]
+/// Describes an edit of an existing comment.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CommentEdit {
+    /// Text of the comment before the edit, if GitHub has reported it.
+    pub previous_text: Option<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct PullRequestOpened {
+    pub repository: GithubRepoName,
+    pub author: GithubUser,
+    pub pr_number: PullRequestNumber,
+    pub description: String,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct WorkflowStarted {
     pub repository: GithubRepoName,
     pub name: String,
     pub branch: String,
     pub commit_sha: CommitSha,
     pub run_id: RunId,
+    /// ID of the GitHub Actions workflow that the run belongs to. External checks do not have one.
+    pub workflow_id: Option<u64>,
     pub workflow_type: WorkflowType,
     pub url: String,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct WorkflowCompleted {
     pub repository: GithubRepoName,
     pub branch: String,
@@ -47,9 +84,68 @@ pub struct WorkflowCompleted {
     pub status: WorkflowStatus,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct CheckSuiteCompleted {
     pub repository: GithubRepoName,
     pub branch: String,
     pub commit_sha: CommitSha,
 }
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct CommitStatusChanged {
+    pub repository: GithubRepoName,
+    pub commit_sha: CommitSha,
+    /// Branches whose head is the commit.
+    pub branches: Vec<String>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RepositoriesRemoved {
+    pub repositories: Vec<GithubRepoName>,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct RepositoryRenamed {
+    pub old_name: GithubRepoName,
+    pub new_name: GithubRepoName,
+}
+
+/// Prefix of branches that GitHub creates for testing merge groups of its native merge queue.
+pub const MERGE_GROUP_BRANCH_PREFIX: &str = "gh-readonly-queue/";
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MergeGroupRequested {
+    pub repository: GithubRepoName,
+    /// Head branch of the merge group, e.g. `gh-readonly-queue/main/pr-1-<base-sha>`.
+    pub branch: String,
+    pub commit_sha: CommitSha,
+}
+
+impl MergeGroupRequested {
+    /// Returns the number of the PR that is tested by the merge group, if it can be recognized
+    /// from the name of the merge group branch.
+    pub fn pr_number(&self) -> Option<PullRequestNumber> {
+        let (_, group) = self.branch.rsplit_once('/')?;
+        let (number, _) = group.strip_prefix("pr-")?.split_once('-')?;
+        number.parse().ok().map(PullRequestNumber)
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MergeGroupCompleted {
+    pub repository: GithubRepoName,
+    /// Head branch of the merge group, e.g. `gh-readonly-queue/main/pr-1-<base-sha>`.
+    pub branch: String,
+    pub commit_sha: CommitSha,
+    /// Conclusion of the completed check suite.
+    pub status: WorkflowStatus,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct MergeGroupDestroyed {
+    pub repository: GithubRepoName,
+    pub commit_sha: CommitSha,
+    /// Whether the merge group was merged, rather than dequeued or invalidated by a change of its
+    /// base branch.
+    pub merged: bool,
+}
@@ -0,0 +1,133 @@
+use crate::bors::handlers::clean::request_confirmation;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::RepositoryClient;
+use crate::bors::RepositoryState;
+use crate::database::{ConfirmationAction, DbClient, PullRequestModel};
+use crate::github::{GithubUser, PullRequest};
+
+/// Changes the branch that bors merges the PR into. The base of an approved PR is only changed
+/// once the command is confirmed, because the reviewer has approved merging it into another
+/// branch.
+///
+/// This does not change the base branch of the PR on GitHub, it only overrides the merge target
+/// used by bors.
+pub(super) async fn command_set_base<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    branch: String,
+) -> anyhow::Result<CommandOutcome> {
+    if repo.client.get_branch_sha(&branch).await?.is_none() {
+        tracing::warn!("Branch {branch} does not exist");
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: Branch `{branch}` does not exist."
+        )));
+    }
+
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    if pr_model.approved_sha.is_some() {
+        let description = format!("merge this approved PR into `{branch}`");
+        return request_confirmation(
+            repo,
+            db,
+            pr,
+            author,
+            ConfirmationAction::SetBase(branch),
+            &description,
+        )
+        .await;
+    }
+    set_base(repo, db, pr, &pr_model, &branch).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+pub(super) async fn set_base<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+    branch: &str,
+) -> anyhow::Result<()> {
+    db.set_base_branch(pr_model, branch).await?;
+    tracing::info!("Base branch set to {branch}");
+
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":dart: This PR will be merged into `{branch}` instead of `{}`.",
+            pr.base.name
+        ),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::database::DbClient;
+    use crate::tests::event::default_pr_number;
+    use crate::tests::permissions::NoPermissions;
+    use crate::tests::state::{default_repo_name, ClientBuilder};
+
+    #[tokio::test]
+    async fn test_set_base() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_branch("release-1.2", "release-sha");
+        state.comment("@bors set-base=release-1.2").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":dart: This PR will be merged into `release-1.2` instead of `main-branch`."],
+        );
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.base_branch, Some("release-1.2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_set_base_nonexistent_branch() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors set-base=release-1.2").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":exclamation: Branch `release-1.2` does not exist."],
+        );
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.base_branch, None);
+    }
+
+    #[tokio::test]
+    async fn test_set_base_insufficient_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state.client().set_branch("release-1.2", "release-sha");
+        state.comment("@bors set-base=release-1.2").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not in review users"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_uses_base_branch() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_branch("release-1.2", "release-sha");
+        state.comment("@bors set-base=release-1.2").await;
+        state.comment("@bors try").await;
+        state
+            .client()
+            .check_branch_history("automation/bors/try-merge", &["release-sha", "sha-merged"]);
+    }
+}
@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use sha2::{Digest, Sha256};
+
+use crate::bors::handlers::base::set_base;
+use crate::bors::handlers::refresh::{elapsed_time, now};
+use crate::bors::handlers::startup::find_stale_branches;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{ConfirmationAction, DbClient};
+use crate::github::{Branch, GithubUser, PullRequest};
+
+/// How long a destructive command waits for its confirmation.
+pub(super) const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Asks for a confirmation of deleting the bors branches that are not used by any running build.
+/// The branches are only deleted once the author confirms the command with `@bors confirm`.
+pub(super) async fn command_clean<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+) -> anyhow::Result<CommandOutcome> {
+    let branches = find_stale_branches(repo, db).await?;
+    if branches.is_empty() {
+        repo.post_comment(
+            pr.number,
+            ":broom: There are no bors branches that could be deleted.",
+        )
+        .await?;
+        return Ok(CommandOutcome::Handled);
+    }
+
+    let description = format!(
+        "delete {} bors branch(es) that are not used by any running build: {}",
+        branches.len(),
+        format_branches(&branches)
+    );
+    request_confirmation(
+        repo,
+        db,
+        pr,
+        author,
+        ConfirmationAction::Clean,
+        &description,
+    )
+    .await
+}
+
+/// Stores a destructive command, which is performed once the author confirms it with
+/// `@bors confirm <token>`. `description` says what the command will do.
+pub(super) async fn request_confirmation<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    action: ConfirmationAction,
+    description: &str,
+) -> anyhow::Result<CommandOutcome> {
+    let token = confirmation_token(repo, pr, author);
+    tracing::info!("{action:?} is waiting for confirmation");
+    db.create_confirmation(
+        &repo.repository,
+        pr.number,
+        &token,
+        action,
+        &author.username,
+    )
+    .await?;
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":warning: This will {description}\nComment `@bors confirm {token}` within {} minutes to proceed.",
+            CONFIRMATION_TIMEOUT.as_secs() / 60
+        ),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Performs a destructive command that was requested by the same author with the given token.
+pub(super) async fn command_confirm<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    token: &str,
+) -> anyhow::Result<CommandOutcome> {
+    let Some(confirmation) = db
+        .find_confirmation(&repo.repository, pr.number, token)
+        .await?
+    else {
+        return Ok(CommandOutcome::Rejected(format!(
+            ":question: There is no command waiting for the confirmation `{token}`."
+        )));
+    };
+    if confirmation.author != author.username {
+        return Ok(CommandOutcome::Rejected(format!(
+            "@{}: :key: Only `{}` can confirm this command.",
+            author.username, confirmation.author
+        )));
+    }
+    db.delete_confirmation(&confirmation).await?;
+    if elapsed_time(confirmation.created_at) > CONFIRMATION_TIMEOUT {
+        tracing::info!("Confirmation {token} has expired");
+        return Ok(CommandOutcome::Rejected(format!(
+            ":hourglass: The confirmation `{token}` has expired, please run the command again."
+        )));
+    }
+
+    match confirmation.action {
+        ConfirmationAction::Clean => {
+            // Builds might have started since the command was requested, so their branches are
+            // kept
+            let branches = find_stale_branches(repo, db).await?;
+            for branch in &branches {
+                tracing::info!("Deleting branch {} at {}", branch.name, branch.sha);
+                repo.client.delete_branch(&branch.name).await?;
+            }
+            let message = if branches.is_empty() {
+                ":broom: There are no bors branches that could be deleted.".to_string()
+            } else {
+                format!(
+                    ":broom: Deleted {} bors branch(es): {}",
+                    branches.len(),
+                    format_branches(&branches)
+                )
+            };
+            repo.post_comment(pr.number, &message).await?;
+        }
+        ConfirmationAction::SetBase(branch) => {
+            let pr_model = db
+                .get_or_create_pull_request(repo.client.repository(), pr.number)
+                .await?;
+            set_base(repo, db, pr, &pr_model, &branch).await?;
+        }
+    }
+    Ok(CommandOutcome::Handled)
+}
+
+/// Creates a short token that identifies a command waiting for a confirmation.
+fn confirmation_token<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    pr: &PullRequest,
+    author: &GithubUser,
+) -> String {
+    let digest = Sha256::digest(format!(
+        "{}/{}/{}/{}",
+        repo.repository,
+        pr.number,
+        author.username,
+        now()
+    ));
+    hex::encode(digest)[..8].to_string()
+}
+
+fn format_branches(branches: &[Branch]) -> String {
+    branches
+        .iter()
+        .map(|branch| format!("`{}`", branch.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bors::handlers::clean::CONFIRMATION_TIMEOUT;
+    use crate::bors::handlers::refresh::with_mocked_time;
+    use crate::bors::RepositoryClient;
+    use crate::database::DbClient;
+    use crate::tests::event::{comment, default_pr_number, user};
+    use crate::tests::state::{default_repo_name, ClientBuilder, TestBorsState};
+
+    const STALE_BRANCH: &str = "automation/bors/auto-release";
+
+    /// Requests the deletion of a stale branch and returns the confirmation token from the reply.
+    async fn request_clean(state: &mut TestBorsState) -> String {
+        state.client().set_branch(STALE_BRANCH, "sha-stale");
+        state.comment("@bors clean").await;
+        last_token(state)
+    }
+
+    /// Returns the confirmation token from the last comment of the bot.
+    fn last_token(state: &mut TestBorsState) -> String {
+        let comment = state.client().get_last_comment(default_pr_number());
+        let (_, rest) = comment.split_once("@bors confirm ").unwrap();
+        rest.split('`').next().unwrap().to_string()
+    }
+
+    async fn stale_branch_exists(state: &mut TestBorsState) -> bool {
+        state
+            .client()
+            .get_branch_sha(STALE_BRANCH)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    #[tokio::test]
+    async fn test_clean_requires_confirmation() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let token = request_clean(&mut state).await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()).replace(&token, "<token>"),
+            @r###"
+        :warning: This will delete 1 bors branch(es) that are not used by any running build: `automation/bors/auto-release`
+        Comment `@bors confirm <token>` within 10 minutes to proceed.
+        "###
+        );
+        assert!(stale_branch_exists(&mut state).await);
+
+        state
+            .comment(format!("@bors confirm {token}").as_str())
+            .await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @":broom: Deleted 1 bors branch(es): `automation/bors/auto-release`"
+        );
+        state.client().check_branch_deleted(STALE_BRANCH);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_by_other_user() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let token = request_clean(&mut state).await;
+        state
+            .comment(comment(&format!("@bors confirm {token}")).author(user("stranger")))
+            .await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @"@stranger: :key: Only `<user>` can confirm this command."
+        );
+        assert!(stale_branch_exists(&mut state).await);
+    }
+
+    #[tokio::test]
+    async fn test_confirm_expired() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let token = request_clean(&mut state).await;
+        with_mocked_time(CONFIRMATION_TIMEOUT + Duration::from_secs(1), async {
+            state
+                .comment(format!("@bors confirm {token}").as_str())
+                .await;
+        })
+        .await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()).replace(&token, "<token>"),
+            @":hourglass: The confirmation `<token>` has expired, please run the command again."
+        );
+        assert!(stale_branch_exists(&mut state).await);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_refresh_prunes_expired_confirmations() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let token = request_clean(&mut state).await;
+        with_mocked_time(CONFIRMATION_TIMEOUT + Duration::from_secs(1), async {
+            state.refresh().await;
+        })
+        .await;
+        assert!(state
+            .db
+            .find_confirmation(&default_repo_name(), default_pr_number().into(), &token)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_set_base_of_approved_pr_requires_confirmation() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_branch("release", "release-sha");
+        state.comment("@bors r+").await;
+        state.comment("@bors set-base=release").await;
+        let token = last_token(&mut state);
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()).replace(&token, "<token>"),
+            @r###"
+        :warning: This will merge this approved PR into `release`
+        Comment `@bors confirm <token>` within 10 minutes to proceed.
+        "###
+        );
+
+        state
+            .comment(format!("@bors confirm {token}").as_str())
+            .await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @":dart: This PR will be merged into `release` instead of `main-branch`."
+        );
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.base_branch, Some("release".to_string()));
+    }
+}
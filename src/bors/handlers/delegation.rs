@@ -0,0 +1,453 @@
+use crate::bors::handlers::{insufficient_privileges, CommandOutcome};
+use crate::bors::RepositoryClient;
+use crate::bors::RepositoryState;
+use crate::database::{DbClient, DelegationScope};
+use crate::github::{GithubUser, PullRequest};
+use crate::permissions::PermissionType;
+
+/// Delegates permissions with the given `scope` on the PR to `delegatee`, or to the PR author
+/// if no delegatee is specified. Other users than the author have to be collaborators of the
+/// repository.
+///
+/// Only reviewers can delegate, a user who has received a delegation cannot delegate further.
+pub(super) async fn command_delegate<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    scope: DelegationScope,
+    delegatee: Option<String>,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(rejection) = check_reviewer(repo, author).await {
+        return Ok(rejection);
+    }
+
+    let delegatee = match delegatee {
+        Some(user) if user != pr.author.username => {
+            if !repo.client.is_collaborator(&user).await? {
+                tracing::warn!("{user} is not a collaborator");
+                return Ok(CommandOutcome::Rejected(format!(
+                    ":exclamation: @{user} is not a collaborator of this repository."
+                )));
+            }
+            user
+        }
+        _ => pr.author.username.clone(),
+    };
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    db.delegate(&pr_model, &delegatee, scope).await?;
+    db.record_command(
+        &pr_model,
+        &author.username,
+        &format!("Granted {} delegation to {delegatee}", scope_name(scope)),
+    )
+    .await?;
+    tracing::info!("Delegated {scope:?} to {delegatee}");
+
+    let message = match scope {
+        DelegationScope::Review => {
+            format!(":v: @{delegatee}, you can now approve this pull request!")
+        }
+        DelegationScope::Try => {
+            format!(":v: @{delegatee}, you can now perform try builds on this pull request!")
+        }
+    };
+    // Delegations are valid until they are removed with `delegate-`
+    let message = format!(
+        "{message}\n:memo: Granted by @{}, scope: {}, expires: never (until removed with `@bors delegate-`).",
+        author.username,
+        scope_name(scope)
+    );
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Removes the delegation with the given `scope` of the PR author, or all their delegations if
+/// no scope is specified.
+pub(super) async fn command_undelegate<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    scope: Option<DelegationScope>,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(rejection) = check_reviewer(repo, author).await {
+        return Ok(rejection);
+    }
+
+    let delegatee = &pr.author.username;
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    db.undelegate(&pr_model, delegatee, scope).await?;
+    let scope_description = scope.map_or("all", scope_name);
+    db.record_command(
+        &pr_model,
+        &author.username,
+        &format!("Revoked {scope_description} delegation of {delegatee}"),
+    )
+    .await?;
+    tracing::info!("Removed delegations {scope:?} of {delegatee}");
+
+    let message = match scope {
+        None => format!("Delegation of @{delegatee} has been removed."),
+        Some(DelegationScope::Review) => {
+            format!("Review delegation of @{delegatee} has been removed.")
+        }
+        Some(DelegationScope::Try) => format!("Try delegation of @{delegatee} has been removed."),
+    };
+    let message = format!(
+        "{message}\n:memo: Revoked by @{}, scope: {scope_description}.",
+        author.username
+    );
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Posts the list of users who have received delegated permissions on the PR.
+///
+/// The list is public, so that reviewers can audit which rights have been granted.
+pub(super) async fn command_list_delegates<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let delegations = db.get_delegations(&pr_model).await?;
+
+    let message = if delegations.is_empty() {
+        ":scroll: Nobody has delegated permissions on this PR.".to_string()
+    } else {
+        // Delegations are valid until they are removed with `delegate-`
+        let rows = delegations
+            .iter()
+            .map(|delegation| {
+                format!(
+                    "| @{} | {} | never |",
+                    delegation.delegatee,
+                    scope_name(delegation.scope)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#":scroll: Delegations of this PR:
+| User | Scope | Expires |
+|------|-------|---------|
+{rows}"#
+        )
+    };
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+fn scope_name(scope: DelegationScope) -> &'static str {
+    match scope {
+        DelegationScope::Review => "review",
+        DelegationScope::Try => "try",
+    }
+}
+
+/// Returns `true` if a delegation with the given `scope` grants the given `permission`.
+/// Access to the repository on GitHub cannot be delegated.
+pub(super) fn delegation_grants(scope: DelegationScope, permission: PermissionType) -> bool {
+    match scope {
+        DelegationScope::Review => {
+            !matches!(permission, PermissionType::Write | PermissionType::Admin)
+        }
+        DelegationScope::Try => matches!(permission, PermissionType::Try | PermissionType::Retry),
+    }
+}
+
+/// Delegations themselves are not taken into account here, so that delegated permissions cannot
+/// be delegated further.
+async fn check_reviewer<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    author: &GithubUser,
+) -> Option<CommandOutcome> {
+    if repo
+        .permissions_resolver
+        .has_permission(&author.username, PermissionType::Review)
+        .await
+    {
+        None
+    } else {
+        tracing::info!("Permission denied");
+        Some(insufficient_privileges(
+            &repo.config,
+            author,
+            PermissionType::Review,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::EntityTrait;
+
+    use entity::audit_log;
+
+    use crate::database::WorkflowStatus;
+    use crate::github::CommitSha;
+    use crate::tests::event::{comment, default_pr_author, default_pr_number, suite_failure, user};
+    use crate::tests::permissions::PermissionsFor;
+    use crate::tests::state::{default_merge_sha, ClientBuilder, TestBorsState};
+
+    async fn create_state() -> TestBorsState {
+        ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["reviewer"])))
+            .create_state()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_delegate_review() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors delegate+").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(default_pr_author()))
+            .await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @":pushpin: Commit pr-sha has been approved by `pr-author`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegate_to_other_user() {
+        let mut state = create_state().await;
+        state
+            .client()
+            .collaborators
+            .insert("contributor".to_string());
+        state
+            .comment(comment("@bors delegate+ @contributor").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("contributor")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":v: @contributor, you can now approve this pull request!\n:memo: Granted by @reviewer, scope: review, expires: never (until removed with `@bors delegate-`).",
+                ":pushpin: Commit pr-sha has been approved by `contributor`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegate_to_non_collaborator() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors delegate+ @stranger").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("stranger")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":exclamation: @stranger is not a collaborator of this repository.",
+                "@stranger: :key: Insufficient privileges: not in review users",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegate_try_allows_retry_but_not_approve() {
+        let mut state = create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state
+            .comment(comment("@bors delegate=try").author(user("reviewer")))
+            .await;
+
+        state
+            .comment(comment("@bors try").author(default_pr_author()))
+            .await;
+        state
+            .perform_workflow_events(
+                1,
+                "automation/bors/try",
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        // Retrying the build creates a new merge commit
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("sha-merged-2".to_string())));
+        state
+            .comment(comment("@bors retry").author(default_pr_author()))
+            .await;
+        state
+            .comment(comment("@bors r+").author(default_pr_author()))
+            .await;
+
+        let client = state.client();
+        let comments = (0..5)
+            .map(|index| client.get_comment(default_pr_number(), index))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(comments, @r###"
+        [
+            ":v: @pr-author, you can now perform try builds on this pull request!\n:memo: Granted by @reviewer, scope: try, expires: never (until removed with `@bors delegate-`).",
+            ":hourglass: Trying commit pr-sha with merge sha-merged…",
+            ":broken_heart: Test failed\n- [workflow-1](https://workflow-1.com) :x:",
+            ":hourglass: Trying commit pr-sha with merge sha-merged-2…",
+            "@pr-author: :key: Insufficient privileges: not in review users",
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_retry_without_failed_build() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors retry").author(user("reviewer")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":exclamation: There is no failed try build to retry."],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undelegate() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors delegate+").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors delegate-").author(user("reviewer")))
+            .await;
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @"@<user>: :key: Insufficient privileges: not in review users"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undelegate_scope() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors delegate+").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors delegate=try").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors delegate=try-").author(user("reviewer")))
+            .await;
+        state.comment("@bors delegates").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":v: @pr-author, you can now approve this pull request!\n:memo: Granted by @reviewer, scope: review, expires: never (until removed with `@bors delegate-`).",
+                ":v: @pr-author, you can now perform try builds on this pull request!\n:memo: Granted by @reviewer, scope: try, expires: never (until removed with `@bors delegate-`).",
+                "Try delegation of @pr-author has been removed.\n:memo: Revoked by @reviewer, scope: try.",
+                ":scroll: Delegations of this PR:\n| User | Scope | Expires |\n|------|-------|---------|\n| @pr-author | review | never |",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_and_delegate() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors r+ delegate+").author(user("reviewer")))
+            .await;
+        // The author can re-approve the PR later
+        state
+            .comment(comment("@bors r+").author(default_pr_author()))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `reviewer`",
+                ":v: @pr-author, you can now approve this pull request!\n:memo: Granted by @reviewer, scope: review, expires: never (until removed with `@bors delegate-`).",
+                ":pushpin: Commit pr-sha has been approved by `reviewer`, `pr-author`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_delegates() {
+        let mut state = create_state().await;
+        state
+            .client()
+            .collaborators
+            .insert("contributor".to_string());
+        state
+            .comment(comment("@bors delegate+").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors delegate=try @contributor").author(user("reviewer")))
+            .await;
+        state.comment("@bors delegates").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :scroll: Delegations of this PR:
+        | User | Scope | Expires |
+        |------|-------|---------|
+        | @pr-author | review | never |
+        | @contributor | try | never |
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_list_delegates_empty() {
+        let mut state = create_state().await;
+        state.comment("@bors delegates").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":scroll: Nobody has delegated permissions on this PR.");
+    }
+
+    #[tokio::test]
+    async fn test_delegate_insufficient_permission() {
+        let mut state = create_state().await;
+        state.comment("@bors delegate+").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not in review users"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delegation_audit_trail() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors delegate=try").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors delegate-").author(user("reviewer")))
+            .await;
+        insta::assert_snapshot!(state.client().get_comment(default_pr_number(), 0), @r###"
+        :v: @pr-author, you can now perform try builds on this pull request!
+        :memo: Granted by @reviewer, scope: try, expires: never (until removed with `@bors delegate-`).
+        "###);
+        insta::assert_snapshot!(state.client().get_comment(default_pr_number(), 1), @r###"
+        Delegation of @pr-author has been removed.
+        :memo: Revoked by @reviewer, scope: all.
+        "###);
+
+        let entries = audit_log::Entity::find()
+            .all(state.db.connection())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| format!("{}: {}", entry.author, entry.command))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(entries, @r###"
+        [
+            "reviewer: Granted try delegation to pr-author",
+            "reviewer: delegate (handled)",
+            "reviewer: Revoked all delegation of pr-author",
+            "reviewer: delegate- (handled)",
+        ]
+        "###);
+    }
+}
@@ -0,0 +1,270 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::bors::handlers::refresh::now;
+use crate::bors::handlers::tree::format_time;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::RepositoryClient;
+use crate::bors::RepositoryState;
+use crate::database::{BuildModel, BuildStatus, DbClient};
+use crate::github::{GithubRepoName, PullRequest};
+
+/// How many builds are shown by `@bors history`.
+const HISTORY_LIMIT: u64 = 5;
+
+/// From how many recent builds is the duration of a new build estimated.
+const ESTIMATE_BUILD_COUNT: u64 = 10;
+
+/// How many recent builds are needed for an estimate, a single build is not representative.
+const MIN_ESTIMATE_BUILD_COUNT: usize = 3;
+
+/// Posts a summary of the last few builds of the PR.
+pub(super) async fn command_history<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let builds = db.get_builds_for_pr(&pr_model, HISTORY_LIMIT).await?;
+
+    let message = if builds.is_empty() {
+        ":scroll: There are no builds of this PR yet.".to_string()
+    } else {
+        let rows = builds
+            .iter()
+            .map(format_build)
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            r#":scroll: Last {} build(s) of this PR:
+| Commit | Status | Duration | Triggered by |
+|--------|--------|----------|--------------|
+{rows}"#,
+            builds.len()
+        )
+    };
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+fn format_build(build: &BuildModel) -> String {
+    let status = match build.status {
+        BuildStatus::Pending => ":hourglass: pending",
+        BuildStatus::Success => ":white_check_mark: success",
+        BuildStatus::Failure => ":x: failure",
+        BuildStatus::Cancelled => ":no_entry_sign: cancelled",
+        BuildStatus::Timeouted => ":alarm_clock: timed out",
+    };
+    let duration = match build.completed_at {
+        Some(completed_at) => format_duration(completed_at - build.created_at),
+        None => "-".to_string(),
+    };
+    let triggered_by = match build.triggered_by {
+        Some(ref user) => format!("@{user}"),
+        None => "-".to_string(),
+    };
+    format!(
+        "| {} | {status} | {duration} | {triggered_by} |",
+        build.commit_sha
+    )
+}
+
+/// Estimates when a build on `branch` started at `start` completes, if it is at the given
+/// (1-based) `position` of the queue of builds on the branch, so that the builds ahead of it have
+/// to complete first. Each build is expected to take as long as the median duration of the recent
+/// successful builds on the branch. Returns `None` if there are not enough such builds.
+async fn estimate_completion(
+    repo: &GithubRepoName,
+    db: &dyn DbClient,
+    branch: &str,
+    position: usize,
+    start: DateTime<Utc>,
+) -> anyhow::Result<Option<DateTime<Utc>>> {
+    let mut durations = db
+        .get_recent_build_durations(repo, branch, ESTIMATE_BUILD_COUNT)
+        .await?;
+    if durations.len() < MIN_ESTIMATE_BUILD_COUNT {
+        return Ok(None);
+    }
+    durations.sort();
+    let middle = durations.len() / 2;
+    let median = if durations.len() % 2 == 0 {
+        (durations[middle - 1] + durations[middle]) / 2
+    } else {
+        durations[middle]
+    };
+    Ok(Some(start + median * position.max(1) as i32))
+}
+
+/// Returns a line with the estimated completion time of a build on `branch` that starts now at
+/// the given `position` of the queue, which can be appended to the message announcing the start
+/// of the build, if there is enough history for an estimate.
+pub(super) async fn build_estimate_line(
+    repo: &GithubRepoName,
+    db: &dyn DbClient,
+    branch: &str,
+    position: usize,
+) -> anyhow::Result<Option<String>> {
+    Ok(estimate_completion(repo, db, branch, position, now())
+        .await?
+        .map(|eta| format!(":stopwatch: Estimated completion: {}", format_time(eta))))
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::EntityTrait;
+
+    use entity::build;
+
+    use crate::bors::handlers::history::{estimate_completion, format_duration};
+    use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+    use crate::database::{BuildStatus, DbClient};
+    use crate::github::CommitSha;
+    use crate::tests::event::default_pr_number;
+    use crate::tests::state::{default_repo_name, ClientBuilder, TestBorsState};
+
+    /// Records a successful build on `branch` that took `duration`.
+    async fn record_build(state: &mut TestBorsState, branch: &str, sha: &str, duration: Duration) {
+        let completed_at = Utc::now().naive_utc();
+        build::Entity::insert(build::ActiveModel {
+            repository: Set(default_repo_name().to_string()),
+            branch: Set(branch.to_string()),
+            commit_sha: Set(sha.to_string()),
+            status: Set("success".to_string()),
+            created_at: Set(completed_at - duration),
+            completed_at: Set(Some(completed_at)),
+            ..Default::default()
+        })
+        .exec(state.db.connection())
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_history_empty() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors history").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":scroll: There are no builds of this PR yet."],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let db = &state.db;
+        let pr = db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        db.attach_try_build(
+            pr,
+            "try".to_string(),
+            CommitSha("sha1".to_string()),
+            "foo",
+            &[],
+        )
+        .await
+        .unwrap();
+        let pr = db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        db.update_build_status(pr.try_build.as_ref().unwrap(), BuildStatus::Failure)
+            .await
+            .unwrap();
+        db.attach_try_build(
+            pr,
+            "try".to_string(),
+            CommitSha("sha2".to_string()),
+            "bar",
+            &[],
+        )
+        .await
+        .unwrap();
+
+        state.comment("@bors history").await;
+        let comment = state.client().get_last_comment(default_pr_number());
+        assert!(comment.starts_with(":scroll: Last 2 build(s) of this PR:"));
+        let rows = comment.lines().skip(3).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], "| sha2 | :hourglass: pending | - | @bar |");
+        assert!(rows[1].starts_with("| sha1 | :x: failure | "));
+        assert!(rows[1].ends_with(" | @foo |"));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_completion() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for (sha, minutes) in [("sha1", 30), ("sha2", 10), ("sha3", 20)] {
+            record_build(&mut state, TRY_BRANCH_NAME, sha, Duration::minutes(minutes)).await;
+        }
+        // Builds on other branches are not taken into account
+        record_build(
+            &mut state,
+            "automation/bors/auto",
+            "sha4",
+            Duration::minutes(1),
+        )
+        .await;
+
+        let repo = default_repo_name();
+        let start = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+        let estimate =
+            |position| estimate_completion(&repo, &state.db, TRY_BRANCH_NAME, position, start);
+        // Each build is expected to take the median duration
+        assert_eq!(
+            estimate(1).await.unwrap(),
+            Some(start + Duration::minutes(20))
+        );
+        assert_eq!(
+            estimate(3).await.unwrap(),
+            Some(start + Duration::minutes(60))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_build_estimate() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for (sha, minutes) in [("sha1", 30), ("sha2", 10), ("sha3", 20)] {
+            record_build(&mut state, TRY_BRANCH_NAME, sha, Duration::minutes(minutes)).await;
+        }
+        state.comment("@bors try").await;
+        let comment = state.client().get_last_comment(default_pr_number());
+        let lines = comment.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            ":hourglass: Trying commit pr-sha with merge sha-merged…"
+        );
+        assert!(lines[1].starts_with(":stopwatch: Estimated completion: "));
+    }
+
+    #[tokio::test]
+    async fn test_try_build_no_estimate_without_enough_history() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for (sha, minutes) in [("sha1", 30), ("sha2", 10)] {
+            record_build(&mut state, TRY_BRANCH_NAME, sha, Duration::minutes(minutes)).await;
+        }
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::seconds(42)), "42s");
+        assert_eq!(format_duration(Duration::seconds(185)), "3m 5s");
+    }
+}
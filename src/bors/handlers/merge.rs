@@ -0,0 +1,1471 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use anyhow::anyhow;
+use chrono::Utc;
+
+use crate::bors::handlers::history::build_estimate_line;
+use crate::bors::handlers::review::dismiss_stale_approval;
+use crate::bors::handlers::trybuild::{auto_merge_commit_message, merge_conflict_message};
+use crate::bors::handlers::workflow::{format_workflow_list, report_check_run};
+use crate::bors::handlers::{
+    check_skip_marker, command_permission, has_permission, skips_ci, CommandOutcome,
+    SKIPPED_CI_NOTE,
+};
+use crate::bors::{
+    CheckRun, CheckSuiteStatus, CommitComparisonStatus, RepositoryClient, RepositoryState,
+};
+use crate::config::{PermissionedCommand, RepositoryConfig};
+use crate::database::{
+    BuildModel, BuildStatus, CheckRunStatus, DbClient, PullRequestModel, TreeState, WorkflowModel,
+    WorkflowStatus,
+};
+use crate::github::{CommitSha, MergeError, PullRequest};
+
+// This branch serves for preparing the merge commit of an approved PR.
+// Same as with try builds, updating it is not atomic, so it should not run CI checks.
+pub(super) const AUTO_MERGE_BRANCH_NAME: &str = "automation/bors/auto-merge";
+
+// This branch should run CI checks of the merge commit. It is shared by all base branches that do
+// not have their own auto branch configured in `auto_branches`.
+pub(super) const AUTO_BRANCH_NAME: &str = "automation/bors/auto";
+
+/// Name of the branch that runs the merge builds of PRs that are merged into `base`. Base branches
+/// with different auto branches have independent merge queues.
+pub(super) fn auto_branch_name<'a>(config: &'a RepositoryConfig, base: &str) -> &'a str {
+    config
+        .auto_branches
+        .get(base)
+        .map(String::as_str)
+        .unwrap_or(AUTO_BRANCH_NAME)
+}
+
+/// Does this branch run merge builds?
+pub(super) fn is_auto_branch(config: &RepositoryConfig, branch: &str) -> bool {
+    branch == AUTO_BRANCH_NAME || config.auto_branches.values().any(|auto| auto == branch)
+}
+
+/// Starts a merge build of the next PR in the merge queue of each base branch, unless a merge
+/// build is already running for that base branch.
+pub(super) async fn process_merge_queue<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    let tree_state = db.get_tree_state(&repo.repository).await?;
+    if let TreeState::Closed { priority: None, .. } = tree_state {
+        tracing::debug!("The tree is closed");
+        return Ok(());
+    }
+
+    let mut queue = db.list_queued_prs(&repo.repository).await?;
+    promote_due_prs(repo, db, &mut queue, &tree_state).await?;
+    promote_parked_prs(repo, db, &mut queue).await?;
+    // Auto branches of the base branches whose merge build is in progress
+    let mut busy_branches = queue
+        .iter()
+        .filter_map(|pr| match pr.auto_build {
+            Some(ref build) if build.status == BuildStatus::Pending => Some(build.branch.clone()),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+
+    // PRs whose merge build has already finished wait until they are approved again
+    let mut candidates = queue
+        .into_iter()
+        .filter(|pr| pr.auto_build.is_none() && !pr.parked && pr.not_before.is_none())
+        // While the tree is closed, only PRs with a high enough priority are merged
+        .filter(|pr| tree_state.allows(pr.priority))
+        .collect::<Vec<_>>();
+    if repo.config.round_robin_authors {
+        let served_authors = db.list_served_authors(&repo.repository).await?;
+        candidates = rotate_authors(candidates, &served_authors);
+    }
+    for pr in candidates {
+        // Avoid loading PRs whose base branch is known to be busy
+        if let Some(ref base_branch) = pr.base_branch {
+            if busy_branches.contains(auto_branch_name(&repo.config, base_branch)) {
+                continue;
+            }
+        }
+        start_auto_build(repo, db, pr, &mut busy_branches).await?;
+    }
+    Ok(())
+}
+
+/// Orders the PRs of each priority in rounds, so that merge builds rotate across the authors of the
+/// PRs: each round contains at most one PR of each author. Within a round, authors go in the order
+/// in which their last merge build has started (`served_authors`), and authors without a started
+/// build go first. Otherwise, the order of the queue is kept.
+fn rotate_authors(
+    queue: Vec<PullRequestModel>,
+    served_authors: &[String],
+) -> Vec<PullRequestModel> {
+    let mut rounds: HashMap<(i32, Option<String>), usize> = HashMap::new();
+    let mut prs = queue
+        .into_iter()
+        .enumerate()
+        .map(|(index, pr)| {
+            let round = rounds.entry((pr.priority, pr.author.clone())).or_default();
+            let served = pr
+                .author
+                .as_ref()
+                .and_then(|author| served_authors.iter().position(|served| served == author));
+            let key = (Reverse(pr.priority), *round, served, index);
+            *round += 1;
+            (key, pr)
+        })
+        .collect::<Vec<_>>();
+    prs.sort_by_key(|(key, _)| *key);
+    prs.into_iter().map(|(_, pr)| pr).collect()
+}
+
+/// Lets delayed PRs into the merge queue once their delay has elapsed.
+///
+/// While the tree is closed for the priority of a PR, the PR stays delayed. A PR that waits for
+/// space in the merge queue is announced once it enters it.
+async fn promote_due_prs<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    queue: &mut [PullRequestModel],
+    tree_state: &TreeState,
+) -> anyhow::Result<()> {
+    let now = Utc::now();
+    for pr in queue.iter_mut().filter(|pr| {
+        matches!(pr.not_before, Some(not_before) if not_before <= now)
+            && tree_state.allows(pr.priority)
+    }) {
+        db.set_not_before(pr, None).await?;
+        pr.not_before = None;
+        tracing::info!("Delay of PR {} has elapsed", pr.number);
+        if pr.parked {
+            continue;
+        }
+        repo.post_status_comment(
+            db,
+            pr,
+            ":alarm_clock: The delay of this PR has elapsed, it has entered the merge queue.",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Does the queued PR take up space in the merge queue? Delayed PRs and PRs whose merge build has
+/// finished without merging them wait for something else than the queue, so they do not count
+/// towards `max_queue_size`.
+pub(super) fn occupies_queue(pr: &PullRequestModel) -> bool {
+    !pr.parked
+        && pr.not_before.is_none()
+        && pr
+            .auto_build
+            .as_ref()
+            .is_none_or(|build| build.status == BuildStatus::Pending)
+}
+
+/// Lets PRs that wait for space into the merge queue, in the queue order, as long as the queue
+/// is not full.
+async fn promote_parked_prs<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    queue: &mut [PullRequestModel],
+) -> anyhow::Result<()> {
+    let active = queue.iter().filter(|pr| occupies_queue(pr)).count();
+    let mut free_space = match repo.config.max_queue_size {
+        Some(max_size) => max_size.saturating_sub(active),
+        None => usize::MAX,
+    };
+    for pr in queue.iter_mut().filter(|pr| pr.parked) {
+        if free_space == 0 {
+            break;
+        }
+        db.set_parked(pr, false).await?;
+        pr.parked = false;
+        free_space -= 1;
+        tracing::info!("PR {} entered the merge queue", pr.number);
+        repo.post_status_comment(
+            db,
+            pr,
+            ":arrow_forward: There is space in the merge queue now, this PR has entered it.",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Merges the approved commit of the PR into its base branch and starts CI on the result, unless
+/// the auto branch of the base branch is in `busy_branches`. Once the build has started, the auto
+/// branch is added to `busy_branches`.
+///
+/// The build is not started if the PR has been removed from the queue, e.g. because its head has
+/// changed since the approval, or if it has the do-not-merge label.
+async fn start_auto_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr_model: PullRequestModel,
+    busy_branches: &mut HashSet<String>,
+) -> anyhow::Result<()> {
+    let approved_sha = CommitSha(
+        pr_model
+            .approved_sha
+            .clone()
+            .ok_or_else(|| anyhow!("PR {} is not approved", pr_model.number))?,
+    );
+    let pr = repo.client.get_pull_request(pr_model.number).await?;
+    let base_branch = pr_model
+        .base_branch
+        .clone()
+        .unwrap_or_else(|| pr.base.name.clone());
+    let auto_branch = auto_branch_name(&repo.config, &base_branch).to_string();
+    if busy_branches.contains(&auto_branch) {
+        tracing::debug!("A merge build into {base_branch} is already in progress");
+        return Ok(());
+    }
+    if dismiss_stale_approval(repo, db, &pr, &pr_model).await? {
+        return Ok(());
+    }
+    // The marker could have been added after the PR was approved
+    if let Some(CommandOutcome::Rejected(message)) = check_skip_marker(repo, &pr).await? {
+        db.dequeue(&pr_model).await?;
+        repo.post_comment(pr_model.number, &message).await?;
+        return Ok(());
+    }
+    // The PR stays in the queue, so that it is merged once the label is removed
+    let do_not_merge = &repo.config.do_not_merge_label;
+    if pr
+        .labels
+        .iter()
+        .any(|label| label.eq_ignore_ascii_case(do_not_merge))
+    {
+        tracing::info!("PR {} is labeled with {do_not_merge}", pr_model.number);
+        return Ok(());
+    }
+    let skip_ci = skips_ci(repo, &pr).await?;
+    let base_sha = match pr_model.base_branch {
+        Some(_) => repo
+            .client
+            .get_branch_sha(&base_branch)
+            .await?
+            .ok_or_else(|| anyhow!("Base branch {base_branch} does not exist"))?,
+        None => pr.base.sha.clone(),
+    };
+    let approvers = db
+        .get_approvals(&pr_model)
+        .await?
+        .into_iter()
+        .filter(|approval| approval.commit_sha == approved_sha.0)
+        .map(|approval| approval.reviewer().to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    repo.client
+        .set_branch_to_sha(AUTO_MERGE_BRANCH_NAME, &base_sha)
+        .await
+        .map_err(|error| anyhow!("Cannot set auto merge branch to {base_sha}: {error:?}"))?;
+
+    match repo
+        .client
+        .merge_branches(
+            AUTO_MERGE_BRANCH_NAME,
+            &approved_sha,
+            &merge_commit_message(&repo.config, &pr, &approvers),
+        )
+        .await
+    {
+        Ok(merge_sha) => {
+            tracing::debug!("Merge successful, SHA: {merge_sha}");
+            // The auto branch runs CI, so it is not touched if the build skips it
+            if !skip_ci {
+                repo.client
+                    .set_branch_to_sha(&auto_branch, &merge_sha)
+                    .await
+                    .map_err(|error| {
+                        anyhow!("Cannot set branch {auto_branch} to {merge_sha}: {error:?}")
+                    })?;
+            }
+
+            db.attach_auto_build(
+                &pr_model,
+                auto_branch.clone(),
+                merge_sha.clone(),
+                &approvers,
+            )
+            .await?;
+            // Nothing that CI would test has changed, so the build is completed right away. It
+            // still has to pass the same checks as a tested build before it is merged.
+            if skip_ci {
+                tracing::info!("Merge build of PR {} skips CI", pr_model.number);
+                // The base branch might move, so other PRs are merged into it in the next round
+                busy_branches.insert(auto_branch);
+                let number = pr_model.number;
+                let mut pr_model = db
+                    .get_or_create_pull_request(&repo.repository, number)
+                    .await?;
+                let build = pr_model
+                    .auto_build
+                    .take()
+                    .ok_or_else(|| anyhow!("Merge build of PR {number} was not attached"))?;
+                let result = land_auto_build(repo, db, pr_model, build, vec![], false, true).await;
+                if result.is_err() {
+                    // No workflow would complete the build later, so it must not stay pending
+                    let pr_model = db
+                        .get_or_create_pull_request(&repo.repository, number)
+                        .await?;
+                    if let Some(build) = pr_model
+                        .auto_build
+                        .filter(|build| build.status == BuildStatus::Pending)
+                    {
+                        db.update_build_status(&build, BuildStatus::Failure).await?;
+                    }
+                }
+                return result;
+            }
+            tracing::info!(
+                "Merge build of PR {} into {base_branch} started",
+                pr_model.number
+            );
+
+            let mut message =
+                format!(":hourglass: Testing commit {approved_sha} with merge {merge_sha}…");
+            // The PRs ahead of this one in the queue into the same base branch are merged first
+            let position = db
+                .list_queued_prs(&repo.repository)
+                .await?
+                .iter()
+                .filter(|queued| {
+                    queued
+                        .base_branch
+                        .as_ref()
+                        .is_none_or(|branch| *branch == base_branch)
+                })
+                .position(|queued| queued.number == pr_model.number)
+                .map_or(1, |index| index + 1);
+            if let Some(estimate) =
+                build_estimate_line(&repo.repository, db, &auto_branch, position).await?
+            {
+                message.push_str(&format!("\n{estimate}"));
+            }
+            repo.post_status_comment(db, &pr_model, &message).await?;
+            busy_branches.insert(auto_branch);
+            Ok(())
+        }
+        Err(MergeError::Conflict) => {
+            tracing::warn!("Merge conflict of PR {}", pr_model.number);
+            db.dequeue(&pr_model).await?;
+            repo.post_comment(pr_model.number, &merge_conflict_message(&pr.head.name))
+                .await?;
+            Ok(())
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Reacts to a finished merge build. If it was successful, the base branch is fast-forwarded to
+/// the tested merge commit, provided that all status checks required by its branch protection
+/// have passed. Then the next merge build is started.
+pub(super) async fn complete_auto_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: PullRequestModel,
+    build: BuildModel,
+    workflows: Vec<WorkflowModel>,
+    has_failure: bool,
+) -> anyhow::Result<()> {
+    land_auto_build(repo, db, pr, build, workflows, has_failure, false).await?;
+    process_merge_queue(repo, db).await
+}
+
+/// Merges the commit of a finished merge build into the base branch, unless the build has failed
+/// or the merge would bypass the protection of the base branch. If `skipped_ci` is set, no CI
+/// has run for the build, because the PR only changes paths that are excluded from it.
+async fn land_auto_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: PullRequestModel,
+    build: BuildModel,
+    workflows: Vec<WorkflowModel>,
+    has_failure: bool,
+    skipped_ci: bool,
+) -> anyhow::Result<()> {
+    // The approval could have been dismissed while the build was running
+    if pr.approved_sha.is_none() {
+        tracing::warn!(
+            "PR {} is no longer approved, its build is not merged",
+            pr.number
+        );
+        db.update_build_status(&build, BuildStatus::Cancelled)
+            .await?;
+        report_check_run(repo, db, &build, CheckRunStatus::Cancelled).await;
+        return Ok(());
+    }
+
+    let workflow_list = format_workflow_list(&workflows);
+    if has_failure {
+        tracing::info!("Merge build failed");
+        db.update_build_status(&build, BuildStatus::Failure).await?;
+        repo.post_event_comment(
+            db,
+            &pr,
+            &format!(
+                r#":broken_heart: Test failed
+{workflow_list}"#
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let base_branch = match &pr.base_branch {
+        Some(branch) => branch.clone(),
+        None => repo.client.get_pull_request(pr.number).await?.base.name,
+    };
+
+    // Make sure that the merge does not bypass the protection of the base branch
+    if let Some(protection) = repo.client.get_branch_protection(&base_branch).await? {
+        let check_runs = repo
+            .client
+            .get_check_runs_for_ref(&build.commit_sha)
+            .await?;
+        let passed_workflows = workflows
+            .iter()
+            .filter(|w| w.status == WorkflowStatus::Success)
+            .map(|w| w.name.as_str())
+            .collect::<HashSet<_>>();
+        let missing_checks =
+            failed_required_checks(&protection.required_checks, &passed_workflows, &check_runs)
+                .into_iter()
+                .map(|check| format!("`{check}`"))
+                .collect::<Vec<_>>();
+        if !missing_checks.is_empty() {
+            tracing::warn!("Required checks {missing_checks:?} did not pass");
+            db.update_build_status(&build, BuildStatus::Failure).await?;
+            let mut message = format!(
+                ":no_entry: Cannot merge into `{base_branch}`, the following required check(s) did not pass: {}",
+                missing_checks.join(", ")
+            );
+            // A build that has skipped CI has no workflows to list
+            if !workflow_list.is_empty() {
+                message.push_str(&format!("\n{workflow_list}"));
+            }
+            repo.post_comment(pr.number, &message).await?;
+            return Ok(());
+        }
+    }
+
+    // The approvers could have lost access to the repository since they have approved the PR
+    let revoked = revoked_approvers(repo, db, &pr).await?;
+    if !revoked.is_empty() {
+        tracing::warn!("Approvers {revoked:?} are no longer allowed to approve the PR");
+        db.update_build_status(&build, BuildStatus::Cancelled)
+            .await?;
+        report_check_run(repo, db, &build, CheckRunStatus::Cancelled).await;
+        db.dequeue(&pr).await?;
+        let revoked = revoked
+            .iter()
+            .map(|approver| format!("`{approver}`"))
+            .collect::<Vec<_>>();
+        repo.post_comment(
+            pr.number,
+            &format!(
+                ":no_entry: The approval of this PR has been dismissed, because the following approver(s) are no longer allowed to approve it: {}. Please approve it again.",
+                revoked.join(", ")
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let sha = CommitSha(build.commit_sha.clone());
+    let comparison = repo.client.compare_commits(&base_branch, &sha.0).await?;
+    let result = match comparison.status {
+        CommitComparisonStatus::Ahead => {
+            repo.client
+                .set_branch_to_sha(&base_branch, &sha)
+                .await
+                .map_err(|error| {
+                    anyhow!("Cannot fast-forward {base_branch} to {sha}: {error:?}")
+                })?;
+            tracing::info!("Merged {sha} into {base_branch}");
+            format!("Pushing {sha} to `{base_branch}`.")
+        }
+        // The base branch already contains the tested commit, there is nothing to push
+        CommitComparisonStatus::Identical | CommitComparisonStatus::Behind => {
+            tracing::info!("{base_branch} already contains {sha}");
+            format!("`{base_branch}` already contains {sha}.")
+        }
+        // The base branch has moved since the build has started, so the merge commit cannot be
+        // fast-forwarded and it has to be tested again on top of the new base
+        CommitComparisonStatus::Diverged => {
+            tracing::warn!("{base_branch} has moved, testing {sha} again");
+            db.update_build_status(&build, BuildStatus::Cancelled)
+                .await?;
+            // The PR keeps its approval time, and thus its place in the merge queue
+            db.requeue(&pr).await?;
+            let message = format!(
+                ":arrows_counterclockwise: `{base_branch}` has moved by {} commit(s) since the merge build has started, the PR will be tested again.",
+                comparison.behind_by
+            );
+            repo.post_status_comment(db, &pr, &message).await?;
+            return Ok(());
+        }
+    };
+
+    db.update_build_status(&build, BuildStatus::Success).await?;
+    let message = if skipped_ci {
+        format!(":fast_forward: {SKIPPED_CI_NOTE}\n{result}")
+    } else {
+        format!(
+            r#":sunny: Test successful
+{workflow_list}
+{result}"#
+        )
+    };
+    repo.post_status_comment(db, &pr, &message).await?;
+    finish_merge(repo, db, &pr, &sha).await
+}
+
+/// Marks the PR and the members of its rollup as merged after `sha` has been pushed to the base
+/// branch, and performs the configured post-merge actions.
+async fn finish_merge<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequestModel,
+    sha: &CommitSha,
+) -> anyhow::Result<()> {
+    db.dequeue(pr).await?;
+    db.mark_merged(pr).await?;
+    // The members of a rollup have been merged as a part of it, so they must not be merged again.
+    // Members whose approval has been dismissed since the rollup was approved are left alone, a
+    // reviewer has to decide what happens with them.
+    let mut merged_members = 0;
+    for member in &pr.rollup_members {
+        let member = db
+            .get_or_create_pull_request(&repo.repository, *member)
+            .await?;
+        if member.approved_sha.is_none() {
+            tracing::warn!(
+                "Rollup member {} is not approved anymore, it is not marked as merged",
+                member.number
+            );
+            continue;
+        }
+        db.dequeue(&member).await?;
+        db.mark_merged(&member).await?;
+        merged_members += 1;
+    }
+    if merged_members > 0 {
+        tracing::info!("Marked {merged_members} rollup member(s) as merged");
+    }
+    if repo.config.merged_comment {
+        repo.post_status_comment(db, pr, &format!(":tada: Merged in {sha}."))
+            .await?;
+    }
+    // GitHub closes the PR once its commits reach the base branch, but e.g. a merge commit that
+    // was rebased by a merge strategy does not contain them, so the PR would be left open
+    if repo.config.close_merged_prs && !repo.client.get_pull_request(pr.number).await?.closed {
+        tracing::info!("Closing merged PR");
+        repo.client.close_pull_request(pr.number).await?;
+    }
+    Ok(())
+}
+
+/// Returns the commit message of the merge of the PR, rendered from the template configured in
+/// the repository, if there is one.
+fn merge_commit_message(config: &RepositoryConfig, pr: &PullRequest, approvers: &str) -> String {
+    let Some(template) = &config.merge_commit_message else {
+        return auto_merge_commit_message(pr, approvers);
+    };
+    // The placeholders are replaced in a single pass, so that e.g. a `{body}` in the title of the
+    // PR is kept as it is
+    let mut message = String::new();
+    let mut rest = template.as_str();
+    while let Some((before, after)) = rest.split_once('{') {
+        let Some((placeholder, after)) = after.split_once('}') else {
+            break;
+        };
+        message.push_str(before);
+        match placeholder {
+            "title" => message.push_str(&pr.title),
+            "number" => message.push_str(&pr.number.to_string()),
+            "approver" => message.push_str(approvers),
+            "body" => message.push_str(&pr.message),
+            _ => message.push_str(&format!("{{{placeholder}}}")),
+        }
+        rest = after;
+    }
+    message.push_str(rest);
+    message
+}
+
+/// Returns the required checks that have not passed. A check has passed if all its check runs
+/// have succeeded. Checks without check runs have passed if a workflow with the same name has
+/// succeeded.
+fn failed_required_checks<'a>(
+    required_checks: &'a [String],
+    passed_workflows: &HashSet<&str>,
+    check_runs: &[CheckRun],
+) -> Vec<&'a str> {
+    required_checks
+        .iter()
+        .map(|check| check.as_str())
+        .filter(|&check| {
+            let mut runs = check_runs.iter().filter(|run| run.name == check).peekable();
+            let passed = if runs.peek().is_some() {
+                runs.all(|run| run.status == CheckSuiteStatus::Success)
+            } else {
+                passed_workflows.contains(check)
+            };
+            !passed
+        })
+        .collect()
+}
+
+/// Returns the approvers of the approved commit of the PR who are no longer allowed to approve it.
+async fn revoked_approvers<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequestModel,
+) -> anyhow::Result<Vec<String>> {
+    let Some(approved_sha) = &pr.approved_sha else {
+        return Ok(vec![]);
+    };
+    let permission = command_permission(&repo.config, PermissionedCommand::Approve);
+
+    let mut revoked = vec![];
+    for approval in db.get_approvals(pr).await? {
+        if approval.commit_sha != *approved_sha {
+            continue;
+        }
+        // Approvals recorded before their author was tracked are checked by the approver
+        let author = approval.author.as_deref().unwrap_or(&approval.approver);
+        if !has_permission(repo, db, pr, author, permission).await? {
+            revoked.push(approval.approver);
+        }
+    }
+    Ok(revoked)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use chrono::{Duration, Utc};
+
+    use crate::bors::handlers::merge::{failed_required_checks, AUTO_BRANCH_NAME};
+    use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+    use crate::bors::{CheckRun, CheckSuiteStatus, CommitComparisonStatus, RepositoryClient};
+    use crate::database::{BuildStatus, CheckRunStatus, DbClient, WorkflowStatus};
+    use crate::github::CommitSha;
+    use crate::tests::event::{comment, default_pr_number, suite_failure, suite_success, user};
+    use crate::tests::github::{BranchBuilder, PRBuilder};
+    use crate::tests::permissions::PermissionsFor;
+    use crate::tests::state::{
+        default_merge_sha, default_repo_name, ClientBuilder, RepoConfigBuilder, TestBorsState,
+    };
+
+    #[tokio::test]
+    async fn test_merge_queue_start_build() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        state.refresh().await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Testing commit pr-sha with merge sha-merged…");
+        state
+            .client()
+            .check_branch_history(AUTO_BRANCH_NAME, &["sha-merged"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_success() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Test successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Pushing sha-merged to `main-branch`.
+        "###);
+        state
+            .client()
+            .check_branch_history("main-branch", &["sha-merged"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_success_comment_and_close() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .merged_comment(true)
+                    .close_merged_prs(true),
+            )
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        merge_with_success(&mut state).await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":tada: Merged in sha-merged.");
+        state.client().check_pr_closed(default_pr_number(), true);
+    }
+
+    #[tokio::test]
+    async fn test_merge_success_keeps_closed_pr() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().close_merged_prs(true))
+            .create_state()
+            .await;
+        state.client().get_pr_fn =
+            Box::new(|pr| Ok(PRBuilder::default().number(pr.0).closed(true).create()));
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        merge_with_success(&mut state).await;
+
+        state.client().check_pr_closed(default_pr_number(), false);
+    }
+
+    #[tokio::test]
+    async fn test_merge_docs_only_skips_ci() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_ci_paths(vec!["docs/**".to_string()]))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_pr_files(default_pr_number(), &["docs/guide.md"]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :fast_forward: Docs-only change, skipping CI: all files changed by this PR match `skip_ci_paths`.
+        Pushing sha-merged to `main-branch`.
+        "###);
+        state
+            .client()
+            .check_branch_history("main-branch", &["sha-merged"]);
+        assert_eq!(
+            state
+                .client()
+                .get_branch_sha(AUTO_BRANCH_NAME)
+                .await
+                .unwrap(),
+            None
+        );
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert!(pr.merged_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_merge_docs_only_respects_branch_protection() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_ci_paths(vec!["docs/**".to_string()]))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_pr_files(default_pr_number(), &["docs/guide.md"]);
+        state
+            .client()
+            .set_branch_protection("main-branch", &["workflow-1"]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":no_entry: Cannot merge into `main-branch`, the following required check(s) did not pass: `workflow-1`");
+        state.client().check_branch_not_created("main-branch");
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.auto_build.unwrap().status, BuildStatus::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_merge_base_identical() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.client().set_comparison(
+            "main-branch",
+            &default_merge_sha(),
+            CommitComparisonStatus::Identical,
+            0,
+            0,
+        );
+        merge_with_success(&mut state).await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Test successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        `main-branch` already contains sha-merged.
+        "###);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_merge_base_behind() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.client().set_comparison(
+            "main-branch",
+            &default_merge_sha(),
+            CommitComparisonStatus::Behind,
+            0,
+            2,
+        );
+        merge_with_success(&mut state).await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Test successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        `main-branch` already contains sha-merged.
+        "###);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_merge_base_diverged() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.client().set_comparison(
+            "main-branch",
+            &default_merge_sha(),
+            CommitComparisonStatus::Diverged,
+            1,
+            1,
+        );
+        state.comment("@bors r+").await;
+        let approved_at = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap()
+            .approved_at;
+        state.refresh().await;
+        // The new base produces a different merge commit
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("sha-merged-2".to_string())));
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        let client = state.client();
+        let comments = (2..4)
+            .map(|index| client.get_comment(default_pr_number(), index))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(comments, @r###"
+        [
+            ":arrows_counterclockwise: `main-branch` has moved by 1 commit(s) since the merge build has started, the PR will be tested again.",
+            ":hourglass: Testing commit pr-sha with merge sha-merged-2…",
+        ]
+        "###);
+        state
+            .client()
+            .check_branch_history(AUTO_BRANCH_NAME, &["sha-merged", "sha-merged-2"]);
+        // The PR keeps its place in the merge queue
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.approved_at, approved_at);
+    }
+
+    #[tokio::test]
+    async fn test_merge_repeated_failures_unapprove() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for attempt in 1..=3 {
+            let sha = format!("sha-merged-{attempt}");
+            state.client().set_checks(&sha, &[suite_failure()]);
+            let merge_sha = sha.clone();
+            state.client().merge_branches_fn = Box::new(move || Ok(CommitSha(merge_sha.clone())));
+            state.comment("@bors r+").await;
+            state.refresh().await;
+            state
+                .perform_workflow_events(attempt, AUTO_BRANCH_NAME, &sha, WorkflowStatus::Failure)
+                .await;
+        }
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":no_entry_sign: The builds of this PR have failed 3 times in a row. The approval has been dismissed. Please fix the failures before starting another build.");
+        // The PR is not tested again
+        state.refresh().await;
+        state.client().check_branch_history(
+            AUTO_BRANCH_NAME,
+            &["sha-merged-1", "sha-merged-2", "sha-merged-3"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_failures_are_counted_separately() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for attempt in 1..=3 {
+            let sha = format!("sha-merged-{attempt}");
+            state.client().set_checks(&sha, &[suite_failure()]);
+            let merge_sha = sha.clone();
+            state.client().merge_branches_fn = Box::new(move || Ok(CommitSha(merge_sha.clone())));
+            let branch = if attempt < 3 {
+                state.comment("@bors try").await;
+                TRY_BRANCH_NAME
+            } else {
+                state.comment("@bors r+").await;
+                state.refresh().await;
+                AUTO_BRANCH_NAME
+            };
+            state
+                .perform_workflow_events(attempt, branch, &sha, WorkflowStatus::Failure)
+                .await;
+        }
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.consecutive_failures, 2);
+        assert_eq!(pr.consecutive_auto_failures, 1);
+        assert!(!state
+            .client()
+            .get_last_comment(default_pr_number())
+            .contains("times in a row"));
+    }
+
+    #[tokio::test]
+    async fn test_merge_repeated_failures_disabled() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().max_consecutive_failures(0))
+            .create_state()
+            .await;
+        for attempt in 1..=3 {
+            let sha = format!("sha-merged-{attempt}");
+            state.client().set_checks(&sha, &[suite_failure()]);
+            let merge_sha = sha.clone();
+            state.client().merge_branches_fn = Box::new(move || Ok(CommitSha(merge_sha.clone())));
+            state.comment("@bors r+").await;
+            state.refresh().await;
+            state
+                .perform_workflow_events(attempt, AUTO_BRANCH_NAME, &sha, WorkflowStatus::Failure)
+                .await;
+        }
+        assert!(!state
+            .client()
+            .get_last_comment(default_pr_number())
+            .contains("times in a row"));
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.consecutive_auto_failures, 3);
+    }
+
+    async fn merge_with_success(state: &mut TestBorsState) {
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_full() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().max_queue_size(Some(2)))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        for pr in 1..=3 {
+            state.comment(comment("@bors r+").pr_number(pr)).await;
+        }
+        state.refresh().await;
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("sha-merged-2".to_string())));
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        state.client().check_comments(
+            3,
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`. The merge queue is full, the PR will enter it once there is space.",
+                ":arrow_forward: There is space in the merge queue now, this PR has entered it.",
+            ],
+        );
+        // The next PR in the queue is tested
+        insta::assert_snapshot!(state.client().get_last_comment(2), @":hourglass: Testing commit pr-sha with merge sha-merged-2…");
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_full_ignores_failed_and_delayed_prs() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().max_queue_size(Some(1)))
+            .create_state()
+            .await;
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        state.comment(comment("@bors delay=1h").pr_number(2)).await;
+
+        // Neither the failed PR 1 nor the delayed PR 2 can make progress
+        state.comment(comment("@bors r+").pr_number(3)).await;
+        insta::assert_snapshot!(state.client().get_last_comment(3), @":pushpin: Commit pr-sha has been approved by `<user>`");
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), 3.into())
+            .await
+            .unwrap();
+        assert!(!pr.parked);
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_round_robin_authors() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().round_robin_authors(true))
+            .create_state()
+            .await;
+        // PRs 1 and 2 are authored by `alice`, PR 3 by `bob`
+        state.client().get_pr_fn = Box::new(|pr| {
+            let author = if pr.0 == 3 { "bob" } else { "alice" };
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .author(user(author))
+                .create())
+        });
+        let builds = AtomicU64::new(0);
+        state.client().merge_branches_fn = Box::new(move || {
+            let build = builds.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(CommitSha(format!("merge-{build}")))
+        });
+        for pr in 1..=3 {
+            state.comment(comment("@bors r+").pr_number(pr)).await;
+        }
+        state.refresh().await;
+
+        // The builds alternate between the authors, even though PR 2 was approved before PR 3
+        let mut started = vec![];
+        for build in 1..=3 {
+            let pr = (1..=3)
+                .find(|&pr| {
+                    state.client().get_last_comment(pr).starts_with(&format!(
+                        ":hourglass: Testing commit pr-sha with merge merge-{build}…"
+                    ))
+                })
+                .unwrap();
+            started.push(pr);
+            let sha = format!("merge-{build}");
+            state.client().set_checks(&sha, &[suite_success()]);
+            state
+                .perform_workflow_events(build, AUTO_BRANCH_NAME, &sha, WorkflowStatus::Success)
+                .await;
+        }
+        assert_eq!(started, vec![1, 3, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_custom_commit_message() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .merge_commit_message(Some("{title} (#{number})\n\nr={approver}".to_string())),
+            )
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        insta::assert_snapshot!(state.client().merge_commit_messages.last().unwrap(), @r###"
+        PR title (#1)
+
+        r=<user>
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_do_not_merge_label() {
+        let mut state = ClientBuilder::default().create_state().await;
+        set_labels(&mut state, &["S-do-not-merge"]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state.client().check_branch_not_created(AUTO_BRANCH_NAME);
+
+        set_labels(&mut state, &[]);
+        state.refresh().await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Testing commit pr-sha with merge sha-merged…");
+    }
+
+    #[tokio::test]
+    async fn test_merge_queues_per_base_branch() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default().auto_branch("release", "automation/bors/auto-release"),
+            )
+            .create_state()
+            .await;
+        // PR 1 is merged into `release`, other PRs into `main-branch`
+        state.client().get_pr_fn = Box::new(|pr| {
+            let mut builder = PRBuilder::default();
+            if pr.0 == 1 {
+                builder.base(
+                    BranchBuilder::default()
+                        .name("release".to_string())
+                        .sha("release-sha".to_string())
+                        .create(),
+                );
+            }
+            Ok(builder.number(pr.0).create())
+        });
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.refresh().await;
+        state
+            .client()
+            .check_branch_history("automation/bors/auto-release", &["sha-merged"]);
+
+        // The build on `release` is still running
+        state.comment(comment("@bors r+").pr_number(2)).await;
+        state.refresh().await;
+        insta::assert_snapshot!(state.client().get_last_comment(2), @":hourglass: Testing commit pr-sha with merge sha-merged…");
+        state
+            .client()
+            .check_branch_history(AUTO_BRANCH_NAME, &["sha-merged"]);
+    }
+
+    #[tokio::test]
+    async fn test_merge_base_branches_share_auto_branch() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            let mut builder = PRBuilder::default();
+            if pr.0 == 1 {
+                builder.base(
+                    BranchBuilder::default()
+                        .name("release".to_string())
+                        .sha("release-sha".to_string())
+                        .create(),
+                );
+            }
+            Ok(builder.number(pr.0).create())
+        });
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.refresh().await;
+
+        // The build on `release` occupies the shared auto branch
+        state.comment(comment("@bors r+").pr_number(2)).await;
+        state.refresh().await;
+        state
+            .client()
+            .check_branch_history(AUTO_BRANCH_NAME, &["sha-merged"]);
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), 2.into())
+            .await
+            .unwrap();
+        assert!(pr.auto_build.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_merge_approver_lost_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["<user>"])))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state.repo().permissions_resolver = Box::new(PermissionsFor::new(&[]));
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":no_entry: The approval of this PR has been dismissed, because the following approver(s) are no longer allowed to approve it: `<user>`. Please approve it again.");
+        state.client().check_branch_not_created("main-branch");
+        state.client().check_check_runs(
+            &default_merge_sha(),
+            &[
+                CheckRunStatus::InProgress,
+                CheckRunStatus::Success,
+                CheckRunStatus::Cancelled,
+            ],
+        );
+        assert!(state
+            .db
+            .list_queued_prs(&default_repo_name())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    fn set_labels(state: &mut TestBorsState, labels: &[&str]) {
+        let labels = labels
+            .iter()
+            .map(|label| label.to_string())
+            .collect::<Vec<_>>();
+        state.client().get_pr_fn = Box::new(move |pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .labels(labels.clone())
+                .create())
+        });
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_delay() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors delay=2h").await;
+        let comment = state.client().get_last_comment(default_pr_number());
+        assert!(comment.starts_with(":pushpin: Commit pr-sha has been approved by `<user>`"));
+        assert!(comment.contains("\n:alarm_clock: The PR will not enter the merge queue before "));
+        state.refresh().await;
+        state.client().check_branch_not_created(AUTO_BRANCH_NAME);
+
+        // Let the delay elapse
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        state
+            .db
+            .set_not_before(&pr, Some(Utc::now() - Duration::minutes(1)))
+            .await
+            .unwrap();
+        state.refresh().await;
+
+        let client = state.client();
+        let comments = (1..3)
+            .map(|index| client.get_comment(default_pr_number(), index))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(comments, @r###"
+        [
+            ":alarm_clock: The delay of this PR has elapsed, it has entered the merge queue.",
+            ":hourglass: Testing commit pr-sha with merge sha-merged…",
+        ]
+        "###);
+    }
+
+    /// Ends the delay of the PR right away.
+    async fn elapse_delay(state: &mut TestBorsState, pr: u64) {
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), pr.into())
+            .await
+            .unwrap();
+        state
+            .db
+            .set_not_before(&pr, Some(Utc::now() - Duration::minutes(1)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_delay_elapsed_while_full() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().max_queue_size(Some(1)))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.refresh().await;
+        state.comment(comment("@bors delay=1h").pr_number(2)).await;
+        elapse_delay(&mut state, 2).await;
+        state.refresh().await;
+        // The PR keeps waiting for space in the merge queue
+        state.client().check_comment_count(2, 1);
+
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("sha-merged-2".to_string())));
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_comment(2, 1), @":arrow_forward: There is space in the merge queue now, this PR has entered it.");
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_delay_elapsed_while_tree_closed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors treeclosed=5").await;
+        state.comment("@bors delay=1h").await;
+        elapse_delay(&mut state, default_pr_number()).await;
+        state.refresh().await;
+        state.client().check_comment_count(default_pr_number(), 2);
+        state.client().check_branch_not_created(AUTO_BRANCH_NAME);
+
+        state.comment("@bors treeopen").await;
+        state.refresh().await;
+        insta::assert_snapshot!(state.client().get_comment(default_pr_number(), 3), @":alarm_clock: The delay of this PR has elapsed, it has entered the merge queue.");
+    }
+
+    #[tokio::test]
+    async fn test_merge_queue_failure() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :x:
+        "###);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_merge_blocked_by_missing_required_check() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state
+            .client()
+            .set_branch_protection("main-branch", &["workflow-1", "lint"]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :no_entry: Cannot merge into `main-branch`, the following required check(s) did not pass: `lint`
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        "###);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[test]
+    fn failed_required_checks_mixed_check_runs() {
+        let required = ["build", "lint", "docs", "workflow-1", "workflow-2"].map(String::from);
+        let check_runs = [
+            ("build", CheckSuiteStatus::Success),
+            ("lint", CheckSuiteStatus::Success),
+            ("lint", CheckSuiteStatus::Failure),
+            ("docs", CheckSuiteStatus::Pending),
+        ]
+        .map(|(name, status)| CheckRun {
+            name: name.to_string(),
+            status,
+        });
+        let passed_workflows = HashSet::from(["workflow-1"]);
+        assert_eq!(
+            failed_required_checks(&required, &passed_workflows, &check_runs),
+            vec!["lint", "docs", "workflow-2"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_blocked_by_failed_check_run() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state
+            .client()
+            .set_branch_protection("main-branch", &["workflow-1", "test"]);
+        state.client().set_check_runs(
+            &default_merge_sha(),
+            &[
+                ("workflow-1", CheckSuiteStatus::Success),
+                ("test", CheckSuiteStatus::Failure),
+            ],
+        );
+        merge_with_success(&mut state).await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :no_entry: Cannot merge into `main-branch`, the following required check(s) did not pass: `test`
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        "###);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_merge_with_required_checks() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state
+            .client()
+            .set_branch_protection("main-branch", &["workflow-1"]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        state
+            .client()
+            .check_branch_history("main-branch", &["sha-merged"]);
+    }
+}
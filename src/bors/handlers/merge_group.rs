@@ -0,0 +1,281 @@
+use crate::bors::event::{MergeGroupCompleted, MergeGroupDestroyed, MergeGroupRequested};
+use crate::bors::{CheckSuiteStatus, RepositoryClient, RepositoryState};
+use crate::database::{BuildStatus, DbClient};
+
+/// Starts observing a merge group that the native GitHub merge queue has created for a PR.
+///
+/// Bors does not manage these merge groups, it only observes them to help repositories that are
+/// transitioning between bors and the GitHub merge queue.
+pub(super) async fn handle_merge_group_requested<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    payload: MergeGroupRequested,
+) -> anyhow::Result<()> {
+    let Some(pr) = payload.pr_number() else {
+        tracing::warn!("Cannot find PR of merge group branch {}", payload.branch);
+        return Ok(());
+    };
+    if db
+        .find_merge_group(repo.client.repository(), &payload.commit_sha)
+        .await?
+        .is_some()
+    {
+        tracing::info!("Merge group {} is already observed", payload.commit_sha);
+        return Ok(());
+    }
+    tracing::info!("Merge group {} of PR {pr} was created", payload.commit_sha);
+    db.create_merge_group(
+        repo.client.repository(),
+        pr,
+        &payload.branch,
+        &payload.commit_sha,
+    )
+    .await
+}
+
+/// Reports the result of an observed merge group to the tested PR.
+///
+/// The combined status of all check suites of the merge group is reported once all of them have
+/// completed.
+pub(super) async fn handle_merge_group_completed<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    payload: MergeGroupCompleted,
+) -> anyhow::Result<()> {
+    let Some(group) = db
+        .find_merge_group(repo.client.repository(), &payload.commit_sha)
+        .await?
+    else {
+        tracing::warn!(
+            "Merge group {} is not observed, ignoring its check suite",
+            payload.commit_sha
+        );
+        return Ok(());
+    };
+    if group.status != BuildStatus::Pending {
+        tracing::info!(
+            "Merge group {} has already finished with {:?}",
+            group.commit_sha,
+            group.status
+        );
+        return Ok(());
+    }
+    tracing::info!(
+        "Merge group check suite of PR {} completed with {:?}",
+        group.pr_number,
+        payload.status
+    );
+
+    let checks = repo
+        .client
+        .get_check_suites_for_commit(&group.branch, &group.commit_sha)
+        .await?;
+    if checks
+        .iter()
+        .any(|check| matches!(check.status, CheckSuiteStatus::Pending))
+    {
+        return Ok(());
+    }
+
+    let failed = checks
+        .iter()
+        .any(|check| matches!(check.status, CheckSuiteStatus::Failure));
+    // The group is finished before its result is posted, so that a repeated delivery of the
+    // completing event is ignored instead of posting the result again
+    let status = if failed {
+        BuildStatus::Failure
+    } else {
+        BuildStatus::Success
+    };
+    db.update_merge_group_status(&group, status).await?;
+
+    let pr = db
+        .get_or_create_pull_request(repo.client.repository(), group.pr_number)
+        .await?;
+    if failed {
+        let message = format!(
+            ":broken_heart: The checks of the GitHub merge queue have failed for {}",
+            group.commit_sha
+        );
+        repo.post_event_comment(db, &pr, &message).await
+    } else {
+        let message = format!(
+            ":sunny: The checks of the GitHub merge queue have passed for {}",
+            group.commit_sha
+        );
+        repo.post_status_comment(db, &pr, &message).await
+    }
+}
+
+/// Stops observing a merge group that the native GitHub merge queue has merged or removed.
+pub(super) async fn handle_merge_group_destroyed<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    payload: MergeGroupDestroyed,
+) -> anyhow::Result<()> {
+    let Some(group) = db
+        .find_merge_group(repo.client.repository(), &payload.commit_sha)
+        .await?
+    else {
+        return Ok(());
+    };
+    if group.status != BuildStatus::Pending {
+        return Ok(());
+    }
+    tracing::info!(
+        "Merge group {} of PR {} was destroyed before its checks have finished",
+        group.commit_sha,
+        group.pr_number
+    );
+    let status = if payload.merged {
+        BuildStatus::Success
+    } else {
+        BuildStatus::Cancelled
+    };
+    db.update_merge_group_status(&group, status).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::event::{
+        BorsEvent, MergeGroupCompleted, MergeGroupDestroyed, MergeGroupRequested,
+    };
+    use crate::database::{BuildStatus, DbClient, WorkflowStatus};
+    use crate::github::{CommitSha, PullRequestNumber};
+    use crate::tests::event::{suite_failure, suite_pending, suite_success};
+    use crate::tests::state::{default_repo_name, ClientBuilder, TestBorsState};
+
+    const BRANCH: &str = "gh-readonly-queue/main/pr-5-base-sha";
+
+    fn group_sha() -> CommitSha {
+        CommitSha("group-sha".to_string())
+    }
+
+    fn merge_group_requested() -> MergeGroupRequested {
+        MergeGroupRequested {
+            repository: default_repo_name(),
+            branch: BRANCH.to_string(),
+            commit_sha: group_sha(),
+        }
+    }
+
+    fn merge_group(status: WorkflowStatus) -> MergeGroupCompleted {
+        MergeGroupCompleted {
+            repository: default_repo_name(),
+            branch: BRANCH.to_string(),
+            commit_sha: group_sha(),
+            status,
+        }
+    }
+
+    async fn group_status(state: &TestBorsState) -> BuildStatus {
+        state
+            .db
+            .find_merge_group(&default_repo_name(), &group_sha())
+            .await
+            .unwrap()
+            .unwrap()
+            .status
+    }
+
+    #[test]
+    fn test_merge_group_pr_number() {
+        let mut group = merge_group_requested();
+        assert_eq!(group.pr_number(), Some(PullRequestNumber(5)));
+
+        group.branch = "gh-readonly-queue/release/1.0/pr-12-base-sha".to_string();
+        assert_eq!(group.pr_number(), Some(PullRequestNumber(12)));
+
+        group.branch = "gh-readonly-queue/main".to_string();
+        assert_eq!(group.pr_number(), None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_failure() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .event(BorsEvent::MergeGroupRequested(merge_group_requested()))
+            .await;
+        state
+            .client()
+            .set_checks("group-sha", &[suite_success(), suite_failure()]);
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Failure)))
+            .await;
+        state.client().check_comments(
+            5,
+            &[":broken_heart: The checks of the GitHub merge queue have failed for group-sha"],
+        );
+        assert_eq!(group_status(&state).await, BuildStatus::Failure);
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_result_is_posted_once() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .event(BorsEvent::MergeGroupRequested(merge_group_requested()))
+            .await;
+        state
+            .client()
+            .set_checks("group-sha", &[suite_success(), suite_success()]);
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Success)))
+            .await;
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Success)))
+            .await;
+        state.client().check_comments(
+            5,
+            &[":sunny: The checks of the GitHub merge queue have passed for group-sha"],
+        );
+        assert_eq!(group_status(&state).await, BuildStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_wait_for_all_suites() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .event(BorsEvent::MergeGroupRequested(merge_group_requested()))
+            .await;
+        state
+            .client()
+            .set_checks("group-sha", &[suite_success(), suite_pending()]);
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Success)))
+            .await;
+        state.client().check_comment_count(5, 0);
+        assert_eq!(group_status(&state).await, BuildStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_not_observed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_checks("group-sha", &[suite_failure()]);
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Failure)))
+            .await;
+        state.client().check_comment_count(5, 0);
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_dequeued() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .event(BorsEvent::MergeGroupRequested(merge_group_requested()))
+            .await;
+        state
+            .event(BorsEvent::MergeGroupDestroyed(MergeGroupDestroyed {
+                repository: default_repo_name(),
+                commit_sha: group_sha(),
+                merged: false,
+            }))
+            .await;
+        assert_eq!(group_status(&state).await, BuildStatus::Cancelled);
+
+        state.client().set_checks("group-sha", &[suite_failure()]);
+        state
+            .event(BorsEvent::MergeGroup(merge_group(WorkflowStatus::Failure)))
+            .await;
+        state.client().check_comment_count(5, 0);
+    }
+}
@@ -4,23 +4,83 @@ use tracing::Instrument;
 use crate::bors::command::BorsCommand;
 use crate::bors::command::CommandParseError;
 use crate::bors::event::{BorsEvent, PullRequestComment};
+use crate::bors::handlers::base::command_set_base;
+use crate::bors::handlers::clean::{command_clean, command_confirm};
+use crate::bors::handlers::delegation::{
+    command_delegate, command_list_delegates, command_undelegate, delegation_grants,
+};
+use crate::bors::handlers::history::command_history;
+use crate::bors::handlers::merge::is_auto_branch;
+use crate::bors::handlers::merge_group::{
+    handle_merge_group_completed, handle_merge_group_destroyed, handle_merge_group_requested,
+};
+use crate::bors::handlers::mute::command_mute;
+use crate::bors::handlers::nag::command_nag;
 use crate::bors::handlers::ping::command_ping;
-use crate::bors::handlers::refresh::refresh_repository;
-use crate::bors::handlers::trybuild::{command_try_build, command_try_cancel, TRY_BRANCH_NAME};
+use crate::bors::handlers::recent::command_recent;
+use crate::bors::handlers::refresh::{now, refresh_repository};
+use crate::bors::handlers::review::{
+    command_approve, command_set_priority, dequeue_stale_approval, dismiss_stale_approval,
+};
+use crate::bors::handlers::rollup::command_approve_rollup;
+use crate::bors::handlers::startup::reconcile_branches;
+use crate::bors::handlers::sync::command_sync;
+use crate::bors::handlers::tree::{command_tree_closed, command_tree_open};
+use crate::bors::handlers::trybuild::{
+    command_try_build, command_try_cancel, command_try_plan, command_try_retry, TRY_BRANCH_NAME,
+};
+use crate::bors::handlers::whoami::command_whoami;
+use crate::bors::handlers::why::command_why;
 use crate::bors::handlers::workflow::{
-    handle_check_suite_completed, handle_workflow_completed, handle_workflow_started,
+    handle_check_suite_completed, handle_commit_status_changed, handle_workflow_completed,
+    handle_workflow_started,
 };
 use crate::bors::{BorsContext, BorsState, RepositoryClient, RepositoryState};
-use crate::database::DbClient;
-use crate::github::GithubRepoName;
+use crate::config::{PermissionedCommand, RepositoryConfig};
+use crate::database::{DbClient, PullRequestModel};
+use crate::github::{GithubRepoName, GithubUser, PullRequest};
+use crate::permissions::PermissionType;
+use crate::utils::glob::glob_matches;
 use crate::utils::logging::LogError;
 
+mod base;
+mod clean;
+mod delegation;
+mod history;
 mod labels;
+mod merge;
+mod merge_group;
+mod mute;
+mod nag;
 mod ping;
+mod recent;
 mod refresh;
+mod review;
+mod rollup;
+mod startup;
+mod sync;
+mod tree;
 mod trybuild;
+mod whoami;
+mod why;
 mod workflow;
 
+const COMMAND_ERROR_MESSAGE: &str = ":x: Encountered an error while executing command";
+const COMMAND_TIMEOUT_MESSAGE: &str = ":hourglass: Command timed out, please retry.";
+const SKIPPED_COMMANDS_NOTE: &str =
+    "The following commands of the comment have not been executed, please repeat them as well.";
+
+/// Result of executing a single bors command.
+pub(super) enum CommandOutcome {
+    /// The command has been executed.
+    Handled,
+    /// The command cannot be performed, e.g. because of missing permissions or because of the
+    /// current state of the PR. The reason will be posted to the PR.
+    Rejected(String),
+    /// The command could not be executed because of an (infrastructure) error.
+    Failed(anyhow::Error),
+}
+
 /// This function performs a single BORS event, it is the main execution function of the bot.
 pub async fn handle_bors_event<Client: RepositoryClient>(
     event: BorsEvent,
@@ -36,22 +96,65 @@ pub async fn handle_bors_event<Client: RepositoryClient>(
             }
 
             if let Some((repo, db)) = get_repo_state(state, &comment.repository) {
+                repo.recent_events
+                    .record("comment", format!("PR #{}", comment.pr_number), now());
                 let span = tracing::info_span!(
                     "Comment",
                     pr = format!("{}#{}", comment.repository, comment.pr_number),
                     author = comment.author.username
                 );
                 let pr_number = comment.pr_number;
+                if let Err(error) = handle_comment(repo, db, ctx, comment.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    record_failed_event(db, &repo.repository, BorsEvent::Comment(comment), &error)
+                        .await;
+                    span.log_error(error);
+                    repo.post_comment(pr_number, COMMAND_ERROR_MESSAGE)
+                        .await
+                        .context("Cannot send comment reacting to an error")?;
+                }
+            }
+        }
+        BorsEvent::PullRequestOpened(payload) => {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "pull request opened",
+                    format!("PR #{}", payload.pr_number),
+                    now(),
+                );
+                if !repo.config.commands_in_pr_description {
+                    tracing::trace!(
+                        "Ignoring description of PR {}, commands in PR descriptions are disabled",
+                        payload.pr_number
+                    );
+                    return Ok(());
+                }
+
+                let span = tracing::info_span!(
+                    "Pull request opened",
+                    pr = format!("{}#{}", payload.repository, payload.pr_number),
+                    author = payload.author.username
+                );
+                let pr_number = payload.pr_number;
+                // Commands from the description are executed as if the author posted them
+                let comment = PullRequestComment {
+                    repository: payload.repository.clone(),
+                    author: payload.author.clone(),
+                    pr_number,
+                    text: payload.description.clone(),
+                    comment_id: None,
+                    edit: None,
+                };
                 if let Err(error) = handle_comment(repo, db, ctx, comment)
                     .instrument(span.clone())
                     .await
                 {
+                    let event = BorsEvent::PullRequestOpened(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
                     span.log_error(error);
-                    repo.client
-                        .post_comment(
-                            pr_number,
-                            ":x: Encountered an error while executing command",
-                        )
+                    repo.post_comment(pr_number, COMMAND_ERROR_MESSAGE)
                         .await
                         .context("Cannot send comment reacting to an error")?;
                 }
@@ -63,46 +166,185 @@ pub async fn handle_bors_event<Client: RepositoryClient>(
                 span.log_error(error);
             }
         }
+        BorsEvent::RepositoriesRemoved(payload) => {
+            // Events of in-flight builds of the removed repositories are ignored from now on
+            for repo in payload.repositories {
+                tracing::info!("Repository {repo} has been removed from the app");
+                state.remove_repository(&repo);
+            }
+        }
+        BorsEvent::RepositoryRenamed(payload) => {
+            let span = tracing::info_span!(
+                "Repository renamed",
+                repo = payload.old_name.to_string(),
+                new_name = payload.new_name.to_string()
+            );
+            // The records are moved first, so that running builds are found under the new name
+            let (_, db) = state.get_all_repos_mut();
+            if let Err(error) = db
+                .rename_repository(&payload.old_name, &payload.new_name)
+                .instrument(span.clone())
+                .await
+            {
+                span.log_error(error);
+                return Ok(());
+            }
+            span.in_scope(|| {
+                tracing::info!(
+                    "Repository {} has been renamed to {}",
+                    payload.old_name,
+                    payload.new_name
+                )
+            });
+            state.rename_repository(&payload.old_name, &payload.new_name);
+        }
         BorsEvent::WorkflowStarted(payload) => {
-            if let Some((_, db)) = get_repo_state(state, &payload.repository) {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "workflow started",
+                    format!("branch `{}`", payload.branch),
+                    now(),
+                );
                 let span = tracing::info_span!(
                     "Workflow started",
                     repo = payload.repository.to_string(),
                     id = payload.run_id.into_inner()
                 );
-                if let Err(error) = handle_workflow_started(db, payload)
+                if let Err(error) = handle_workflow_started(repo, db, payload.clone())
                     .instrument(span.clone())
                     .await
                 {
+                    let event = BorsEvent::WorkflowStarted(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
                     span.log_error(error);
                 }
             }
         }
         BorsEvent::WorkflowCompleted(payload) => {
             if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "workflow completed",
+                    format!("branch `{}`", payload.branch),
+                    now(),
+                );
                 let span = tracing::info_span!(
                     "Workflow completed",
                     repo = payload.repository.to_string(),
                     id = payload.run_id.into_inner()
                 );
-                if let Err(error) = handle_workflow_completed(repo, db, payload)
+                if let Err(error) = handle_workflow_completed(repo, db, payload.clone())
                     .instrument(span.clone())
                     .await
                 {
+                    let event = BorsEvent::WorkflowCompleted(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
                     span.log_error(error);
                 }
             }
         }
         BorsEvent::CheckSuiteCompleted(payload) => {
             if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "check suite completed",
+                    format!("branch `{}`", payload.branch),
+                    now(),
+                );
                 let span = tracing::info_span!(
                     "Check suite completed",
                     repo = payload.repository.to_string(),
                 );
-                if let Err(error) = handle_check_suite_completed(repo, db, payload)
+                if let Err(error) = handle_check_suite_completed(repo, db, payload.clone())
                     .instrument(span.clone())
                     .await
                 {
+                    let event = BorsEvent::CheckSuiteCompleted(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
+                    span.log_error(error);
+                }
+            }
+        }
+        BorsEvent::MergeGroupRequested(payload) => {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "merge group requested",
+                    format!("branch `{}`", payload.branch),
+                    now(),
+                );
+                let span = tracing::info_span!(
+                    "Merge group requested",
+                    repo = payload.repository.to_string(),
+                    branch = payload.branch
+                );
+                if let Err(error) = handle_merge_group_requested(repo, db, payload.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    let event = BorsEvent::MergeGroupRequested(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
+                    span.log_error(error);
+                }
+            }
+        }
+        BorsEvent::MergeGroupDestroyed(payload) => {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "merge group destroyed",
+                    format!("commit `{}`", payload.commit_sha),
+                    now(),
+                );
+                let span = tracing::info_span!(
+                    "Merge group destroyed",
+                    repo = payload.repository.to_string(),
+                );
+                if let Err(error) = handle_merge_group_destroyed(repo, db, payload.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    let event = BorsEvent::MergeGroupDestroyed(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
+                    span.log_error(error);
+                }
+            }
+        }
+        BorsEvent::MergeGroup(payload) => {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "merge group completed",
+                    format!("branch `{}`", payload.branch),
+                    now(),
+                );
+                let span = tracing::info_span!(
+                    "Merge group completed",
+                    repo = payload.repository.to_string(),
+                    branch = payload.branch
+                );
+                if let Err(error) = handle_merge_group_completed(repo, db, payload.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    let event = BorsEvent::MergeGroup(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
+                    span.log_error(error);
+                }
+            }
+        }
+        BorsEvent::CommitStatusChanged(payload) => {
+            if let Some((repo, db)) = get_repo_state(state, &payload.repository) {
+                repo.recent_events.record(
+                    "commit status changed",
+                    format!("commit `{}`", payload.commit_sha),
+                    now(),
+                );
+                let span = tracing::info_span!(
+                    "Commit status changed",
+                    repo = payload.repository.to_string(),
+                );
+                if let Err(error) = handle_commit_status_changed(repo, db, payload.clone())
+                    .instrument(span.clone())
+                    .await
+                {
+                    let event = BorsEvent::CommitStatusChanged(payload);
+                    record_failed_event(db, &repo.repository, event, &error).await;
                     span.log_error(error);
                 }
             }
@@ -112,7 +354,32 @@ pub async fn handle_bors_event<Client: RepositoryClient>(
             let (repos, db) = state.get_all_repos_mut();
             futures::future::join_all(repos.into_iter().map(|repo| async {
                 let subspan = tracing::info_span!("Repo", repo = repo.repository.to_string());
-                refresh_repository(repo, db).instrument(subspan).await
+                if !repo.refresh_backoff.is_due(now()) {
+                    tracing::debug!(parent: &subspan, "Skipping refresh after failed refreshes");
+                    return;
+                }
+                let result = refresh_repository(repo, db)
+                    .instrument(subspan.clone())
+                    .await;
+                repo.refresh_backoff.record(result.is_ok(), now());
+                if let Err(error) = result {
+                    subspan.log_error(error);
+                }
+            }))
+            .instrument(span)
+            .await;
+        }
+        BorsEvent::Startup => {
+            let span = tracing::info_span!("Startup");
+            let (repos, db) = state.get_all_repos_mut();
+            futures::future::join_all(repos.into_iter().map(|repo| async {
+                let subspan = tracing::info_span!("Repo", repo = repo.repository.to_string());
+                if let Err(error) = reconcile_branches(repo, db)
+                    .instrument(subspan.clone())
+                    .await
+                {
+                    subspan.log_error(error);
+                }
             }))
             .instrument(span)
             .await;
@@ -121,6 +388,26 @@ pub async fn handle_bors_event<Client: RepositoryClient>(
     Ok(())
 }
 
+/// Stores an event whose handling has failed, so that operators can inspect it and replay it
+/// later. Failing to store the event is only logged, as the event has already failed.
+async fn record_failed_event(
+    db: &dyn DbClient,
+    repo: &GithubRepoName,
+    event: BorsEvent,
+    error: &anyhow::Error,
+) {
+    let result = match serde_json::to_string(&event) {
+        Ok(event) => {
+            db.record_failed_event(repo, &event, &format!("{error:#}"))
+                .await
+        }
+        Err(error) => Err(error.into()),
+    };
+    if let Err(error) = result {
+        tracing::error!("Could not record failed event: {error:?}");
+    }
+}
+
 fn get_repo_state<'a, Client: RepositoryClient>(
     state: &'a mut dyn BorsState<Client>,
     repo: &GithubRepoName,
@@ -139,37 +426,156 @@ async fn handle_comment<Client: RepositoryClient>(
     database: &mut dyn DbClient,
     ctx: &BorsContext,
     comment: PullRequestComment,
+) -> anyhow::Result<()> {
+    if repo
+        .config
+        .ignored_bots
+        .iter()
+        .any(|bot| bot.eq_ignore_ascii_case(&comment.author.username))
+    {
+        tracing::info!(
+            "Ignoring commands of {}, the bot is configured to be ignored",
+            comment.author.username
+        );
+        return Ok(());
+    }
+
+    if let Some(comment_id) = comment.comment_id {
+        if repo.processed_comments.contains(comment_id, &comment.text) {
+            tracing::info!("Comment {comment_id} has already been processed");
+            return Ok(());
+        }
+    }
+
+    let processed = comment
+        .comment_id
+        .map(|comment_id| (comment_id, comment.text.clone()));
+    execute_comment(repo, database, ctx, comment).await?;
+    // The comment is only remembered once it has been handled, so that a comment whose handling
+    // has failed can be replayed
+    if let Some((comment_id, text)) = processed {
+        repo.processed_comments.record(comment_id, &text);
+    }
+    Ok(())
+}
+
+/// Executes the commands of the comment.
+async fn execute_comment<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    database: &mut dyn DbClient,
+    ctx: &BorsContext,
+    comment: PullRequestComment,
 ) -> anyhow::Result<()> {
     let pr_number = comment.pr_number;
-    let commands = ctx.parser.parse_commands(&comment.text);
+    let mut commands = ctx.parser.parse_commands(&comment.text);
+    if let Some(edit) = &comment.edit {
+        // Only commands added by the edit are executed, the others have already been executed
+        // when the comment was posted
+        let Some(previous_text) = &edit.previous_text else {
+            tracing::info!("Ignoring an edited comment, its previous text is unknown");
+            return Ok(());
+        };
+        let mut previous = ctx.parser.parse_commands(previous_text);
+        commands.retain(|command| match previous.iter().position(|c| c == command) {
+            Some(index) => {
+                let _ = previous.remove(index);
+                false
+            }
+            None => true,
+        });
+        if commands.is_empty() {
+            tracing::info!("The edit of the comment has not added any commands");
+            return Ok(());
+        }
+    }
     let pull_request = repo.client.get_pull_request(pr_number).await?;
 
     tracing::debug!("Commands: {commands:?}");
     tracing::trace!("Text: {}", comment.text);
 
-    for command in commands {
+    // `sync` validates the approval by itself, so that it can report the dismissal in its summary
+    if !commands.is_empty()
+        && !commands
+            .iter()
+            .any(|command| matches!(command, Ok(BorsCommand::Sync)))
+    {
+        let pr_model = database
+            .get_or_create_pull_request(repo.client.repository(), pr_number)
+            .await?;
+        // A comment that approves the current head replaces the stale approval right away, so
+        // its dismissal is not announced
+        if commands.iter().any(|command| {
+            matches!(
+                command,
+                Ok(BorsCommand::Approve { .. } | BorsCommand::ApproveRollup)
+            )
+        }) {
+            dequeue_stale_approval(repo, database, &pull_request, &pr_model).await?;
+        } else {
+            dismiss_stale_approval(repo, database, &pull_request, &pr_model).await?;
+        }
+    }
+
+    let command_count = commands.len();
+    for (index, command) in commands.into_iter().enumerate() {
         match command {
             Ok(command) => {
-                let result = match command {
-                    BorsCommand::Ping => {
-                        let span = tracing::info_span!("Ping");
-                        command_ping(repo, &pull_request).instrument(span).await
-                    }
-                    BorsCommand::Try { parent } => {
-                        let span = tracing::info_span!("Try");
-                        command_try_build(repo, database, &pull_request, &comment.author, parent)
-                            .instrument(span)
+                let name = command.name();
+                let timeout = repo.config.command_timeout;
+                let execution =
+                    execute_command(repo, database, ctx, &pull_request, &comment.author, command);
+                let result = tokio::time::timeout(timeout, execution).await;
+
+                // The command is recorded once its outcome is known, so that the audit log also
+                // tells if it was performed
+                let outcome = match &result {
+                    Ok(Ok(CommandOutcome::Handled)) => "handled",
+                    Ok(Ok(CommandOutcome::Rejected(_))) => "rejected",
+                    Ok(Ok(CommandOutcome::Failed(_)) | Err(_)) => "failed",
+                    Err(_) => "timed out",
+                };
+                let pr_model = database
+                    .get_or_create_pull_request(repo.client.repository(), pr_number)
+                    .await?;
+                database
+                    .record_command(
+                        &pr_model,
+                        &comment.author.username,
+                        &format!("{name} ({outcome})"),
+                    )
+                    .await?;
+
+                let Ok(result) = result else {
+                    tracing::error!("Command timed out after {timeout:?}");
+                    // The aborted command might have been interrupted in the middle, e.g. after
+                    // some of its changes have been stored, so the later commands of the comment
+                    // are dropped, and the author is told to repeat them
+                    let message = if index + 1 < command_count {
+                        format!("{COMMAND_TIMEOUT_MESSAGE} {SKIPPED_COMMANDS_NOTE}")
+                    } else {
+                        COMMAND_TIMEOUT_MESSAGE.to_string()
+                    };
+                    repo.post_comment(pull_request.number, &message)
+                        .await
+                        .context("Cannot send comment reacting to a timeout")?;
+                    return Ok(());
+                };
+                match result.unwrap_or_else(CommandOutcome::Failed) {
+                    CommandOutcome::Handled => {}
+                    CommandOutcome::Rejected(reason) => {
+                        tracing::info!("Command rejected: {reason}");
+                        repo.post_comment(pull_request.number, &reason)
                             .await
+                            .context("Could not reply to PR comment")?;
                     }
-                    BorsCommand::TryCancel => {
-                        let span = tracing::info_span!("Cancel try");
-                        command_try_cancel(repo, database, &pull_request, &comment.author)
-                            .instrument(span)
+                    CommandOutcome::Failed(error) => {
+                        tracing::error!("Cannot execute Bors command: {error:?}");
+                        repo.post_comment(pull_request.number, COMMAND_ERROR_MESSAGE)
                             .await
+                            .context("Cannot send comment reacting to an error")?;
+                        // Do not continue with other commands after an error
+                        return Ok(());
                     }
-                };
-                if result.is_err() {
-                    return result.context("Cannot execute Bors command");
                 }
             }
             Err(error) => {
@@ -187,6 +593,9 @@ async fn handle_comment<Client: RepositoryClient>(
                     CommandParseError::DuplicateArg(arg) => {
                         format!(r#"Argument "{arg}" found multiple times."#)
                     }
+                    CommandParseError::UnterminatedQuote { arg } => {
+                        format!(r#"The value of argument "{arg}" is missing a closing quote."#)
+                    }
                     CommandParseError::ValidationError(error) => {
                         format!("Invalid command: {error}")
                     }
@@ -194,8 +603,7 @@ async fn handle_comment<Client: RepositoryClient>(
 
                 tracing::warn!("{error_msg}");
 
-                repo.client
-                    .post_comment(pull_request.number, &error_msg)
+                repo.post_comment(pull_request.number, &error_msg)
                     .await
                     .context("Could not reply to PR comment")?;
             }
@@ -204,15 +612,612 @@ async fn handle_comment<Client: RepositoryClient>(
     Ok(())
 }
 
+/// Executes a single command of `author` on the PR, after checking that the author has the
+/// permission required by the command.
+async fn execute_command<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    database: &mut dyn DbClient,
+    ctx: &BorsContext,
+    pull_request: &PullRequest,
+    author: &GithubUser,
+    command: BorsCommand,
+) -> anyhow::Result<CommandOutcome> {
+    // Commands are rejected before execution if the author lacks the required permission
+    let permission_check = match required_permission(&repo.config, &command) {
+        Some(permission) => {
+            check_permissions(repo, database, pull_request, author, permission).await
+        }
+        None => Ok(None),
+    };
+    match permission_check.transpose() {
+        Some(rejection) => rejection,
+        None => match command {
+            BorsCommand::Ping => {
+                let span = tracing::info_span!("Ping");
+                command_ping(repo, database, ctx, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Approve {
+                priority,
+                rollup,
+                team,
+                delay,
+            } => {
+                let span = tracing::info_span!("Approve");
+                command_approve(
+                    repo,
+                    database,
+                    pull_request,
+                    author,
+                    priority,
+                    rollup,
+                    team,
+                    delay,
+                )
+                .instrument(span)
+                .await
+            }
+            BorsCommand::ApproveRollup => {
+                let span = tracing::info_span!("Approve rollup");
+                command_approve_rollup(repo, database, pull_request, author)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Try {
+                parent,
+                workflows,
+                rebase,
+            } => {
+                let span = tracing::info_span!("Try", rebase);
+                command_try_build(
+                    repo,
+                    database,
+                    pull_request,
+                    author,
+                    parent,
+                    workflows,
+                    rebase,
+                )
+                .instrument(span)
+                .await
+            }
+            BorsCommand::TryPlan { parent, workflows } => {
+                let span = tracing::info_span!("Try plan");
+                command_try_plan(repo, database, pull_request, parent, workflows)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::TryCancel => {
+                let span = tracing::info_span!("Cancel try");
+                command_try_cancel(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::History => {
+                let span = tracing::info_span!("History");
+                command_history(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Retry { workflow } => {
+                let span = tracing::info_span!("Retry");
+                command_try_retry(repo, database, pull_request, author, workflow)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Delegate { scope, delegatee } => {
+                let span = tracing::info_span!("Delegate", scope = format!("{scope:?}"));
+                command_delegate(repo, database, pull_request, author, scope, delegatee)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Undelegate { scope } => {
+                let span = tracing::info_span!("Undelegate");
+                command_undelegate(repo, database, pull_request, author, scope)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Delegates => {
+                let span = tracing::info_span!("Delegates");
+                command_list_delegates(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Mute | BorsCommand::Unmute => {
+                let muted = command == BorsCommand::Mute;
+                let span = tracing::info_span!("Mute", muted);
+                command_mute(repo, database, pull_request, author, muted)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::TreeClosed {
+                priority,
+                until,
+                reason,
+            } => {
+                let span = tracing::info_span!("Tree closed");
+                command_tree_closed(repo, database, pull_request, priority, until, reason)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::TreeOpen => {
+                let span = tracing::info_span!("Tree open");
+                command_tree_open(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Sync => {
+                let span = tracing::info_span!("Sync");
+                command_sync(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Recent => {
+                let span = tracing::info_span!("Recent");
+                command_recent(repo, pull_request).instrument(span).await
+            }
+            BorsCommand::Whoami => {
+                let span = tracing::info_span!("Whoami");
+                command_whoami(repo, database, pull_request, author)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Why => {
+                let span = tracing::info_span!("Why");
+                command_why(repo, database, pull_request)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Clean => {
+                let span = tracing::info_span!("Clean");
+                command_clean(repo, database, pull_request, author)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Confirm { token } => {
+                let span = tracing::info_span!("Confirm", token);
+                command_confirm(repo, database, pull_request, author, &token)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::Nag { interval } => {
+                let span = tracing::info_span!("Nag");
+                command_nag(repo, database, pull_request, author, interval)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::SetPriority { priority } => {
+                let span = tracing::info_span!("Set priority", priority);
+                command_set_priority(repo, database, pull_request, priority)
+                    .instrument(span)
+                    .await
+            }
+            BorsCommand::SetBase { branch } => {
+                let span = tracing::info_span!("Set base", branch);
+                command_set_base(repo, database, pull_request, author, branch)
+                    .instrument(span)
+                    .await
+            }
+        },
+    }
+}
+
+/// Returns the permission required to execute the command, if any. The default permission of the
+/// command can be overridden in the configuration of the repository.
+fn required_permission(config: &RepositoryConfig, command: &BorsCommand) -> Option<PermissionType> {
+    let command = match command {
+        BorsCommand::Approve { .. } | BorsCommand::ApproveRollup => PermissionedCommand::Approve,
+        BorsCommand::Try { .. } | BorsCommand::TryPlan { .. } => PermissionedCommand::Try,
+        BorsCommand::TryCancel => PermissionedCommand::TryCancel,
+        BorsCommand::Retry { .. } => PermissionedCommand::Retry,
+        BorsCommand::Sync => PermissionedCommand::Sync,
+        BorsCommand::Recent => PermissionedCommand::Recent,
+        BorsCommand::TreeClosed { .. } | BorsCommand::TreeOpen => PermissionedCommand::Tree,
+        BorsCommand::SetBase { .. } => PermissionedCommand::SetBase,
+        BorsCommand::SetPriority { .. } => PermissionedCommand::SetPriority,
+        BorsCommand::Clean => PermissionedCommand::Clean,
+        // Delegation commands, `nag`, `mute` and `confirm` check the permissions of the author by
+        // themselves
+        BorsCommand::Confirm { .. }
+        | BorsCommand::Ping
+        | BorsCommand::History
+        | BorsCommand::Delegate { .. }
+        | BorsCommand::Undelegate { .. }
+        | BorsCommand::Delegates
+        | BorsCommand::Nag { .. }
+        | BorsCommand::Mute
+        | BorsCommand::Unmute
+        | BorsCommand::Whoami
+        | BorsCommand::Why => return None,
+    };
+    Some(command_permission(config, command))
+}
+
+/// Returns the permission required by the given command, which can be overridden by the
+/// configuration of the repository.
+fn command_permission(config: &RepositoryConfig, command: PermissionedCommand) -> PermissionType {
+    let default = match command {
+        PermissionedCommand::Try | PermissionedCommand::TryCancel => PermissionType::Try,
+        PermissionedCommand::Retry => PermissionType::Retry,
+        PermissionedCommand::Approve
+        | PermissionedCommand::Sync
+        | PermissionedCommand::SetBase
+        | PermissionedCommand::SetPriority
+        | PermissionedCommand::Tree
+        | PermissionedCommand::Recent
+        | PermissionedCommand::Clean => PermissionType::Review,
+    };
+    config
+        .command_permissions
+        .get(&command)
+        .copied()
+        .unwrap_or(default)
+}
+
+/// Checks if the given `author` has the given `permission` in the repository, or if the permission
+/// was delegated to them on the given PR.
+/// If they do not, returns a rejection that should be reported to them.
+async fn check_permissions<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    permission: PermissionType,
+) -> anyhow::Result<Option<CommandOutcome>> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    if has_permission(repo, db, &pr_model, &author.username, permission).await? {
+        return Ok(None);
+    }
+
+    tracing::info!("Permission denied");
+    Ok(Some(insufficient_privileges(
+        &repo.config,
+        author,
+        permission,
+    )))
+}
+
+/// Does the given user have the given `permission` in the repository, or was it delegated to them
+/// on the given PR?
+async fn has_permission<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr_model: &PullRequestModel,
+    username: &str,
+    permission: PermissionType,
+) -> anyhow::Result<bool> {
+    if repo
+        .permissions_resolver
+        .has_permission(username, permission)
+        .await
+    {
+        return Ok(true);
+    }
+
+    let delegated = db
+        .get_delegations(pr_model)
+        .await?
+        .into_iter()
+        .any(|delegation| {
+            delegation.delegatee == username && delegation_grants(delegation.scope, permission)
+        });
+    if delegated {
+        tracing::info!("Permission {permission:?} granted through delegation");
+    }
+    Ok(delegated)
+}
+
+/// Checks if the author of the PR has opted out of builds and merges by putting the skip marker
+/// into the title of the PR or into the message of its head commit.
+/// If they did, returns a rejection that explains why the PR will not be built.
+async fn check_skip_marker<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    pr: &PullRequest,
+) -> anyhow::Result<Option<CommandOutcome>> {
+    let marker = repo.config.skip_marker.clone();
+    let location = if pr.title.contains(&marker) {
+        "title of the PR"
+    } else if repo
+        .client
+        .get_commit(&pr.head.sha)
+        .await?
+        .message
+        .contains(&marker)
+    {
+        "head commit message"
+    } else {
+        return Ok(None);
+    };
+
+    tracing::info!("PR is marked with {marker}");
+    Ok(Some(CommandOutcome::Rejected(format!(
+        ":no_entry_sign: The {location} contains `{marker}`, so this PR will not be built or merged."
+    ))))
+}
+
+/// Checks if all files changed by the PR match the paths configured in `skip_ci_paths`, so that
+/// its builds do not have to run CI.
+async fn skips_ci<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    pr: &PullRequest,
+) -> anyhow::Result<bool> {
+    if repo.config.skip_ci_paths.is_empty() {
+        return Ok(false);
+    }
+    let files = repo.client.list_pr_files(pr.number).await?;
+    let skip = !files.is_empty()
+        && files.iter().all(|file| {
+            repo.config
+                .skip_ci_paths
+                .iter()
+                .any(|pattern| glob_matches(pattern, file))
+        });
+    if skip {
+        tracing::info!("PR only changes files excluded from CI");
+    }
+    Ok(skip)
+}
+
+/// Note posted instead of the results of a build that was skipped by [`skips_ci`].
+const SKIPPED_CI_NOTE: &str =
+    "Docs-only change, skipping CI: all files changed by this PR match `skip_ci_paths`.";
+
+fn insufficient_privileges(
+    config: &RepositoryConfig,
+    author: &GithubUser,
+    permission: PermissionType,
+) -> CommandOutcome {
+    let users = match permission {
+        PermissionType::Review => "review",
+        PermissionType::Try | PermissionType::Retry => "try",
+        PermissionType::Write => "write",
+        PermissionType::Admin => "admin",
+    };
+    CommandOutcome::Rejected(
+        config
+            .insufficient_privileges_message
+            .replace("{user}", &author.username)
+            .replace("{permission}", users),
+    )
+}
+
 /// Is this branch interesting for the bot?
-fn is_bors_observed_branch(branch: &str) -> bool {
-    branch == TRY_BRANCH_NAME
+fn is_bors_observed_branch(config: &RepositoryConfig, branch: &str) -> bool {
+    branch == TRY_BRANCH_NAME || is_auto_branch(config, branch)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::tests::event::{comment, default_pr_number};
-    use crate::tests::state::{test_bot_user, ClientBuilder};
+    use sea_orm::EntityTrait;
+
+    use entity::audit_log;
+
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use crate::bors::event::{BorsEvent, CommentEdit, RepositoriesRemoved, RepositoryRenamed};
+    use crate::bors::handlers::merge::AUTO_BRANCH_NAME;
+    use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+    use crate::bors::BorsState;
+    use crate::config::PermissionedCommand;
+    use crate::database::{BuildStatus, DbClient, WorkflowStatus};
+    use crate::github::{CommitSha, GithubRepoName, MergeError};
+    use crate::permissions::PermissionType;
+    use crate::tests::event::{
+        comment, default_pr_number, edited_comment, pr_opened, suite_success, user,
+        CheckSuiteCompletedBuilder, WorkflowCompletedBuilder, WorkflowStartedBuilder,
+    };
+    use crate::tests::permissions::{NoPermissions, PermissionsFor};
+    use crate::tests::state::{
+        default_merge_sha, default_repo_name, test_bot_user, ClientBuilder, RepoConfigBuilder,
+    };
+
+    #[tokio::test]
+    async fn test_command_timeout() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().command_timeout(Duration::from_millis(100)))
+            .create_state()
+            .await;
+        state.client().stall_merges = true;
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Command timed out, please retry.");
+
+        // The aborted command does not block the PR
+        state.client().stall_merges = false;
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
+    }
+
+    #[tokio::test]
+    async fn test_command_timeout_after_db_write() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .command_timeout(Duration::from_millis(100))
+                    .rebase_notice_threshold(Some(10)),
+            )
+            .create_state()
+            .await;
+        // The approval is stored before the comparison with the base branch stalls
+        state.client().stall_comparisons = true;
+        state.comment("@bors r+\n@bors ping").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Command timed out, please retry. The following commands of the comment have not been executed, please repeat them as well.");
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert!(pr.approved_sha.is_some());
+
+        // The command can be repeated
+        state.client().stall_comparisons = false;
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `<user>`");
+    }
+
+    #[tokio::test]
+    async fn test_command_handled() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors ping").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_recorded_in_audit_log() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["reviewer"])))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors ping").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("contributor")))
+            .await;
+
+        let entries = audit_log::Entity::find()
+            .all(state.db.connection())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.author, entry.command))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec![
+                ("reviewer".to_string(), "ping (handled)".to_string()),
+                ("contributor".to_string(), "r+ (rejected)".to_string())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_comment_processed_once() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(comment("@bors try").comment_id(Some(1)))
+            .await;
+        state
+            .comment(comment("@bors try").comment_id(Some(1)))
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_edited_comment_does_not_rerun_commands() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(comment("@bors try").comment_id(Some(1)))
+            .await;
+        state
+            .comment(edited_comment(
+                1,
+                "@bors try",
+                "@bors try\nThanks for the review!",
+            ))
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_edited_comment_runs_added_commands() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(comment("@bors ping").comment_id(Some(1)))
+            .await;
+        state
+            .comment(edited_comment(1, "@bors ping", "@bors ping\n@bors r+"))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                "Pong 🏓! Queued PRs: 0, events in flight: 0",
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edited_comment_without_previous_text() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(
+                comment("@bors ping")
+                    .comment_id(Some(1))
+                    .edit(Some(CommentEdit {
+                        previous_text: None,
+                    })),
+            )
+            .await;
+        state.client().check_comment_count(default_pr_number(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_command_rejected() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not in try users"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_rejected_custom_message() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .config(
+                RepoConfigBuilder::default().insufficient_privileges_message(
+                    "Sorry @{user}, you need {permission} rights, see CONTRIBUTING.md".to_string(),
+                ),
+            )
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Sorry @<user>, you need review rights, see CONTRIBUTING.md"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_failed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().merge_branches_fn = Box::new(|| Err(MergeError::NotFound));
+        state.comment("@bors try\n@bors ping").await;
+
+        // The error is reported and the following commands are not executed
+        state.client().check_comments(
+            default_pr_number(),
+            &[":x: Encountered an error while executing command"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_event_is_recorded() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| Err(anyhow::anyhow!("PR {pr} not found")));
+        state.comment("@bors ping").await;
+
+        let events = state
+            .db
+            .get_failed_events(&default_repo_name(), 10)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        insta::assert_snapshot!(events[0].error, @"PR 1 not found");
+        let event: BorsEvent = serde_json::from_str(&events[0].event).unwrap();
+        assert!(matches!(event, BorsEvent::Comment(comment) if comment.text == "@bors ping"));
+    }
 
     #[tokio::test]
     async fn test_ignore_bot_comment() {
@@ -222,4 +1227,248 @@ mod tests {
             .await;
         state.client().check_comments(default_pr_number(), &[]);
     }
+
+    #[tokio::test]
+    async fn test_ignore_configured_bot_commands() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().ignored_bots(vec!["dependabot[bot]".to_string()]))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors try").author(user("dependabot[bot]")))
+            .await;
+        state.client().check_comments(default_pr_number(), &[]);
+        state
+            .client()
+            .check_branch_not_created("automation/bors/try");
+
+        state.comment("@bors ping").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_permissions() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&[]).permission(PermissionType::Try, &["contributor"]),
+            ))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors try").author(user("contributor")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("contributor")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":hourglass: Trying commit pr-sha with merge sha-merged…",
+                "@contributor: :key: Insufficient privileges: not in review users",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_permission_override() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&[]).permission(PermissionType::Try, &["contributor"]),
+            ))
+            .config(
+                RepoConfigBuilder::default()
+                    .command_permission(PermissionedCommand::Try, PermissionType::Review)
+                    .command_permission(PermissionedCommand::Sync, PermissionType::Try),
+            )
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors try").author(user("contributor")))
+            .await;
+        state
+            .comment(comment("@bors sync").author(user("contributor")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                "@contributor: :key: Insufficient privileges: not in review users",
+                ":arrows_counterclockwise: This PR is already in sync with GitHub.",
+            ],
+        );
+        state
+            .client()
+            .check_branch_not_created("automation/bors/try");
+    }
+
+    #[tokio::test]
+    async fn test_command_repository_permissions() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&[])
+                    .permission(PermissionType::Write, &["collaborator", "maintainer"])
+                    .permission(PermissionType::Admin, &["maintainer"]),
+            ))
+            .config(
+                RepoConfigBuilder::default()
+                    .command_permission(PermissionedCommand::Try, PermissionType::Write)
+                    .command_permission(PermissionedCommand::Approve, PermissionType::Admin),
+            )
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors try").author(user("collaborator")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("collaborator")))
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("maintainer")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":hourglass: Trying commit pr-sha with merge sha-merged…",
+                "@collaborator: :key: Insufficient privileges: not in admin users",
+                ":pushpin: Commit pr-sha has been approved by `maintainer`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repository_removed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .event(BorsEvent::RepositoriesRemoved(RepositoriesRemoved {
+                repositories: vec![default_repo_name()],
+            }))
+            .await;
+        assert!(state.get_repo_state_mut(&default_repo_name()).is_none());
+
+        // Events of the in-flight build are ignored, its build is kept in the DB as it was
+        state
+            .check_suite_completed(
+                CheckSuiteCompletedBuilder::default().branch(TRY_BRANCH_NAME.to_string()),
+            )
+            .await;
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.try_build.unwrap().status, BuildStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn test_repository_renamed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let builds = AtomicU64::new(0);
+        state.client().merge_branches_fn = Box::new(move || {
+            let build = builds.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(CommitSha(format!("merge-{build}")))
+        });
+        state.client().set_checks("merge-1", &[suite_success()]);
+        for pr in [1, 2] {
+            state.comment(comment("@bors r+").pr_number(pr)).await;
+        }
+        state.refresh().await;
+
+        let new_name = GithubRepoName::new("owner", "renamed");
+        state
+            .event(BorsEvent::RepositoryRenamed(RepositoryRenamed {
+                old_name: default_repo_name(),
+                new_name: new_name.clone(),
+            }))
+            .await;
+        assert!(state.get_repo_state_mut(&default_repo_name()).is_none());
+
+        // The in-flight build is completed under the new name, and the queue continues
+        let branch = AUTO_BRANCH_NAME.to_string();
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .repo(new_name.clone())
+                    .branch(branch.clone())
+                    .commit_sha("merge-1".to_string()),
+            )
+            .await;
+        state
+            .workflow_completed(
+                WorkflowCompletedBuilder::default()
+                    .repo(new_name.clone())
+                    .branch(branch.clone())
+                    .commit_sha("merge-1".to_string())
+                    .status(WorkflowStatus::Success),
+            )
+            .await;
+        let client = state.client_of(&new_name);
+        client.check_branch_history("main-branch", &["merge-1"]);
+        insta::assert_snapshot!(client.get_last_comment(2), @":hourglass: Testing commit pr-sha with merge merge-2…");
+        assert_eq!(state.db.list_queued_prs(&new_name).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_comment_with_bot_login_is_not_internal() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(
+                comment("@bors ping")
+                    .author(user(&test_bot_user().username))
+                    .create(),
+            )
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_user_comment_quoting_bot_is_not_internal() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment("> :x: Encountered an error while executing command\n\n@bors ping")
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pr_opened_description_commands_disabled() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.pull_request_opened(pr_opened("@bors try")).await;
+        state.client().check_comments(default_pr_number(), &[]);
+        state
+            .client()
+            .check_branch_not_created("automation/bors/try");
+    }
+
+    #[tokio::test]
+    async fn test_pr_opened_description_starts_try_build() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().commands_in_pr_description(true))
+            .create_state()
+            .await;
+        state
+            .pull_request_opened(pr_opened("This PR fixes a bug.\n\n@bors try"))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[&format!(
+                ":hourglass: Trying commit pr-sha with merge {}…",
+                default_merge_sha()
+            )],
+        );
+        state
+            .client()
+            .check_branch_history("automation/bors/try", &[&default_merge_sha()]);
+    }
 }
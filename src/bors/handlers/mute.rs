@@ -0,0 +1,162 @@
+use crate::bors::handlers::{has_permission, CommandOutcome};
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::DbClient;
+use crate::github::{GithubUser, PullRequest};
+use crate::permissions::PermissionType;
+
+/// Mutes or unmutes status comments of the bot on the PR. The bot keeps building and merging the
+/// PR while it is muted, and failures are still reported.
+/// Only the author of the PR and reviewers can mute the PR.
+pub(super) async fn command_mute<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    muted: bool,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    if author.username != pr.author.username
+        && !has_permission(
+            repo,
+            db,
+            &pr_model,
+            &author.username,
+            PermissionType::Review,
+        )
+        .await?
+    {
+        return Ok(CommandOutcome::Rejected(format!(
+            "@{}: :key: Only the author of the PR and reviewers can mute the bot.",
+            author.username
+        )));
+    }
+
+    db.set_muted(&pr_model, muted).await?;
+    let message = if muted {
+        tracing::info!("PR muted");
+        ":mute: Status comments are muted on this PR. Failures will still be reported."
+    } else {
+        tracing::info!("PR unmuted");
+        ":sound: Status comments are unmuted on this PR."
+    };
+    repo.post_comment(pr.number, message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+    use crate::database::WorkflowStatus;
+    use crate::github::CommitSha;
+    use crate::tests::event::{comment, default_pr_number, suite_failure, suite_success, user};
+    use crate::tests::permissions::NoPermissions;
+    use crate::tests::state::{default_merge_sha, ClientBuilder, RepoConfigBuilder};
+
+    #[tokio::test]
+    async fn test_mute_suppresses_status_comments() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors mute").await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":mute: Status comments are muted on this PR. Failures will still be reported."],
+        );
+
+        // Failures get through
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("merge2".to_string())));
+        state.client().set_checks("merge2", &[suite_failure()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(2, TRY_BRANCH_NAME, "merge2", WorkflowStatus::Failure)
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-2](https://workflow-2.com) :x:
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_mute_suppresses_approval_comment() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors mute").await;
+        state.comment("@bors r+").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":mute: Status comments are muted on this PR. Failures will still be reported."],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mute_suppresses_status_log_entries() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().status_log(true))
+            .create_state()
+            .await;
+        state.comment("@bors mute").await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[":mute: Status comments are muted on this PR. Failures will still be reported."],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unmute() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors mute").await;
+        state.comment("@bors unmute").await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_mute_no_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors mute").author(user("stranger")))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @"@stranger: :key: Only the author of the PR and reviewers can mute the bot.");
+    }
+}
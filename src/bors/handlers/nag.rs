@@ -0,0 +1,235 @@
+use std::time::Duration;
+
+use crate::bors::handlers::refresh::now;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::DbClient;
+use crate::github::{GithubUser, PullRequest};
+
+/// Asks the bot to remind the requested reviewers of the PR every `interval`, until the PR is
+/// approved, merged or closed. Only the author of the PR can ask for the reminders.
+pub(super) async fn command_nag<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    interval: Duration,
+) -> anyhow::Result<CommandOutcome> {
+    if author.username != pr.author.username {
+        return Ok(CommandOutcome::Rejected(format!(
+            "@{}: :key: Only the author of the PR can ask for review reminders.",
+            author.username
+        )));
+    }
+
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    db.set_nag(&pr_model, Some(interval), now()).await?;
+    tracing::info!("Reviewers will be reminded every {interval:?}");
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":bell: The reviewers of this PR will be reminded about it every {} until it is approved.",
+            format_interval(interval)
+        ),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Reminds the requested reviewers of PRs whose authors have asked for it, once the reminder
+/// interval has elapsed since the last reminder.
+///
+/// The reminders of merged and closed PRs are cancelled instead.
+///
+/// The reminders are deferred while the rate-limit budget of the repository is low.
+pub(super) async fn nag_reviewers<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    let now = now();
+    for pr_model in db.list_nagged_prs(&repo.repository).await? {
+        let (Some(interval), Some(last_nag_at)) = (pr_model.nag_interval, pr_model.last_nag_at)
+        else {
+            continue;
+        };
+        if pr_model.merged_at.is_some() {
+            tracing::info!(
+                "PR {} has been merged, cancelling its reminders",
+                pr_model.number
+            );
+            db.set_nag(&pr_model, None, now).await?;
+            continue;
+        }
+        if (now - last_nag_at).to_std().unwrap_or(Duration::ZERO) < interval {
+            continue;
+        }
+        if !repo.has_rate_limit_budget() {
+            tracing::info!("Deferring review reminders, the rate-limit budget is low");
+            return Ok(());
+        }
+
+        let pr = repo.client.get_pull_request(pr_model.number).await?;
+        if pr.closed {
+            tracing::info!("PR {} has been closed, cancelling its reminders", pr.number);
+            db.set_nag(&pr_model, None, now).await?;
+            continue;
+        }
+        db.set_last_nag(&pr_model, now).await?;
+        if pr.requested_reviewers.is_empty() {
+            tracing::info!("PR {} has no requested reviewers to remind", pr.number);
+            continue;
+        }
+        let reviewers = pr
+            .requested_reviewers
+            .iter()
+            .map(|reviewer| format!("@{reviewer}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        repo.post_status_comment(
+            db,
+            &pr_model,
+            &format!(":bell: {reviewers}: this PR is waiting for your review."),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Formats the interval in the largest unit that represents it exactly, e.g. `2h`.
+pub(super) fn format_interval(interval: Duration) -> String {
+    let seconds = interval.as_secs();
+    for (unit, length) in [("d", 24 * 60 * 60), ("h", 60 * 60), ("m", 60)] {
+        if seconds >= length && seconds.is_multiple_of(length) {
+            return format!("{}{unit}", seconds / length);
+        }
+    }
+    format!("{seconds}s")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bors::handlers::nag::format_interval;
+    use crate::bors::handlers::refresh::with_mocked_time;
+    use crate::database::DbClient;
+    use crate::tests::event::{comment, default_pr_author, default_pr_number};
+    use crate::tests::github::PRBuilder;
+    use crate::tests::state::{default_repo_name, ClientBuilder, TestBorsState};
+
+    const HOUR: u64 = 3600;
+
+    async fn create_state() -> TestBorsState {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .requested_reviewers(vec!["reviewer".to_string()])
+                .create())
+        });
+        state
+    }
+
+    #[test]
+    fn format_intervals() {
+        assert_eq!(format_interval(Duration::from_secs(24 * HOUR)), "1d");
+        assert_eq!(format_interval(Duration::from_secs(36 * HOUR)), "36h");
+        assert_eq!(format_interval(Duration::from_secs(90)), "90s");
+    }
+
+    #[tokio::test]
+    async fn test_nag_only_by_pr_author() {
+        let mut state = create_state().await;
+        state.comment("@bors nag=24h").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @"@<user>: :key: Only the author of the PR can ask for review reminders.");
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_nag_once_after_interval_until_approved() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors nag=24h").author(default_pr_author()))
+            .await;
+        state.refresh().await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        with_mocked_time(Duration::from_secs(25 * HOUR), async {
+            state.refresh().await;
+            state.refresh().await;
+        })
+        .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":bell: @reviewer: this PR is waiting for your review.");
+        state.client().check_comment_count(default_pr_number(), 2);
+
+        state.comment("@bors r+").await;
+        with_mocked_time(Duration::from_secs(50 * HOUR), async {
+            state.refresh().await;
+        })
+        .await;
+        // The reviewers have been reminded exactly once
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":bell: The reviewers of this PR will be reminded about it every 1d until it is approved.",
+                ":bell: @reviewer: this PR is waiting for your review.",
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+                ":hourglass: Testing commit pr-sha with merge sha-merged…",
+            ],
+        );
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_nag_cancelled_when_closed() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors nag=24h").author(default_pr_author()))
+            .await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .requested_reviewers(vec!["reviewer".to_string()])
+                .closed(true)
+                .create())
+        });
+        with_mocked_time(Duration::from_secs(25 * HOUR), async {
+            state.refresh().await;
+        })
+        .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.nag_interval, None);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_nag_cancelled_when_merged() {
+        let mut state = create_state().await;
+        state
+            .comment(comment("@bors nag=24h").author(default_pr_author()))
+            .await;
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        state.db.mark_merged(&pr).await.unwrap();
+        with_mocked_time(Duration::from_secs(25 * HOUR), async {
+            state.refresh().await;
+        })
+        .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.nag_interval, None);
+    }
+}
@@ -1,26 +1,99 @@
+use crate::bors::handlers::CommandOutcome;
 use crate::bors::RepositoryClient;
-use crate::bors::RepositoryState;
+use crate::bors::{BorsContext, RepositoryState};
+use crate::database::DbClient;
 use crate::github::PullRequest;
 
+/// Replies to the ping, along with the load of the bot: how many PRs are in the merge queue of the
+/// repository and how many webhook requests are in flight.
 pub(super) async fn command_ping<Client: RepositoryClient>(
     repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    ctx: &BorsContext,
     pr: &PullRequest,
-) -> anyhow::Result<()> {
-    repo.client.post_comment(pr.number, "Pong 🏓!").await?;
-    Ok(())
+) -> anyhow::Result<CommandOutcome> {
+    let queued = db.list_queued_prs(&repo.repository).await?.len();
+    let in_flight = ctx.concurrency_guard.in_flight();
+    repo.post_comment(
+        pr.number,
+        &format!("Pong 🏓! Queued PRs: {queued}, events in flight: {in_flight}"),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
 }
 
 #[cfg(test)]
 mod tests {
     use crate::tests::event::default_pr_number;
-    use crate::tests::state::ClientBuilder;
+    use crate::tests::state::{ClientBuilder, RepoConfigBuilder};
 
     #[tokio::test]
     async fn test_ping() {
         let mut state = ClientBuilder::default().create_state().await;
         state.comment("@bors ping").await;
-        state
-            .client()
-            .check_comments(default_pr_number(), &["Pong 🏓!"]);
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_reports_queue_depth() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        state.comment("@bors ping").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @"Pong 🏓! Queued PRs: 1, events in flight: 0");
+    }
+
+    #[tokio::test]
+    async fn test_ping_alias() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors are you there?").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_twice_posts_two_comments() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors ping").await;
+        state.comment("@bors ping").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                "Pong 🏓! Queued PRs: 0, events in flight: 0",
+                "Pong 🏓! Queued PRs: 0, events in flight: 0",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_comment_footer() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default().comment_footer(Some("— bors (staging)".to_string())),
+            )
+            .create_state()
+            .await;
+        state.comment("@bors ping").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0\n\n— bors (staging)"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_empty_comment_footer() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().comment_footer(Some(String::new())))
+            .create_state()
+            .await;
+        state.comment("@bors ping").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["Pong 🏓! Queued PRs: 0, events in flight: 0"],
+        );
     }
 }
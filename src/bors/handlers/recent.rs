@@ -0,0 +1,72 @@
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::github::PullRequest;
+
+/// How many events are listed in the reply.
+const LISTED_EVENTS: usize = 10;
+
+/// Posts the events that the bot has most recently received for the repository, to help
+/// maintainers find out whether a webhook has been missed.
+pub(super) async fn command_recent<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    let events = repo
+        .recent_events
+        .latest(LISTED_EVENTS)
+        .map(|event| {
+            format!(
+                "- {}: {} ({})",
+                event.received_at.format("%Y-%m-%d %H:%M:%S UTC"),
+                event.kind,
+                event.target
+            )
+        })
+        .collect::<Vec<_>>();
+    let message = if events.is_empty() {
+        ":scroll: No events have been received since the bot has started.".to_string()
+    } else {
+        format!(
+            ":scroll: Most recent events received for this repository:\n{}",
+            events.join("\n")
+        )
+    };
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::event::{default_pr_number, WorkflowStartedBuilder};
+    use crate::tests::permissions::NoPermissions;
+    use crate::tests::state::ClientBuilder;
+
+    #[tokio::test]
+    async fn test_recent_events() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .workflow_started(WorkflowStartedBuilder::default().branch("feature".to_string()))
+            .await;
+        state.comment("@bors recent").await;
+
+        let reply = state.client().get_last_comment(default_pr_number());
+        let lines = reply.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            ":scroll: Most recent events received for this repository:"
+        );
+        assert!(lines[1].ends_with(": comment (PR #1)"));
+        assert!(lines[2].ends_with(": workflow started (branch `feature`)"));
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_recent_events_no_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state.comment("@bors recent").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @"@<user>: :key: Insufficient privileges: not in review users");
+    }
+}
@@ -2,9 +2,22 @@ use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 
+use crate::bors::event::CheckSuiteCompleted;
+
+use crate::bors::handlers::clean::CONFIRMATION_TIMEOUT;
+use crate::bors::handlers::merge::process_merge_queue;
+use crate::bors::handlers::nag::nag_reviewers;
+use crate::bors::handlers::review::expire_approvals;
+use crate::bors::handlers::rollup::suggest_rollup;
+use crate::bors::handlers::tree::reopen_tree;
 use crate::bors::handlers::trybuild::cancel_build_workflows;
+use crate::bors::handlers::workflow::{report_check_run, try_complete_build};
 use crate::bors::{RepositoryClient, RepositoryState};
-use crate::database::{BuildStatus, DbClient};
+use crate::database::{BuildStatus, CheckRunStatus, DbClient};
+use crate::github::CommitSha;
+
+/// For how long are failed events kept, so that they can be inspected and replayed.
+const FAILED_EVENT_RETENTION: Duration = Duration::from_secs(30 * 24 * 3600);
 
 pub async fn refresh_repository<Client: RepositoryClient>(
     repo: &mut RepositoryState<Client>,
@@ -16,11 +29,24 @@ pub async fn refresh_repository<Client: RepositoryClient>(
     tracing::info!("Found {} running build(s)", running_builds.len());
 
     for build in running_builds {
+        // Builds whose checks have finished are completed once the settle delay has elapsed
+        if let Some(finished_at) = build.checks_finished_at {
+            if elapsed_time(finished_at) >= repo.config.settle_delay {
+                let event = CheckSuiteCompleted {
+                    repository: repo.repository.clone(),
+                    branch: build.branch.clone(),
+                    commit_sha: CommitSha(build.commit_sha.clone()),
+                };
+                try_complete_build(repo, db, event).await?;
+                continue;
+            }
+        }
         if elapsed_time(build.created_at) >= timeout {
             tracing::info!("Cancelling build {}", build.commit_sha);
 
             db.update_build_status(&build, BuildStatus::Cancelled)
                 .await?;
+            report_check_run(repo, db, &build, CheckRunStatus::Cancelled).await;
             if let Some(pr) = db.find_pr_by_build(&build).await? {
                 if let Err(error) = cancel_build_workflows(repo, db, &build).await {
                     tracing::error!(
@@ -29,11 +55,7 @@ pub async fn refresh_repository<Client: RepositoryClient>(
                     );
                 }
 
-                if let Err(error) = repo
-                    .client
-                    .post_comment(pr.number, ":boom: Test timed out")
-                    .await
-                {
+                if let Err(error) = repo.post_comment(pr.number, ":boom: Test timed out").await {
                     tracing::error!("Could not send comment to PR {}: {error:?}", pr.number);
                 }
             } else {
@@ -42,38 +64,87 @@ pub async fn refresh_repository<Client: RepositoryClient>(
         }
     }
 
-    Ok(())
+    let retention = chrono::Duration::from_std(repo.config.audit_log_retention)?;
+    let pruned = db
+        .prune_audit_log(&repo.repository, now() - retention)
+        .await?;
+    if pruned > 0 {
+        tracing::info!("Pruned {pruned} audit log entries");
+    }
+    let expired = chrono::Duration::from_std(CONFIRMATION_TIMEOUT)?;
+    let pruned = db
+        .prune_confirmations(&repo.repository, now() - expired)
+        .await?;
+    if pruned > 0 {
+        tracing::info!("Pruned {pruned} expired confirmations");
+    }
+    let retention = chrono::Duration::from_std(FAILED_EVENT_RETENTION)?;
+    let pruned = db
+        .prune_failed_events(&repo.repository, now() - retention)
+        .await?;
+    if pruned > 0 {
+        tracing::info!("Pruned {pruned} failed events");
+    }
+
+    suggest_rollup(repo, db).await?;
+    nag_reviewers(repo, db).await?;
+    expire_approvals(repo, db).await?;
+    reopen_tree(repo, db).await?;
+    process_merge_queue(repo, db).await
 }
 
 #[cfg(not(test))]
-fn now() -> DateTime<Utc> {
+pub(super) fn now() -> DateTime<Utc> {
     Utc::now()
 }
 
 #[cfg(test)]
 thread_local! {
-    static MOCK_TIME: std::cell::RefCell<Option<DateTime<Utc>>> = std::cell::RefCell::new(None);
+    static MOCK_TIME: std::cell::RefCell<Option<DateTime<Utc>>> = const { std::cell::RefCell::new(None) };
 }
 
 #[cfg(test)]
-fn now() -> DateTime<Utc> {
+pub(super) fn now() -> DateTime<Utc> {
     MOCK_TIME.with(|time| time.borrow_mut().unwrap_or_else(Utc::now))
 }
 
-fn elapsed_time(date: DateTime<Utc>) -> Duration {
+pub(super) fn elapsed_time(date: DateTime<Utc>) -> Duration {
     let time: DateTime<Utc> = now();
     (time - date).to_std().unwrap_or(Duration::ZERO)
 }
 
+/// Executes `future` as if it was executed `in_future` from now.
+#[cfg(test)]
+pub(super) async fn with_mocked_time<Fut: std::future::Future<Output = ()>>(
+    in_future: Duration,
+    future: Fut,
+) {
+    // It is important to use this function only with a single threaded runtime,
+    // otherwise the `MOCK_TIME` variable might get mixed up between different threads.
+    assert_eq!(
+        tokio::runtime::Handle::current().runtime_flavor(),
+        tokio::runtime::RuntimeFlavor::CurrentThread
+    );
+    MOCK_TIME.with(|time| {
+        *time.borrow_mut() = Some(Utc::now() + chrono::Duration::from_std(in_future).unwrap());
+    });
+    future.await;
+    MOCK_TIME.with(|time| {
+        *time.borrow_mut() = None;
+    });
+}
+
 #[cfg(test)]
 mod tests {
-    use std::future::Future;
     use std::time::Duration;
 
     use chrono::Utc;
-    use tokio::runtime::RuntimeFlavor;
+    use sea_orm::ActiveValue::Set;
+    use sea_orm::EntityTrait;
+
+    use entity::audit_log;
 
-    use crate::bors::handlers::refresh::MOCK_TIME;
+    use crate::bors::handlers::refresh::{with_mocked_time, FAILED_EVENT_RETENTION};
     use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
     use crate::database::DbClient;
     use crate::tests::event::{default_pr_number, WorkflowStartedBuilder};
@@ -137,19 +208,75 @@ mod tests {
         .await;
     }
 
-    async fn with_mocked_time<Fut: Future<Output = ()>>(in_future: Duration, future: Fut) {
-        // It is important to use this function only with a single threaded runtime,
-        // otherwise the `MOCK_TIME` variable might get mixed up between different threads.
+    #[tokio::test(flavor = "current_thread")]
+    async fn refresh_prune_audit_log() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default().audit_log_retention(Duration::from_secs(90 * 86400)),
+            )
+            .create_state()
+            .await;
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        for (command, age_days) in [("old", 100), ("recent", 1)] {
+            let entry = audit_log::ActiveModel {
+                pull_request: Set(pr.id),
+                author: Set("user".to_string()),
+                command: Set(command.to_string()),
+                created_at: Set((Utc::now() - chrono::Duration::days(age_days)).naive_utc()),
+                ..Default::default()
+            };
+            audit_log::Entity::insert(entry)
+                .exec_without_returning(state.db.connection())
+                .await
+                .unwrap();
+        }
+
+        // Pruning is idempotent
+        state.refresh().await;
+        state.refresh().await;
+
+        let commands = audit_log::Entity::find()
+            .all(state.db.connection())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.command)
+            .collect::<Vec<_>>();
+        assert_eq!(commands, vec!["recent".to_string()]);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn refresh_prune_failed_events() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .db
+            .record_failed_event(&default_repo_name(), "{}", "Cannot load PR")
+            .await
+            .unwrap();
+
+        state.refresh().await;
         assert_eq!(
-            tokio::runtime::Handle::current().runtime_flavor(),
-            RuntimeFlavor::CurrentThread
+            state
+                .db
+                .get_failed_events(&default_repo_name(), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
         );
-        MOCK_TIME.with(|time| {
-            *time.borrow_mut() = Some(Utc::now() + chrono::Duration::from_std(in_future).unwrap());
-        });
-        future.await;
-        MOCK_TIME.with(|time| {
-            *time.borrow_mut() = None;
-        });
+        with_mocked_time(FAILED_EVENT_RETENTION + Duration::from_secs(1), async {
+            state.refresh().await;
+        })
+        .await;
+        assert!(state
+            .db
+            .get_failed_events(&default_repo_name(), 10)
+            .await
+            .unwrap()
+            .is_empty());
     }
 }
@@ -0,0 +1,884 @@
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::bors::handlers::delegation::delegation_grants;
+use crate::bors::handlers::merge::occupies_queue;
+use crate::bors::handlers::nag::format_interval;
+use crate::bors::handlers::refresh::elapsed_time;
+use crate::bors::handlers::tree::tree_closed_notice;
+use crate::bors::handlers::trybuild::cancel_build_workflows;
+use crate::bors::handlers::workflow::report_check_run;
+use crate::bors::handlers::{check_skip_marker, CommandOutcome};
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{BuildStatus, CheckRunStatus, DbClient, PullRequestModel, RollupMode};
+use crate::github::{GithubUser, PullRequest};
+use crate::permissions::PermissionType;
+
+/// Approves a PR on behalf of `author`.
+///
+/// The PR enters the merge queue once it has been approved by at least `required_approvals`
+/// distinct users at its current head commit. The queue is then processed during the next
+/// refresh. If `priority` or `rollup` are specified, they are stored for the PR. If `delay` is
+/// specified, the PR does not enter the active merge queue before the delay elapses.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn command_approve<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    priority: Option<i32>,
+    rollup: Option<RollupMode>,
+    team: Option<String>,
+    delay: Option<Duration>,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(team) = &team {
+        if !repo
+            .permissions_resolver
+            .is_team_member(&author.username, team)
+            .await
+        {
+            return Ok(CommandOutcome::Rejected(format!(
+                "@{}: :key: Insufficient privileges: not a member of team `{team}`",
+                author.username
+            )));
+        }
+    }
+    if let Some(rejection) = check_self_approval(repo, db, pr, author).await? {
+        return Ok(rejection);
+    }
+    if let Some(rejection) = check_skip_marker(repo, pr).await? {
+        return Ok(rejection);
+    }
+
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    // A new approval without a delay cancels the delay of a previous one
+    let not_before = delay
+        .map(|delay| anyhow::Ok(Utc::now() + chrono::Duration::from_std(delay)?))
+        .transpose()?;
+    let required = repo.config.required_approvals;
+    let queue_full = match repo.config.max_queue_size {
+        Some(max_size) => {
+            let active = db
+                .list_queued_prs(&repo.repository)
+                .await?
+                .into_iter()
+                .filter(|queued| occupies_queue(queued) && queued.number != pr.number)
+                .count();
+            active >= max_size
+        }
+        None => false,
+    };
+
+    // The approval and its effect on the merge queue are stored together, so that a failure
+    // cannot leave e.g. an approved PR outside of the queue
+    let pr_model_ref = &pr_model;
+    db.transaction(Box::new(move |db| {
+        Box::pin(async move {
+            db.approve(
+                pr_model_ref,
+                &author.username,
+                team.as_deref(),
+                &pr.head.sha,
+            )
+            .await?;
+            if let Some(priority) = priority {
+                db.set_priority(pr_model_ref, priority).await?;
+            }
+            if let Some(rollup) = rollup {
+                db.set_rollup(pr_model_ref, rollup).await?;
+            }
+            db.set_not_before(pr_model_ref, not_before).await?;
+            // Reviewers do not need to be reminded about an approved PR
+            if pr_model_ref.nag_interval.is_some() {
+                db.set_nag(pr_model_ref, None, Utc::now()).await?;
+            }
+            if head_approvers(&*db, pr_model_ref, pr).await?.len() >= required {
+                db.enqueue(pr_model_ref, &pr.head.sha, &pr.title, &pr.author.username)
+                    .await?;
+                if queue_full {
+                    db.set_parked(pr_model_ref, true).await?;
+                }
+            }
+            Ok(())
+        })
+    }))
+    .await?;
+    tracing::info!("PR approved by {}", author.username);
+
+    let approvers = head_approvers(db, &pr_model, pr)
+        .await?
+        .into_iter()
+        .map(|approver| format!("`{approver}`"))
+        .collect::<Vec<_>>();
+    let mut message = if approvers.len() >= required {
+        if queue_full {
+            tracing::info!("PR is waiting for space in the merge queue");
+            format!(
+                ":pushpin: Commit {} has been approved by {}. The merge queue is full, the PR will enter it once there is space.",
+                pr.head.sha,
+                approvers.join(", ")
+            )
+        } else {
+            tracing::info!("PR entered the merge queue");
+            format!(
+                ":pushpin: Commit {} has been approved by {}",
+                pr.head.sha,
+                approvers.join(", ")
+            )
+        }
+    } else {
+        format!(
+            ":hourglass: Commit {} has been approved by {}. {} more approval(s) needed before it can enter the merge queue.",
+            pr.head.sha,
+            approvers.join(", "),
+            required - approvers.len()
+        )
+    };
+    if let Some(not_before) = not_before {
+        message.push_str(&format!(
+            "\n:alarm_clock: The PR will not enter the merge queue before {}.",
+            not_before.format("%Y-%m-%d %H:%M:%S UTC")
+        ));
+    }
+    // Remind about the closed tree only once, repeated approvals during the same closure are quiet
+    if approvers.len() >= required && !pr_model.tree_closed_notified {
+        let tree_state = db.get_tree_state(&repo.repository).await?;
+        if !tree_state.allows(priority.unwrap_or(pr_model.priority)) {
+            message.push_str(&format!("\n{}", tree_closed_notice(&tree_state)));
+            db.set_tree_closed_notified(&pr_model).await?;
+        }
+    }
+    if approvers.len() >= required {
+        if let Some(notice) = rebase_notice(repo, db, pr, &pr_model).await? {
+            message.push_str(&format!("\n{notice}"));
+        }
+    }
+    repo.post_status_comment(db, &pr_model, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Returns the users that have approved the current head commit of the PR, as only these
+/// approvals are relevant.
+pub(super) async fn head_approvers(
+    db: &(impl DbClient + ?Sized),
+    pr_model: &PullRequestModel,
+    pr: &PullRequest,
+) -> anyhow::Result<Vec<String>> {
+    Ok(db
+        .get_approvals(pr_model)
+        .await?
+        .into_iter()
+        .filter(|approval| approval.commit_sha == pr.head.sha.0)
+        .map(|approval| approval.reviewer().to_string())
+        .collect())
+}
+
+/// Advises the PR author to rebase the PR if it is far behind its base branch, as merging it is
+/// then more likely to fail. The advice is given only once for each head commit of the PR.
+async fn rebase_notice<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+) -> anyhow::Result<Option<String>> {
+    let Some(threshold) = repo.config.rebase_notice_threshold else {
+        return Ok(None);
+    };
+    if pr_model.rebase_notified_sha.as_deref() == Some(pr.head.sha.0.as_str()) {
+        return Ok(None);
+    }
+    let base = pr_model.base_branch.as_deref().unwrap_or(&pr.base.name);
+    let comparison = repo.client.compare_commits(base, &pr.head.sha.0).await?;
+    if comparison.behind_by < threshold {
+        return Ok(None);
+    }
+    db.set_rebase_notified(pr_model, &pr.head.sha).await?;
+    Ok(Some(format!(
+        ":warning: This PR is {} commits behind `{base}`, consider rebasing it to reduce the risk of a failed merge.",
+        comparison.behind_by
+    )))
+}
+
+/// Changes the merge queue priority of a PR and reports its new position in the queue, so that
+/// the effect of the change is visible.
+pub(super) async fn command_set_priority<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    priority: i32,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    db.set_priority(&pr_model, priority).await?;
+    tracing::info!("Priority set to {priority}");
+
+    let queue = db.list_queued_prs(&repo.repository).await?;
+    let message = match queue.iter().position(|queued| queued.number == pr.number) {
+        Some(index) => format!(
+            ":arrow_up_down: Priority of this PR has been set to {priority}, it is now at position {} of {} in the merge queue.",
+            index + 1,
+            queue.len()
+        ),
+        None => format!(
+            ":arrow_up_down: Priority of this PR has been set to {priority}. It will take effect once the PR enters the merge queue."
+        ),
+    };
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Removes the PR from the merge queue if its head commit is different from the approved one,
+/// e.g. because it was force-pushed, and tells the author that the approval has been dismissed.
+/// Returns `true` if the approval has been dismissed.
+pub(super) async fn dismiss_stale_approval<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+) -> anyhow::Result<bool> {
+    let Some(approved_sha) = dequeue_stale_approval(repo, db, pr, pr_model).await? else {
+        return Ok(false);
+    };
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":warning: The head commit of this PR has changed from {approved_sha} to {} since it was approved. The approval has been dismissed.",
+            pr.head.sha
+        ),
+    )
+    .await?;
+    rerequest_review(repo, db, pr_model, &approved_sha).await?;
+    Ok(true)
+}
+
+/// Removes the PR from the merge queue if its head commit is different from the approved one,
+/// without telling anyone. A running merge build of the PR is cancelled, so that the dismissed
+/// commit is not merged once the build finishes. Returns the dismissed commit, if any.
+pub(super) async fn dequeue_stale_approval<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+) -> anyhow::Result<Option<String>> {
+    let Some(approved_sha) = &pr_model.approved_sha else {
+        return Ok(None);
+    };
+    if *approved_sha == pr.head.sha.0 {
+        return Ok(None);
+    }
+
+    tracing::info!(
+        "Head of PR {} changed from {approved_sha} to {}, dismissing approval",
+        pr.number,
+        pr.head.sha
+    );
+    db.dequeue(pr_model).await?;
+    if let Some(build) = pr_model
+        .auto_build
+        .as_ref()
+        .filter(|build| build.status == BuildStatus::Pending)
+    {
+        tracing::info!(
+            "Cancelling merge build {} of the dismissed approval",
+            build.commit_sha
+        );
+        db.update_build_status(build, BuildStatus::Cancelled)
+            .await?;
+        report_check_run(repo, db, build, CheckRunStatus::Cancelled).await;
+        if let Err(error) = cancel_build_workflows(repo, db, build).await {
+            tracing::error!(
+                "Could not cancel workflows for SHA {}: {error:?}",
+                build.commit_sha
+            );
+        }
+    }
+    Ok(Some(approved_sha.clone()))
+}
+
+/// Removes PRs from the merge queue whose approval is older than the approval expiry of the
+/// repository, so that stale code is reviewed again. PRs that are currently being merged are left
+/// alone.
+pub(super) async fn expire_approvals<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    let Some(expiry) = repo.config.approval_expiry else {
+        return Ok(());
+    };
+    for pr_model in db.list_queued_prs(&repo.repository).await? {
+        let Some(approved_sha) = pr_model.approved_sha.clone() else {
+            continue;
+        };
+        if pr_model
+            .auto_build
+            .as_ref()
+            .is_some_and(|build| build.status == BuildStatus::Pending)
+        {
+            continue;
+        }
+        // The latest approval of the approved commit counts, re-approving the PR renews it
+        let approved_at = db
+            .get_approvals(&pr_model)
+            .await?
+            .into_iter()
+            .filter(|approval| approval.commit_sha == approved_sha)
+            .map(|approval| approval.created_at)
+            .max()
+            .or(pr_model.approved_at);
+        let Some(approved_at) = approved_at else {
+            continue;
+        };
+        if elapsed_time(approved_at) < expiry {
+            continue;
+        }
+
+        tracing::info!(
+            "Approval of PR {} from {approved_at} has expired, dismissing it",
+            pr_model.number
+        );
+        db.dequeue(&pr_model).await?;
+        repo.post_comment(
+            pr_model.number,
+            &format!(
+                ":hourglass_flowing_sand: This PR was approved more than {} ago and has not been merged since. The approval has expired, please review the PR again.",
+                format_interval(expiry)
+            ),
+        )
+        .await?;
+        rerequest_review(repo, db, &pr_model, &approved_sha).await?;
+    }
+    Ok(())
+}
+
+/// Asks the users and teams that have approved the given commit of the PR to review it again, if
+/// enabled in the repository configuration.
+pub(super) async fn rerequest_review<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr_model: &PullRequestModel,
+    approved_sha: &str,
+) -> anyhow::Result<()> {
+    if !repo.config.rerequest_review_on_dismissal {
+        return Ok(());
+    }
+    // Approvals on behalf of a team are requested from the team
+    let (team_reviewers, reviewers): (Vec<_>, Vec<_>) = db
+        .get_approvals(pr_model)
+        .await?
+        .into_iter()
+        .filter(|approval| approval.commit_sha == approved_sha)
+        .partition(|approval| approval.team.is_some());
+    let reviewers = reviewers
+        .into_iter()
+        .map(|approval| approval.approver)
+        .collect::<Vec<_>>();
+    let team_reviewers = team_reviewers
+        .into_iter()
+        .filter_map(|approval| approval.team)
+        .collect::<Vec<_>>();
+    tracing::info!("Requesting a new review from {reviewers:?} and teams {team_reviewers:?}");
+    repo.client
+        .request_reviewers(pr_model.number, &reviewers, &team_reviewers)
+        .await
+}
+
+/// Checks if `author` is trying to approve their own PR.
+/// Authors can only approve their own PRs if the approval has been delegated to them.
+async fn check_self_approval<Client: RepositoryClient>(
+    repo: &RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+) -> anyhow::Result<Option<CommandOutcome>> {
+    if author.username != pr.author.username {
+        return Ok(None);
+    }
+
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let delegated = db
+        .get_delegations(&pr_model)
+        .await?
+        .into_iter()
+        .any(|delegation| {
+            delegation.delegatee == author.username
+                && delegation_grants(delegation.scope, PermissionType::Review)
+        });
+    if delegated {
+        return Ok(None);
+    }
+
+    tracing::info!("Self-approval denied");
+    Ok(Some(CommandOutcome::Rejected(format!(
+        "@{}: :key: You cannot approve your own PR.",
+        author.username
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bors::handlers::merge::AUTO_BRANCH_NAME;
+    use crate::bors::handlers::refresh::with_mocked_time;
+    use crate::bors::CommitComparisonStatus;
+    use crate::database::{DbClient, TreeState, WorkflowStatus};
+    use crate::tests::event::{
+        comment, default_pr_author, default_pr_number, suite_success, user,
+        CheckSuiteCompletedBuilder, WorkflowCompletedBuilder, WorkflowStartedBuilder,
+    };
+    use crate::tests::github::{BranchBuilder, CommitBuilder, PRBuilder};
+    use crate::tests::permissions::{NoPermissions, PermissionsFor};
+    use crate::tests::state::{
+        default_merge_sha, default_repo_name, ClientBuilder, RepoConfigBuilder, TestBorsState,
+    };
+
+    const HOUR: u64 = 3600;
+
+    #[tokio::test]
+    async fn test_approve_no_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not in review users"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `<user>`");
+    }
+
+    #[tokio::test]
+    async fn test_approve_formatted_command() {
+        for text in ["**@bors r+**", "_@bors r+_", "`@bors r+`"] {
+            let mut state = ClientBuilder::default().create_state().await;
+            state.comment(text).await;
+            assert_eq!(
+                state.client().get_last_comment(default_pr_number()),
+                ":pushpin: Commit pr-sha has been approved by `<user>`"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approve_own_pr() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment(comment("@bors r+").author(default_pr_author()))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@pr-author: :key: You cannot approve your own PR."],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_own_pr_delegated() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors delegate+").await;
+        state
+            .comment(comment("@bors r+").author(default_pr_author()))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `pr-author`");
+    }
+
+    #[tokio::test]
+    async fn test_approve_team_non_member() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&["<user>"]).team("reviewers", &["alice"]),
+            ))
+            .create_state()
+            .await;
+        state.comment("@bors r=team:reviewers").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not a member of team `reviewers`"],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_team() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&["alice"]).team("reviewers", &["alice"]),
+            ))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors r=team:reviewers").author(user("alice")))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `reviewers`");
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        let approval = state.db.get_approvals(&pr).await.unwrap().remove(0);
+        assert_eq!(approval.approver, "alice");
+        assert_eq!(approval.team.as_deref(), Some("reviewers"));
+    }
+
+    #[tokio::test]
+    async fn test_approve_skip_marker_in_commit_message() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().add_commit(
+            CommitBuilder::default()
+                .sha("pr-sha".to_string())
+                .message("WIP\n\n[bors skip]".to_string())
+                .create(),
+        );
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":no_entry_sign: The head commit message contains `[bors skip]`, so this PR will not be built or merged.");
+        state.refresh().await;
+        state.client().check_branch_not_created(AUTO_BRANCH_NAME);
+    }
+
+    #[tokio::test]
+    async fn test_approve_required_approvals() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().required_approvals(2))
+            .create_state()
+            .await;
+
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Commit pr-sha has been approved by `<user>`. 1 more approval(s) needed before it can enter the merge queue.");
+
+        // The same user approving again does not count
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Commit pr-sha has been approved by `<user>`. 1 more approval(s) needed before it can enter the merge queue.");
+
+        state
+            .comment(comment("@bors r+").author(user("reviewer")))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `<user>`, `reviewer`");
+    }
+
+    #[tokio::test]
+    async fn test_force_push_dismisses_approval_in_queue() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        force_push(&mut state);
+        state.refresh().await;
+
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+                ":warning: The head commit of this PR has changed from pr-sha to pr-sha-2 since it was approved. The approval has been dismissed.",
+            ],
+        );
+        state
+            .client()
+            .check_branch_not_created("automation/bors/auto");
+    }
+
+    #[tokio::test]
+    async fn test_force_push_dismisses_approval_on_command() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        force_push(&mut state);
+        state.comment("@bors ping").await;
+        // The approval is only dismissed once
+        state.comment("@bors history").await;
+
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+                ":warning: The head commit of this PR has changed from pr-sha to pr-sha-2 since it was approved. The approval has been dismissed.",
+                "Pong 🏓! Queued PRs: 0, events in flight: 0",
+                ":scroll: There are no builds of this PR yet.",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_force_push_cancels_running_merge_build() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(AUTO_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        force_push(&mut state);
+        state.comment("@bors ping").await;
+        state.client().check_cancelled_workflows(&[1]);
+
+        state
+            .workflow_completed(
+                WorkflowCompletedBuilder::default()
+                    .branch(AUTO_BRANCH_NAME.to_string())
+                    .run_id(1)
+                    .status(WorkflowStatus::Success),
+            )
+            .await;
+        state
+            .check_suite_completed(
+                CheckSuiteCompletedBuilder::default().branch(AUTO_BRANCH_NAME.to_string()),
+            )
+            .await;
+        // The dismissed commit is not merged
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @"Pong 🏓! Queued PRs: 0, events in flight: 0");
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_force_push_rerequests_review() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rerequest_review_on_dismissal(true))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("reviewer")))
+            .await;
+        force_push(&mut state);
+        state.refresh().await;
+
+        state
+            .client()
+            .check_requested_reviewers(default_pr_number(), &["reviewer"]);
+    }
+
+    #[tokio::test]
+    async fn test_force_push_reapproval_does_not_announce_dismissal() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rerequest_review_on_dismissal(true))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors r+").author(user("reviewer")))
+            .await;
+        force_push(&mut state);
+        state
+            .comment(comment("@bors r+").author(user("reviewer")))
+            .await;
+
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `reviewer`",
+                ":pushpin: Commit pr-sha-2 has been approved by `reviewer`",
+            ],
+        );
+        state
+            .client()
+            .check_requested_reviewers(default_pr_number(), &[]);
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.approved_sha.as_deref(), Some("pr-sha-2"));
+    }
+
+    #[tokio::test]
+    async fn test_force_push_rerequests_team_review() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(
+                PermissionsFor::new(&["alice"]).team("reviewers", &["alice"]),
+            ))
+            .config(RepoConfigBuilder::default().rerequest_review_on_dismissal(true))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors r=team:reviewers").author(user("alice")))
+            .await;
+        force_push(&mut state);
+        state.refresh().await;
+
+        state
+            .client()
+            .check_requested_reviewers(default_pr_number(), &[])
+            .check_requested_team_reviewers(default_pr_number(), &["reviewers"]);
+    }
+
+    #[tokio::test]
+    async fn test_force_push_does_not_rerequest_review_by_default() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        force_push(&mut state);
+        state.refresh().await;
+
+        state
+            .client()
+            .check_requested_reviewers(default_pr_number(), &[]);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_expired_approval_is_dismissed() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().approval_expiry(Some(Duration::from_secs(HOUR))))
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+
+        with_mocked_time(Duration::from_secs(2 * HOUR), async {
+            state.refresh().await;
+        })
+        .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass_flowing_sand: This PR was approved more than 1h ago and has not been merged since. The approval has expired, please review the PR again.");
+        assert!(state
+            .db
+            .list_queued_prs(&default_repo_name())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    fn tree_closed() -> TreeState {
+        TreeState::Closed {
+            priority: None,
+            reason: None,
+            reopen_at: None,
+        }
+    }
+
+    fn force_push(state: &mut TestBorsState) {
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .head(
+                    BranchBuilder::default()
+                        .sha("pr-sha-2".to_string())
+                        .create(),
+                )
+                .create())
+        });
+    }
+
+    #[tokio::test]
+    async fn test_approve_tree_closed_notifies_once() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .db
+            .set_tree_state(&default_repo_name(), &tree_closed())
+            .await
+            .unwrap();
+        state.comment("@bors r+").await;
+        state.comment("@bors r+").await;
+
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`\n:evergreen_tree: The tree is currently closed, this PR will be merged once it reopens.",
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_tree_closed_notifies_again_after_reopen() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let repo = default_repo_name();
+        state
+            .db
+            .set_tree_state(&repo, &tree_closed())
+            .await
+            .unwrap();
+        state.comment("@bors r+").await;
+        state
+            .db
+            .set_tree_state(&repo, &TreeState::Open)
+            .await
+            .unwrap();
+        state
+            .db
+            .set_tree_state(&repo, &tree_closed())
+            .await
+            .unwrap();
+        state.comment("@bors r+ p=1").await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :pushpin: Commit pr-sha has been approved by `<user>`
+        :evergreen_tree: The tree is currently closed, this PR will be merged once it reopens.
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_approve_far_behind_base_notifies_once() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rebase_notice_threshold(Some(20)))
+            .create_state()
+            .await;
+        state.client().set_comparison(
+            "main-branch",
+            "pr-sha",
+            CommitComparisonStatus::Diverged,
+            1,
+            50,
+        );
+        state.comment("@bors r+").await;
+        state.comment("@bors r+").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`\n:warning: This PR is 50 commits behind `main-branch`, consider rebasing it to reduce the risk of a failed merge.",
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_slightly_behind_base() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rebase_notice_threshold(Some(20)))
+            .create_state()
+            .await;
+        state.client().set_comparison(
+            "main-branch",
+            "pr-sha",
+            CommitComparisonStatus::Diverged,
+            1,
+            5,
+        );
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `<user>`");
+    }
+
+    #[tokio::test]
+    async fn test_set_priority_reports_queue_position() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.comment(comment("@bors r+").pr_number(2)).await;
+
+        state.comment(comment("@bors p=5").pr_number(2)).await;
+        insta::assert_snapshot!(state.client().get_last_comment(2), @":arrow_up_down: Priority of this PR has been set to 5, it is now at position 1 of 2 in the merge queue.");
+        let queue = state
+            .db
+            .list_queued_prs(&default_repo_name())
+            .await
+            .unwrap();
+        assert_eq!(
+            queue.iter().map(|pr| pr.number.0).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_priority_not_approved() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors p=5").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":arrow_up_down: Priority of this PR has been set to 5. It will take effect once the PR enters the merge queue.");
+    }
+}
@@ -0,0 +1,334 @@
+use crate::bors::handlers::review::command_approve;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{DbClient, RollupMode};
+use crate::github::{GithubUser, PullRequest, PullRequestNumber};
+
+/// Approves a rollup PR and records the PRs that it batches, which are marked as merged once the
+/// rollup is merged.
+///
+/// The members are read from the checklist in the description of the rollup PR. All of them have
+/// to be approved, and they are only recorded once the rollup itself has been approved.
+pub(super) async fn command_approve_rollup<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+) -> anyhow::Result<CommandOutcome> {
+    let members = parse_rollup_members(&pr.message);
+    if members.is_empty() {
+        return Ok(CommandOutcome::Rejected(
+            ":exclamation: The description of this PR does not list any PRs to roll up, e.g. `- [x] #123`.".to_string(),
+        ));
+    }
+    let mut unapproved = vec![];
+    for member in &members {
+        let member_model = db
+            .get_or_create_pull_request(repo.client.repository(), *member)
+            .await?;
+        if *member == pr.number
+            || member_model.approved_sha.is_none()
+            || member_model.merged_at.is_some()
+        {
+            unapproved.push(format!("#{member}"));
+        }
+    }
+    if !unapproved.is_empty() {
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: Only approved PRs can be rolled up, these are not: {}.",
+            unapproved.join(", ")
+        )));
+    }
+
+    // A rollup cannot be rolled up again
+    let outcome = command_approve(
+        repo,
+        db,
+        pr,
+        author,
+        None,
+        Some(RollupMode::Never),
+        None,
+        None,
+    )
+    .await?;
+    if matches!(outcome, CommandOutcome::Handled) {
+        let pr_model = db
+            .get_or_create_pull_request(repo.client.repository(), pr.number)
+            .await?;
+        db.set_rollup_members(&pr_model, &members).await?;
+        tracing::info!("Rollup of {} PR(s)", members.len());
+    }
+    Ok(outcome)
+}
+
+/// Returns the PRs listed in the checklist of a rollup PR description, like `- [x] #123 (title)`.
+/// Unchecked items are skipped, as they are not part of the rollup. Plain list items without a
+/// checkbox are also accepted.
+fn parse_rollup_members(description: &str) -> Vec<PullRequestNumber> {
+    let mut members = vec![];
+    for line in description.lines() {
+        let line = line.trim_start();
+        let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")) else {
+            continue;
+        };
+        let item = item.trim_start();
+        let item = match item.get(..3) {
+            Some("[x]" | "[X]") => item[3..].trim_start(),
+            Some("[ ]") => continue,
+            _ => item,
+        };
+        let Some(number) = item.strip_prefix('#') else {
+            continue;
+        };
+        let digits = number
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .unwrap_or_default();
+        if let Ok(number) = digits.parse() {
+            let number = PullRequestNumber(number);
+            if !members.contains(&number) {
+                members.push(number);
+            }
+        }
+    }
+    members
+}
+
+/// Recommends merging the `rollup=maybe` PRs waiting in the merge queue together in a rollup, once
+/// there are at least `rollup_suggestion_threshold` of them.
+///
+/// The suggestion is only advisory and it is posted to the first candidate in the queue. It is not
+/// repeated until the set of candidates changes. It is deferred while the rate-limit budget of
+/// the repository is low.
+pub(super) async fn suggest_rollup<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    let Some(threshold) = repo.config.rollup_suggestion_threshold else {
+        return Ok(());
+    };
+
+    let candidates = db
+        .list_queued_prs(&repo.repository)
+        .await?
+        .into_iter()
+        .filter(|pr| pr.rollup == Some(RollupMode::Maybe) && pr.auto_build.is_none())
+        .collect::<Vec<_>>();
+    if candidates.len() < threshold {
+        return Ok(());
+    }
+
+    let numbers = candidates.iter().map(|pr| pr.number).collect::<Vec<_>>();
+    if numbers == db.get_suggested_rollup(&repo.repository).await? {
+        return Ok(());
+    }
+    if !repo.has_rate_limit_budget() {
+        tracing::info!("Deferring the rollup suggestion, the rate-limit budget is low");
+        return Ok(());
+    }
+
+    tracing::info!("Suggesting a rollup of {} PR(s)", candidates.len());
+    let list = candidates
+        .iter()
+        .map(|pr| match &pr.title {
+            Some(title) => format!("- #{} {title}", pr.number),
+            None => format!("- #{}", pr.number),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    repo.post_comment(
+        numbers[0],
+        &format!(
+            r#":bulb: There are {} PRs marked with `rollup=maybe` waiting in the merge queue. Consider merging them together in a rollup:
+{list}"#,
+            candidates.len()
+        ),
+    )
+    .await?;
+    db.set_suggested_rollup(&repo.repository, &numbers).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::handlers::merge::AUTO_BRANCH_NAME;
+    use crate::bors::handlers::rollup::parse_rollup_members;
+    use crate::database::{DbClient, WorkflowStatus};
+    use crate::github::api::rate_limit::RateLimit;
+    use crate::github::PullRequestNumber;
+    use crate::tests::event::{comment, default_pr_number, suite_success};
+    use crate::tests::github::PRBuilder;
+    use crate::tests::state::{
+        default_merge_sha, default_repo_name, ClientBuilder, RepoConfigBuilder, TestBorsState,
+    };
+
+    const ROLLUP_DESCRIPTION: &str = r#"Successful merges:
+
+- [x] #2 (Fix the build)
+- [ ] #3 (Not included)
+ * [X] #4
+- #5 (Without a checkbox)
+- Unrelated item #6"#;
+
+    #[test]
+    fn parse_members() {
+        assert_eq!(
+            parse_rollup_members(ROLLUP_DESCRIPTION),
+            vec![
+                PullRequestNumber(2),
+                PullRequestNumber(4),
+                PullRequestNumber(5)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_approve_rollup_without_members() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors approve-rollup").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: The description of this PR does not list any PRs to roll up, e.g. `- [x] #123`.");
+    }
+
+    #[tokio::test]
+    async fn test_approve_rollup_marks_members_merged() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .message(ROLLUP_DESCRIPTION.to_string())
+                .create())
+        });
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        for member in [2, 4, 5] {
+            state.comment(comment("@bors r+").pr_number(member)).await;
+        }
+        // The rollup is built before its members
+        state.comment("@bors p=5").await;
+        state.comment("@bors approve-rollup").await;
+        // A member that is not approved anymore is not marked as merged
+        let member = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), PullRequestNumber(5))
+            .await
+            .unwrap();
+        state.db.dequeue(&member).await.unwrap();
+        state.refresh().await;
+        state
+            .perform_workflow_events(
+                1,
+                AUTO_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        let mut merged = vec![];
+        for number in [2, 3, 4, 5] {
+            let pr = state
+                .db
+                .get_or_create_pull_request(&default_repo_name(), PullRequestNumber(number))
+                .await
+                .unwrap();
+            if pr.merged_at.is_some() {
+                merged.push(number);
+            }
+        }
+        assert_eq!(merged, vec![2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_approve_rollup_with_unapproved_members() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .message(ROLLUP_DESCRIPTION.to_string())
+                .create())
+        });
+        state.comment(comment("@bors r+").pr_number(2)).await;
+        state.comment("@bors approve-rollup").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: Only approved PRs can be rolled up, these are not: #4, #5.");
+
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert!(pr.approved_sha.is_none());
+        assert!(pr.rollup_members.is_empty());
+    }
+
+    async fn approve_rollup_maybe(state: &mut TestBorsState, pr: u64) {
+        state
+            .comment(comment("@bors r+ rollup=maybe").pr_number(pr))
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_suggest_rollup_once() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rollup_suggestion_threshold(Some(3)))
+            .create_state()
+            .await;
+        // Keep the queue occupied, so that the candidates are not merged one by one
+        state.comment(comment("@bors r+").pr_number(10)).await;
+        state.refresh().await;
+        for pr in 1..=3 {
+            approve_rollup_maybe(&mut state, pr).await;
+        }
+        state.refresh().await;
+        state.refresh().await;
+
+        state.client().check_comments(
+            1,
+            &[
+                ":pushpin: Commit pr-sha has been approved by `<user>`",
+                ":bulb: There are 3 PRs marked with `rollup=maybe` waiting in the merge queue. Consider merging them together in a rollup:\n- #1 PR title\n- #2 PR title\n- #3 PR title",
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggest_rollup_below_threshold() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rollup_suggestion_threshold(Some(3)))
+            .create_state()
+            .await;
+        state.comment(comment("@bors r+").pr_number(10)).await;
+        state.refresh().await;
+        for pr in 1..=2 {
+            approve_rollup_maybe(&mut state, pr).await;
+        }
+        state.refresh().await;
+
+        state.client().check_comment_count(1, 1);
+    }
+
+    #[tokio::test]
+    async fn test_suggest_rollup_deferred_on_low_rate_limit() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rollup_suggestion_threshold(Some(3)))
+            .create_state()
+            .await;
+        state.comment(comment("@bors r+").pr_number(10)).await;
+        state.refresh().await;
+        for pr in 1..=3 {
+            approve_rollup_maybe(&mut state, pr).await;
+        }
+        state.client().rate_limit = Some(RateLimit {
+            remaining: 100,
+            limit: 5000,
+        });
+        state.refresh().await;
+        state.client().check_comment_count(1, 1);
+
+        state.client().rate_limit = Some(RateLimit {
+            remaining: 5000,
+            limit: 5000,
+        });
+        state.refresh().await;
+        state.client().check_comment_count(1, 2);
+    }
+}
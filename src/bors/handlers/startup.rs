@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use crate::bors::handlers::merge::{AUTO_BRANCH_NAME, AUTO_MERGE_BRANCH_NAME};
+use crate::bors::handlers::trybuild::{TRY_BRANCH_NAME, TRY_MERGE_BRANCH_NAME};
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::DbClient;
+use crate::github::Branch;
+
+/// Prefix of the names of all branches managed by bors.
+const BORS_BRANCH_PREFIX: &str = "automation/bors/";
+
+/// Deletes the bors branches that are left behind without a running build. If e.g. the auto branch
+/// of a base branch is removed from the config while its build is running, nothing else would ever
+/// clean up the branch.
+pub(super) async fn reconcile_branches<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    for branch in find_stale_branches(repo, db).await? {
+        tracing::info!("Deleting stale branch {} at {}", branch.name, branch.sha);
+        repo.client.delete_branch(&branch.name).await?;
+    }
+    Ok(())
+}
+
+/// Returns the bors branches that are not used by any running build. The working branches of the
+/// current configuration are never stale, they are shared by all builds and CI of the repository
+/// is set up to run on them.
+pub(super) async fn find_stale_branches<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<Vec<Branch>> {
+    let mut used_branches = db
+        .get_running_builds(&repo.repository)
+        .await?
+        .into_iter()
+        .map(|build| build.branch)
+        .collect::<HashSet<_>>();
+    used_branches.extend(
+        [
+            TRY_BRANCH_NAME,
+            TRY_MERGE_BRANCH_NAME,
+            AUTO_BRANCH_NAME,
+            AUTO_MERGE_BRANCH_NAME,
+        ]
+        .map(String::from),
+    );
+    used_branches.extend(repo.config.auto_branches.values().cloned());
+    Ok(repo
+        .client
+        .list_branches(BORS_BRANCH_PREFIX)
+        .await?
+        .into_iter()
+        .filter(|branch| !used_branches.contains(&branch.name))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::event::BorsEvent;
+    use crate::bors::RepositoryClient;
+    use crate::tests::state::{ClientBuilder, RepoConfigBuilder};
+
+    #[tokio::test]
+    async fn test_startup_deletes_stale_branch() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_branch("automation/bors/auto-release", "sha-stale");
+        state.client().set_branch("feature", "sha-feature");
+        state.event(BorsEvent::Startup).await;
+
+        state
+            .client()
+            .check_branch_deleted("automation/bors/auto-release")
+            .check_branch_history("feature", &["sha-feature"]);
+    }
+
+    #[tokio::test]
+    async fn test_startup_keeps_working_branches() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default().auto_branch("release", "automation/bors/auto-release"),
+            )
+            .create_state()
+            .await;
+        let branches = [
+            "automation/bors/try",
+            "automation/bors/try-merge",
+            "automation/bors/auto",
+            "automation/bors/auto-merge",
+            "automation/bors/auto-release",
+        ];
+        for branch in branches {
+            state.client().set_branch(branch, "sha-stale");
+        }
+        state.event(BorsEvent::Startup).await;
+
+        for branch in branches {
+            assert!(state
+                .client()
+                .get_branch_sha(branch)
+                .await
+                .unwrap()
+                .is_some());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_startup_keeps_branch_of_running_build() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors try").await;
+        state.event(BorsEvent::Startup).await;
+
+        assert!(state
+            .client()
+            .get_branch_sha("automation/bors/try")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,255 @@
+use crate::bors::event::CheckSuiteCompleted;
+use crate::bors::handlers::review::dismiss_stale_approval;
+use crate::bors::handlers::workflow::try_complete_build;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{BuildModel, BuildStatus, DbClient, WorkflowStatus, WorkflowType};
+use crate::github::{CommitSha, PullRequest};
+
+/// Reconciles the state of the PR stored in the DB with its current state on GitHub, e.g. after
+/// the bot has missed some webhooks.
+///
+/// The stored approval is validated against the current head of the PR and the workflows of
+/// running builds are updated with the status of their workflow runs. The merge build of a
+/// dismissed approval is cancelled instead of being completed. A summary of the changes is posted
+/// to the PR.
+pub(super) async fn command_sync<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    let mut pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let mut changes = vec![];
+
+    if let Some(approved_sha) = pr_model.approved_sha.clone() {
+        if dismiss_stale_approval(repo, db, pr, &pr_model).await? {
+            changes.push(format!(
+                "Dismissed the approval of {approved_sha}, the head commit is now {}",
+                pr.head.sha
+            ));
+            // The merge build of the dismissed approval has been cancelled, it must not be
+            // completed below
+            if let Some(build) = pr_model
+                .auto_build
+                .take()
+                .filter(|build| build.status == BuildStatus::Pending)
+            {
+                changes.push(format!("Cancelled the merge build {}", build.commit_sha));
+            }
+        }
+    }
+
+    let running_builds = [pr_model.try_build, pr_model.auto_build]
+        .into_iter()
+        .flatten()
+        .filter(|build| build.status == BuildStatus::Pending);
+    for build in running_builds {
+        if sync_build_workflows(repo, db, &build, &mut changes).await? {
+            try_complete_build(
+                repo,
+                db,
+                CheckSuiteCompleted {
+                    repository: repo.repository.clone(),
+                    branch: build.branch.clone(),
+                    commit_sha: CommitSha(build.commit_sha.clone()),
+                },
+            )
+            .await?;
+        }
+    }
+
+    let message = if changes.is_empty() {
+        ":arrows_counterclockwise: This PR is already in sync with GitHub.".to_string()
+    } else {
+        format!(
+            ":arrows_counterclockwise: Synchronized this PR with GitHub:\n{}",
+            changes
+                .iter()
+                .map(|change| format!("- {change}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        )
+    };
+    repo.post_comment(pr.number, &message).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Updates pending workflows of the build with the status of their GitHub workflow runs.
+/// Returns `true` if the status of any workflow has changed.
+async fn sync_build_workflows<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+    changes: &mut Vec<String>,
+) -> anyhow::Result<bool> {
+    let runs = repo
+        .client
+        .get_workflow_runs_for_commit(&build.branch, &CommitSha(build.commit_sha.clone()))
+        .await?;
+
+    let mut updated = false;
+    // External CI workflows are not visible in GitHub Actions
+    let workflows = db.get_workflows_for_build(build).await?;
+    for workflow in workflows
+        .iter()
+        .filter(|w| w.status == WorkflowStatus::Pending && w.workflow_type == WorkflowType::Github)
+    {
+        let Some(run) = runs.iter().find(|run| run.run_id == workflow.run_id) else {
+            continue;
+        };
+        if run.status == WorkflowStatus::Pending {
+            continue;
+        }
+        db.update_workflow_status(workflow.run_id.0, run.status)
+            .await?;
+        let status = match run.status {
+            WorkflowStatus::Success => "succeeded",
+            WorkflowStatus::Failure | WorkflowStatus::Pending => "failed",
+        };
+        changes.push(format!(
+            "Workflow `{}` of build {} has {status}",
+            workflow.name, build.commit_sha
+        ));
+        updated = true;
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::handlers::merge::AUTO_BRANCH_NAME;
+    use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+    use crate::database::WorkflowStatus;
+    use crate::tests::event::{
+        comment, default_pr_number, suite_success, user, WorkflowStartedBuilder,
+    };
+    use crate::tests::github::{BranchBuilder, PRBuilder};
+    use crate::tests::permissions::PermissionsFor;
+    use crate::tests::state::{default_merge_sha, ClientBuilder};
+
+    #[tokio::test]
+    async fn test_sync_dismisses_stale_approval() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .head(
+                    BranchBuilder::default()
+                        .sha("pr-sha-2".to_string())
+                        .create(),
+                )
+                .create())
+        });
+        state.comment("@bors sync").await;
+
+        insta::assert_snapshot!(state.client().get_comment(default_pr_number(), 1), @":warning: The head commit of this PR has changed from pr-sha to pr-sha-2 since it was approved. The approval has been dismissed.");
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :arrows_counterclockwise: Synchronized this PR with GitHub:
+        - Dismissed the approval of pr-sha, the head commit is now pr-sha-2
+        "###);
+        // The PR has left the merge queue
+        state.refresh().await;
+        state
+            .client()
+            .check_branch_not_created("automation/bors/auto");
+    }
+
+    #[tokio::test]
+    async fn test_sync_stale_approval_with_running_merge_build() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors r+").await;
+        state.refresh().await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(AUTO_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .head(
+                    BranchBuilder::default()
+                        .sha("pr-sha-2".to_string())
+                        .create(),
+                )
+                .create())
+        });
+        // The workflow of the merge build has finished, but the event was never received
+        state
+            .client()
+            .set_workflow_runs(&default_merge_sha(), &[(1, WorkflowStatus::Success)]);
+        state.comment("@bors sync").await;
+
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :arrows_counterclockwise: Synchronized this PR with GitHub:
+        - Dismissed the approval of pr-sha, the head commit is now pr-sha-2
+        - Cancelled the merge build sha-merged
+        "###);
+        state.client().check_cancelled_workflows(&[1]);
+        state.client().check_branch_not_created("main-branch");
+    }
+
+    #[tokio::test]
+    async fn test_sync_completes_build_with_missed_events() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .name("workflow-1".to_string())
+                    .url(Some("https://workflow-1.com".to_string()))
+                    .run_id(1),
+            )
+            .await;
+        // The workflow completed event was never received
+        state
+            .client()
+            .set_workflow_runs(&default_merge_sha(), &[(1, WorkflowStatus::Success)]);
+        state.comment("@bors sync").await;
+
+        let client = state.client();
+        let comments = (1..3)
+            .map(|index| client.get_comment(default_pr_number(), index))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(comments, @r###"
+        [
+            ":sunny: Try build successful\n- [workflow-1](https://workflow-1.com) :white_check_mark:\nBuild commit: sha-merged (`sha-merged`)",
+            ":arrows_counterclockwise: Synchronized this PR with GitHub:\n- Workflow `workflow-1` of build sha-merged has succeeded",
+        ]
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_sync_in_sync() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors sync").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":arrows_counterclockwise: This PR is already in sync with GitHub.");
+    }
+
+    #[tokio::test]
+    async fn test_sync_insufficient_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["reviewer"])))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors sync").author(user("contributor")))
+            .await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@contributor: :key: Insufficient privileges: not in review users"],
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use chrono::{DateTime, Utc};
+
+use crate::bors::handlers::refresh::now;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{DbClient, TreeState};
+use crate::github::PullRequest;
+
+/// Closes the tree of the repository, so that PRs below the given `priority` (or all PRs, if it
+/// is not specified) are not merged until the tree is opened again, either explicitly or
+/// automatically at the time `until`. The `reason` is shown to the authors of PRs approved while
+/// the tree is closed.
+pub(super) async fn command_tree_closed<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    priority: Option<i32>,
+    until: Option<DateTime<Utc>>,
+    reason: Option<String>,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(until) = until.filter(|until| *until <= now()) {
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: The time {} is in the past, the tree would be opened right away.",
+            format_time(until)
+        )));
+    }
+    let state = TreeState::Closed {
+        priority,
+        reason,
+        reopen_at: until,
+    };
+    db.set_tree_state(&repo.repository, &state).await?;
+    tracing::info!("Tree closed: {state:?}");
+    repo.post_comment(pr.number, &tree_state_message(&state))
+        .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Opens the tree of the repository, the merge queue is then processed during the next refresh.
+pub(super) async fn command_tree_open<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    db.set_tree_state(&repo.repository, &TreeState::Open)
+        .await?;
+    tracing::info!("Tree opened");
+    repo.post_comment(pr.number, &tree_state_message(&TreeState::Open))
+        .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Opens the tree of the repository if the time at which it should reopen has passed. The PRs
+/// whose authors were told that the tree is closed are notified.
+pub(super) async fn reopen_tree<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+) -> anyhow::Result<()> {
+    let TreeState::Closed {
+        reopen_at: Some(reopen_at),
+        ..
+    } = db.get_tree_state(&repo.repository).await?
+    else {
+        return Ok(());
+    };
+    if reopen_at > now() {
+        return Ok(());
+    }
+
+    // The notification flags are reset when the tree is opened
+    let notified = db
+        .list_queued_prs(&repo.repository)
+        .await?
+        .into_iter()
+        .filter(|pr| pr.tree_closed_notified)
+        .collect::<Vec<_>>();
+    db.set_tree_state(&repo.repository, &TreeState::Open)
+        .await?;
+    tracing::info!("Tree reopened at the scheduled time {reopen_at}");
+    for pr in notified {
+        repo.post_status_comment(
+            db,
+            &pr,
+            ":evergreen_tree: The tree has been reopened at the scheduled time, this PR can be merged now.",
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Notice for the author of a PR that has been approved while the tree is closed.
+pub(super) fn tree_closed_notice(state: &TreeState) -> String {
+    let mut notice = match state {
+        TreeState::Closed {
+            priority: Some(priority),
+            ..
+        } => format!(":evergreen_tree: The tree is currently closed for PRs with priority below {priority}, this PR will be merged once it reopens."),
+        _ => ":evergreen_tree: The tree is currently closed, this PR will be merged once it reopens."
+            .to_string(),
+    };
+    if let TreeState::Closed {
+        reason: Some(reason),
+        ..
+    } = state
+    {
+        notice.push_str(&format!(" Reason: {reason}"));
+    }
+    if let TreeState::Closed {
+        reopen_at: Some(reopen_at),
+        ..
+    } = state
+    {
+        notice.push_str(&format!(
+            " The tree will reopen at {}.",
+            format_time(*reopen_at)
+        ));
+    }
+    notice
+}
+
+fn tree_state_message(state: &TreeState) -> String {
+    match state {
+        TreeState::Open => ":evergreen_tree: The tree is now open.".to_string(),
+        TreeState::Closed {
+            priority,
+            reason,
+            reopen_at,
+        } => {
+            let mut message = match priority {
+                Some(priority) => format!(
+                    ":evergreen_tree: The tree is now closed for PRs with priority below {priority}."
+                ),
+                None => ":evergreen_tree: The tree is now closed.".to_string(),
+            };
+            if let Some(reason) = reason {
+                message.push_str(&format!(" Reason: {reason}"));
+            }
+            if let Some(reopen_at) = reopen_at {
+                message.push_str(&format!(
+                    " The tree will reopen at {}.",
+                    format_time(*reopen_at)
+                ));
+            }
+            message
+        }
+    }
+}
+
+pub(super) fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::bors::handlers::refresh::{now, with_mocked_time};
+    use crate::database::{DbClient, TreeState};
+    use crate::tests::event::default_pr_number;
+    use crate::tests::state::{default_repo_name, ClientBuilder};
+
+    #[tokio::test]
+    async fn test_tree_closed_reason_in_approval() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors treeclosed deploying hotfix").await;
+        state.comment("@bors r+").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :pushpin: Commit pr-sha has been approved by `<user>`
+        :evergreen_tree: The tree is currently closed, this PR will be merged once it reopens. Reason: deploying hotfix
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_tree_closed_priority() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors treeclosed=5 deploying hotfix").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":evergreen_tree: The tree is now closed for PRs with priority below 5. Reason: deploying hotfix");
+        // PRs with a high enough priority are still merged
+        state.comment("@bors r+ p=5").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":pushpin: Commit pr-sha has been approved by `<user>`");
+        state.comment("@bors r+ p=1").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :pushpin: Commit pr-sha has been approved by `<user>`
+        :evergreen_tree: The tree is currently closed for PRs with priority below 5, this PR will be merged once it reopens. Reason: deploying hotfix
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_tree_open_clears_reason() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors treeclosed deploying hotfix").await;
+        state.comment("@bors treeopen").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":evergreen_tree: The tree is now open.");
+        assert_eq!(
+            state.db.get_tree_state(&default_repo_name()).await.unwrap(),
+            TreeState::Open
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tree_reopens_at_scheduled_time() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let until = (now() + chrono::Duration::hours(1)).format("%Y-%m-%dT%H:%MZ");
+        state
+            .comment(format!("@bors treeclosed until={until} deploying hotfix").as_str())
+            .await;
+        state.comment("@bors r+").await;
+
+        // The tree stays closed until the scheduled time
+        state.refresh().await;
+        assert!(!state
+            .db
+            .get_tree_state(&default_repo_name())
+            .await
+            .unwrap()
+            .is_open());
+
+        with_mocked_time(Duration::from_secs(2 * 60 * 60), async {
+            state.refresh().await;
+        })
+        .await;
+        assert_eq!(
+            state.db.get_tree_state(&default_repo_name()).await.unwrap(),
+            TreeState::Open
+        );
+        insta::assert_snapshot!(state.client().get_comment(default_pr_number(), 2), @":evergreen_tree: The tree has been reopened at the scheduled time, this PR can be merged now.");
+    }
+
+    #[tokio::test]
+    async fn test_tree_closed_until_past_time() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .comment("@bors treeclosed until=2020-01-01T09:00Z")
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: The time 2020-01-01 09:00:00 UTC is in the past, the tree would be opened right away.");
+        assert_eq!(
+            state.db.get_tree_state(&default_repo_name()).await.unwrap(),
+            TreeState::Open
+        );
+    }
+}
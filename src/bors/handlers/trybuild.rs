@@ -1,21 +1,27 @@
 use anyhow::anyhow;
 
+use crate::bors::command::FULL_SHA_LENGTH;
+use crate::bors::handlers::history::build_estimate_line;
 use crate::bors::handlers::labels::handle_label_trigger;
+use crate::bors::handlers::refresh::now;
+use crate::bors::handlers::workflow::{deactivate_deployment, report_check_run};
+use crate::bors::handlers::{check_skip_marker, skips_ci, CommandOutcome, SKIPPED_CI_NOTE};
 use crate::bors::RepositoryClient;
 use crate::bors::RepositoryState;
 use crate::database::{
-    BuildModel, BuildStatus, DbClient, PullRequestModel, WorkflowStatus, WorkflowType,
+    BuildModel, BuildStatus, CheckRunStatus, DbClient, PullRequestModel, WorkflowStatus,
+    WorkflowType,
 };
 use crate::github::{
     CommitSha, GithubUser, LabelTrigger, MergeError, PullRequest, PullRequestNumber,
+    ResolveRefError,
 };
-use crate::permissions::PermissionType;
 
 // This branch serves for preparing the final commit.
 // It will be reset to master and merged with the branch that should be tested.
 // Because this action (reset + merge) is not atomic, this branch should not run CI checks to avoid
 // starting them twice.
-const TRY_MERGE_BRANCH_NAME: &str = "automation/bors/try-merge";
+pub(super) const TRY_MERGE_BRANCH_NAME: &str = "automation/bors/try-merge";
 
 // This branch should run CI checks.
 pub(super) const TRY_BRANCH_NAME: &str = "automation/bors/try";
@@ -24,58 +30,249 @@ pub(super) const TRY_BRANCH_NAME: &str = "automation/bors/try";
 /// for running CI checks.
 ///
 /// If `parent` is set, it will use it as a base commit for the merge.
-/// Otherwise, it will use the latest commit on the main repository branch.
+/// Otherwise, it will use the base commit of the PR recorded by GitHub, or the latest commit of a
+/// branch selected using `@bors set-base`. If `rebase` is set, the latest commit of the base
+/// branch of the PR is used instead, to catch conflicts with changes merged in the meantime.
+///
+/// If `workflows` is not empty, the selected workflows are listed in the message of the merge
+/// commit, so that CI can run only them.
 pub(super) async fn command_try_build<Client: RepositoryClient>(
     repo: &mut RepositoryState<Client>,
     db: &mut dyn DbClient,
     pr: &PullRequest,
     author: &GithubUser,
     parent: Option<CommitSha>,
-) -> anyhow::Result<()> {
-    if !check_try_permissions(repo, pr, author).await? {
-        return Ok(());
+    workflows: Vec<String>,
+    rebase: bool,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(rejection) = check_workflows_exist(repo, &workflows).await? {
+        return Ok(rejection);
+    }
+    let parent = match resolve_parent(repo, parent).await? {
+        Ok(parent) => parent,
+        Err(rejection) => return Ok(rejection),
+    };
+
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    start_try_build(repo, db, pr, pr_model, author, parent, &workflows, rebase).await
+}
+
+/// Resolves an abbreviated SHA of the selected parent commit to the full SHA, so that it can be used
+/// for creating branches. If it cannot be resolved, returns a rejection that explains why.
+async fn resolve_parent<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    parent: Option<CommitSha>,
+) -> anyhow::Result<Result<Option<CommitSha>, CommandOutcome>> {
+    let parent = match parent {
+        Some(parent) if parent.0.len() < FULL_SHA_LENGTH => parent,
+        parent => return Ok(Ok(parent)),
+    };
+    match repo.client.resolve_ref(&parent.0).await {
+        Ok(sha) => Ok(Ok(Some(sha))),
+        Err(ResolveRefError::NotFound) => Ok(Err(CommandOutcome::Rejected(format!(
+            ":exclamation: Commit `{parent}` was not found in this repository."
+        )))),
+        Err(ResolveRefError::Ambiguous) => Ok(Err(CommandOutcome::Rejected(format!(
+            ":exclamation: Commit SHA `{parent}` is ambiguous, please use a longer one."
+        )))),
+        Err(ResolveRefError::Other(error)) => Err(error),
     }
+}
+
+/// Checks that all selected workflows exist in the repository.
+/// If they do not, returns a rejection that lists the unknown ones.
+async fn check_workflows_exist<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    workflows: &[String],
+) -> anyhow::Result<Option<CommandOutcome>> {
+    if workflows.is_empty() {
+        return Ok(None);
+    }
+    let available = repo.client.get_workflows().await?;
+    let unknown = workflows
+        .iter()
+        .filter(|workflow| !available.contains(workflow))
+        .map(|workflow| format!("`{workflow}`"))
+        .collect::<Vec<_>>();
+    if unknown.is_empty() {
+        return Ok(None);
+    }
+    tracing::warn!("Unknown workflows selected: {unknown:?}");
+    Ok(Some(CommandOutcome::Rejected(format!(
+        ":exclamation: Unknown workflow(s): {}",
+        unknown.join(", ")
+    ))))
+}
 
+/// Retries the last try build of the PR, if it has failed.
+///
+/// The build is started again on top of the latest commit of the base branch. If a `workflow` is
+/// given, only that workflow is run again instead, on the same commit.
+pub(super) async fn command_try_retry<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+    workflow: Option<String>,
+) -> anyhow::Result<CommandOutcome> {
     let pr_model = db
         .get_or_create_pull_request(repo.client.repository(), pr.number)
         .await?;
+    let Some(build) = pr_model
+        .try_build
+        .as_ref()
+        .filter(|build| build.status == BuildStatus::Failure)
+    else {
+        tracing::warn!("No failed build to retry");
+        return Ok(CommandOutcome::Rejected(
+            ":exclamation: There is no failed try build to retry.".to_string(),
+        ));
+    };
+    let max_failures = repo.config.max_consecutive_failures;
+    if max_failures > 0 && pr_model.consecutive_failures as usize >= max_failures {
+        tracing::warn!("Builds of the PR have failed too many times in a row");
+        return Ok(CommandOutcome::Rejected(format!(
+            ":no_entry_sign: The builds of this PR have failed {} times in a row. Please fix the failures instead of retrying the build.",
+            pr_model.consecutive_failures
+        )));
+    }
+    match workflow {
+        Some(workflow) => retry_workflow(repo, db, pr, build, &workflow).await,
+        None => {
+            // The build is retried with the workflows that were selected for it
+            let workflows = build.selected_workflows.clone();
+            start_try_build(repo, db, pr, pr_model, author, None, &workflows, false).await
+        }
+    }
+}
+
+/// Runs a single failed workflow of the failed try build again. The other workflows keep their
+/// statuses, so the build succeeds once the retried workflow succeeds. The build is considered to
+/// start again, so that the workflow has the whole build timeout to finish.
+async fn retry_workflow<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    build: &BuildModel,
+    name: &str,
+) -> anyhow::Result<CommandOutcome> {
+    let workflows = db.get_workflows_for_build(build).await?;
+    let Some(workflow) = workflows.iter().find(|workflow| workflow.name == name) else {
+        let names = workflows
+            .iter()
+            .map(|workflow| format!("`{}`", workflow.name))
+            .collect::<Vec<_>>();
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: The try build has no check named `{name}`. Its checks are: {}.",
+            names.join(", ")
+        )));
+    };
+    if workflow.status != WorkflowStatus::Failure {
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: Check `{name}` has not failed, only failed checks can be retried."
+        )));
+    }
+    if workflow.workflow_type != WorkflowType::Github {
+        return Ok(CommandOutcome::Rejected(format!(
+            ":exclamation: Check `{name}` does not run on Github Actions, so it cannot be retried on its own. Use `retry` to retry the whole build."
+        )));
+    }
+
+    repo.client.dispatch_workflow(workflow.run_id).await?;
+    db.update_workflow_status(*workflow.run_id, WorkflowStatus::Pending)
+        .await?;
+    db.update_build_status(build, BuildStatus::Pending).await?;
+    db.restart_build(build, now()).await?;
+    report_check_run(repo, db, build, CheckRunStatus::InProgress).await;
+    tracing::info!("Retrying workflow {name} of build {}", build.commit_sha);
+
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":repeat: Retrying check `{name}` of try build {}…",
+            build.commit_sha
+        ),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
+}
 
+#[allow(clippy::too_many_arguments)]
+async fn start_try_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    mut pr_model: PullRequestModel,
+    author: &GithubUser,
+    parent: Option<CommitSha>,
+    workflows: &[String],
+    rebase: bool,
+) -> anyhow::Result<CommandOutcome> {
     if let Some(ref build) = pr_model.try_build {
         if build.status == BuildStatus::Pending {
             tracing::warn!("Try build already in progress");
-            repo.client
-                .post_comment(
-                    pr.number,
-                    ":exclamation: A try build is currently in progress. You can cancel it using @bors try cancel.",
-                )
-                .await?;
-            return Ok(());
+            return Ok(CommandOutcome::Rejected(
+                ":exclamation: A try build is currently in progress. You can cancel it using @bors try cancel.".to_string(),
+            ));
         }
     }
+    if let Some(rejection) = check_skip_marker(repo, pr).await? {
+        return Ok(rejection);
+    }
+    // The previous try build is superseded once the new one is attached to the PR
+    let previous_build = pr_model.try_build.take();
+    if skips_ci(repo, pr).await? {
+        db.attach_try_build(
+            pr_model,
+            TRY_BRANCH_NAME.to_string(),
+            pr.head.sha.clone(),
+            &author.username,
+            workflows,
+        )
+        .await?;
+        if let Some(ref build) = previous_build {
+            deactivate_deployment(repo, db, build).await;
+        }
+        let pr_model = db
+            .get_or_create_pull_request(repo.client.repository(), pr.number)
+            .await?;
+        if let Some(ref build) = pr_model.try_build {
+            db.update_build_status(build, BuildStatus::Success).await?;
+        }
+        tracing::info!("Try build skipped");
+        handle_label_trigger(repo, pr.number, LabelTrigger::TryBuildSucceeded).await?;
+        repo.post_status_comment(
+            db,
+            &pr_model,
+            &format!(":fast_forward: Try build skipped\n{SKIPPED_CI_NOTE}"),
+        )
+        .await?;
+        return Ok(CommandOutcome::Handled);
+    }
 
-    let base_sha = parent.as_ref().unwrap_or(&pr.base.sha);
+    let plan = plan_try_build(repo, pr, &pr_model, parent, rebase).await?;
 
     // First set the try branch to our base commit (either the selected parent or the main branch).
     repo.client
-        .set_branch_to_sha(TRY_MERGE_BRANCH_NAME, base_sha)
+        .set_branch_to_sha(TRY_MERGE_BRANCH_NAME, &plan.base_sha)
         .await
         .map_err(|error| {
-            let base = if let Some(ref parent) = parent {
-                parent.0.as_str()
-            } else {
-                "main branch"
-            };
-            anyhow!("Cannot set try merge branch to {base}: {error:?}")
+            anyhow!(
+                "Cannot set try merge branch to {}: {error:?}",
+                plan.describe_base()
+            )
         })?;
 
     // Then merge the PR commit into the try branch
+    let mut commit_message = auto_merge_commit_message(pr, "<try>");
+    if !workflows.is_empty() {
+        commit_message.push_str(&format!("\n\ntry-workflows: {}", workflows.join(",")));
+    }
     match repo
         .client
-        .merge_branches(
-            TRY_MERGE_BRANCH_NAME,
-            &pr.head.sha,
-            &auto_merge_commit_message(pr, "<try>"),
-        )
+        .merge_branches(TRY_MERGE_BRANCH_NAME, &plan.head_sha, &commit_message)
         .await
     {
         Ok(merge_sha) => {
@@ -87,44 +284,172 @@ pub(super) async fn command_try_build<Client: RepositoryClient>(
                 .await
                 .map_err(|error| anyhow!("Cannot set try branch to main branch: {error:?}"))?;
 
-            db.attach_try_build(pr_model, TRY_BRANCH_NAME.to_string(), merge_sha.clone())
-                .await?;
+            db.attach_try_build(
+                pr_model,
+                TRY_BRANCH_NAME.to_string(),
+                merge_sha.clone(),
+                &author.username,
+                workflows,
+            )
+            .await?;
+            if let Some(ref build) = previous_build {
+                deactivate_deployment(repo, db, build).await;
+            }
             tracing::info!("Try build started");
 
             handle_label_trigger(repo, pr.number, LabelTrigger::TryBuildStarted).await?;
 
-            repo.client
-                .post_comment(
-                    pr.number,
-                    &format!(
-                        ":hourglass: Trying commit {} with merge {merge_sha}…",
-                        pr.head.sha
-                    ),
-                )
+            let mut message = format!(
+                ":hourglass: Trying commit {} with merge {merge_sha}…",
+                pr.head.sha
+            );
+            if !workflows.is_empty() {
+                let list = workflows
+                    .iter()
+                    .map(|workflow| format!("`{workflow}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                message.push_str(&format!("\nWorkflows: {list}"));
+            }
+            if let Some(estimate) =
+                build_estimate_line(&repo.repository, db, TRY_BRANCH_NAME, 1).await?
+            {
+                message.push_str(&format!("\n{estimate}"));
+            }
+            let pr_model = db
+                .get_or_create_pull_request(repo.client.repository(), pr.number)
                 .await?;
-            Ok(())
+            repo.post_status_comment(db, &pr_model, &message).await?;
+            Ok(CommandOutcome::Handled)
+        }
+        Err(MergeError::Conflict) if rebase => {
+            tracing::warn!("Merge conflict with the latest base commit");
+            Ok(CommandOutcome::Rejected(format!(
+                ":lock: Rebase failed\n\nThis pull request conflicts with the latest commit {} of {}, so the try build could not be based on it. Please rebase the PR and try again.",
+                plan.base_sha,
+                plan.describe_base()
+            )))
         }
         Err(MergeError::Conflict) => {
             tracing::warn!("Merge conflict");
-            repo.client
-                .post_comment(pr.number, &merge_conflict_message(&pr.head.name))
-                .await?;
-            Ok(())
+            Ok(CommandOutcome::Rejected(merge_conflict_message(
+                &pr.head.name,
+            )))
         }
-        Err(error) => Err(error.into()),
+        Err(error) => Ok(CommandOutcome::Failed(error.into())),
     }
 }
 
-pub(super) async fn command_try_cancel<Client: RepositoryClient>(
+/// Describes what a try build would do, without performing any actions.
+///
+/// If `parent` is set, it is used as the base commit for the merge, otherwise the latest commit
+/// on the base branch of the PR is used. If `workflows` is not empty, only the selected workflows
+/// are listed.
+pub(super) async fn command_try_plan<Client: RepositoryClient>(
     repo: &mut RepositoryState<Client>,
     db: &mut dyn DbClient,
     pr: &PullRequest,
-    author: &GithubUser,
-) -> anyhow::Result<()> {
-    if !check_try_permissions(repo, pr, author).await? {
-        return Ok(());
+    parent: Option<CommitSha>,
+    workflows: Vec<String>,
+) -> anyhow::Result<CommandOutcome> {
+    if let Some(rejection) = check_workflows_exist(repo, &workflows).await? {
+        return Ok(rejection);
     }
+    let parent = match resolve_parent(repo, parent).await? {
+        Ok(parent) => parent,
+        Err(rejection) => return Ok(rejection),
+    };
 
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let plan = plan_try_build(repo, pr, &pr_model, parent, false).await?;
+    let workflows = if workflows.is_empty() {
+        repo.client.get_workflows().await?
+    } else {
+        workflows
+    };
+
+    let workflows = if workflows.is_empty() {
+        "No workflows would be started.".to_string()
+    } else {
+        let list = workflows
+            .iter()
+            .map(|name| format!("- {name}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("The following workflow(s) would be started:\n{list}")
+    };
+    repo.post_comment(
+        pr.number,
+        &format!(
+            r#":information_source: A try build would merge commit {} into {} and push the result to branch `{TRY_BRANCH_NAME}`.
+{workflows}"#,
+            plan.head_sha,
+            plan.describe_base()
+        ),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+/// Commits that will be used for a try build.
+struct TryBuildPlan {
+    /// Commit that will be used as the base of the merge.
+    base_sha: CommitSha,
+    /// Explicitly selected parent commit, if any.
+    parent: Option<CommitSha>,
+    /// Branch whose latest commit is used as the base, if any. It is either selected using
+    /// `@bors set-base`, or it is the base branch of the PR for `@bors try rebase`.
+    base_branch: Option<String>,
+    /// Commit of the PR that will be merged into the base.
+    head_sha: CommitSha,
+}
+
+impl TryBuildPlan {
+    fn describe_base(&self) -> String {
+        match (&self.parent, &self.base_branch) {
+            (Some(parent), _) => parent.to_string(),
+            (None, Some(branch)) => format!("branch `{branch}`"),
+            (None, None) => "main branch".to_string(),
+        }
+    }
+}
+
+async fn plan_try_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+    parent: Option<CommitSha>,
+    rebase: bool,
+) -> anyhow::Result<TryBuildPlan> {
+    let base_branch = match &pr_model.base_branch {
+        Some(branch) => Some(branch.clone()),
+        None if rebase => Some(pr.base.name.clone()),
+        None => None,
+    };
+    let base_sha = match (&parent, &base_branch) {
+        (Some(parent), _) => parent.clone(),
+        (None, Some(branch)) => repo
+            .client
+            .get_branch_sha(branch)
+            .await?
+            .ok_or_else(|| anyhow!("Base branch {branch} does not exist"))?,
+        (None, None) => pr.base.sha.clone(),
+    };
+    Ok(TryBuildPlan {
+        base_sha,
+        parent,
+        base_branch,
+        head_sha: pr.head.sha.clone(),
+    })
+}
+
+pub(super) async fn command_try_cancel<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
     let pr_number: PullRequestNumber = pr.number;
     let pr = db
         .get_or_create_pull_request(repo.client.repository(), pr_number)
@@ -132,8 +457,9 @@ pub(super) async fn command_try_cancel<Client: RepositoryClient>(
 
     let Some(build) = get_pending_build(pr) else {
         tracing::warn!("No build found");
-        repo.client.post_comment(pr_number, ":exclamation: There is currently no try build in progress.").await?;
-        return Ok(());
+        return Ok(CommandOutcome::Rejected(
+            ":exclamation: There is currently no try build in progress.".to_string(),
+        ));
     };
 
     if let Err(error) = cancel_build_workflows(repo, db, &build).await {
@@ -145,14 +471,18 @@ pub(super) async fn command_try_cancel<Client: RepositoryClient>(
 
     db.update_build_status(&build, BuildStatus::Cancelled)
         .await?;
+    report_check_run(repo, db, &build, CheckRunStatus::Cancelled).await;
+
+    // The try branch would otherwise keep pointing to the cancelled merge commit
+    if let Err(error) = repo.client.delete_branch(&build.branch).await {
+        tracing::error!("Could not delete branch {}: {error:?}", build.branch);
+    }
 
     tracing::info!("Try build cancelled");
 
-    repo.client
-        .post_comment(pr_number, "Try build cancelled.")
-        .await?;
+    repo.post_comment(pr_number, "Try build cancelled.").await?;
 
-    Ok(())
+    Ok(CommandOutcome::Handled)
 }
 
 pub async fn cancel_build_workflows<Client: RepositoryClient>(
@@ -177,7 +507,7 @@ fn get_pending_build(pr: PullRequestModel) -> Option<BuildModel> {
         .and_then(|b| (b.status == BuildStatus::Pending).then_some(b))
 }
 
-fn auto_merge_commit_message(pr: &PullRequest, reviewer: &str) -> String {
+pub(super) fn auto_merge_commit_message(pr: &PullRequest, reviewer: &str) -> String {
     let pr_number = pr.number;
     format!(
         r#"Auto merge of #{pr_number} - {pr_label}, r={reviewer}
@@ -190,7 +520,7 @@ fn auto_merge_commit_message(pr: &PullRequest, reviewer: &str) -> String {
     )
 }
 
-fn merge_conflict_message(branch: &str) -> String {
+pub(super) fn merge_conflict_message(branch: &str) -> String {
     format!(
         r#":lock: Merge conflict
 
@@ -224,51 +554,143 @@ handled during merge and rebase. This is normal, and you should still perform st
     )
 }
 
-async fn check_try_permissions<Client: RepositoryClient>(
-    repo: &mut RepositoryState<Client>,
-    pr: &PullRequest,
-    author: &GithubUser,
-) -> anyhow::Result<bool> {
-    let result = if !repo
-        .permissions_resolver
-        .has_permission(&author.username, PermissionType::Try)
-        .await
-    {
-        tracing::info!("Permission denied");
-        repo.client
-            .post_comment(
-                pr.number,
-                &format!(
-                    "@{}: :key: Insufficient privileges: not in try users",
-                    author.username
-                ),
-            )
-            .await?;
-        false
-    } else {
-        true
-    };
-    Ok(result)
-}
-
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
     use sea_orm::EntityTrait;
 
     use entity::workflow;
 
+    use crate::bors::handlers::refresh::with_mocked_time;
     use crate::bors::handlers::trybuild::{TRY_BRANCH_NAME, TRY_MERGE_BRANCH_NAME};
+    use crate::bors::RepositoryClient;
     use crate::database::{BuildStatus, DbClient, WorkflowStatus, WorkflowType};
     use crate::github::{CommitSha, LabelTrigger, MergeError};
     use crate::tests::event::{
-        default_pr_number, suite_failure, suite_pending, suite_success, WorkflowStartedBuilder,
+        default_pr_number, suite_failure, suite_pending, suite_success, WorkflowCompletedBuilder,
+        WorkflowStartedBuilder,
     };
-    use crate::tests::github::{BranchBuilder, PRBuilder};
+    use crate::tests::github::{BranchBuilder, CommitBuilder, PRBuilder};
     use crate::tests::permissions::NoPermissions;
     use crate::tests::state::{
         default_merge_sha, default_repo_name, ClientBuilder, RepoConfigBuilder,
     };
 
+    #[tokio::test]
+    async fn test_retry_single_workflow() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_failure()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+
+        state.comment("@bors retry workflow-2").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":repeat: Retrying check `workflow-2` of try build sha-merged…");
+        assert_eq!(state.client().dispatched_workflows, vec![2]);
+        let status = |run_id: u64| {
+            let db = &state.db;
+            async move {
+                db.find_workflow(run_id.into())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .status
+            }
+        };
+        assert_eq!(status(1).await, WorkflowStatus::Success);
+        assert_eq!(status(2).await, WorkflowStatus::Pending);
+
+        // The build succeeds once the retried workflow succeeds
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_success()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_retried_workflow_gets_whole_timeout() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().timeout(Duration::from_secs(3600)))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        with_mocked_time(Duration::from_secs(50 * 60), async {
+            state.comment("@bors retry workflow=workflow-1").await;
+        })
+        .await;
+        // The build has started more than the timeout ago, but it was retried less than that
+        with_mocked_time(Duration::from_secs(90 * 60), async {
+            state.refresh().await;
+        })
+        .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":repeat: Retrying check `workflow-1` of try build sha-merged…");
+    }
+
+    #[tokio::test]
+    async fn test_retry_unknown_workflow() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+
+        state.comment("@bors retry lint").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: The try build has no check named `lint`. Its checks are: `workflow-1`.");
+        assert!(state.client().dispatched_workflows.is_empty());
+    }
+
     #[tokio::test]
     async fn test_try_no_permission() {
         let mut state = ClientBuilder::default()
@@ -282,6 +704,19 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_try_plan_no_permission() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(NoPermissions))
+            .create_state()
+            .await;
+        state.comment("@bors try?").await;
+        state.client().check_comments(
+            default_pr_number(),
+            &["@<user>: :key: Insufficient privileges: not in try users"],
+        );
+    }
+
     #[tokio::test]
     async fn test_try_merge_comment() {
         let mut state = ClientBuilder::default().create_state().await;
@@ -320,6 +755,32 @@ mod tests {
             .check_branch_history(TRY_BRANCH_NAME, &[&default_merge_sha()]);
     }
 
+    #[tokio::test]
+    async fn test_try_rebase() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_branch("main-branch", "main-latest");
+        state.comment("@bors try rebase").await;
+        state.client().check_branch_history(
+            TRY_MERGE_BRANCH_NAME,
+            &["main-latest", &default_merge_sha()],
+        );
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
+    }
+
+    #[tokio::test]
+    async fn test_try_rebase_conflict() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().set_branch("main-branch", "main-latest");
+        state.client().merge_branches_fn = Box::new(|| Err(MergeError::Conflict));
+        state.comment("@bors try rebase").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :lock: Rebase failed
+
+        This pull request conflicts with the latest commit main-latest of branch `main-branch`, so the try build could not be based on it. Please rebase the PR and try again.
+        "###);
+        state.client().check_branch_not_created(TRY_BRANCH_NAME);
+    }
+
     #[tokio::test]
     async fn test_try_merge_explicit_parent() {
         let mut state = ClientBuilder::default().create_state().await;
@@ -335,6 +796,96 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_try_merge_abbreviated_parent() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().add_commit(
+            CommitBuilder::default()
+                .sha("ea9c1b050cc8b420c2c211d2177811e564a4dc60".to_string())
+                .create(),
+        );
+        state.comment("@bors try parent=ea9c1b0").await;
+        state.client().check_branch_history(
+            TRY_MERGE_BRANCH_NAME,
+            &[
+                "ea9c1b050cc8b420c2c211d2177811e564a4dc60",
+                &default_merge_sha(),
+            ],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_merge_ambiguous_parent() {
+        let mut state = ClientBuilder::default().create_state().await;
+        for sha in [
+            "ea9c1b050cc8b420c2c211d2177811e564a4dc60",
+            "ea9c1b0fffffffffffffffffffffffffffffffff",
+        ] {
+            state
+                .client()
+                .add_commit(CommitBuilder::default().sha(sha.to_string()).create());
+        }
+        state.comment("@bors try parent=ea9c1b0").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: Commit SHA `ea9c1b0` is ambiguous, please use a longer one.");
+        state
+            .client()
+            .check_branch_not_created(TRY_MERGE_BRANCH_NAME);
+    }
+
+    #[tokio::test]
+    async fn test_try_merge_missing_parent() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors try parent=ea9c1b0").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: Commit `ea9c1b0` was not found in this repository.");
+        state
+            .client()
+            .check_branch_not_created(TRY_MERGE_BRANCH_NAME);
+    }
+
+    #[tokio::test]
+    async fn test_try_docs_only_skips_ci() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_ci_paths(vec!["docs/**".to_string()]))
+            .create_state()
+            .await;
+        state.client().set_pr_files(
+            default_pr_number(),
+            &["docs/guide.md", "docs/book/intro.md"],
+        );
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :fast_forward: Try build skipped
+        Docs-only change, skipping CI: all files changed by this PR match `skip_ci_paths`.
+        "###);
+        assert_eq!(
+            state
+                .client()
+                .get_branch_sha(TRY_BRANCH_NAME)
+                .await
+                .unwrap(),
+            None
+        );
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        assert_eq!(pr.try_build.unwrap().status, BuildStatus::Success);
+    }
+
+    #[tokio::test]
+    async fn test_try_partially_docs_runs_ci() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_ci_paths(vec!["docs/**".to_string()]))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_pr_files(default_pr_number(), &["docs/guide.md", "src/lib.rs"]);
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
+    }
+
     #[tokio::test]
     async fn test_try_merge_conflict() {
         let mut state = ClientBuilder::default().create_state().await;
@@ -373,6 +924,41 @@ mod tests {
         "###);
     }
 
+    #[tokio::test]
+    async fn test_try_skip_marker_in_title() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_marker("[no ci]".to_string()))
+            .create_state()
+            .await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .title("[no ci] Experiment".to_string())
+                .create())
+        });
+        state.comment("@bors try").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":no_entry_sign: The title of the PR contains `[no ci]`, so this PR will not be built or merged.");
+        state.client().check_branch_not_created(TRY_BRANCH_NAME);
+    }
+
+    #[tokio::test]
+    async fn test_try_default_skip_marker_ignored_with_custom_marker() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().skip_marker("[no ci]".to_string()))
+            .create_state()
+            .await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .title("[bors skip] Experiment".to_string())
+                .create())
+        });
+        state.comment("@bors try").await;
+        state
+            .client()
+            .check_branch_history(TRY_BRANCH_NAME, &[&default_merge_sha()]);
+    }
+
     #[tokio::test]
     async fn test_try_merge_insert_into_db() {
         let mut state = ClientBuilder::default().create_state().await;
@@ -433,6 +1019,128 @@ mod tests {
         "###);
     }
 
+    #[tokio::test]
+    async fn test_try_plan() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["build".to_string(), "test".to_string()];
+
+        state.comment("@bors try?").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :information_source: A try build would merge commit pr-sha into main branch and push the result to branch `automation/bors/try`.
+        The following workflow(s) would be started:
+        - build
+        - test
+        "###);
+        state
+            .client()
+            .check_branch_not_created(TRY_MERGE_BRANCH_NAME)
+            .check_branch_not_created(TRY_BRANCH_NAME);
+        assert!(state
+            .db
+            .get_running_builds(&default_repo_name())
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_try_selected_workflows() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["build".to_string(), "test".to_string()];
+
+        state.comment("@bors try workflows=test").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :hourglass: Trying commit pr-sha with merge sha-merged…
+        Workflows: `test`
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_try_selected_workflows_complete_build() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["workflow-1".to_string(), "workflow-2".to_string()];
+        // The suite of the workflow that was not selected never completes
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+
+        state.comment("@bors try workflows=workflow-1").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_retry_keeps_selected_workflows() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["workflow-1".to_string(), "workflow-2".to_string()];
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+
+        state.comment("@bors try workflows=workflow-1").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("merge2".to_string())));
+        state.comment("@bors retry").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :hourglass: Trying commit pr-sha with merge merge2…
+        Workflows: `workflow-1`
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_try_unknown_workflow() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["build".to_string()];
+
+        state.comment("@bors try workflows=build,lint").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":exclamation: Unknown workflow(s): `lint`");
+        state.client().check_branch_not_created(TRY_BRANCH_NAME);
+    }
+
+    #[tokio::test]
+    async fn test_try_plan_selected_workflows() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().workflows = vec!["build".to_string(), "test".to_string()];
+
+        state.comment("@bors try? workflows=build").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :information_source: A try build would merge commit pr-sha into main branch and push the result to branch `automation/bors/try`.
+        The following workflow(s) would be started:
+        - build
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_try_plan_explicit_parent() {
+        let mut state = ClientBuilder::default().create_state().await;
+
+        state
+            .comment("@bors try? parent=ea9c1b050cc8b420c2c211d2177811e564a4dc60")
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :information_source: A try build would merge commit pr-sha into ea9c1b050cc8b420c2c211d2177811e564a4dc60 and push the result to branch `automation/bors/try`.
+        No workflows would be started.
+        "###);
+    }
+
     #[tokio::test]
     async fn test_try_cancel_no_running_build() {
         let mut state = ClientBuilder::default().create_state().await;
@@ -569,6 +1277,49 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_try_cancel_then_late_workflow_completion() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+
+        state.comment("@bors try").await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(123),
+            )
+            .await;
+        state.comment("@bors try cancel").await;
+        state
+            .client()
+            .check_branch_deleted(TRY_BRANCH_NAME)
+            .check_cancelled_workflows(&[123]);
+
+        // The cancelled workflow run finishes anyway
+        state
+            .workflow_completed(
+                WorkflowCompletedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(123)
+                    .status(WorkflowStatus::Success),
+            )
+            .await;
+
+        let workflow = state.db.find_workflow(123.into()).await.unwrap().unwrap();
+        assert_eq!(workflow.status, WorkflowStatus::Pending);
+        assert_eq!(workflow.build.status, BuildStatus::Cancelled);
+        state.client().check_comments(
+            default_pr_number(),
+            &[
+                ":hourglass: Trying commit pr-sha with merge sha-merged…",
+                "Try build cancelled.",
+            ],
+        );
+    }
+
     #[tokio::test]
     async fn test_try_build_start_modify_labels() {
         let mut state = ClientBuilder::default()
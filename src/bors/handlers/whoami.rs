@@ -0,0 +1,115 @@
+use crate::bors::handlers::delegation::delegation_grants;
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::DbClient;
+use crate::github::{GithubUser, PullRequest};
+use crate::permissions::PermissionType;
+
+/// Posts the permissions that the bot resolves for the author on the PR, to help them understand
+/// why some of their commands are rejected.
+pub(super) async fn command_whoami<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+    author: &GithubUser,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let delegations = db
+        .get_delegations(&pr_model)
+        .await?
+        .into_iter()
+        .filter(|delegation| delegation.delegatee == author.username)
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![];
+    for (name, permission) in [
+        ("review", PermissionType::Review),
+        ("try", PermissionType::Try),
+    ] {
+        let status = if repo
+            .permissions_resolver
+            .has_permission(&author.username, permission)
+            .await
+        {
+            ":white_check_mark:"
+        } else if delegations
+            .iter()
+            .any(|delegation| delegation_grants(delegation.scope, permission))
+        {
+            ":white_check_mark: (delegated on this PR)"
+        } else {
+            ":x:"
+        };
+        lines.push(format!("- {name}: {status}"));
+    }
+    // Delegated permissions cannot be delegated further
+    let can_delegate = repo
+        .permissions_resolver
+        .has_permission(&author.username, PermissionType::Review)
+        .await;
+    lines.push(format!(
+        "- delegate: {}",
+        if can_delegate {
+            ":white_check_mark:"
+        } else {
+            ":x:"
+        }
+    ));
+
+    repo.post_comment(
+        pr.number,
+        &format!(
+            ":bust_in_silhouette: Permissions of @{}:\n{}",
+            author.username,
+            lines.join("\n")
+        ),
+    )
+    .await?;
+    Ok(CommandOutcome::Handled)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::event::{comment, default_pr_author, default_pr_number, user};
+    use crate::tests::permissions::PermissionsFor;
+    use crate::tests::state::ClientBuilder;
+
+    #[tokio::test]
+    async fn test_whoami_collaborator() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["reviewer"])))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors whoami").author(user("reviewer")))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :bust_in_silhouette: Permissions of @reviewer:
+        - review: :white_check_mark:
+        - try: :white_check_mark:
+        - delegate: :white_check_mark:
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_whoami_delegated() {
+        let mut state = ClientBuilder::default()
+            .permission_resolver(Box::new(PermissionsFor::new(&["reviewer"])))
+            .create_state()
+            .await;
+        state
+            .comment(comment("@bors delegate=try").author(user("reviewer")))
+            .await;
+        state
+            .comment(comment("@bors whoami").author(default_pr_author()))
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :bust_in_silhouette: Permissions of @pr-author:
+        - review: :x:
+        - try: :white_check_mark: (delegated on this PR)
+        - delegate: :x:
+        "###);
+    }
+}
@@ -0,0 +1,281 @@
+use crate::bors::handlers::merge::auto_branch_name;
+use crate::bors::handlers::review::head_approvers;
+use crate::bors::handlers::tree::{format_time, tree_closed_notice};
+use crate::bors::handlers::CommandOutcome;
+use crate::bors::{RepositoryClient, RepositoryState};
+use crate::database::{BuildStatus, DbClient, PullRequestModel};
+use crate::github::PullRequest;
+
+/// Explains what currently keeps the PR from being merged, in the order in which the merge queue
+/// checks it.
+pub(super) async fn command_why<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &mut dyn DbClient,
+    pr: &PullRequest,
+) -> anyhow::Result<CommandOutcome> {
+    let pr_model = db
+        .get_or_create_pull_request(repo.client.repository(), pr.number)
+        .await?;
+    let reason = merge_blocker(repo, db, pr, &pr_model).await?;
+    repo.post_comment(pr.number, &reason).await?;
+    Ok(CommandOutcome::Handled)
+}
+
+async fn merge_blocker<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequest,
+    pr_model: &PullRequestModel,
+) -> anyhow::Result<String> {
+    if pr_model.merged_at.is_some() {
+        return Ok(":tada: This PR has already been merged.".to_string());
+    }
+    if pr_model.approved_sha.is_none() {
+        let required = repo.config.required_approvals;
+        let approvers = head_approvers(db, pr_model, pr).await?;
+        if approvers.is_empty() {
+            return Ok(format!(
+                ":mag: This PR is not approved, commit {} has to be approved with `@bors r+`.",
+                pr.head.sha
+            ));
+        }
+        // The approvals are kept when the PR is removed from the queue, e.g. because they have
+        // expired or the builds of the PR kept failing
+        if approvers.len() >= required {
+            return Ok(format!(
+                ":mag: The approval of commit {} has been dismissed or has expired, the PR has to be approved again with `@bors r+`.",
+                pr.head.sha
+            ));
+        }
+        let missing = required.saturating_sub(approvers.len());
+        let approvers = approvers
+            .iter()
+            .map(|approver| format!("`{approver}`"))
+            .collect::<Vec<_>>();
+        return Ok(format!(
+            ":mag: This PR has {} of the {required} required approval(s) (by {}), it needs {missing} more before it can enter the merge queue.",
+            approvers.len(),
+            approvers.join(", "),
+        ));
+    }
+
+    match pr_model.auto_build.as_ref().map(|build| &build.status) {
+        Some(BuildStatus::Pending) => {
+            return Ok(":mag: The merge build of this PR is running.".to_string());
+        }
+        Some(BuildStatus::Failure) => {
+            return Ok(":mag: The last merge build of this PR has failed or its required checks did not pass. Approve the PR again to retry it.".to_string());
+        }
+        Some(BuildStatus::Timeouted) => {
+            return Ok(
+                ":mag: The last merge build of this PR has timed out. Approve the PR again to retry it."
+                    .to_string(),
+            );
+        }
+        Some(BuildStatus::Success | BuildStatus::Cancelled) | None => {}
+    }
+
+    let tree_state = db.get_tree_state(&repo.repository).await?;
+    if !tree_state.allows(pr_model.priority) {
+        return Ok(tree_closed_notice(&tree_state));
+    }
+    let do_not_merge = &repo.config.do_not_merge_label;
+    if pr
+        .labels
+        .iter()
+        .any(|label| label.eq_ignore_ascii_case(do_not_merge))
+    {
+        return Ok(format!(
+            ":mag: This PR is held by the `{do_not_merge}` label, it will be merged once the label is removed."
+        ));
+    }
+    if let Some(not_before) = pr_model.not_before {
+        return Ok(format!(
+            ":mag: This PR is delayed, it will enter the active merge queue at {}.",
+            format_time(not_before)
+        ));
+    }
+    if pr_model.parked {
+        return Ok(
+            ":mag: The merge queue is full, this PR will enter it once there is space.".to_string(),
+        );
+    }
+
+    let base_branch = pr_model
+        .base_branch
+        .clone()
+        .unwrap_or_else(|| pr.base.name.clone());
+    if pr.mergeable == Some(false) {
+        return Ok(format!(
+            ":mag: This PR conflicts with `{base_branch}`, it has to be rebased before it can be merged."
+        ));
+    }
+    if let Some(threshold) = repo.config.rebase_notice_threshold {
+        let comparison = repo
+            .client
+            .compare_commits(&base_branch, &pr.head.sha.0)
+            .await?;
+        if comparison.behind_by >= threshold {
+            return Ok(format!(
+                ":mag: This PR is {} commit(s) behind `{base_branch}`, rebase it to reduce the risk of a failed merge build.",
+                comparison.behind_by
+            ));
+        }
+    }
+
+    // The remaining PRs wait for the merge builds into the same base branch, one by one
+    let auto_branch = auto_branch_name(&repo.config, &base_branch).to_string();
+    let mut ahead = 0;
+    for queued in db.list_queued_prs(&repo.repository).await? {
+        if queued.number == pr_model.number {
+            break;
+        }
+        let queued_base = match queued.base_branch {
+            Some(ref branch) => branch.clone(),
+            None => repo.client.get_pull_request(queued.number).await?.base.name,
+        };
+        if queued_base != base_branch {
+            continue;
+        }
+        match queued.auto_build {
+            Some(ref build)
+                if build.status == BuildStatus::Pending && build.branch == auto_branch =>
+            {
+                return Ok(format!(
+                    ":mag: The merge build of #{} into `{base_branch}` is running, this PR waits until it finishes.",
+                    queued.number
+                ));
+            }
+            None if !queued.parked
+                && queued.not_before.is_none()
+                && tree_state.allows(queued.priority) =>
+            {
+                ahead += 1
+            }
+            _ => {}
+        }
+    }
+    if ahead > 0 {
+        return Ok(format!(
+            ":mag: This PR is waiting for its turn, {ahead} PR(s) into `{base_branch}` are ahead of it in the merge queue."
+        ));
+    }
+    Ok(
+        ":mag: Nothing blocks this PR, its merge build will start once the merge queue is processed."
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::CommitComparisonStatus;
+    use crate::database::DbClient;
+    use crate::tests::event::{comment, default_pr_number};
+    use crate::tests::github::PRBuilder;
+    use crate::tests::state::{default_repo_name, ClientBuilder, RepoConfigBuilder};
+
+    #[tokio::test]
+    async fn test_why_not_approved() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: This PR is not approved, commit pr-sha has to be approved with `@bors r+`.");
+    }
+
+    #[tokio::test]
+    async fn test_why_insufficient_approvals() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().required_approvals(2))
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: This PR has 1 of the 2 required approval(s) (by `<user>`), it needs 1 more before it can enter the merge queue.");
+    }
+
+    #[tokio::test]
+    async fn test_why_approval_dismissed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        let pr = state
+            .db
+            .get_or_create_pull_request(&default_repo_name(), default_pr_number().into())
+            .await
+            .unwrap();
+        state.db.dequeue(&pr).await.unwrap();
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: The approval of commit pr-sha has been dismissed or has expired, the PR has to be approved again with `@bors r+`.");
+    }
+
+    #[tokio::test]
+    async fn test_why_behind_base() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().rebase_notice_threshold(Some(10)))
+            .create_state()
+            .await;
+        state.comment("@bors r+").await;
+        state.client().set_comparison(
+            "main-branch",
+            "pr-sha",
+            CommitComparisonStatus::Diverged,
+            1,
+            25,
+        );
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: This PR is 25 commit(s) behind `main-branch`, rebase it to reduce the risk of a failed merge build.");
+    }
+
+    #[tokio::test]
+    async fn test_why_tree_closed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors treeclosed").await;
+        state.comment("@bors r+").await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":evergreen_tree: The tree is currently closed, this PR will be merged once it reopens.");
+    }
+
+    #[tokio::test]
+    async fn test_why_held_by_label() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .labels(vec!["S-do-not-merge".to_string()])
+                .create())
+        });
+        state.comment("@bors r+").await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: This PR is held by the `S-do-not-merge` label, it will be merged once the label is removed.");
+    }
+
+    #[tokio::test]
+    async fn test_why_conflict() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| {
+            Ok(PRBuilder::default()
+                .number(pr.0)
+                .mergeable(Some(false))
+                .create())
+        });
+        state.comment("@bors r+").await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: This PR conflicts with `main-branch`, it has to be rebased before it can be merged.");
+    }
+
+    #[tokio::test]
+    async fn test_why_waiting_for_build_of_other_pr() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment(comment("@bors r+").pr_number(1)).await;
+        state.refresh().await;
+        state.comment(comment("@bors r+").pr_number(2)).await;
+        state.comment(comment("@bors why").pr_number(2)).await;
+        insta::assert_snapshot!(state.client().get_last_comment(2), @":mag: The merge build of #1 into `main-branch` is running, this PR waits until it finishes.");
+    }
+
+    #[tokio::test]
+    async fn test_why_nothing_blocks() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors r+").await;
+        state.comment("@bors why").await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":mag: Nothing blocks this PR, its merge build will start once the merge queue is processed.");
+    }
+}
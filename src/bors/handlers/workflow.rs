@@ -1,15 +1,33 @@
-use crate::bors::event::{CheckSuiteCompleted, WorkflowCompleted, WorkflowStarted};
+use crate::bors::event::{
+    CheckSuiteCompleted, CommitStatusChanged, WorkflowCompleted, WorkflowStarted,
+};
 use crate::bors::handlers::is_bors_observed_branch;
 use crate::bors::handlers::labels::handle_label_trigger;
-use crate::bors::{self, RepositoryClient, RepositoryState};
-use crate::database::{BuildStatus, DbClient, WorkflowStatus};
-use crate::github::LabelTrigger;
+use crate::bors::handlers::merge::{complete_auto_build, is_auto_branch};
+use crate::bors::handlers::refresh::{elapsed_time, now};
+use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
+use crate::bors::notifier::BuildCompletedEvent;
+use crate::bors::{Artifact, CheckSuiteStatus, RepositoryClient, RepositoryState};
+use crate::config::{BuildStatusSource, RepositoryConfig};
+use crate::database::{
+    BuildModel, BuildStatus, CheckRunStatus, DbClient, PullRequestModel, WorkflowModel,
+    WorkflowStatus, WorkflowType,
+};
+use crate::github::{CommitSha, LabelTrigger, PullRequestNumber};
 
-pub(super) async fn handle_workflow_started(
+/// How many artifacts are listed in the comment about a successful try build.
+const MAX_LISTED_ARTIFACTS: usize = 10;
+
+pub(super) async fn handle_workflow_started<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
     db: &mut dyn DbClient,
     payload: WorkflowStarted,
 ) -> anyhow::Result<()> {
-    if !is_bors_observed_branch(&payload.branch) {
+    if !is_bors_observed_branch(&repo.config, &payload.branch) {
+        return Ok(());
+    }
+    if !is_observed_workflow(&repo.config, &payload.name, payload.workflow_id) {
+        tracing::trace!("Ignoring workflow {}, it is not observed", payload.name);
         return Ok(());
     }
 
@@ -21,7 +39,14 @@ pub(super) async fn handle_workflow_started(
         payload.commit_sha
     );
 
-    let Some(build) = db.find_build(&payload.repository, payload.branch.clone(), payload.commit_sha.clone()).await? else {
+    let Some(build) = db
+        .find_build(
+            &payload.repository,
+            payload.branch.clone(),
+            payload.commit_sha.clone(),
+        )
+        .await?
+    else {
         tracing::warn!("Build for workflow not found");
         return Ok(());
     };
@@ -32,16 +57,24 @@ pub(super) async fn handle_workflow_started(
         return Ok(());
     }
 
+    // A retried workflow run keeps its ID, its status has been reset when it was retried
+    if db.find_workflow(payload.run_id).await?.is_some() {
+        tracing::info!("Workflow {} has been restarted", payload.run_id);
+        return Ok(());
+    }
+
     tracing::info!("Storing workflow started into DB");
     db.create_workflow(
         &build,
         payload.name,
         payload.url,
         payload.run_id,
+        payload.workflow_id,
         payload.workflow_type,
         WorkflowStatus::Pending,
     )
     .await?;
+    report_check_run(repo, db, &build, CheckRunStatus::InProgress).await;
 
     Ok(())
 }
@@ -51,6 +84,34 @@ pub(super) async fn handle_workflow_completed<Client: RepositoryClient>(
     db: &mut dyn DbClient,
     payload: WorkflowCompleted,
 ) -> anyhow::Result<()> {
+    // GitHub sometimes sends the same completion event multiple times, in that case, the workflow
+    // will already have the received status.
+    if let Some(workflow) = db.find_workflow(payload.run_id).await? {
+        if workflow.status == payload.status {
+            tracing::info!(
+                "Ignoring duplicate completion of workflow {} with status {:?}",
+                payload.run_id,
+                payload.status
+            );
+            return Ok(());
+        }
+        // Workflows of cancelled builds can still finish, but nobody is interested in them anymore
+        if workflow.build.status == BuildStatus::Cancelled {
+            tracing::info!(
+                "Ignoring completion of workflow {} of a cancelled build",
+                payload.run_id
+            );
+            return Ok(());
+        }
+    } else if !repo.config.observed_workflows.is_empty() {
+        // The start of observed workflows is always stored
+        tracing::trace!(
+            "Ignoring completion of workflow {}, it is not observed",
+            payload.run_id
+        );
+        return Ok(());
+    }
+
     tracing::info!("Updating status of workflow to {:?}", payload.status);
     db.update_workflow_status(*payload.run_id, payload.status)
         .await?;
@@ -77,12 +138,144 @@ pub(super) async fn handle_check_suite_completed<Client: RepositoryClient>(
     try_complete_build(repo, db, payload).await
 }
 
-async fn try_complete_build<Client: RepositoryClient>(
+/// Handles a legacy commit status that has reached a final state, by trying to complete the
+/// builds of the commit on the branches observed by bors.
+pub(super) async fn handle_commit_status_changed<Client: RepositoryClient>(
     repo: &mut RepositoryState<Client>,
     db: &mut dyn DbClient,
+    payload: CommitStatusChanged,
+) -> anyhow::Result<()> {
+    if repo.config.build_status_source == BuildStatusSource::CheckSuites {
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Received commit status changed (commit={})",
+        payload.commit_sha
+    );
+    let branches = payload
+        .branches
+        .into_iter()
+        .filter(|branch| is_bors_observed_branch(&repo.config, branch))
+        .collect::<Vec<_>>();
+    for branch in branches {
+        let event = CheckSuiteCompleted {
+            repository: payload.repository.clone(),
+            branch,
+            commit_sha: payload.commit_sha.clone(),
+        };
+        try_complete_build(repo, db, event).await?;
+    }
+    Ok(())
+}
+
+/// Does the workflow with the given name and ID affect builds? Entries of `observed_workflows` match
+/// either the name or the numeric ID of a workflow.
+fn is_observed_workflow(config: &RepositoryConfig, name: &str, workflow_id: Option<u64>) -> bool {
+    config.observed_workflows.is_empty()
+        || config
+            .observed_workflows
+            .iter()
+            .any(|observed| observed_workflow_matches(observed, name, workflow_id))
+}
+
+fn observed_workflow_matches(observed: &str, name: &str, workflow_id: Option<u64>) -> bool {
+    observed == name || workflow_id.is_some_and(|id| observed == id.to_string())
+}
+
+/// Status of a CI check of a build.
+struct BuildCheck {
+    status: CheckSuiteStatus,
+    /// Does the check come from GitHub Actions? Only its workflows can be retried by bors.
+    github_actions: bool,
+}
+
+/// Returns the statuses of the CI checks of a build, from the systems configured with
+/// `build_status_source`.
+///
+/// If only some workflows are observed, the check suites of GitHub Actions are replaced by the
+/// observed workflows of the build, and the build is pending until each of them has completed.
+/// Check suites of other CI apps are kept. The workflows selected for the build, e.g. with
+/// `@bors try workflows=<names>`, are observed instead of the configured ones.
+async fn get_build_checks<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+    branch: &str,
+    sha: &CommitSha,
+) -> anyhow::Result<Vec<BuildCheck>> {
+    let source = repo.config.build_status_source;
+    let mut checks = vec![];
+    if matches!(
+        source,
+        BuildStatusSource::CheckSuites | BuildStatusSource::Both
+    ) {
+        let suites = repo.client.get_check_suites_for_commit(branch, sha).await?;
+        let observed_workflows = if build.selected_workflows.is_empty() {
+            &repo.config.observed_workflows
+        } else {
+            &build.selected_workflows
+        };
+        let observe_all = observed_workflows.is_empty();
+        checks.extend(
+            suites
+                .into_iter()
+                .filter(|suite| observe_all || !suite.github_actions)
+                .map(|suite| BuildCheck {
+                    status: suite.status,
+                    github_actions: suite.github_actions,
+                }),
+        );
+        if !observe_all {
+            let workflows = db.get_workflows_for_build(build).await?;
+            for observed in observed_workflows {
+                let mut statuses = workflows
+                    .iter()
+                    .filter(|workflow| {
+                        observed_workflow_matches(observed, &workflow.name, workflow.workflow_id)
+                    })
+                    .map(|workflow| match workflow.status {
+                        WorkflowStatus::Pending => CheckSuiteStatus::Pending,
+                        WorkflowStatus::Success => CheckSuiteStatus::Success,
+                        WorkflowStatus::Failure => CheckSuiteStatus::Failure,
+                    })
+                    .collect::<Vec<_>>();
+                // The workflow has not started yet
+                if statuses.is_empty() {
+                    statuses.push(CheckSuiteStatus::Pending);
+                }
+                checks.extend(statuses.into_iter().map(|status| BuildCheck {
+                    status,
+                    github_actions: true,
+                }));
+            }
+        }
+    }
+    if matches!(
+        source,
+        BuildStatusSource::Statuses | BuildStatusSource::Both
+    ) {
+        let status = match repo.client.get_combined_status(sha).await? {
+            Some(status) => Some(status.into()),
+            // Without any statuses, the check suites decide the result of the build. If only the
+            // statuses are used, the build waits until CI has reported some.
+            None if source == BuildStatusSource::Statuses => Some(CheckSuiteStatus::Pending),
+            None => None,
+        };
+        checks.extend(status.map(|status| BuildCheck {
+            status,
+            github_actions: false,
+        }));
+    }
+    Ok(checks)
+}
+
+pub(super) async fn try_complete_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
     payload: CheckSuiteCompleted,
 ) -> anyhow::Result<()> {
-    if !is_bors_observed_branch(&payload.branch) {
+    if !is_bors_observed_branch(&repo.config, &payload.branch) {
         return Ok(());
     }
 
@@ -92,8 +285,12 @@ async fn try_complete_build<Client: RepositoryClient>(
             payload.branch.clone(),
             payload.commit_sha.clone(),
         )
-        .await? else {
-        tracing::warn!("Received check suite finished for an unknown build: {}", payload.commit_sha);
+        .await?
+    else {
+        tracing::warn!(
+            "Received check suite finished for an unknown build: {}",
+            payload.commit_sha
+        );
         return Ok(());
     };
 
@@ -107,22 +304,27 @@ async fn try_complete_build<Client: RepositoryClient>(
         return Ok(());
     };
 
-    let checks = repo
-        .client
-        .get_check_suites_for_commit(&payload.branch, &payload.commit_sha)
-        .await?;
+    let checks = get_build_checks(repo, db, &build, &payload.branch, &payload.commit_sha).await?;
 
     // Some checks are still running, let's wait for the next event
     if checks
         .iter()
-        .any(|check| matches!(check.status, bors::CheckSuiteStatus::Pending))
+        .any(|check| matches!(check.status, CheckSuiteStatus::Pending))
     {
+        // A check has started while the build was waiting for late checks
+        if build.checks_finished_at.is_some() {
+            db.set_checks_finished_at(&build, None).await?;
+        }
         return Ok(());
     }
 
     let has_failure = checks
         .iter()
-        .any(|check| matches!(check.status, bors::CheckSuiteStatus::Failure));
+        .any(|check| matches!(check.status, CheckSuiteStatus::Failure));
+    // Failures outside of GitHub Actions cannot be retried, so they fail the build right away
+    let has_external_failure = checks
+        .iter()
+        .any(|check| matches!(check.status, CheckSuiteStatus::Failure) && !check.github_actions);
 
     let mut workflows = db.get_workflows_for_build(&build).await?;
     workflows.sort_by(|a, b| a.name.cmp(&b.name));
@@ -138,67 +340,451 @@ async fn try_complete_build<Client: RepositoryClient>(
         return Ok(());
     }
 
-    let workflow_list = workflows
-        .into_iter()
-        .map(|w| {
-            format!(
-                "- [{}]({}) {}",
-                w.name,
-                w.url,
-                if w.status == WorkflowStatus::Success {
-                    ":white_check_mark:"
-                } else {
-                    ":x:"
-                }
-            )
-        })
+    // Give checks that start late a chance to be taken into account. The build is completed by a
+    // later event, or by a refresh once the delay has elapsed.
+    let settle_delay = repo.config.settle_delay;
+    if !settle_delay.is_zero() {
+        match build.checks_finished_at {
+            None => {
+                tracing::info!("All checks are finished, waiting {settle_delay:?} for late checks");
+                db.set_checks_finished_at(&build, Some(now())).await?;
+                return Ok(());
+            }
+            Some(finished_at) if elapsed_time(finished_at) < settle_delay => {
+                tracing::info!("Waiting for late checks");
+                return Ok(());
+            }
+            Some(_) => {}
+        }
+    }
+
+    if has_failure
+        && !has_external_failure
+        && retry_flaky_workflows(repo, db, &pr, &build, &workflows).await?
+    {
+        return Ok(());
+    }
+
+    let status = if has_failure {
+        BuildStatus::Failure
+    } else {
+        BuildStatus::Success
+    };
+    let check_run_status = if has_failure {
+        CheckRunStatus::Failure
+    } else {
+        CheckRunStatus::Success
+    };
+    report_check_run(repo, db, &build, check_run_status).await;
+    if let Some(notifier) = &repo.notifier {
+        let event = BuildCompletedEvent::new(
+            &repo.repository,
+            pr.number,
+            &build.branch,
+            &build.commit_sha,
+            &status,
+        );
+        // Notifications are best-effort, they should not block the handling of the build
+        if let Err(error) = notifier.notify(&event).await {
+            tracing::error!("Could not send notification about build completion: {error:?}");
+        }
+    }
+
+    // Try builds and merge builds are counted separately, so that e.g. an experimental try build
+    // does not unapprove a PR whose merge builds pass
+    let pr_number = pr.number;
+    let auto = is_auto_branch(&repo.config, &build.branch);
+    let previous_failures = if auto {
+        pr.consecutive_auto_failures
+    } else {
+        pr.consecutive_failures
+    };
+    let failures = if has_failure {
+        previous_failures + 1
+    } else {
+        0
+    };
+    if failures != previous_failures {
+        if auto {
+            db.set_consecutive_auto_failures(&pr, failures).await?;
+        } else {
+            db.set_consecutive_failures(&pr, failures).await?;
+        }
+    }
+
+    if auto {
+        complete_auto_build(repo, db, pr, build, workflows, has_failure).await?;
+    } else {
+        complete_try_build(repo, db, &pr, build, workflows, status).await?;
+    }
+
+    let max_failures = repo.config.max_consecutive_failures;
+    if max_failures > 0 && failures as usize >= max_failures {
+        stop_after_repeated_failures(repo, db, pr_number, failures).await?;
+    }
+    Ok(())
+}
+
+/// How many times are the failed flaky checks of a build retried automatically.
+const MAX_AUTO_RETRIES: i32 = 1;
+
+/// Runs the failed workflows of the build again if all of them are known to be flaky, and the
+/// build has not been retried automatically too many times yet. Only workflows from Github Actions
+/// can be retried. Returns `true` if the workflows are being retried.
+///
+/// The retried build is considered to start again, so that the workflows have the whole build
+/// timeout to finish.
+async fn retry_flaky_workflows<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequestModel,
+    build: &BuildModel,
+    workflows: &[WorkflowModel],
+) -> anyhow::Result<bool> {
+    if build.auto_retries >= MAX_AUTO_RETRIES {
+        return Ok(false);
+    }
+    let failed = workflows
+        .iter()
+        .filter(|workflow| workflow.status == WorkflowStatus::Failure)
+        .collect::<Vec<_>>();
+    let all_flaky = failed.iter().all(|workflow| {
+        repo.config.flaky_checks.contains(&workflow.name)
+            && workflow.workflow_type == WorkflowType::Github
+    });
+    if failed.is_empty() || !all_flaky {
+        return Ok(false);
+    }
+
+    for workflow in &failed {
+        repo.client.dispatch_workflow(workflow.run_id).await?;
+        db.update_workflow_status(*workflow.run_id, WorkflowStatus::Pending)
+            .await?;
+    }
+    db.set_auto_retries(build, build.auto_retries + 1).await?;
+    db.restart_build(build, now()).await?;
+
+    let names = failed
+        .iter()
+        .map(|workflow| format!("`{}`", workflow.name))
         .collect::<Vec<_>>()
-        .join("\n");
+        .join(", ");
+    tracing::info!(
+        "Retrying flaky checks {names} of build {}",
+        build.commit_sha
+    );
+    repo.post_status_comment(
+        db,
+        pr,
+        &format!(":repeat: Only checks that are known to be flaky have failed, retrying {names}…"),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Reports the aggregate status of the build in the bors check run of its commit.
+///
+/// Most workflow events do not change the status of the build, so GitHub is only contacted when
+/// the status differs from the last reported one. Reporting is best-effort, a failure does not
+/// stop the handling of the build.
+pub(super) async fn report_check_run<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+    status: CheckRunStatus,
+) {
+    // Check runs cannot be created with a personal access token, so the deployment keeps track
+    // of its own reported status
+    report_deployment(repo, db, build, status).await;
+    if build.check_run_status == Some(status) {
+        return;
+    }
+    let sha = CommitSha(build.commit_sha.clone());
+    if let Err(error) = repo.client.create_or_update_check_run(&sha, status).await {
+        tracing::error!("Could not report check run status of {sha}: {error:?}");
+        return;
+    }
+    if let Err(error) = db.set_check_run_status(build, status).await {
+        tracing::error!("Could not store check run status of {sha}: {error:?}");
+    }
+}
+
+/// Reports the status of a try build in its GitHub deployment, if deployments are enabled in the
+/// repository configuration. The deployment is created when the first status is reported.
+///
+/// Like the check run, the deployment is reported on a best-effort basis.
+async fn report_deployment<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+    status: CheckRunStatus,
+) {
+    let Some(environment) = repo.config.try_deployment_environment.clone() else {
+        return;
+    };
+    if build.branch != TRY_BRANCH_NAME || build.deployment_status == Some(status) {
+        return;
+    }
+    let deployment_id = match build.deployment_id {
+        Some(deployment_id) => deployment_id,
+        None => {
+            let sha = CommitSha(build.commit_sha.clone());
+            let deployment_id = match repo.client.create_deployment(&sha, &environment).await {
+                Ok(deployment_id) => deployment_id,
+                Err(error) => {
+                    tracing::error!("Could not create deployment of {sha}: {error:?}");
+                    return;
+                }
+            };
+            if let Err(error) = db.set_deployment_id(build, deployment_id).await {
+                tracing::error!("Could not store deployment of {sha}: {error:?}");
+            }
+            deployment_id
+        }
+    };
+    update_deployment_status(repo, db, build, deployment_id, status).await;
+}
+
+/// Marks the successful deployment of a try build inactive, once the build is superseded by a
+/// newer try build of the same PR.
+pub(super) async fn deactivate_deployment<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+) {
+    let Some(deployment_id) = build.deployment_id else {
+        return;
+    };
+    if build.deployment_status != Some(CheckRunStatus::Success) {
+        return;
+    }
+    update_deployment_status(repo, db, build, deployment_id, CheckRunStatus::Cancelled).await;
+}
+
+async fn update_deployment_status<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    build: &BuildModel,
+    deployment_id: u64,
+    status: CheckRunStatus,
+) {
+    if let Err(error) = repo
+        .client
+        .update_deployment_status(deployment_id, status)
+        .await
+    {
+        tracing::error!("Could not update status of deployment {deployment_id}: {error:?}");
+        return;
+    }
+    if let Err(error) = db.set_deployment_status(build, status).await {
+        tracing::error!("Could not store status of deployment {deployment_id}: {error:?}");
+    }
+}
+
+async fn complete_try_build<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr: &PullRequestModel,
+    build: BuildModel,
+    workflows: Vec<WorkflowModel>,
+    status: BuildStatus,
+) -> anyhow::Result<()> {
+    let workflow_list = format_workflow_list(&workflows);
+    let has_failure = status == BuildStatus::Failure;
 
     let message = if !has_failure {
         tracing::info!("Workflow succeeded");
 
-        let sha = &payload.commit_sha;
-        format!(
+        let sha = &build.commit_sha;
+        let mut message = format!(
             r#":sunny: Try build successful
 {workflow_list}
 Build commit: {sha} (`{sha}`)"#
-        )
+        );
+        let artifacts = collect_artifacts(repo, &workflows).await;
+        if !artifacts.is_empty() {
+            message.push_str(&format!(
+                "\nArtifacts:\n{}",
+                format_artifact_list(&artifacts)
+            ));
+        }
+        message
     } else {
         tracing::info!("Workflow failed");
-        format!(
+        let mut message = format!(
             r#":broken_heart: Test failed
 {workflow_list}"#
-        )
+        );
+        let newly_failing = find_newly_failing_workflows(db, pr, &workflows).await?;
+        if !newly_failing.is_empty() {
+            message.push_str(&format!(
+                "\nNewly failing since the last successful try: {}",
+                newly_failing
+                    .iter()
+                    .map(|name| format!("`{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        message
     };
-    repo.client.post_comment(pr.number, &message).await?;
+    // The build is completed before its result is posted, so that a repeated delivery of the
+    // completing event is ignored instead of posting the result again
+    db.update_build_status(&build, status).await?;
+    if has_failure {
+        repo.post_event_comment(db, pr, &message).await?;
+    } else {
+        repo.post_status_comment(db, pr, &message).await?;
+    }
 
-    let (status, trigger) = if has_failure {
-        (BuildStatus::Failure, LabelTrigger::TryBuildFailed)
+    let trigger = if has_failure {
+        LabelTrigger::TryBuildFailed
     } else {
-        (BuildStatus::Success, LabelTrigger::TryBuildSucceeded)
+        LabelTrigger::TryBuildSucceeded
     };
-    db.update_build_status(&build, status).await?;
 
     handle_label_trigger(repo, pr.number, trigger).await?;
     Ok(())
 }
 
+/// Returns the names of the failed workflows of a try build that have succeeded in the last
+/// successful try build of the PR.
+async fn find_newly_failing_workflows<'a>(
+    db: &dyn DbClient,
+    pr: &PullRequestModel,
+    workflows: &'a [WorkflowModel],
+) -> anyhow::Result<Vec<&'a str>> {
+    let Some(last_success) = db.find_last_successful_build(pr, TRY_BRANCH_NAME).await? else {
+        return Ok(vec![]);
+    };
+    let previous = db.get_workflows_for_build(&last_success).await?;
+    Ok(workflows
+        .iter()
+        .filter(|workflow| workflow.status == WorkflowStatus::Failure)
+        .filter(|workflow| {
+            previous.iter().any(|previous| {
+                previous.name == workflow.name && previous.status == WorkflowStatus::Success
+            })
+        })
+        .map(|workflow| workflow.name.as_str())
+        .collect())
+}
+
+/// Collects the artifacts uploaded by the Github Actions workflows of a build. Artifacts are only
+/// informative, so workflows whose artifacts cannot be loaded are skipped.
+async fn collect_artifacts<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    workflows: &[WorkflowModel],
+) -> Vec<Artifact> {
+    let mut artifacts = vec![];
+    for workflow in workflows
+        .iter()
+        .filter(|w| w.workflow_type == WorkflowType::Github)
+    {
+        match repo.client.list_artifacts(workflow.run_id).await {
+            Ok(workflow_artifacts) => artifacts.extend(workflow_artifacts),
+            Err(error) => {
+                tracing::warn!(
+                    "Cannot load artifacts of workflow {}: {error:?}",
+                    workflow.run_id
+                );
+            }
+        }
+    }
+    artifacts
+}
+
+/// Lists at most `MAX_LISTED_ARTIFACTS` artifacts, the rest is only counted.
+fn format_artifact_list(artifacts: &[Artifact]) -> String {
+    let mut lines = artifacts
+        .iter()
+        .take(MAX_LISTED_ARTIFACTS)
+        .map(|artifact| format!("- [{}]({})", artifact.name, artifact.url))
+        .collect::<Vec<_>>();
+    if artifacts.len() > MAX_LISTED_ARTIFACTS {
+        lines.push(format!(
+            "- … and {} more",
+            artifacts.len() - MAX_LISTED_ARTIFACTS
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Unapproves a PR whose builds keep failing, so that it does not occupy the merge queue with
+/// builds that are unlikely to succeed until its author fixes them.
+async fn stop_after_repeated_failures<Client: RepositoryClient>(
+    repo: &mut RepositoryState<Client>,
+    db: &dyn DbClient,
+    pr_number: PullRequestNumber,
+    failures: i32,
+) -> anyhow::Result<()> {
+    let pr = db
+        .get_or_create_pull_request(&repo.repository, pr_number)
+        .await?;
+    tracing::warn!("Builds of PR {pr_number} have failed {failures} times in a row");
+    let unapproved = if pr.approved_sha.is_some() {
+        db.dequeue(&pr).await?;
+        " The approval has been dismissed."
+    } else {
+        ""
+    };
+    repo.post_comment(
+        pr_number,
+        &format!(
+            ":no_entry_sign: The builds of this PR have failed {failures} times in a row.{unapproved} Please fix the failures before starting another build."
+        ),
+    )
+    .await
+}
+
+/// Formats the workflows of a build as a markdown list with their results.
+pub(super) fn format_workflow_list(workflows: &[WorkflowModel]) -> String {
+    workflows
+        .iter()
+        .map(|w| {
+            format!(
+                "- [{}]({}) {}",
+                w.name,
+                w.url,
+                if w.status == WorkflowStatus::Success {
+                    ":white_check_mark:"
+                } else {
+                    ":x:"
+                }
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use std::assert_eq;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::async_trait;
 
     use sea_orm::EntityTrait;
 
     use entity::workflow;
 
+    use crate::bors::event::{BorsEvent, CommitStatusChanged};
+    use crate::bors::handlers::refresh::with_mocked_time;
     use crate::bors::handlers::trybuild::TRY_BRANCH_NAME;
-    use crate::database::WorkflowStatus;
+    use crate::bors::handlers::workflow::{format_artifact_list, MAX_LISTED_ARTIFACTS};
+    use crate::bors::notifier::{BuildCompletedEvent, BuildCompletedStatus, Notifier};
+    use crate::bors::{Artifact, CombinedStatus, RepositoryClient};
+    use crate::config::BuildStatusSource;
+    use crate::database::{CheckRunStatus, WorkflowStatus};
+    use crate::github::{CommitSha, LabelTrigger};
     use crate::tests::event::{
         default_pr_number, suite_failure, suite_pending, suite_success, CheckSuiteCompletedBuilder,
         WorkflowCompletedBuilder, WorkflowStartedBuilder,
     };
-    use crate::tests::state::{default_merge_sha, ClientBuilder};
+    use crate::tests::state::{
+        default_merge_sha, default_repo_name, ClientBuilder, RepoConfigBuilder, TestBorsState,
+    };
 
     #[tokio::test]
     async fn test_unknown_build() {
@@ -300,39 +886,52 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_try_failure() {
-        let mut state = ClientBuilder::default().create_state().await;
+    async fn test_unobserved_workflow_does_not_complete_build() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().observed_workflows(vec!["workflow-1".to_string()]))
+            .create_state()
+            .await;
+        // The check suite of the unobserved workflow has failed
         state
             .client()
             .set_checks(&default_merge_sha(), &[suite_failure()]);
-
         state.comment("@bors try").await;
         state
             .perform_workflow_events(
-                1,
+                2,
                 TRY_BRANCH_NAME,
                 &default_merge_sha(),
                 WorkflowStatus::Failure,
             )
             .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
 
-        insta::assert_snapshot!(
-            state.client().get_last_comment(default_pr_number()),
-            @r###"
-        :broken_heart: Test failed
-        - [workflow-1](https://workflow-1.com) :x:
-        "###
-        );
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
     }
 
     #[tokio::test]
-    async fn test_try_success_multiple_suites() {
-        let mut state = ClientBuilder::default().create_state().await;
-        state
-            .client()
-            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
-
+    async fn test_observed_workflows_wait_for_all_workflows() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .observed_workflows(vec!["workflow-1".to_string(), "workflow-2".to_string()]),
+            )
+            .create_state()
+            .await;
         state.comment("@bors try").await;
+        // `workflow-2` has not started yet when `workflow-1` finishes
         state
             .perform_workflow_events(
                 1,
@@ -341,11 +940,8 @@ mod tests {
                 WorkflowStatus::Success,
             )
             .await;
-        state.client().check_comment_count(default_pr_number(), 1);
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
 
-        state
-            .client()
-            .set_checks(&default_merge_sha(), &[suite_success(), suite_success()]);
         state
             .perform_workflow_events(
                 2,
@@ -363,26 +959,22 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_try_failure_multiple_suites() {
-        let mut state = ClientBuilder::default().create_state().await;
-        state
-            .client()
-            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
-
+    async fn test_observed_workflow_by_id() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().observed_workflows(vec!["42".to_string()]))
+            .create_state()
+            .await;
         state.comment("@bors try").await;
         state
-            .perform_workflow_events(
-                1,
-                TRY_BRANCH_NAME,
-                &default_merge_sha(),
-                WorkflowStatus::Success,
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .name("CI".to_string())
+                    .workflow_id(Some(42))
+                    .run_id(1),
             )
             .await;
-        state.client().check_comment_count(default_pr_number(), 1);
-
-        state
-            .client()
-            .set_checks(&default_merge_sha(), &[suite_success(), suite_failure()]);
+        // Workflows with other IDs are ignored
         state
             .perform_workflow_events(
                 2,
@@ -391,23 +983,789 @@ mod tests {
                 WorkflowStatus::Failure,
             )
             .await;
+        state
+            .workflow_completed(
+                WorkflowCompletedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1)
+                    .status(WorkflowStatus::Success),
+            )
+            .await;
         insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
-        :broken_heart: Test failed
-        - [workflow-1](https://workflow-1.com) :white_check_mark:
-        - [workflow-2](https://workflow-2.com) :x:
+        :sunny: Try build successful
+        - [CI](https://CI-1) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
         "###);
     }
 
     #[tokio::test]
-    async fn test_try_suite_completed_received_before_workflow_completed() {
-        let mut state = ClientBuilder::default().create_state().await;
+    async fn test_observed_workflows_keep_external_check_suites() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().observed_workflows(vec!["workflow-1".to_string()]))
+            .create_state()
+            .await;
+        let mut external = suite_pending();
+        external.github_actions = false;
         state
             .client()
-            .set_checks(&default_merge_sha(), &[suite_success()]);
-
+            .set_checks(&default_merge_sha(), &[suite_success(), external]);
         state.comment("@bors try").await;
         state
-            .workflow_started(WorkflowStartedBuilder::default().branch(TRY_BRANCH_NAME.to_string()))
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":hourglass: Trying commit pr-sha with merge sha-merged…");
+
+        let mut external = suite_failure();
+        external.github_actions = false;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), external]);
+        state
+            .check_suite_completed(
+                CheckSuiteCompletedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .commit_sha(default_merge_sha()),
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        "###);
+    }
+
+    async fn run_try_workflows(
+        state: &mut TestBorsState,
+        sha: &str,
+        workflows: &[(u64, &str, WorkflowStatus)],
+    ) {
+        for (run_id, name, _) in workflows {
+            state
+                .workflow_started(
+                    WorkflowStartedBuilder::default()
+                        .branch(TRY_BRANCH_NAME.to_string())
+                        .commit_sha(sha.to_string())
+                        .name(name.to_string())
+                        .url(Some(format!("https://{name}.com")))
+                        .run_id(*run_id),
+                )
+                .await;
+        }
+        for (run_id, _, status) in workflows {
+            state
+                .workflow_completed(
+                    WorkflowCompletedBuilder::default()
+                        .branch(TRY_BRANCH_NAME.to_string())
+                        .commit_sha(sha.to_string())
+                        .run_id(*run_id)
+                        .status(*status),
+                )
+                .await;
+        }
+        state
+            .check_suite_completed(
+                CheckSuiteCompletedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .commit_sha(sha.to_string()),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_try_failure_lists_newly_failing_workflows() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_success()]);
+        state.comment("@bors try").await;
+        run_try_workflows(
+            &mut state,
+            &default_merge_sha(),
+            &[
+                (1, "workflow-1", WorkflowStatus::Success),
+                (2, "workflow-2", WorkflowStatus::Success),
+            ],
+        )
+        .await;
+
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("merge2".to_string())));
+        state.client().set_checks(
+            "merge2",
+            &[suite_failure(), suite_success(), suite_failure()],
+        );
+        state.comment("@bors try").await;
+        run_try_workflows(
+            &mut state,
+            "merge2",
+            &[
+                (3, "workflow-1", WorkflowStatus::Failure),
+                (4, "workflow-2", WorkflowStatus::Success),
+                (5, "workflow-3", WorkflowStatus::Failure),
+            ],
+        )
+        .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :x:
+        - [workflow-2](https://workflow-2.com) :white_check_mark:
+        - [workflow-3](https://workflow-3.com) :x:
+        Newly failing since the last successful try: `workflow-1`
+        "###);
+    }
+
+    /// Runs a try build whose `workflow-1` succeeds and flaky `workflow-2` fails.
+    async fn run_build_with_flaky_failure(state: &mut TestBorsState) {
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_failure()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_flaky_check_is_retried() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().flaky_checks(vec!["workflow-2".to_string()]))
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        run_build_with_flaky_failure(&mut state).await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":repeat: Only checks that are known to be flaky have failed, retrying `workflow-2`…");
+        assert_eq!(state.client().dispatched_workflows, vec![2]);
+
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_success()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_flaky_check_is_retried_once() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().flaky_checks(vec!["workflow-2".to_string()]))
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        run_build_with_flaky_failure(&mut state).await;
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :x:
+        "###);
+        assert_eq!(state.client().dispatched_workflows, vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_non_flaky_failure_is_not_retried() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().flaky_checks(vec!["workflow-1".to_string()]))
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        run_build_with_flaky_failure(&mut state).await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :x:
+        "###);
+        assert!(state.client().dispatched_workflows.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flaky_check_with_external_failure_is_not_retried() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().flaky_checks(vec!["workflow-2".to_string()]))
+            .create_state()
+            .await;
+        let mut external = suite_failure();
+        external.github_actions = false;
+        state.comment("@bors try").await;
+        state.client().set_checks(
+            &default_merge_sha(),
+            &[suite_success(), suite_pending(), external.clone()],
+        );
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().set_checks(
+            &default_merge_sha(),
+            &[suite_success(), suite_failure(), external],
+        );
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :x:
+        "###);
+        assert!(state.client().dispatched_workflows.is_empty());
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_retried_flaky_check_gets_whole_timeout() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .flaky_checks(vec!["workflow-2".to_string()])
+                    .timeout(Duration::from_secs(3600)),
+            )
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        with_mocked_time(Duration::from_secs(50 * 60), async {
+            run_build_with_flaky_failure(&mut state).await;
+        })
+        .await;
+        // The build has started more than the timeout ago, but it was retried less than that
+        with_mocked_time(Duration::from_secs(90 * 60), async {
+            state.refresh().await;
+        })
+        .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @":repeat: Only checks that are known to be flaky have failed, retrying `workflow-2`…");
+    }
+
+    #[tokio::test]
+    async fn test_check_run_reports_build_status() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        state
+            .workflow_completed(
+                WorkflowCompletedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1)
+                    .status(WorkflowStatus::Success),
+            )
+            .await;
+        state.client().check_check_runs(
+            &default_merge_sha(),
+            &[CheckRunStatus::InProgress, CheckRunStatus::Success],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_run_updated_only_on_change() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+
+        for run_id in [1, 2] {
+            state
+                .workflow_started(
+                    WorkflowStartedBuilder::default()
+                        .branch(TRY_BRANCH_NAME.to_string())
+                        .run_id(run_id),
+                )
+                .await;
+        }
+        state
+            .client()
+            .check_check_runs(&default_merge_sha(), &[CheckRunStatus::InProgress]);
+
+        for run_id in [1, 2] {
+            state
+                .workflow_completed(
+                    WorkflowCompletedBuilder::default()
+                        .branch(TRY_BRANCH_NAME.to_string())
+                        .run_id(run_id)
+                        .status(WorkflowStatus::Success),
+                )
+                .await;
+        }
+        state.client().check_check_runs(
+            &default_merge_sha(),
+            &[CheckRunStatus::InProgress, CheckRunStatus::Success],
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_build_deployment() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .try_deployment_environment(Some("staging".to_string())),
+            )
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_deployments(&[(
+            &default_merge_sha(),
+            "staging",
+            &[CheckRunStatus::InProgress, CheckRunStatus::Success],
+        )]);
+    }
+
+    #[tokio::test]
+    async fn test_try_build_deployment_inactive_on_cancel() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .try_deployment_environment(Some("staging".to_string())),
+            )
+            .create_state()
+            .await;
+        state.comment("@bors try").await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        state.comment("@bors try cancel").await;
+        state.client().check_deployments(&[(
+            &default_merge_sha(),
+            "staging",
+            &[CheckRunStatus::InProgress, CheckRunStatus::Cancelled],
+        )]);
+    }
+
+    #[tokio::test]
+    async fn test_try_build_deployment_without_check_runs() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .try_deployment_environment(Some("staging".to_string())),
+            )
+            .create_state()
+            .await;
+        state.client().fail_check_runs = true;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(2),
+            )
+            .await;
+        state.client().check_deployments(&[(
+            &default_merge_sha(),
+            "staging",
+            &[CheckRunStatus::InProgress],
+        )]);
+    }
+
+    #[tokio::test]
+    async fn test_try_build_deployment_inactive_when_superseded() {
+        let mut state = ClientBuilder::default()
+            .config(
+                RepoConfigBuilder::default()
+                    .try_deployment_environment(Some("staging".to_string())),
+            )
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("merge2".to_string())));
+        state.comment("@bors try").await;
+        state.client().check_deployments(&[(
+            &default_merge_sha(),
+            "staging",
+            &[
+                CheckRunStatus::InProgress,
+                CheckRunStatus::Success,
+                CheckRunStatus::Cancelled,
+            ],
+        )]);
+    }
+
+    #[tokio::test]
+    async fn test_no_deployment_by_default() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.comment("@bors try").await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        state.client().check_deployments(&[]);
+    }
+
+    #[tokio::test]
+    async fn test_try_success_artifacts() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.client().set_artifacts(
+            1,
+            &[
+                ("dist-linux", "https://artifact-1.com"),
+                ("dist-windows", "https://artifact-2.com"),
+            ],
+        );
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        Artifacts:
+        - [dist-linux](https://artifact-1.com)
+        - [dist-windows](https://artifact-2.com)
+        "###
+        );
+    }
+
+    #[test]
+    fn test_artifact_list_capped() {
+        let artifacts = (1..=12)
+            .map(|index| Artifact {
+                name: format!("artifact-{index}"),
+                url: format!("https://artifact-{index}.com"),
+            })
+            .collect::<Vec<_>>();
+        let list = format_artifact_list(&artifacts);
+        assert_eq!(list.lines().count(), MAX_LISTED_ARTIFACTS + 1);
+        assert_eq!(list.lines().last(), Some("- … and 2 more"));
+    }
+
+    #[tokio::test]
+    async fn test_try_failure() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :x:
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_failure_legacy_status() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().build_status_source(BuildStatusSource::Both))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state
+            .client()
+            .set_combined_status(&default_merge_sha(), CombinedStatus::Failure);
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_success_without_legacy_statuses() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().build_status_source(BuildStatusSource::Both))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_success_legacy_status_changed() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().build_status_source(BuildStatusSource::Statuses))
+            .create_state()
+            .await;
+
+        state.comment("@bors try").await;
+        state
+            .client()
+            .set_combined_status(&default_merge_sha(), CombinedStatus::Success);
+        state
+            .event(BorsEvent::CommitStatusChanged(CommitStatusChanged {
+                repository: default_repo_name(),
+                commit_sha: CommitSha(default_merge_sha()),
+                branches: vec![
+                    TRY_BRANCH_NAME.to_string(),
+                    "automation/bors/try-merge".to_string(),
+                ],
+            }))
+            .await;
+
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @r###"
+        :sunny: Try build successful
+
+        Build commit: sha-merged (`sha-merged`)
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_try_workflow_completed_twice() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().add_label(LabelTrigger::TryBuildSucceeded, "foo"))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+
+        state.comment("@bors try").await;
+        state
+            .workflow_started(
+                WorkflowStartedBuilder::default()
+                    .branch(TRY_BRANCH_NAME.to_string())
+                    .run_id(1),
+            )
+            .await;
+        let event = || {
+            WorkflowCompletedBuilder::default()
+                .branch(TRY_BRANCH_NAME.to_string())
+                .run_id(1)
+                .status(WorkflowStatus::Success)
+        };
+        state.workflow_completed(event()).await;
+        state.workflow_completed(event()).await;
+
+        state.client().check_comment_count(default_pr_number(), 2);
+        state
+            .client()
+            .check_added_labels(default_pr_number(), &["foo"]);
+    }
+
+    #[tokio::test]
+    async fn test_try_success_multiple_suites() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_success()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :sunny: Try build successful
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :white_check_mark:
+        Build commit: sha-merged (`sha-merged`)
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_try_failure_multiple_suites() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_pending()]);
+
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_failure()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :broken_heart: Test failed
+        - [workflow-1](https://workflow-1.com) :white_check_mark:
+        - [workflow-2](https://workflow-2.com) :x:
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_try_suite_completed_received_before_workflow_completed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+
+        state.comment("@bors try").await;
+        state
+            .workflow_started(WorkflowStartedBuilder::default().branch(TRY_BRANCH_NAME.to_string()))
             .await;
 
         // Check suite completed received before the workflow has finished.
@@ -448,4 +1806,180 @@ mod tests {
         state.check_suite_completed(event()).await;
         state.client().check_comment_count(default_pr_number(), 2);
     }
+
+    #[derive(Clone, Default)]
+    struct MockNotifier {
+        events: Arc<Mutex<Vec<BuildCompletedEvent>>>,
+    }
+
+    #[async_trait]
+    impl Notifier for MockNotifier {
+        async fn notify(&self, event: &BuildCompletedEvent) -> anyhow::Result<()> {
+            self.events.lock().unwrap().push(event.clone());
+            Ok(())
+        }
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_settle_delay_success() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().settle_delay(Duration::from_secs(60)))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        with_mocked_time(Duration::from_secs(61), async move {
+            state.refresh().await;
+            insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+            :sunny: Try build successful
+            - [workflow-1](https://workflow-1.com) :white_check_mark:
+            Build commit: sha-merged (`sha-merged`)
+            "###);
+        })
+        .await;
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_settle_delay_late_check() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().settle_delay(Duration::from_secs(60)))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Success,
+            )
+            .await;
+
+        // A check that arrives within the settle delay fails the build
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_success(), suite_failure()]);
+        state
+            .perform_workflow_events(
+                2,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        state.client().check_comment_count(default_pr_number(), 1);
+
+        with_mocked_time(Duration::from_secs(61), async move {
+            state.refresh().await;
+            insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+            :broken_heart: Test failed
+            - [workflow-1](https://workflow-1.com) :white_check_mark:
+            - [workflow-2](https://workflow-2.com) :x:
+            "###);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn test_notify_build_completed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let notifier = MockNotifier::default();
+        state.repo().notifier = Some(Box::new(notifier.clone()));
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+
+        state.comment("@bors try").await;
+        state
+            .check_suite_completed(
+                CheckSuiteCompletedBuilder::default().branch(TRY_BRANCH_NAME.to_string()),
+            )
+            .await;
+
+        assert_eq!(
+            *notifier.events.lock().unwrap(),
+            vec![BuildCompletedEvent {
+                repository: "owner/name".to_string(),
+                pr: default_pr_number(),
+                branch: TRY_BRANCH_NAME.to_string(),
+                commit_sha: default_merge_sha(),
+                status: BuildCompletedStatus::Failure,
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_log_appends_events_to_one_comment() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().status_log(true))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state.comment("@bors try").await;
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+        state.client().merge_branches_fn = Box::new(|| Ok(CommitSha("merge2".to_string())));
+        state.comment("@bors try").await;
+
+        state.client().check_comment_count(default_pr_number(), 1);
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :scroll: Status of this PR:
+        - :hourglass: Trying commit pr-sha with merge sha-merged…
+        - :broken_heart: Test failed
+          - [workflow-1](https://workflow-1.com) :x:
+        - :hourglass: Trying commit pr-sha with merge merge2…
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_status_log_starts_new_comment_when_full() {
+        let mut state = ClientBuilder::default()
+            .config(RepoConfigBuilder::default().status_log(true))
+            .create_state()
+            .await;
+        state
+            .client()
+            .set_checks(&default_merge_sha(), &[suite_failure()]);
+        state.comment("@bors try").await;
+        let full_log = format!(":scroll: Status of this PR:\n- {}", "x".repeat(65500));
+        state.client().edit_comment(1, &full_log).await.unwrap();
+        state
+            .perform_workflow_events(
+                1,
+                TRY_BRANCH_NAME,
+                &default_merge_sha(),
+                WorkflowStatus::Failure,
+            )
+            .await;
+
+        state.client().check_comment_count(default_pr_number(), 2);
+        insta::assert_snapshot!(state.client().get_last_comment(default_pr_number()), @r###"
+        :scroll: Status of this PR:
+        - :broken_heart: Test failed
+          - [workflow-1](https://workflow-1.com) :x:
+        "###);
+    }
 }
@@ -4,18 +4,35 @@ use octocrab::models::RunId;
 use std::future::Future;
 use std::pin::Pin;
 
-use crate::github::{CommitSha, GithubRepoName, MergeError, PullRequest, PullRequestNumber};
+use crate::github::api::rate_limit::RateLimit;
+use crate::github::{
+    Branch, Commit, CommitSha, GithubRepoName, MergeError, PullRequest, PullRequestNumber,
+    ResolveRefError,
+};
 use crate::permissions::PermissionResolver;
 
+pub mod backoff;
 mod command;
 mod context;
 pub mod event;
 mod handlers;
+pub mod notifier;
+mod processed_comments;
+mod recent_events;
 
+use crate::bors::backoff::RefreshBackoff;
 use crate::bors::event::PullRequestComment;
-use crate::database::DbClient;
+use crate::bors::notifier::{Notifier, WebhookNotifier};
+use crate::bors::processed_comments::ProcessedComments;
+use crate::bors::recent_events::RecentEvents;
+use crate::database::{CheckRunStatus, DbClient, PullRequestModel, WorkflowStatus};
+
+/// First line of the comment that logs the status events of a PR.
+const STATUS_LOG_HEADER: &str = ":scroll: Status of this PR:";
+/// Maximum number of characters of a GitHub comment.
+const MAX_COMMENT_LENGTH: usize = 65536;
 pub use command::CommandParser;
-pub use context::BorsContext;
+pub use context::{BorsContext, ConcurrencyGuard};
 pub use handlers::handle_bors_event;
 
 /// Provides functionality for working with a remote repository.
@@ -23,15 +40,45 @@ pub use handlers::handle_bors_event;
 pub trait RepositoryClient {
     fn repository(&self) -> &GithubRepoName;
 
+    /// Returns the last known rate-limit budget of the client, if it is known.
+    fn rate_limit(&self) -> Option<RateLimit>;
+
+    /// Queries the current rate-limit budget of the client from GitHub.
+    async fn get_rate_limit(&mut self) -> anyhow::Result<RateLimit>;
+
     /// Resolve a pull request from this repository by it's number.
     async fn get_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<PullRequest>;
 
+    /// Returns the paths of the files changed by the PR.
+    async fn list_pr_files(&mut self, pr: PullRequestNumber) -> anyhow::Result<Vec<String>>;
+
     /// Post a comment to the pull request with the given number.
     async fn post_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<()>;
 
+    /// Post a comment to the pull request with the given number, and return its ID, so that the
+    /// comment can be edited later.
+    async fn create_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<u64>;
+
+    /// Returns the text of the comment with the given ID.
+    async fn get_comment_text(&mut self, comment_id: u64) -> anyhow::Result<String>;
+
+    /// Replaces the text of the comment with the given ID.
+    async fn edit_comment(&mut self, comment_id: u64, text: &str) -> anyhow::Result<()>;
+
+    /// Returns the SHA of the latest commit of the given branch, or `None` if the branch does not
+    /// exist.
+    async fn get_branch_sha(&mut self, branch: &str) -> anyhow::Result<Option<CommitSha>>;
+
+    /// Returns all branches whose name starts with the given prefix.
+    async fn list_branches(&mut self, prefix: &str) -> anyhow::Result<Vec<Branch>>;
+
     /// Set the given branch to a commit with the given `sha`.
     async fn set_branch_to_sha(&mut self, branch: &str, sha: &CommitSha) -> anyhow::Result<()>;
 
+    /// Deletes the given branch. Deleting a branch that does not exist is not an error, as the
+    /// cleanup can race with other deletions, e.g. GitHub deleting head branches of merged PRs.
+    async fn delete_branch(&mut self, branch: &str) -> anyhow::Result<()>;
+
     /// Merge `head` into `base`. Returns the SHA of the merge commit.
     async fn merge_branches(
         &mut self,
@@ -47,6 +94,42 @@ pub trait RepositoryClient {
         sha: &CommitSha,
     ) -> anyhow::Result<Vec<CheckSuite>>;
 
+    /// Returns the metadata of the commit with the given `sha`.
+    async fn get_commit(&mut self, sha: &CommitSha) -> anyhow::Result<Commit>;
+
+    /// Resolves an abbreviated commit SHA to the full SHA of the commit.
+    async fn resolve_ref(&mut self, short_sha: &str) -> Result<CommitSha, ResolveRefError>;
+
+    /// Returns the latest check run of each check of the given ref, which can be a branch name or a
+    /// commit SHA.
+    async fn get_check_runs_for_ref(&mut self, reference: &str) -> anyhow::Result<Vec<CheckRun>>;
+
+    /// Find all Github Actions workflow runs attached to the given commit and branch.
+    async fn get_workflow_runs_for_commit(
+        &mut self,
+        branch: &str,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Vec<WorkflowRun>>;
+
+    /// Compares `head` against `base`, both of which can be branch names or commit SHAs.
+    async fn compare_commits(&mut self, base: &str, head: &str)
+        -> anyhow::Result<CommitComparison>;
+
+    /// Returns `true` if the user with the given name is a collaborator of this repository.
+    async fn is_collaborator(&mut self, username: &str) -> anyhow::Result<bool>;
+
+    /// Returns the protection rules of the given branch, or `None` if the branch is not protected.
+    async fn get_branch_protection(
+        &mut self,
+        branch: &str,
+    ) -> anyhow::Result<Option<BranchProtection>>;
+
+    /// Returns the names of all active Github Actions workflows of this repository.
+    async fn get_workflows(&mut self) -> anyhow::Result<Vec<String>>;
+
+    /// Runs the failed jobs of the given Github Actions workflow run again, on the same commit.
+    async fn dispatch_workflow(&mut self, run_id: RunId) -> anyhow::Result<()>;
+
     /// Cancels Github Actions workflows.
     async fn cancel_workflows(&mut self, run_ids: Vec<RunId>) -> anyhow::Result<()>;
 
@@ -59,20 +142,140 @@ pub trait RepositoryClient {
         pr: PullRequestNumber,
         labels: &[String],
     ) -> anyhow::Result<()>;
+
+    /// Request a review of the PR from the given users and teams of the repository owner.
+    async fn request_reviewers(
+        &mut self,
+        pr: PullRequestNumber,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> anyhow::Result<()>;
+
+    /// Close the PR without merging it.
+    async fn close_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<()>;
+
+    /// Returns the combined state of the legacy commit statuses (not check suites) of the given
+    /// commit, or `None` if the commit has no statuses.
+    async fn get_combined_status(
+        &mut self,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Option<CombinedStatus>>;
+
+    /// Returns the artifacts uploaded by the given Github Actions workflow run.
+    async fn list_artifacts(&mut self, run_id: RunId) -> anyhow::Result<Vec<Artifact>>;
+
+    /// Reports the status of a build in the bors check run of the given commit. The check run is
+    /// created if it does not exist yet.
+    async fn create_or_update_check_run(
+        &mut self,
+        sha: &CommitSha,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()>;
+
+    /// Creates a GitHub deployment of the given commit to `environment`, and returns its ID.
+    async fn create_deployment(
+        &mut self,
+        sha: &CommitSha,
+        environment: &str,
+    ) -> anyhow::Result<u64>;
+
+    /// Reports the status of a build in the given deployment. A cancelled build marks the
+    /// deployment as inactive.
+    async fn update_deployment_status(
+        &mut self,
+        deployment_id: u64,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()>;
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CheckSuiteStatus {
     Pending,
     Failure,
     Success,
 }
 
+/// Combined state of all legacy commit statuses of a commit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CombinedStatus {
+    /// Some statuses are still pending.
+    Pending,
+    /// Some status has failed or errored.
+    Failure,
+    /// All statuses have succeeded.
+    Success,
+}
+
+impl From<CombinedStatus> for CheckSuiteStatus {
+    fn from(status: CombinedStatus) -> Self {
+        match status {
+            CombinedStatus::Pending => CheckSuiteStatus::Pending,
+            CombinedStatus::Failure => CheckSuiteStatus::Failure,
+            CombinedStatus::Success => CheckSuiteStatus::Success,
+        }
+    }
+}
+
 /// A GitHub check suite.
 /// Corresponds to a single GitHub actions workflow run, or to a single external CI check run.
 #[derive(Clone)]
 pub struct CheckSuite {
     pub(crate) status: CheckSuiteStatus,
+    /// Was the suite created by GitHub Actions, rather than by an external CI app?
+    pub(crate) github_actions: bool,
+}
+
+/// A single check of a commit, e.g. a job of a Github Actions workflow or a check reported by an
+/// external CI.
+#[derive(Clone, Debug)]
+pub struct CheckRun {
+    pub name: String,
+    pub status: CheckSuiteStatus,
+}
+
+/// A single Github Actions workflow run, as seen by GitHub.
+#[derive(Clone, Debug)]
+pub struct WorkflowRun {
+    pub run_id: RunId,
+    pub status: WorkflowStatus,
+}
+
+/// A file uploaded by a Github Actions workflow run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Artifact {
+    pub name: String,
+    /// Link to the page from which the artifact can be downloaded.
+    pub url: String,
+}
+
+/// Relation of a commit to some base commit.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CommitComparisonStatus {
+    /// Both commits are the same.
+    Identical,
+    /// The commit contains the base commit.
+    Ahead,
+    /// The commit is contained in the base commit.
+    Behind,
+    /// Both commits contain changes that the other one does not have.
+    Diverged,
+}
+
+/// Result of comparing a commit against some base commit.
+#[derive(Clone, Debug)]
+pub struct CommitComparison {
+    pub status: CommitComparisonStatus,
+    /// How many commits the commit has that the base does not have.
+    pub ahead_by: u64,
+    /// How many commits the base has that the commit does not have.
+    pub behind_by: u64,
+}
+
+/// Protection rules of a branch that are relevant for merging into it.
+#[derive(Clone, Debug)]
+pub struct BranchProtection {
+    /// Names of status checks that have to pass before the branch can be updated.
+    pub required_checks: Vec<String>,
 }
 
 /// Main state holder for the bot.
@@ -90,6 +293,13 @@ pub trait BorsState<Client: RepositoryClient> {
     /// Get all repositories.
     fn get_all_repos_mut(&mut self) -> (Vec<&mut RepositoryState<Client>>, &mut dyn DbClient);
 
+    /// Stop tracking the given repository, e.g. because it was removed from the GitHub app.
+    /// Its records in the database are kept.
+    fn remove_repository(&mut self, repo: &GithubRepoName);
+
+    /// Continue tracking the given repository under its new name, after it was renamed on GitHub.
+    fn rename_repository(&mut self, old_name: &GithubRepoName, new_name: &GithubRepoName);
+
     /// Reload state of repositories due to some external change.
     fn reload_repositories(&mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + '_>>;
 }
@@ -102,4 +312,223 @@ pub struct RepositoryState<Client: RepositoryClient> {
     pub client: Client,
     pub permissions_resolver: Box<dyn PermissionResolver>,
     pub config: RepositoryConfig,
+    /// Comments whose commands have already been executed.
+    processed_comments: ProcessedComments,
+    /// Events that were recently received for the repository.
+    recent_events: RecentEvents,
+    /// Receives notifications about completed builds, if configured.
+    pub notifier: Option<Box<dyn Notifier>>,
+    /// Decides when the repository is refreshed again after a failed refresh.
+    pub refresh_backoff: RefreshBackoff,
+}
+
+impl<Client: RepositoryClient> RepositoryState<Client> {
+    pub fn new(
+        repository: GithubRepoName,
+        client: Client,
+        permissions_resolver: Box<dyn PermissionResolver>,
+        config: RepositoryConfig,
+    ) -> Self {
+        let notifier = config
+            .notification_webhook
+            .clone()
+            .map(|url| Box::new(WebhookNotifier::new(url)) as Box<dyn Notifier>);
+        Self {
+            repository,
+            client,
+            permissions_resolver,
+            config,
+            processed_comments: Default::default(),
+            recent_events: Default::default(),
+            notifier,
+            refresh_backoff: Default::default(),
+        }
+    }
+
+    /// Post a comment to the pull request with the given number.
+    ///
+    /// If a comment footer is configured, it is appended to the text.
+    pub async fn post_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<()> {
+        match self.config.comment_footer.as_deref() {
+            Some(footer) if !footer.is_empty() => {
+                self.client
+                    .post_comment(pr, &format!("{text}\n\n{footer}"))
+                    .await?
+            }
+            _ => self.client.post_comment(pr, text).await?,
+        }
+        Ok(())
+    }
+
+    /// Post a status comment, e.g. about a started or successful build, to the given PR. All
+    /// comments that the bot posts on its own, without being asked by a command, are status
+    /// comments, unless they report a failure.
+    ///
+    /// The comment is skipped if the PR was muted with `@bors mute`. Failures must be reported with
+    /// [`RepositoryState::post_event_comment`] instead, so that they are posted even to muted PRs.
+    /// If the status log is enabled, the text is appended to the status log of the PR instead.
+    pub async fn post_status_comment(
+        &mut self,
+        db: &dyn DbClient,
+        pr: &PullRequestModel,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        if pr.muted {
+            tracing::info!(
+                "Skipping status comment on PR {}, because it is muted",
+                pr.number
+            );
+            return Ok(());
+        }
+        if self.config.status_log {
+            return self.append_to_status_log(db, pr, text).await;
+        }
+        self.post_comment(pr.number, text).await
+    }
+
+    /// Post a comment about an event of the given PR that has to be reported even if the PR is
+    /// muted, e.g. a failed build.
+    ///
+    /// If the status log is enabled, the text is appended to the status log of the PR instead.
+    pub async fn post_event_comment(
+        &mut self,
+        db: &dyn DbClient,
+        pr: &PullRequestModel,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        if self.config.status_log {
+            return self.append_to_status_log(db, pr, text).await;
+        }
+        self.post_comment(pr.number, text).await
+    }
+
+    /// Appends the text as a new line to the comment that logs the status events of the PR.
+    /// The comment is created by the first event, and it is edited in place by the later ones.
+    /// Once the comment cannot be edited or it would grow over the length limit of GitHub, a new
+    /// status log is started.
+    async fn append_to_status_log(
+        &mut self,
+        db: &dyn DbClient,
+        pr: &PullRequestModel,
+        text: &str,
+    ) -> anyhow::Result<()> {
+        // Further lines of the text are indented, so that they belong to the line of the event
+        let entry = format!("- {}", text.replace('\n', "\n  "));
+        let footer = match self.config.comment_footer.as_deref() {
+            Some(footer) if !footer.is_empty() => format!("\n\n{footer}"),
+            _ => String::new(),
+        };
+        if let Some(comment_id) = pr.status_comment_id {
+            match self.client.get_comment_text(comment_id).await {
+                Ok(body) => {
+                    let log = body.strip_suffix(footer.as_str()).unwrap_or(&body);
+                    let body = format!("{log}\n{entry}{footer}");
+                    if body.chars().count() > MAX_COMMENT_LENGTH {
+                        tracing::info!(
+                            "Status log of PR {} is full, starting a new one",
+                            pr.number
+                        );
+                    } else {
+                        match self.client.edit_comment(comment_id, &body).await {
+                            Ok(()) => return Ok(()),
+                            Err(error) => {
+                                tracing::warn!(
+                                    "Cannot update status log of PR {}, starting a new one: {error:?}",
+                                    pr.number
+                                );
+                            }
+                        }
+                    }
+                }
+                Err(error) => {
+                    tracing::warn!(
+                        "Cannot load status log of PR {}, starting a new one: {error:?}",
+                        pr.number
+                    );
+                }
+            }
+        }
+        let comment_id = self
+            .client
+            .create_comment(pr.number, &format!("{STATUS_LOG_HEADER}\n{entry}{footer}"))
+            .await?;
+        db.set_status_comment_id(pr, comment_id).await
+    }
+
+    /// Returns `true` if the rate-limit budget of the client allows performing non-urgent
+    /// operations. If the budget is not known, it is assumed to be sufficient.
+    pub fn has_rate_limit_budget(&self) -> bool {
+        match self.client.rate_limit() {
+            Some(budget) => budget.remaining >= self.config.rate_limit_reserve,
+            None => true,
+        }
+    }
+
+    /// Queries the rate-limit budget of the client and logs it. A budget that is already below the
+    /// configured reserve right after the repository is loaded suggests that the credentials are
+    /// misconfigured, or shared with another application, so it produces a warning.
+    pub async fn check_rate_limit(&mut self) {
+        match self.client.get_rate_limit().await {
+            Ok(budget) if budget.remaining < self.config.rate_limit_reserve => {
+                tracing::warn!(
+                    "Rate-limit budget of {} is already low: {}/{} requests remain. Is the token shared with another application?",
+                    self.repository,
+                    budget.remaining,
+                    budget.limit
+                );
+            }
+            Ok(budget) => {
+                tracing::info!(
+                    "Rate-limit budget of {}: {}/{} requests remain",
+                    self.repository,
+                    budget.remaining,
+                    budget.limit
+                );
+            }
+            Err(error) => {
+                tracing::error!(
+                    "Cannot query the rate limit of {}, are the credentials valid? {error:?}",
+                    self.repository
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::api::rate_limit::RateLimit;
+    use crate::tests::logs::capture_logs;
+    use crate::tests::state::ClientBuilder;
+
+    #[tokio::test]
+    async fn test_check_rate_limit_warns_about_low_budget() {
+        let mut repo = ClientBuilder::default().create();
+        repo.client.rate_limit = Some(RateLimit {
+            remaining: 10,
+            limit: 5000,
+        });
+        let logs = capture_logs();
+        repo.check_rate_limit().await;
+        let logs = logs.contents();
+        assert!(logs.contains("WARN"), "{logs}");
+        assert!(
+            logs.contains("already low: 10/5000 requests remain"),
+            "{logs}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_rate_limit_sufficient_budget() {
+        let mut repo = ClientBuilder::default().create();
+        repo.client.rate_limit = Some(RateLimit {
+            remaining: 4000,
+            limit: 5000,
+        });
+        let logs = capture_logs();
+        repo.check_rate_limit().await;
+        let logs = logs.contents();
+        assert!(!logs.contains("WARN"), "{logs}");
+        assert!(logs.contains("4000/5000 requests remain"), "{logs}");
+    }
 }
@@ -0,0 +1,87 @@
+//! Notifications about finished builds that are sent outside of GitHub, e.g. to a chat.
+use std::time::Duration;
+
+use axum::async_trait;
+
+use crate::database::BuildStatus;
+use crate::github::{GithubRepoName, PullRequestNumber};
+
+/// Payload sent to a notifier when a build of a PR completes.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+pub struct BuildCompletedEvent {
+    pub repository: String,
+    pub pr: u64,
+    pub branch: String,
+    pub commit_sha: String,
+    pub status: BuildCompletedStatus,
+}
+
+impl BuildCompletedEvent {
+    pub fn new(
+        repository: &GithubRepoName,
+        pr: PullRequestNumber,
+        branch: &str,
+        commit_sha: &str,
+        status: &BuildStatus,
+    ) -> Self {
+        Self {
+            repository: repository.to_string(),
+            pr: pr.0,
+            branch: branch.to_string(),
+            commit_sha: commit_sha.to_string(),
+            status: match status {
+                BuildStatus::Success => BuildCompletedStatus::Success,
+                _ => BuildCompletedStatus::Failure,
+            },
+        }
+    }
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildCompletedStatus {
+    Success,
+    Failure,
+}
+
+/// Receives notifications about completed builds.
+#[async_trait]
+pub trait Notifier {
+    async fn notify(&self, event: &BuildCompletedEvent) -> anyhow::Result<()>;
+}
+
+/// How long to wait for the webhook to respond, so that an unresponsive webhook does not stall
+/// the handling of the build.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends notifications as JSON to a webhook URL.
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(WEBHOOK_TIMEOUT)
+                .build()
+                .expect("Cannot create webhook client"),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &BuildCompletedEvent) -> anyhow::Result<()> {
+        self.client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(event)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// How many comments are remembered at most.
+const CAPACITY: usize = 1000;
+
+/// Remembers which comments have been processed and with which text, so that a comment is not
+/// processed again when GitHub delivers it repeatedly, e.g. as an edit that has not changed its
+/// text.
+///
+/// Only the most recently processed comments are remembered, to bound the memory usage.
+#[derive(Default)]
+pub struct ProcessedComments {
+    /// Hash of the last processed text of each comment.
+    hashes: HashMap<u64, u64>,
+    /// IDs of the comments, from the least recently processed.
+    order: VecDeque<u64>,
+}
+
+impl ProcessedComments {
+    /// Has the comment with the given ID already been processed with the given text?
+    pub fn contains(&self, comment_id: u64, text: &str) -> bool {
+        self.hashes.get(&comment_id) == Some(&hash_text(text))
+    }
+
+    /// Records that the comment with the given ID has been processed with the given text.
+    pub fn record(&mut self, comment_id: u64, text: &str) {
+        let hash = hash_text(text);
+        if self.hashes.insert(comment_id, hash).is_some() {
+            self.order.retain(|id| *id != comment_id);
+        }
+        self.order.push_back(comment_id);
+        if self.order.len() > CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+    }
+}
+
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bors::processed_comments::{ProcessedComments, CAPACITY};
+
+    #[test]
+    fn record_changed_text() {
+        let mut comments = ProcessedComments::default();
+        assert!(!comments.contains(1, "@bors r+"));
+        comments.record(1, "@bors r+");
+        assert!(comments.contains(1, "@bors r+"));
+        assert!(!comments.contains(1, "@bors r+ thanks"));
+        assert!(!comments.contains(2, "@bors r+"));
+    }
+
+    #[test]
+    fn forget_oldest_comments() {
+        let mut comments = ProcessedComments::default();
+        for id in 0..=CAPACITY as u64 {
+            comments.record(id, "text");
+        }
+        assert!(!comments.contains(0, "text"));
+        assert!(comments.contains(CAPACITY as u64, "text"));
+    }
+}
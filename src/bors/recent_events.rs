@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Utc};
+
+/// How many events are remembered at most.
+const CAPACITY: usize = 50;
+
+/// An inbound event of a repository, as received from GitHub.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecentEvent {
+    /// Type of the event, e.g. `comment`.
+    pub kind: &'static str,
+    /// What the event is about, e.g. `PR #1` or a branch.
+    pub target: String,
+    pub received_at: DateTime<Utc>,
+}
+
+/// Remembers the most recently received events of a repository, so that maintainers can check
+/// whether the bot has received a webhook, e.g. with `@bors recent`.
+///
+/// The events are only kept in memory, so they are lost when the bot is restarted.
+#[derive(Default)]
+pub struct RecentEvents {
+    /// Events from the least recently received one.
+    events: VecDeque<RecentEvent>,
+}
+
+impl RecentEvents {
+    pub fn record(&mut self, kind: &'static str, target: String, received_at: DateTime<Utc>) {
+        self.events.push_back(RecentEvent {
+            kind,
+            target,
+            received_at,
+        });
+        if self.events.len() > CAPACITY {
+            self.events.pop_front();
+        }
+    }
+
+    /// Returns at most `count` events, from the most recently received one.
+    pub fn latest(&self, count: usize) -> impl Iterator<Item = &RecentEvent> {
+        self.events.iter().rev().take(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::bors::recent_events::{RecentEvents, CAPACITY};
+
+    #[test]
+    fn latest_events_first() {
+        let mut events = RecentEvents::default();
+        for index in 0..CAPACITY + 5 {
+            events.record("comment", format!("PR #{index}"), Utc::now());
+        }
+        let latest = events
+            .latest(2)
+            .map(|event| event.target.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(latest, vec!["PR #54", "PR #53"]);
+        assert_eq!(events.latest(usize::MAX).count(), CAPACITY);
+    }
+}
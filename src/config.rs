@@ -5,6 +5,7 @@ use serde::de::Error;
 use serde::{Deserialize, Deserializer};
 
 use crate::github::{LabelModification, LabelTrigger};
+use crate::permissions::PermissionType;
 
 pub const CONFIG_FILE_PATH: &str = "rust-bors.toml";
 
@@ -19,12 +20,206 @@ pub struct RepositoryConfig {
     pub timeout: Duration,
     #[serde(default, deserialize_with = "deserialize_labels")]
     pub labels: HashMap<LabelTrigger, Vec<LabelModification>>,
+    /// How many distinct users have to approve a PR before it enters the merge queue.
+    #[serde(default = "default_required_approvals")]
+    pub required_approvals: usize,
+    /// Execute commands found in the description of a PR when it is opened.
+    #[serde(default)]
+    pub commands_in_pr_description: bool,
+    /// Text appended to every comment posted by the bot, e.g. to distinguish multiple bot
+    /// instances.
+    #[serde(default)]
+    pub comment_footer: Option<String>,
+    /// PRs whose title or head commit message contains this marker will not be built or merged.
+    #[serde(
+        default = "default_skip_marker",
+        deserialize_with = "deserialize_skip_marker"
+    )]
+    pub skip_marker: String,
+    /// Suggest a rollup once at least this many `rollup=maybe` PRs are waiting in the merge queue.
+    /// If not set, rollups are never suggested.
+    #[serde(default)]
+    pub rollup_suggestion_threshold: Option<usize>,
+    /// How many approved PRs can be in the merge queue at the same time. PRs approved when the
+    /// queue is full wait until some space frees up. If not set, the queue size is unlimited.
+    #[serde(default)]
+    pub max_queue_size: Option<usize>,
+    /// URL that receives a JSON notification whenever a build completes.
+    #[serde(default)]
+    pub notification_webhook: Option<String>,
+    /// After this many try builds or this many merge builds of a PR fail in a row, the PR is
+    /// unapproved and its builds cannot be retried until a build succeeds again. The failures of
+    /// try builds and merge builds are counted separately. Set to 0 to never stop the builds.
+    #[serde(default = "default_max_consecutive_failures")]
+    pub max_consecutive_failures: usize,
+    /// Request a new review from the approvers of a PR when its approval is dismissed because of
+    /// a new push.
+    #[serde(default)]
+    pub rerequest_review_on_dismissal: bool,
+    /// For how long are executed commands kept in the audit log.
+    #[serde(
+        default = "default_audit_log_retention",
+        deserialize_with = "deserialize_duration_from_secs"
+    )]
+    pub audit_log_retention: Duration,
+    /// Non-urgent operations, e.g. rollup suggestions, are deferred while fewer than this many
+    /// GitHub API requests remain in the current rate-limit window.
+    #[serde(default = "default_rate_limit_reserve")]
+    pub rate_limit_reserve: u64,
+    /// Logins of bots, e.g. `dependabot[bot]`, whose commands are ignored.
+    #[serde(default)]
+    pub ignored_bots: Vec<String>,
+    /// Which CI reporting system determines the result of builds.
+    #[serde(default)]
+    pub build_status_source: BuildStatusSource,
+    /// Permissions required to execute commands, overriding the default permission of each
+    /// command. Besides the `review` and `try` permissions of bors, a command can require `write`
+    /// or `admin` access to the repository on GitHub.
+    #[serde(default)]
+    pub command_permissions: HashMap<PermissionedCommand, PermissionType>,
+    /// When an approved PR is at least this many commits behind its base branch, the PR author is
+    /// advised to rebase it. If not set, the advice is never given.
+    #[serde(default)]
+    pub rebase_notice_threshold: Option<u64>,
+    /// Approved PRs with this label stay in the merge queue, but they are not merged until the
+    /// label is removed.
+    #[serde(default = "default_do_not_merge_label")]
+    pub do_not_merge_label: String,
+    /// Comment posted when the author of a command lacks the permission to execute it.
+    /// `{user}` is replaced by the author of the command and `{permission}` by the name of the
+    /// missing permission, e.g. `review`.
+    #[serde(default = "default_insufficient_privileges_message")]
+    pub insufficient_privileges_message: String,
+    /// Template of the commit message of merges performed by the merge queue. `{title}`,
+    /// `{number}`, `{approver}` and `{body}` are replaced by the title of the PR, its number, its
+    /// approvers and its description. If not set, the default message of bors is used.
+    #[serde(default, deserialize_with = "deserialize_merge_commit_message")]
+    pub merge_commit_message: Option<String>,
+    /// Rotate merge builds across the authors of PRs with the same priority, so that an author
+    /// with many approved PRs cannot hold up the PRs of other authors.
+    #[serde(default)]
+    pub round_robin_authors: bool,
+    /// How long to wait after all checks of a build have finished, so that checks that start late
+    /// are taken into account before the build is completed. If zero, the build is completed
+    /// right away.
+    #[serde(default, deserialize_with = "deserialize_duration_from_secs")]
+    pub settle_delay: Duration,
+    /// How long an approval stays valid. Approved PRs that have not been merged within this
+    /// duration are removed from the merge queue and have to be approved again. If not set,
+    /// approvals never expire.
+    #[serde(default, deserialize_with = "deserialize_optional_duration_from_secs")]
+    pub approval_expiry: Option<Duration>,
+    /// GitHub environment to which try builds are deployed. If set, each try build creates a
+    /// deployment of its commit, whose status follows the status of the build.
+    #[serde(default)]
+    pub try_deployment_environment: Option<String>,
+    /// Names or numeric IDs of the workflows that determine the result of builds. Builds wait until
+    /// each of them has completed. Other GitHub Actions workflows are ignored, and their check
+    /// suites do not affect the builds, check suites of other CI apps are still checked. If empty,
+    /// all workflows are observed.
+    #[serde(default)]
+    pub observed_workflows: Vec<String>,
+    /// Names of checks that are known to be flaky. If only these checks fail in a build, they are
+    /// retried once automatically before the failure is reported.
+    #[serde(default)]
+    pub flaky_checks: Vec<String>,
+    /// Log the status events of PRs (approvals, started and finished builds) as lines of a single
+    /// comment per PR, which is edited in place, instead of posting a new comment for each event.
+    #[serde(default)]
+    pub status_log: bool,
+    /// Post a comment with the merged commit to the PR after it has been merged.
+    #[serde(default)]
+    pub merged_comment: bool,
+    /// Close the PR after it has been merged, if GitHub has not closed it by itself.
+    #[serde(default)]
+    pub close_merged_prs: bool,
+    /// Globs of paths, e.g. `docs/**`, that do not need to be tested. If a PR only changes files
+    /// matching them, its try and merge builds are marked as successful without running CI.
+    #[serde(default)]
+    pub skip_ci_paths: Vec<String>,
+    /// How long a single command may run. Commands that take longer, e.g. because GitHub does not
+    /// respond, are aborted.
+    #[serde(
+        default = "default_command_timeout",
+        deserialize_with = "deserialize_duration_from_secs"
+    )]
+    pub command_timeout: Duration,
+    /// Branches that run the merge builds of the given base branches, e.g.
+    /// `release = "automation/bors/auto-release"`. Base branches that are not listed share the
+    /// `automation/bors/auto` branch, and only one of their merge builds runs at a time. Base
+    /// branches with different auto branches have independent merge queues.
+    #[serde(default, deserialize_with = "deserialize_auto_branches")]
+    pub auto_branches: HashMap<String, String>,
+}
+
+/// Placeholders that can be used in the `merge_commit_message` template.
+const MERGE_COMMIT_MESSAGE_PLACEHOLDERS: &[&str] = &["title", "number", "approver", "body"];
+
+/// Command whose required permission can be configured.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PermissionedCommand {
+    Approve,
+    Try,
+    TryCancel,
+    Retry,
+    Sync,
+    SetBase,
+    SetPriority,
+    Tree,
+    Recent,
+    Clean,
+}
+
+/// CI reporting system that determines whether a build has succeeded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildStatusSource {
+    /// Check suites of GitHub Actions and of external CI apps.
+    #[default]
+    CheckSuites,
+    /// Legacy commit statuses.
+    Statuses,
+    /// Both check suites and legacy commit statuses have to succeed.
+    Both,
 }
 
 fn default_timeout() -> Duration {
     Duration::from_secs(3600)
 }
 
+fn default_command_timeout() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_required_approvals() -> usize {
+    1
+}
+
+fn default_skip_marker() -> String {
+    "[bors skip]".to_string()
+}
+
+fn default_do_not_merge_label() -> String {
+    "S-do-not-merge".to_string()
+}
+
+fn default_insufficient_privileges_message() -> String {
+    "@{user}: :key: Insufficient privileges: not in {permission} users".to_string()
+}
+
+pub(crate) fn default_audit_log_retention() -> Duration {
+    Duration::from_secs(90 * 24 * 3600)
+}
+
+fn default_max_consecutive_failures() -> usize {
+    3
+}
+
+fn default_rate_limit_reserve() -> u64 {
+    500
+}
+
 fn deserialize_duration_from_secs<'de, D>(deserializer: D) -> Result<Duration, D::Error>
 where
     D: Deserializer<'de>,
@@ -33,6 +228,78 @@ where
     Ok(Duration::from_secs(seconds))
 }
 
+fn deserialize_optional_duration_from_secs<'de, D>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let seconds = Option::<u64>::deserialize(deserializer)?;
+    Ok(seconds.map(Duration::from_secs))
+}
+
+fn deserialize_merge_commit_message<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let template = String::deserialize(deserializer)?;
+    let mut rest = template.as_str();
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(Error::custom(
+                "Unclosed placeholder in merge commit message",
+            ));
+        };
+        let placeholder = &rest[start + 1..start + end];
+        if !MERGE_COMMIT_MESSAGE_PLACEHOLDERS.contains(&placeholder) {
+            return Err(Error::custom(format!(
+                "Unknown placeholder `{{{placeholder}}}` in merge commit message, use one of {}",
+                MERGE_COMMIT_MESSAGE_PLACEHOLDERS
+                    .iter()
+                    .map(|placeholder| format!("`{{{placeholder}}}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        rest = &rest[start + end + 1..];
+    }
+    Ok(Some(template))
+}
+
+fn deserialize_skip_marker<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let marker = String::deserialize(deserializer)?;
+    // An empty marker is contained in every title, so it would block all builds
+    if marker.trim().is_empty() {
+        return Err(Error::custom("Skip marker cannot be empty"));
+    }
+    Ok(marker)
+}
+
+/// Working branches of the bot that cannot be used to run merge builds.
+const RESERVED_BRANCH_NAMES: &[&str] = &[
+    "automation/bors/auto-merge",
+    "automation/bors/try",
+    "automation/bors/try-merge",
+];
+
+fn deserialize_auto_branches<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let branches = HashMap::<String, String>::deserialize(deserializer)?;
+    for (base, branch) in &branches {
+        if branch.is_empty() || RESERVED_BRANCH_NAMES.contains(&branch.as_str()) || branch == base {
+            return Err(Error::custom(format!(
+                "Branch `{branch}` cannot run the merge builds of `{base}`"
+            )));
+        }
+    }
+    Ok(branches)
+}
+
 fn deserialize_labels<'de, D>(
     deserializer: D,
 ) -> Result<HashMap<LabelTrigger, Vec<LabelModification>>, D::Error>
@@ -107,15 +374,54 @@ where
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeMap;
+    use std::collections::{BTreeMap, HashMap};
+    use std::time::Duration;
 
-    use crate::config::{default_timeout, RepositoryConfig};
+    use crate::config::{
+        default_audit_log_retention, default_command_timeout, default_do_not_merge_label,
+        default_insufficient_privileges_message, default_max_consecutive_failures,
+        default_rate_limit_reserve, default_required_approvals, default_skip_marker,
+        default_timeout, BuildStatusSource, PermissionedCommand, RepositoryConfig,
+    };
+    use crate::permissions::PermissionType;
 
     #[test]
     fn deserialize_empty() {
         let content = "";
         let config = load_config(content);
         assert_eq!(config.timeout, default_timeout());
+        assert_eq!(config.required_approvals, default_required_approvals());
+        assert!(!config.commands_in_pr_description);
+        assert_eq!(config.comment_footer, None);
+        assert_eq!(config.skip_marker, default_skip_marker());
+        assert_eq!(config.rollup_suggestion_threshold, None);
+        assert_eq!(config.approval_expiry, None);
+        assert!(config.observed_workflows.is_empty());
+        assert!(config.flaky_checks.is_empty());
+        assert!(!config.status_log);
+        assert!(!config.merged_comment);
+        assert!(!config.close_merged_prs);
+        assert!(config.skip_ci_paths.is_empty());
+        assert_eq!(config.command_timeout, default_command_timeout());
+        assert!(config.auto_branches.is_empty());
+        assert_eq!(config.max_queue_size, None);
+        assert_eq!(config.notification_webhook, None);
+        assert_eq!(
+            config.max_consecutive_failures,
+            default_max_consecutive_failures()
+        );
+        assert!(!config.rerequest_review_on_dismissal);
+        assert_eq!(config.audit_log_retention, default_audit_log_retention());
+        assert_eq!(config.rate_limit_reserve, default_rate_limit_reserve());
+        assert!(config.ignored_bots.is_empty());
+        assert_eq!(config.build_status_source, BuildStatusSource::CheckSuites);
+        assert!(config.command_permissions.is_empty());
+        assert_eq!(config.do_not_merge_label, default_do_not_merge_label());
+        assert_eq!(config.rebase_notice_threshold, None);
+        assert_eq!(
+            config.insufficient_privileges_message,
+            default_insufficient_privileges_message()
+        );
     }
 
     #[test]
@@ -125,6 +431,207 @@ mod tests {
         assert_eq!(config.timeout.as_secs(), 3600);
     }
 
+    #[test]
+    fn deserialize_required_approvals() {
+        let content = "required_approvals = 2";
+        let config = load_config(content);
+        assert_eq!(config.required_approvals, 2);
+    }
+
+    #[test]
+    fn deserialize_build_status_source() {
+        let config = load_config(r#"build_status_source = "statuses""#);
+        assert_eq!(config.build_status_source, BuildStatusSource::Statuses);
+    }
+
+    #[test]
+    fn deserialize_command_permissions() {
+        let content = r#"[command_permissions]
+try = "write"
+approve = "admin"
+set_base = "try"
+"#;
+        let config = load_config(content);
+        assert_eq!(
+            config.command_permissions,
+            HashMap::from([
+                (PermissionedCommand::Try, PermissionType::Write),
+                (PermissionedCommand::Approve, PermissionType::Admin),
+                (PermissionedCommand::SetBase, PermissionType::Try),
+            ])
+        );
+    }
+
+    #[test]
+    fn deserialize_commands_in_pr_description() {
+        let content = "commands_in_pr_description = true";
+        let config = load_config(content);
+        assert!(config.commands_in_pr_description);
+    }
+
+    #[test]
+    fn deserialize_comment_footer() {
+        let content = r#"comment_footer = "— bors (staging)""#;
+        let config = load_config(content);
+        assert_eq!(config.comment_footer.as_deref(), Some("— bors (staging)"));
+    }
+
+    #[test]
+    fn deserialize_do_not_merge_label() {
+        let content = r#"do_not_merge_label = "blocked""#;
+        let config = load_config(content);
+        assert_eq!(config.do_not_merge_label, "blocked");
+    }
+
+    #[test]
+    fn deserialize_insufficient_privileges_message() {
+        let content = r#"insufficient_privileges_message = "Sorry @{user}, see CONTRIBUTING.md""#;
+        let config = load_config(content);
+        assert_eq!(
+            config.insufficient_privileges_message,
+            "Sorry @{user}, see CONTRIBUTING.md"
+        );
+    }
+
+    #[test]
+    fn deserialize_merge_commit_message() {
+        let content = r#"merge_commit_message = "{title} (#{number})\n\nReviewed-by: {approver}""#;
+        let config = load_config(content);
+        assert_eq!(
+            config.merge_commit_message.as_deref(),
+            Some("{title} (#{number})\n\nReviewed-by: {approver}")
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown placeholder `{author}` in merge commit message")]
+    fn deserialize_merge_commit_message_unknown_placeholder() {
+        load_config(r#"merge_commit_message = "{title} by {author}""#);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unclosed placeholder in merge commit message")]
+    fn deserialize_merge_commit_message_unclosed_placeholder() {
+        load_config(r#"merge_commit_message = "{title""#);
+    }
+
+    #[test]
+    fn deserialize_status_log() {
+        let config = load_config("status_log = true");
+        assert!(config.status_log);
+    }
+
+    #[test]
+    fn deserialize_merged_comment_and_close() {
+        let config = load_config("merged_comment = true\nclose_merged_prs = true");
+        assert!(config.merged_comment);
+        assert!(config.close_merged_prs);
+    }
+
+    #[test]
+    fn deserialize_skip_ci_paths() {
+        let config = load_config(r#"skip_ci_paths = ["docs/**", "*.md"]"#);
+        assert_eq!(config.skip_ci_paths, vec!["docs/**", "*.md"]);
+    }
+
+    #[test]
+    fn deserialize_command_timeout() {
+        let config = load_config("command_timeout = 60");
+        assert_eq!(config.command_timeout, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn deserialize_auto_branches() {
+        let config = load_config(
+            r#"[auto_branches]
+release = "automation/bors/auto-release""#,
+        );
+        assert_eq!(
+            config.auto_branches,
+            HashMap::from([(
+                "release".to_string(),
+                "automation/bors/auto-release".to_string()
+            )])
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "Branch `automation/bors/auto-merge` cannot run the merge builds of `merge`"
+    )]
+    fn deserialize_auto_branches_reserved_name() {
+        load_config(
+            r#"[auto_branches]
+merge = "automation/bors/auto-merge""#,
+        );
+    }
+
+    #[test]
+    fn deserialize_flaky_checks() {
+        let config = load_config(r#"flaky_checks = ["Network tests"]"#);
+        assert_eq!(config.flaky_checks, vec!["Network tests"]);
+    }
+
+    #[test]
+    fn deserialize_observed_workflows() {
+        let config = load_config(r#"observed_workflows = ["CI", "Lint"]"#);
+        assert_eq!(config.observed_workflows, vec!["CI", "Lint"]);
+    }
+
+    #[test]
+    fn deserialize_try_deployment_environment() {
+        let config = load_config(r#"try_deployment_environment = "staging""#);
+        assert_eq!(
+            config.try_deployment_environment,
+            Some("staging".to_string())
+        );
+    }
+
+    #[test]
+    fn deserialize_approval_expiry() {
+        let config = load_config("approval_expiry = 86400");
+        assert_eq!(config.approval_expiry, Some(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn deserialize_settle_delay() {
+        let config = load_config("settle_delay = 30");
+        assert_eq!(config.settle_delay, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn deserialize_round_robin_authors() {
+        let config = load_config("round_robin_authors = true");
+        assert!(config.round_robin_authors);
+    }
+
+    #[test]
+    fn deserialize_skip_marker() {
+        let content = r#"skip_marker = "[no merge]""#;
+        let config = load_config(content);
+        assert_eq!(config.skip_marker, "[no merge]");
+    }
+
+    #[test]
+    #[should_panic(expected = "Skip marker cannot be empty")]
+    fn deserialize_empty_skip_marker() {
+        load_config(r#"skip_marker = """#);
+    }
+
+    #[test]
+    fn deserialize_rollup_suggestion_threshold() {
+        let content = "rollup_suggestion_threshold = 3";
+        let config = load_config(content);
+        assert_eq!(config.rollup_suggestion_threshold, Some(3));
+    }
+
+    #[test]
+    fn deserialize_max_queue_size() {
+        let content = "max_queue_size = 10";
+        let config = load_config(content);
+        assert_eq!(config.max_queue_size, Some(10));
+    }
+
     #[test]
     fn deserialize_labels() {
         let content = r#"[labels]
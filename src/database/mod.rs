@@ -4,8 +4,11 @@
 //!
 //! That is why the database model types are explicitly specified here, instead of just using the
 //! ORM (currently SeaORM) types.
+use std::sync::Arc;
+
 use axum::async_trait;
 use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
 use octocrab::models::RunId;
 
 pub use sea_orm_client::SeaORMClient;
@@ -32,6 +35,19 @@ pub enum BuildStatus {
     Timeouted,
 }
 
+/// Aggregate status of a build, as reported to GitHub in the bors check run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckRunStatus {
+    /// Some workflows of the build are still running.
+    InProgress,
+    /// The build has succeeded.
+    Success,
+    /// The build has failed.
+    Failure,
+    /// The build has been cancelled, either by a user or because it has timed out.
+    Cancelled,
+}
+
 /// Represents a single (merged) commit.
 pub struct BuildModel {
     pub id: PrimaryKey,
@@ -40,6 +56,24 @@ pub struct BuildModel {
     pub commit_sha: String,
     pub status: BuildStatus,
     pub created_at: DateTime<Utc>,
+    /// Name of the user that has started the build.
+    pub triggered_by: Option<String>,
+    /// When has the build received its final status.
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Status of the build that was last reported to GitHub in the bors check run, if any.
+    pub check_run_status: Option<CheckRunStatus>,
+    /// When have all checks of the build finished, if the build waits for checks that start late
+    /// before it is completed.
+    pub checks_finished_at: Option<DateTime<Utc>>,
+    /// GitHub deployment that reports the status of the build, if any.
+    pub deployment_id: Option<u64>,
+    /// Status of the build that was last reported in its GitHub deployment, if any.
+    pub deployment_status: Option<CheckRunStatus>,
+    /// How many times were the failed checks of the build retried automatically.
+    pub auto_retries: i32,
+    /// Workflows that were selected to run in the build, e.g. with `@bors try workflows=<names>`.
+    /// If it is empty, all workflows run.
+    pub selected_workflows: Vec<String>,
 }
 
 /// Represents a pull request.
@@ -49,18 +83,191 @@ pub struct PullRequestModel {
     pub number: PullRequestNumber,
     pub try_build: Option<BuildModel>,
     pub created_at: DateTime<Utc>,
+    /// Branch that bors merges the PR into, if it was overridden with `@bors set-base`.
+    /// If it is `None`, the base branch of the PR on GitHub is used.
+    pub base_branch: Option<String>,
+    /// Approved commit of the PR, if the PR is waiting in the merge queue.
+    pub approved_sha: Option<String>,
+    /// The last merge build of the approved commit, if any.
+    pub auto_build: Option<BuildModel>,
+    /// PRs with a higher priority are merged first.
+    pub priority: i32,
+    pub rollup: Option<RollupMode>,
+    /// Title of the PR at the time it was approved.
+    pub title: Option<String>,
+    /// When has the PR entered the merge queue.
+    pub approved_at: Option<DateTime<Utc>>,
+    /// The PR is approved, but it waits until there is space in the merge queue.
+    pub parked: bool,
+    /// The PR author was already told that the tree is closed, since the tree was last closed.
+    pub tree_closed_notified: bool,
+    /// How many try builds of the PR have failed in a row since its last successful try build.
+    pub consecutive_failures: i32,
+    /// How many merge builds of the PR have failed in a row since its last successful merge build.
+    pub consecutive_auto_failures: i32,
+    /// The approved PR does not enter the active merge queue before this time.
+    pub not_before: Option<DateTime<Utc>>,
+    /// Head commit of the PR for which the PR author was told that the PR is far behind its base.
+    pub rebase_notified_sha: Option<String>,
+    /// PRs batched by this rollup PR, which are merged together with it.
+    pub rollup_members: Vec<PullRequestNumber>,
+    /// When was the PR merged by bors, either on its own or as a member of a rollup.
+    pub merged_at: Option<DateTime<Utc>>,
+    /// How often are the requested reviewers reminded about the PR, if the PR author has asked
+    /// for it.
+    pub nag_interval: Option<std::time::Duration>,
+    /// When were the reviewers last reminded about the PR, or when were the reminders enabled.
+    pub last_nag_at: Option<DateTime<Utc>>,
+    /// Status comments of the bot are not posted to the PR.
+    pub muted: bool,
+    /// Author of the PR at the time it was approved.
+    pub author: Option<String>,
+    /// Comment of the bot that logs the status events of the PR, if the status log is enabled.
+    pub status_comment_id: Option<u64>,
+}
+
+/// Describes whether a PR can be merged together with other PRs in a rollup.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RollupMode {
+    /// The PR should always be included in a rollup.
+    Always,
+    /// The PR can be included in a rollup.
+    Maybe,
+    /// The PR can be included in a rollup, but it might cause problems.
+    Iffy,
+    /// The PR must be merged on its own.
+    Never,
+}
+
+/// Describes whether the PRs of a repository are merged.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TreeState {
+    #[default]
+    Open,
+    Closed {
+        /// PRs with at least this priority are still merged. If not set, no PRs are merged.
+        priority: Option<i32>,
+        /// Why the tree was closed.
+        reason: Option<String>,
+        /// When is the tree opened again automatically, if it was scheduled.
+        reopen_at: Option<DateTime<Utc>>,
+    },
+}
+
+impl TreeState {
+    pub fn is_open(&self) -> bool {
+        matches!(self, TreeState::Open)
+    }
+
+    /// Returns `true` if a PR with the given priority can be merged.
+    pub fn allows(&self, priority: i32) -> bool {
+        match self {
+            TreeState::Open => true,
+            TreeState::Closed {
+                priority: Some(threshold),
+                ..
+            } => priority >= *threshold,
+            TreeState::Closed { priority: None, .. } => false,
+        }
+    }
+}
+
+/// Represents an approval of a pull request by a single user.
+pub struct ApprovalModel {
+    pub id: PrimaryKey,
+    pub approver: String,
+    /// Commit that was approved.
+    pub commit_sha: String,
+    pub created_at: DateTime<Utc>,
+    /// User who has given the approval. It is not known for approvals recorded before it was
+    /// tracked.
+    pub author: Option<String>,
+    /// Team on whose behalf the approval was given, e.g. with `@bors r=team:<name>`.
+    pub team: Option<String>,
+}
+
+impl ApprovalModel {
+    /// Returns the name under which the approval is shown, approvals on behalf of a team are
+    /// attributed to the team.
+    pub fn reviewer(&self) -> &str {
+        self.team.as_deref().unwrap_or(&self.approver)
+    }
+}
+
+/// Represents an event whose handling has failed.
+pub struct FailedEventModel {
+    pub id: PrimaryKey,
+    pub repository: String,
+    /// The event, serialized to JSON.
+    pub event: String,
+    /// Summary of the error that has stopped the handling of the event.
+    pub error: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Destructive command that waits until its author confirms it with `@bors confirm <token>`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfirmationAction {
+    /// Delete the bors branches that are not used by any running build.
+    Clean,
+    /// Change the branch that an approved PR is merged into.
+    SetBase(String),
+}
+
+/// Represents a destructive command that waits for a confirmation.
+pub struct ConfirmationModel {
+    pub id: PrimaryKey,
+    pub repository: String,
+    pub pr_number: PullRequestNumber,
+    /// Token that has to be passed to `@bors confirm`.
+    pub token: String,
+    pub action: ConfirmationAction,
+    /// User that has requested the command. Only they can confirm it.
+    pub author: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Represents a merge group of the native GitHub merge queue that is observed by bors.
+pub struct MergeGroupModel {
+    pub id: PrimaryKey,
+    pub repository: String,
+    /// PR that is tested by the merge group.
+    pub pr_number: PullRequestNumber,
+    /// Head branch of the merge group, e.g. `gh-readonly-queue/main/pr-1-<base-sha>`.
+    pub branch: String,
+    pub commit_sha: CommitSha,
+    pub status: BuildStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which permissions were delegated to a user.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DelegationScope {
+    /// The user can perform try builds.
+    Try,
+    /// The user can approve the PR.
+    Review,
+}
+
+/// Represents a delegation of permissions on a pull request to a single user.
+pub struct DelegationModel {
+    pub id: PrimaryKey,
+    pub delegatee: String,
+    pub scope: DelegationScope,
+    pub created_at: DateTime<Utc>,
 }
 
 /// Describes whether a workflow is a Github Actions workflow or if it's a job from some external
 /// CI.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum WorkflowType {
     Github,
     External,
 }
 
 /// Status of a workflow.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum WorkflowStatus {
     /// Workflow is running.
     Pending,
@@ -77,14 +284,35 @@ pub struct WorkflowModel {
     pub name: String,
     pub url: String,
     pub run_id: RunId,
+    /// ID of the GitHub Actions workflow that the run belongs to. External checks do not have one.
+    pub workflow_id: Option<u64>,
     pub workflow_type: WorkflowType,
     pub status: WorkflowStatus,
     pub created_at: DateTime<Utc>,
 }
 
+/// Operation executed by [`DbClient::transaction`], which receives a handle to the transaction.
+pub type TransactionOperation<'a> = Box<
+    dyn FnOnce(Arc<dyn DbClient + Send + Sync>) -> BoxFuture<'a, anyhow::Result<()>> + Send + 'a,
+>;
+
 /// Provides access to a database.
 #[async_trait]
 pub trait DbClient {
+    /// Applies all pending schema migrations, in order.
+    async fn run_migrations(&self) -> anyhow::Result<()>;
+
+    /// Returns the name of the last applied schema migration, or `None` if no migration has been
+    /// applied yet.
+    async fn get_schema_version(&self) -> anyhow::Result<Option<String>>;
+
+    /// Executes `operation` in a database transaction, so that either all or none of its writes
+    /// are applied. The transaction is committed if the operation succeeds and rolled back if it
+    /// fails, in which case its error is returned.
+    ///
+    /// The operation must only access the database through the handle that it receives.
+    async fn transaction<'a>(&'a self, operation: TransactionOperation<'a>) -> anyhow::Result<()>;
+
     /// Finds a Pull request row for the given repository and PR number.
     /// If it doesn't exist, a new row is created.
     async fn get_or_create_pull_request(
@@ -99,14 +327,313 @@ pub trait DbClient {
         build: &BuildModel,
     ) -> anyhow::Result<Option<PullRequestModel>>;
 
-    /// Attaches an existing build to the given PR.
+    /// Records an approval of the given PR by `approver` at the given commit, optionally on behalf
+    /// of `team`. If the user has already approved the PR before, the approval is updated to the
+    /// new commit.
+    async fn approve(
+        &self,
+        pr: &PullRequestModel,
+        approver: &str,
+        team: Option<&str>,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<()>;
+
+    /// Returns all approvals of the given PR.
+    async fn get_approvals(&self, pr: &PullRequestModel) -> anyhow::Result<Vec<ApprovalModel>>;
+
+    /// Delegates permissions with the given `scope` on the given PR to `delegatee`.
+    async fn delegate(
+        &self,
+        pr: &PullRequestModel,
+        delegatee: &str,
+        scope: DelegationScope,
+    ) -> anyhow::Result<()>;
+
+    /// Removes the delegation with the given `scope` of `delegatee` on the given PR, or all
+    /// delegations of `delegatee` if no scope is specified.
+    async fn undelegate(
+        &self,
+        pr: &PullRequestModel,
+        delegatee: &str,
+        scope: Option<DelegationScope>,
+    ) -> anyhow::Result<()>;
+
+    /// Returns all delegations of the given PR.
+    async fn get_delegations(&self, pr: &PullRequestModel) -> anyhow::Result<Vec<DelegationModel>>;
+
+    /// Records that `author` has executed `command` on the given PR.
+    async fn record_command(
+        &self,
+        pr: &PullRequestModel,
+        author: &str,
+        command: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Removes audit log entries of PRs of the repository that were recorded before `before`.
+    /// Returns the number of removed entries.
+    async fn prune_audit_log(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64>;
+
+    /// Stores an event of the repository whose handling has failed with `error`, so that it can
+    /// be inspected and replayed later. The event is serialized to JSON.
+    async fn record_failed_event(
+        &self,
+        repo: &GithubRepoName,
+        event: &str,
+        error: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Returns the last `limit` failed events of the repository, starting with the most recent
+    /// one.
+    async fn get_failed_events(
+        &self,
+        repo: &GithubRepoName,
+        limit: u64,
+    ) -> anyhow::Result<Vec<FailedEventModel>>;
+
+    /// Finds a failed event by its ID.
+    async fn find_failed_event(&self, id: PrimaryKey) -> anyhow::Result<Option<FailedEventModel>>;
+
+    /// Removes the failed events of the given repository that were recorded before `before`.
+    /// Returns the number of removed events.
+    async fn prune_failed_events(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64>;
+
+    /// Stores a command requested by `author` on the given PR, which waits until it is confirmed
+    /// with `token`.
+    async fn create_confirmation(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        token: &str,
+        action: ConfirmationAction,
+        author: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Finds the command of the given PR that waits for a confirmation with `token`.
+    async fn find_confirmation(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        token: &str,
+    ) -> anyhow::Result<Option<ConfirmationModel>>;
+
+    /// Removes a command that waits for a confirmation, e.g. because it was performed.
+    async fn delete_confirmation(&self, confirmation: &ConfirmationModel) -> anyhow::Result<()>;
+
+    /// Removes the commands of the given repository that were requested before `before`, so they
+    /// cannot be confirmed anymore. Returns the number of removed commands.
+    async fn prune_confirmations(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64>;
+
+    /// Starts observing a merge group of the native GitHub merge queue that tests the given PR.
+    async fn create_merge_group(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        branch: &str,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<()>;
+
+    /// Finds the observed merge group of the given repository with the given head commit.
+    async fn find_merge_group(
+        &self,
+        repo: &GithubRepoName,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<Option<MergeGroupModel>>;
+
+    /// Updates the status of an observed merge group.
+    async fn update_merge_group_status(
+        &self,
+        group: &MergeGroupModel,
+        status: BuildStatus,
+    ) -> anyhow::Result<()>;
+
+    /// Sets the branch that bors merges the given PR into.
+    async fn set_base_branch(&self, pr: &PullRequestModel, branch: &str) -> anyhow::Result<()>;
+
+    /// Places the PR with the given `title` and `author` into the merge queue with the given
+    /// approved commit. Any previous merge build of the PR is detached from it.
+    async fn enqueue(
+        &self,
+        pr: &PullRequestModel,
+        commit_sha: &CommitSha,
+        title: &str,
+        author: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Detaches the merge build from the PR, so that the PR is tested again at its current place in
+    /// the merge queue. The approval of the PR is kept as it is.
+    async fn requeue(&self, pr: &PullRequestModel) -> anyhow::Result<()>;
+
+    /// Removes the PR from the merge queue.
+    async fn dequeue(&self, pr: &PullRequestModel) -> anyhow::Result<()>;
+
+    /// Sets the number of try builds of the PR that have failed in a row.
+    async fn set_consecutive_failures(
+        &self,
+        pr: &PullRequestModel,
+        failures: i32,
+    ) -> anyhow::Result<()>;
+
+    /// Sets the number of merge builds of the PR that have failed in a row.
+    async fn set_consecutive_auto_failures(
+        &self,
+        pr: &PullRequestModel,
+        failures: i32,
+    ) -> anyhow::Result<()>;
+
+    /// Sets the time before which the approved PR does not enter the active merge queue.
+    async fn set_not_before(
+        &self,
+        pr: &PullRequestModel,
+        not_before: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()>;
+
+    /// Records that the PR author was told to rebase the given head commit of the PR.
+    async fn set_rebase_notified(
+        &self,
+        pr: &PullRequestModel,
+        head_sha: &CommitSha,
+    ) -> anyhow::Result<()>;
+
+    /// Records the PRs that are batched by the given rollup PR.
+    async fn set_rollup_members(
+        &self,
+        pr: &PullRequestModel,
+        members: &[PullRequestNumber],
+    ) -> anyhow::Result<()>;
+
+    /// Records that the PR has been merged.
+    async fn mark_merged(&self, pr: &PullRequestModel) -> anyhow::Result<()>;
+
+    /// Enables reminders of the reviewers of the PR with the given interval, counted from
+    /// `since`, or disables them if `interval` is `None`.
+    async fn set_nag(
+        &self,
+        pr: &PullRequestModel,
+        interval: Option<std::time::Duration>,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+
+    /// Records when the reviewers of the PR were last reminded about it.
+    async fn set_last_nag(&self, pr: &PullRequestModel, at: DateTime<Utc>) -> anyhow::Result<()>;
+
+    /// Returns all PRs of the repository whose reviewers should be reminded about them.
+    async fn list_nagged_prs(&self, repo: &GithubRepoName)
+        -> anyhow::Result<Vec<PullRequestModel>>;
+
+    /// Marks whether the queued PR waits for space in the merge queue.
+    async fn set_parked(&self, pr: &PullRequestModel, parked: bool) -> anyhow::Result<()>;
+
+    /// Marks whether status comments of the bot should be posted to the PR.
+    async fn set_muted(&self, pr: &PullRequestModel, muted: bool) -> anyhow::Result<()>;
+
+    /// Stores the comment of the bot that logs the status events of the PR.
+    async fn set_status_comment_id(
+        &self,
+        pr: &PullRequestModel,
+        comment_id: u64,
+    ) -> anyhow::Result<()>;
+
+    /// Returns all PRs of the repository that are in the merge queue, in the order in which they
+    /// should be merged: by priority first, and then by the time they have entered the queue.
+    async fn list_queued_prs(&self, repo: &GithubRepoName)
+        -> anyhow::Result<Vec<PullRequestModel>>;
+
+    /// Returns the authors of PRs of the repository whose merge builds have started, from the
+    /// least to the most recently started one.
+    async fn list_served_authors(&self, repo: &GithubRepoName) -> anyhow::Result<Vec<String>>;
+
+    /// Sets the merge queue priority of the given PR.
+    async fn set_priority(&self, pr: &PullRequestModel, priority: i32) -> anyhow::Result<()>;
+
+    /// Sets the rollup mode of the given PR.
+    async fn set_rollup(&self, pr: &PullRequestModel, rollup: RollupMode) -> anyhow::Result<()>;
+
+    /// Returns whether the tree of the repository is open. Repositories without a stored state
+    /// have an open tree.
+    async fn get_tree_state(&self, repo: &GithubRepoName) -> anyhow::Result<TreeState>;
+
+    /// Opens or closes the tree of the repository.
+    /// Reopening the tree resets the tree closed notices of all PRs of the repository.
+    async fn set_tree_state(&self, repo: &GithubRepoName, state: &TreeState) -> anyhow::Result<()>;
+
+    /// Returns the PRs that were last suggested to be merged in a rollup in the repository.
+    async fn get_suggested_rollup(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<PullRequestNumber>>;
+
+    /// Stores the PRs that were suggested to be merged in a rollup in the repository.
+    async fn set_suggested_rollup(
+        &self,
+        repo: &GithubRepoName,
+        prs: &[PullRequestNumber],
+    ) -> anyhow::Result<()>;
+
+    /// Marks that the PR was notified about the tree being closed.
+    async fn set_tree_closed_notified(&self, pr: &PullRequestModel) -> anyhow::Result<()>;
+
+    /// Moves all records of the repository, including its PRs and builds, to its new name.
+    async fn rename_repository(
+        &self,
+        old_name: &GithubRepoName,
+        new_name: &GithubRepoName,
+    ) -> anyhow::Result<()>;
+
+    /// Attaches a new try build to the given PR, which runs only the selected `workflows`, or all
+    /// workflows if none are selected.
     async fn attach_try_build(
         &self,
         pr: PullRequestModel,
         branch: String,
         commit_sha: CommitSha,
+        triggered_by: &str,
+        workflows: &[String],
     ) -> anyhow::Result<()>;
 
+    /// Creates a new merge build of the given PR.
+    async fn attach_auto_build(
+        &self,
+        pr: &PullRequestModel,
+        branch: String,
+        commit_sha: CommitSha,
+        triggered_by: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Returns the last `limit` builds of the given PR, starting with the most recent one.
+    async fn get_builds_for_pr(
+        &self,
+        pr: &PullRequestModel,
+        limit: u64,
+    ) -> anyhow::Result<Vec<BuildModel>>;
+
+    /// Returns how long the last `limit` successful builds on the given branch of the repository
+    /// took, starting with the most recent one.
+    async fn get_recent_build_durations(
+        &self,
+        repo: &GithubRepoName,
+        branch: &str,
+        limit: u64,
+    ) -> anyhow::Result<Vec<chrono::Duration>>;
+
+    /// Returns the most recent successful build of the given PR on the given branch.
+    async fn find_last_successful_build(
+        &self,
+        pr: &PullRequestModel,
+        branch: &str,
+    ) -> anyhow::Result<Option<BuildModel>>;
+
     /// Finds a build row by its repository, commit SHA and branch.
     async fn find_build(
         &self,
@@ -125,17 +652,59 @@ pub trait DbClient {
         status: BuildStatus,
     ) -> anyhow::Result<()>;
 
+    /// Stores the status of the build that was last reported in the bors check run.
+    async fn set_check_run_status(
+        &self,
+        build: &BuildModel,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()>;
+
+    /// Stores when all checks of the build have finished, or clears it when a check has started
+    /// again.
+    async fn set_checks_finished_at(
+        &self,
+        build: &BuildModel,
+        finished_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()>;
+
+    /// Stores the GitHub deployment that reports the status of the build.
+    async fn set_deployment_id(&self, build: &BuildModel, deployment_id: u64)
+        -> anyhow::Result<()>;
+
+    /// Stores the status of the build that was last reported in its GitHub deployment.
+    async fn set_deployment_status(
+        &self,
+        build: &BuildModel,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()>;
+
+    /// Marks the build as started again at `started_at`, e.g. when its failed checks are retried.
+    /// The build has to wait for late checks again.
+    async fn restart_build(
+        &self,
+        build: &BuildModel,
+        started_at: DateTime<Utc>,
+    ) -> anyhow::Result<()>;
+
+    /// Sets how many times were the failed checks of the build retried automatically.
+    async fn set_auto_retries(&self, build: &BuildModel, retries: i32) -> anyhow::Result<()>;
+
     /// Creates a new workflow attached to a build.
+    #[allow(clippy::too_many_arguments)]
     async fn create_workflow(
         &self,
         build: &BuildModel,
         name: String,
         url: String,
         run_id: RunId,
+        workflow_id: Option<u64>,
         workflow_type: WorkflowType,
         status: WorkflowStatus,
     ) -> anyhow::Result<()>;
 
+    /// Finds a workflow by its run ID.
+    async fn find_workflow(&self, run_id: RunId) -> anyhow::Result<Option<WorkflowModel>>;
+
     /// Updates the status of a workflow with the given run ID in the DB.
     async fn update_workflow_status(
         &self,
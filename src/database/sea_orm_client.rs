@@ -1,29 +1,68 @@
-use anyhow::anyhow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
 use axum::async_trait;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use octocrab::models::RunId;
-use sea_orm::sea_query::OnConflict;
+use sea_orm::sea_query::{Expr, OnConflict};
 use sea_orm::ActiveValue::{Set, Unchanged};
-use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, QueryFilter, TransactionTrait};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseTransaction, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, QueryTrait, TransactionTrait,
+};
 
-use entity::{build, pull_request, workflow};
+use entity::{
+    approval, audit_log, build, confirmation, delegation, failed_event, merge_group, pull_request,
+    repository, workflow,
+};
 use migration::sea_orm::DatabaseConnection;
+use migration::{IntoSchemaManagerConnection, Migrator, MigratorTrait};
 
 use crate::database::{
-    BuildModel, BuildStatus, DbClient, PullRequestModel, WorkflowModel, WorkflowStatus,
+    ApprovalModel, BuildModel, BuildStatus, CheckRunStatus, ConfirmationAction, ConfirmationModel,
+    DbClient, DelegationModel, DelegationScope, FailedEventModel, MergeGroupModel, PrimaryKey,
+    PullRequestModel, RollupMode, TransactionOperation, TreeState, WorkflowModel, WorkflowStatus,
     WorkflowType,
 };
 use crate::github::PullRequestNumber;
 use crate::github::{CommitSha, GithubRepoName};
 
 /// Provides access to a database using SeaORM mapping.
-pub struct SeaORMClient {
-    db: DatabaseConnection,
+///
+/// The client either uses a database connection directly, or it works inside a transaction
+/// started by [`DbClient::transaction`].
+///
+/// If a read replica is configured, queries that only serve reports (e.g. the build history)
+/// are sent to it. Reads whose results are used to modify the state of the database, e.g.
+/// choosing the next PR from the merge queue, always go to the primary connection, because the
+/// replica can lag behind it.
+#[derive(Clone)]
+pub struct SeaORMClient<C = DatabaseConnection> {
+    db: C,
+    replica: Option<C>,
 }
 
 impl SeaORMClient {
     pub fn new(connection: DatabaseConnection) -> Self {
-        Self { db: connection }
+        Self {
+            db: connection,
+            replica: None,
+        }
+    }
+
+    /// Sends the report queries of the client to the given read replica.
+    pub fn with_replica(self, replica: DatabaseConnection) -> Self {
+        Self {
+            replica: Some(replica),
+            ..self
+        }
+    }
+
+    /// Returns a client that sends all of its queries to the read replica, or to the primary
+    /// connection if no replica is configured. It must only be used for reading.
+    pub fn read_only(&self) -> Self {
+        Self::new(self.replica.clone().unwrap_or_else(|| self.db.clone()))
     }
 
     pub fn connection(&mut self) -> &mut DatabaseConnection {
@@ -31,8 +70,72 @@ impl SeaORMClient {
     }
 }
 
+impl<C: ConnectionTrait> SeaORMClient<C> {
+    /// Connection used by queries that can tolerate replication lag.
+    fn reader(&self) -> &C {
+        self.replica.as_ref().unwrap_or(&self.db)
+    }
+
+    /// Converts a PR row to a model, loading its merge build.
+    async fn load_pr(
+        &self,
+        pr: pull_request::Model,
+        try_build: Option<build::Model>,
+    ) -> anyhow::Result<PullRequestModel> {
+        let auto_build = match pr.auto_build {
+            Some(id) => build::Entity::find_by_id(id).one(&self.db).await?,
+            None => None,
+        };
+        Ok(pr_from_db(pr, try_build, auto_build))
+    }
+}
+
 #[async_trait]
-impl DbClient for SeaORMClient {
+impl<C> DbClient for SeaORMClient<C>
+where
+    C: ConnectionTrait + TransactionTrait + Send + Sync + 'static,
+    for<'c> &'c C: IntoSchemaManagerConnection<'c>,
+{
+    async fn run_migrations(&self) -> anyhow::Result<()> {
+        let applied = Migrator::get_migration_models(&self.db)
+            .await
+            .context("Cannot load applied migrations")?
+            .into_iter()
+            .map(|migration| migration.version)
+            .collect::<HashSet<_>>();
+        for migration in Migrator::migrations() {
+            if !applied.contains(migration.name()) {
+                tracing::info!("Applying migration {}", migration.name());
+            }
+        }
+        Migrator::up(&self.db, None)
+            .await
+            .context("Cannot apply migrations")?;
+        Ok(())
+    }
+
+    async fn get_schema_version(&self) -> anyhow::Result<Option<String>> {
+        let applied = Migrator::get_migration_models(&self.db)
+            .await
+            .context("Cannot load applied migrations")?;
+        Ok(applied.last().map(|migration| migration.version.clone()))
+    }
+
+    async fn transaction<'a>(&'a self, operation: TransactionOperation<'a>) -> anyhow::Result<()> {
+        let client = Arc::new(SeaORMClient::<DatabaseTransaction> {
+            db: self.db.begin().await?,
+            replica: None,
+        });
+        let result = operation(client.clone()).await;
+        let client = Arc::try_unwrap(client)
+            .map_err(|_| anyhow!("The transaction is still used after its operation has ended"))?;
+        match result {
+            Ok(()) => client.db.commit().await?,
+            Err(_) => client.db.rollback().await?,
+        }
+        result
+    }
+
     async fn get_or_create_pull_request(
         &self,
         repo: &GithubRepoName,
@@ -69,19 +172,779 @@ impl DbClient for SeaORMClient {
             .await?
             .ok_or_else(|| anyhow!("Cannot find PR row"))?;
 
-        Ok(pr_from_db(pr, build))
+        self.load_pr(pr, build).await
+    }
+
+    async fn find_pr_by_build(
+        &self,
+        build: &BuildModel,
+    ) -> anyhow::Result<Option<PullRequestModel>> {
+        let result = pull_request::Entity::find()
+            .filter(
+                pull_request::Column::TryBuild
+                    .eq(build.id)
+                    .or(pull_request::Column::AutoBuild.eq(build.id)),
+            )
+            .find_also_related(build::Entity)
+            .one(&self.db)
+            .await?;
+        match result {
+            Some((pr, build)) => Ok(Some(self.load_pr(pr, build).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn approve(
+        &self,
+        pr: &PullRequestModel,
+        approver: &str,
+        team: Option<&str>,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<()> {
+        let approval = approval::ActiveModel {
+            pull_request: Set(pr.id),
+            approver: Set(approver.to_string()),
+            commit_sha: Set(commit_sha.0.clone()),
+            created_at: Set(Utc::now().naive_utc()),
+            author: Set(Some(approver.to_string())),
+            team: Set(team.map(str::to_string)),
+            ..Default::default()
+        };
+        approval::Entity::insert(approval)
+            .on_conflict(
+                OnConflict::columns([approval::Column::PullRequest, approval::Column::Approver])
+                    .update_columns([
+                        approval::Column::CommitSha,
+                        approval::Column::CreatedAt,
+                        approval::Column::Author,
+                        approval::Column::Team,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_approvals(&self, pr: &PullRequestModel) -> anyhow::Result<Vec<ApprovalModel>> {
+        let approvals = approval::Entity::find()
+            .filter(approval::Column::PullRequest.eq(pr.id))
+            .order_by_asc(approval::Column::Id)
+            .all(&self.db)
+            .await?;
+        Ok(approvals.into_iter().map(approval_from_db).collect())
+    }
+
+    async fn delegate(
+        &self,
+        pr: &PullRequestModel,
+        delegatee: &str,
+        scope: DelegationScope,
+    ) -> anyhow::Result<()> {
+        let delegation = delegation::ActiveModel {
+            pull_request: Set(pr.id),
+            delegatee: Set(delegatee.to_string()),
+            scope: Set(delegation_scope_to_db(scope).to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        match delegation::Entity::insert(delegation)
+            .on_conflict(OnConflict::new().do_nothing().to_owned())
+            .exec_without_returning(&self.db)
+            .await
+        {
+            Ok(_) | Err(DbErr::RecordNotInserted) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn undelegate(
+        &self,
+        pr: &PullRequestModel,
+        delegatee: &str,
+        scope: Option<DelegationScope>,
+    ) -> anyhow::Result<()> {
+        let mut condition = delegation::Column::PullRequest
+            .eq(pr.id)
+            .and(delegation::Column::Delegatee.eq(delegatee));
+        if let Some(scope) = scope {
+            condition = condition.and(delegation::Column::Scope.eq(delegation_scope_to_db(scope)));
+        }
+        delegation::Entity::delete_many()
+            .filter(condition)
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_delegations(&self, pr: &PullRequestModel) -> anyhow::Result<Vec<DelegationModel>> {
+        let delegations = delegation::Entity::find()
+            .filter(delegation::Column::PullRequest.eq(pr.id))
+            .order_by_asc(delegation::Column::Id)
+            .all(&self.db)
+            .await?;
+        delegations.into_iter().map(delegation_from_db).collect()
+    }
+
+    async fn record_command(
+        &self,
+        pr: &PullRequestModel,
+        author: &str,
+        command: &str,
+    ) -> anyhow::Result<()> {
+        let entry = audit_log::ActiveModel {
+            pull_request: Set(pr.id),
+            author: Set(author.to_string()),
+            command: Set(command.to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        audit_log::Entity::insert(entry)
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn prune_audit_log(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64> {
+        let prs = pull_request::Entity::find()
+            .select_only()
+            .column(pull_request::Column::Id)
+            .filter(pull_request::Column::Repository.eq(full_repo_name(repo)))
+            .into_query();
+        let result = audit_log::Entity::delete_many()
+            .filter(
+                audit_log::Column::CreatedAt
+                    .lt(before.naive_utc())
+                    .and(audit_log::Column::PullRequest.in_subquery(prs)),
+            )
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn record_failed_event(
+        &self,
+        repo: &GithubRepoName,
+        event: &str,
+        error: &str,
+    ) -> anyhow::Result<()> {
+        let entry = failed_event::ActiveModel {
+            repository: Set(full_repo_name(repo)),
+            event: Set(event.to_string()),
+            error: Set(error.to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        failed_event::Entity::insert(entry)
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_failed_events(
+        &self,
+        repo: &GithubRepoName,
+        limit: u64,
+    ) -> anyhow::Result<Vec<FailedEventModel>> {
+        let events = failed_event::Entity::find()
+            .filter(failed_event::Column::Repository.eq(full_repo_name(repo)))
+            .order_by_desc(failed_event::Column::Id)
+            .limit(limit)
+            .all(self.reader())
+            .await?;
+        Ok(events.into_iter().map(failed_event_from_db).collect())
+    }
+
+    async fn find_failed_event(&self, id: PrimaryKey) -> anyhow::Result<Option<FailedEventModel>> {
+        let event = failed_event::Entity::find_by_id(id).one(&self.db).await?;
+        Ok(event.map(failed_event_from_db))
+    }
+
+    async fn prune_failed_events(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64> {
+        let result = failed_event::Entity::delete_many()
+            .filter(failed_event::Column::Repository.eq(full_repo_name(repo)))
+            .filter(failed_event::Column::CreatedAt.lt(before.naive_utc()))
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn create_confirmation(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        token: &str,
+        action: ConfirmationAction,
+        author: &str,
+    ) -> anyhow::Result<()> {
+        let entry = confirmation::ActiveModel {
+            repository: Set(full_repo_name(repo)),
+            pr_number: Set(pr.0 as i64),
+            token: Set(token.to_string()),
+            action: Set(confirmation_action_to_db(&action)),
+            author: Set(author.to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        confirmation::Entity::insert(entry)
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_confirmation(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        token: &str,
+    ) -> anyhow::Result<Option<ConfirmationModel>> {
+        let confirmation = confirmation::Entity::find()
+            .filter(confirmation::Column::Repository.eq(full_repo_name(repo)))
+            .filter(confirmation::Column::PrNumber.eq(pr.0 as i64))
+            .filter(confirmation::Column::Token.eq(token))
+            .one(&self.db)
+            .await?;
+        confirmation.map(confirmation_from_db).transpose()
+    }
+
+    async fn delete_confirmation(&self, confirmation: &ConfirmationModel) -> anyhow::Result<()> {
+        confirmation::Entity::delete_by_id(confirmation.id)
+            .exec(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn prune_confirmations(
+        &self,
+        repo: &GithubRepoName,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64> {
+        let result = confirmation::Entity::delete_many()
+            .filter(confirmation::Column::Repository.eq(full_repo_name(repo)))
+            .filter(confirmation::Column::CreatedAt.lt(before.naive_utc()))
+            .exec(&self.db)
+            .await?;
+        Ok(result.rows_affected)
+    }
+
+    async fn create_merge_group(
+        &self,
+        repo: &GithubRepoName,
+        pr: PullRequestNumber,
+        branch: &str,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<()> {
+        let entry = merge_group::ActiveModel {
+            repository: Set(full_repo_name(repo)),
+            pr_number: Set(pr.0 as i64),
+            branch: Set(branch.to_string()),
+            commit_sha: Set(commit_sha.0.clone()),
+            status: Set(build_status_to_db(BuildStatus::Pending).to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        merge_group::Entity::insert(entry)
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_merge_group(
+        &self,
+        repo: &GithubRepoName,
+        commit_sha: &CommitSha,
+    ) -> anyhow::Result<Option<MergeGroupModel>> {
+        let group = merge_group::Entity::find()
+            .filter(merge_group::Column::Repository.eq(full_repo_name(repo)))
+            .filter(merge_group::Column::CommitSha.eq(&commit_sha.0))
+            .order_by_desc(merge_group::Column::Id)
+            .one(&self.db)
+            .await?;
+        Ok(group.map(merge_group_from_db))
+    }
+
+    async fn update_merge_group_status(
+        &self,
+        group: &MergeGroupModel,
+        status: BuildStatus,
+    ) -> anyhow::Result<()> {
+        let model = merge_group::ActiveModel {
+            id: Unchanged(group.id),
+            status: Set(build_status_to_db(status).to_string()),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_base_branch(&self, pr: &PullRequestModel, branch: &str) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            base_branch: Set(Some(branch.to_string())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn enqueue(
+        &self,
+        pr: &PullRequestModel,
+        commit_sha: &CommitSha,
+        title: &str,
+        author: &str,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            approved_sha: Set(Some(commit_sha.0.clone())),
+            auto_build: Set(None),
+            title: Set(Some(title.to_string())),
+            author: Set(Some(author.to_string())),
+            approved_at: Set(Some(Utc::now().naive_utc())),
+            parked: Set(false),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn requeue(&self, pr: &PullRequestModel) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            auto_build: Set(None),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn dequeue(&self, pr: &PullRequestModel) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            approved_sha: Set(None),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_consecutive_failures(
+        &self,
+        pr: &PullRequestModel,
+        failures: i32,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            consecutive_failures: Set(failures),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_consecutive_auto_failures(
+        &self,
+        pr: &PullRequestModel,
+        failures: i32,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            consecutive_auto_failures: Set(failures),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_rebase_notified(
+        &self,
+        pr: &PullRequestModel,
+        head_sha: &CommitSha,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            rebase_notified_sha: Set(Some(head_sha.0.clone())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_rollup_members(
+        &self,
+        pr: &PullRequestModel,
+        members: &[PullRequestNumber],
+    ) -> anyhow::Result<()> {
+        let members = members
+            .iter()
+            .map(|member| member.0.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            rollup_members: Set(Some(members)),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn mark_merged(&self, pr: &PullRequestModel) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            merged_at: Set(Some(Utc::now().naive_utc())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_not_before(
+        &self,
+        pr: &PullRequestModel,
+        not_before: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            not_before: Set(not_before.map(|time| time.naive_utc())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_parked(&self, pr: &PullRequestModel, parked: bool) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            parked: Set(parked),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_muted(&self, pr: &PullRequestModel, muted: bool) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            muted: Set(muted),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_status_comment_id(
+        &self,
+        pr: &PullRequestModel,
+        comment_id: u64,
+    ) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            status_comment_id: Set(Some(comment_id as i64)),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn list_queued_prs(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<PullRequestModel>> {
+        let rows = pull_request::Entity::find()
+            .filter(
+                pull_request::Column::Repository
+                    .eq(full_repo_name(repo))
+                    .and(pull_request::Column::ApprovedSha.is_not_null()),
+            )
+            .order_by_desc(pull_request::Column::Priority)
+            .order_by_asc(pull_request::Column::ApprovedAt)
+            .order_by_asc(pull_request::Column::Id)
+            .find_also_related(build::Entity)
+            .all(&self.db)
+            .await?;
+
+        let mut prs = Vec::with_capacity(rows.len());
+        for (pr, build) in rows {
+            prs.push(self.load_pr(pr, build).await?);
+        }
+        Ok(prs)
+    }
+
+    async fn list_served_authors(&self, repo: &GithubRepoName) -> anyhow::Result<Vec<String>> {
+        let prs = pull_request::Entity::find()
+            .filter(
+                pull_request::Column::Repository
+                    .eq(full_repo_name(repo))
+                    .and(pull_request::Column::Author.is_not_null())
+                    .and(pull_request::Column::AutoBuildStartedAt.is_not_null()),
+            )
+            .order_by_asc(pull_request::Column::AutoBuildStartedAt)
+            .order_by_asc(pull_request::Column::Id)
+            .all(&self.db)
+            .await?;
+
+        let mut authors: Vec<String> = vec![];
+        for author in prs.into_iter().filter_map(|pr| pr.author) {
+            authors.retain(|served| *served != author);
+            authors.push(author);
+        }
+        Ok(authors)
+    }
+
+    async fn set_nag(
+        &self,
+        pr: &PullRequestModel,
+        interval: Option<std::time::Duration>,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let interval = interval
+            .map(|interval| i32::try_from(interval.as_secs()))
+            .transpose()
+            .context("Reminder interval is too long")?;
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            nag_interval: Set(interval),
+            last_nag_at: Set(interval.map(|_| since.naive_utc())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_last_nag(&self, pr: &PullRequestModel, at: DateTime<Utc>) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            last_nag_at: Set(Some(at.naive_utc())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn list_nagged_prs(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<PullRequestModel>> {
+        let rows = pull_request::Entity::find()
+            .filter(
+                pull_request::Column::Repository
+                    .eq(full_repo_name(repo))
+                    .and(pull_request::Column::NagInterval.is_not_null()),
+            )
+            .order_by_asc(pull_request::Column::Id)
+            .find_also_related(build::Entity)
+            .all(&self.db)
+            .await?;
+
+        let mut prs = Vec::with_capacity(rows.len());
+        for (pr, build) in rows {
+            prs.push(self.load_pr(pr, build).await?);
+        }
+        Ok(prs)
+    }
+
+    async fn set_priority(&self, pr: &PullRequestModel, priority: i32) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            priority: Set(priority),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_rollup(&self, pr: &PullRequestModel, rollup: RollupMode) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            rollup: Set(Some(rollup_mode_to_db(rollup).to_string())),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn get_tree_state(&self, repo: &GithubRepoName) -> anyhow::Result<TreeState> {
+        let repository = repository::Entity::find()
+            .filter(repository::Column::Name.eq(full_repo_name(repo)))
+            .one(&self.db)
+            .await?;
+        let state = match repository {
+            Some(repository) if !repository.tree_open => TreeState::Closed {
+                priority: repository.tree_closed_priority,
+                reason: repository.tree_closed_reason,
+                reopen_at: repository.tree_reopen_at.map(datetime_from_db),
+            },
+            // Repositories without a stored state have an open tree
+            _ => TreeState::Open,
+        };
+        Ok(state)
+    }
+
+    async fn get_suggested_rollup(
+        &self,
+        repo: &GithubRepoName,
+    ) -> anyhow::Result<Vec<PullRequestNumber>> {
+        let repository = repository::Entity::find()
+            .filter(repository::Column::Name.eq(full_repo_name(repo)))
+            .one(&self.db)
+            .await?;
+        let Some(suggested) = repository.and_then(|repository| repository.suggested_rollup) else {
+            return Ok(vec![]);
+        };
+        suggested
+            .split(',')
+            .filter(|number| !number.is_empty())
+            .map(|number| Ok(PullRequestNumber(number.parse()?)))
+            .collect()
+    }
+
+    async fn set_suggested_rollup(
+        &self,
+        repo: &GithubRepoName,
+        prs: &[PullRequestNumber],
+    ) -> anyhow::Result<()> {
+        let suggested = prs
+            .iter()
+            .map(|pr| pr.0.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        // Repositories without a stored state have an open tree
+        let repository = repository::ActiveModel {
+            name: Set(full_repo_name(repo)),
+            tree_open: Set(true),
+            suggested_rollup: Set(Some(suggested)),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        repository::Entity::insert(repository)
+            .on_conflict(
+                OnConflict::column(repository::Column::Name)
+                    .update_column(repository::Column::SuggestedRollup)
+                    .to_owned(),
+            )
+            .exec_without_returning(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_tree_state(&self, repo: &GithubRepoName, state: &TreeState) -> anyhow::Result<()> {
+        let (priority, reason, reopen_at) = match state {
+            TreeState::Open => (None, None, None),
+            TreeState::Closed {
+                priority,
+                reason,
+                reopen_at,
+            } => (*priority, reason.clone(), *reopen_at),
+        };
+        let open = state.is_open();
+        let tx = self.db.begin().await?;
+        let repository = repository::ActiveModel {
+            name: Set(full_repo_name(repo)),
+            tree_open: Set(open),
+            tree_closed_priority: Set(priority),
+            tree_closed_reason: Set(reason),
+            tree_reopen_at: Set(reopen_at.map(|time| time.naive_utc())),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        repository::Entity::insert(repository)
+            .on_conflict(
+                OnConflict::column(repository::Column::Name)
+                    .update_columns([
+                        repository::Column::TreeOpen,
+                        repository::Column::TreeClosedPriority,
+                        repository::Column::TreeClosedReason,
+                        repository::Column::TreeReopenAt,
+                    ])
+                    .to_owned(),
+            )
+            .exec_without_returning(&tx)
+            .await?;
+        if open {
+            let model = pull_request::ActiveModel {
+                tree_closed_notified: Set(false),
+                ..Default::default()
+            };
+            pull_request::Entity::update_many()
+                .set(model)
+                .filter(pull_request::Column::Repository.eq(full_repo_name(repo)))
+                .exec(&tx)
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn set_tree_closed_notified(&self, pr: &PullRequestModel) -> anyhow::Result<()> {
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            tree_closed_notified: Set(true),
+            ..Default::default()
+        };
+        pr_model.update(&self.db).await?;
+        Ok(())
     }
 
-    async fn find_pr_by_build(
+    async fn rename_repository(
         &self,
-        build: &BuildModel,
-    ) -> anyhow::Result<Option<PullRequestModel>> {
-        let result = pull_request::Entity::find()
-            .filter(pull_request::Column::TryBuild.eq(build.id))
-            .find_also_related(build::Entity)
-            .one(&self.db)
+        old_name: &GithubRepoName,
+        new_name: &GithubRepoName,
+    ) -> anyhow::Result<()> {
+        let (old_name, new_name) = (full_repo_name(old_name), full_repo_name(new_name));
+        let tx = self.db.begin().await?;
+        pull_request::Entity::update_many()
+            .col_expr(
+                pull_request::Column::Repository,
+                Expr::value(new_name.clone()),
+            )
+            .filter(pull_request::Column::Repository.eq(&old_name))
+            .exec(&tx)
+            .await?;
+        build::Entity::update_many()
+            .col_expr(build::Column::Repository, Expr::value(new_name.clone()))
+            .filter(build::Column::Repository.eq(&old_name))
+            .exec(&tx)
+            .await?;
+        repository::Entity::update_many()
+            .col_expr(repository::Column::Name, Expr::value(new_name.clone()))
+            .filter(repository::Column::Name.eq(&old_name))
+            .exec(&tx)
             .await?;
-        Ok(result.map(|(pr, build)| pr_from_db(pr, build)))
+        failed_event::Entity::update_many()
+            .col_expr(
+                failed_event::Column::Repository,
+                Expr::value(new_name.clone()),
+            )
+            .filter(failed_event::Column::Repository.eq(&old_name))
+            .exec(&tx)
+            .await?;
+        confirmation::Entity::update_many()
+            .col_expr(
+                confirmation::Column::Repository,
+                Expr::value(new_name.clone()),
+            )
+            .filter(confirmation::Column::Repository.eq(&old_name))
+            .exec(&tx)
+            .await?;
+        merge_group::Entity::update_many()
+            .col_expr(
+                merge_group::Column::Repository,
+                Expr::value(new_name.clone()),
+            )
+            .filter(merge_group::Column::Repository.eq(&old_name))
+            .exec(&tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
     }
 
     async fn attach_try_build(
@@ -89,14 +952,39 @@ impl DbClient for SeaORMClient {
         pr: PullRequestModel,
         branch: String,
         commit_sha: CommitSha,
+        triggered_by: &str,
+        workflows: &[String],
     ) -> anyhow::Result<()> {
-        let build = build::ActiveModel {
-            repository: Set(pr.repository.clone()),
-            branch: Set(branch),
-            commit_sha: Set(commit_sha.0),
-            status: Set(build_status_to_db(BuildStatus::Pending).to_string()),
+        let mut build = new_build(&pr, branch, commit_sha, triggered_by);
+        if !workflows.is_empty() {
+            build.selected_workflows = Set(Some(workflows.join(",")));
+        }
+
+        let tx = self.db.begin().await?;
+        let build = build::Entity::insert(build)
+            .exec_with_returning(&tx)
+            .await
+            .map_err(|error| anyhow!("Cannot insert build into DB: {error:?}"))?;
+
+        let pr_model = pull_request::ActiveModel {
+            id: Unchanged(pr.id),
+            try_build: Set(Some(build.id)),
             ..Default::default()
         };
+        pr_model.update(&tx).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn attach_auto_build(
+        &self,
+        pr: &PullRequestModel,
+        branch: String,
+        commit_sha: CommitSha,
+        triggered_by: &str,
+    ) -> anyhow::Result<()> {
+        let build = new_build(pr, branch, commit_sha, triggered_by);
 
         let tx = self.db.begin().await?;
         let build = build::Entity::insert(build)
@@ -106,7 +994,8 @@ impl DbClient for SeaORMClient {
 
         let pr_model = pull_request::ActiveModel {
             id: Unchanged(pr.id),
-            try_build: Set(Some(build.id)),
+            auto_build: Set(Some(build.id)),
+            auto_build_started_at: Set(Some(Utc::now().naive_utc())),
             ..Default::default()
         };
         pr_model.update(&tx).await?;
@@ -115,6 +1004,66 @@ impl DbClient for SeaORMClient {
         Ok(())
     }
 
+    async fn get_builds_for_pr(
+        &self,
+        pr: &PullRequestModel,
+        limit: u64,
+    ) -> anyhow::Result<Vec<BuildModel>> {
+        let builds = build::Entity::find()
+            .filter(build::Column::PullRequest.eq(pr.id))
+            .order_by_desc(build::Column::Id)
+            .limit(limit)
+            .all(self.reader())
+            .await?;
+        Ok(builds.into_iter().map(build_from_db).collect())
+    }
+
+    async fn get_recent_build_durations(
+        &self,
+        repo: &GithubRepoName,
+        branch: &str,
+        limit: u64,
+    ) -> anyhow::Result<Vec<chrono::Duration>> {
+        let builds = build::Entity::find()
+            .filter(
+                build::Column::Repository
+                    .eq(full_repo_name(repo))
+                    .and(build::Column::Branch.eq(branch))
+                    .and(build::Column::Status.eq(build_status_to_db(BuildStatus::Success)))
+                    .and(build::Column::CompletedAt.is_not_null()),
+            )
+            .order_by_desc(build::Column::CompletedAt)
+            .limit(limit)
+            .all(self.reader())
+            .await?;
+        Ok(builds
+            .into_iter()
+            .filter_map(|build| {
+                build
+                    .completed_at
+                    .map(|completed_at| completed_at - build.created_at)
+            })
+            .collect())
+    }
+
+    async fn find_last_successful_build(
+        &self,
+        pr: &PullRequestModel,
+        branch: &str,
+    ) -> anyhow::Result<Option<BuildModel>> {
+        let build = build::Entity::find()
+            .filter(
+                build::Column::PullRequest
+                    .eq(pr.id)
+                    .and(build::Column::Branch.eq(branch))
+                    .and(build::Column::Status.eq(build_status_to_db(BuildStatus::Success))),
+            )
+            .order_by_desc(build::Column::Id)
+            .one(&self.db)
+            .await?;
+        Ok(build.map(build_from_db))
+    }
+
     async fn find_build(
         &self,
         repo: &GithubRepoName,
@@ -150,9 +1099,95 @@ impl DbClient for SeaORMClient {
         build: &BuildModel,
         status: BuildStatus,
     ) -> anyhow::Result<()> {
+        let completed_at = match status {
+            BuildStatus::Pending => None,
+            _ => Some(Utc::now().naive_utc()),
+        };
         let model = build::ActiveModel {
             id: Unchanged(build.id),
             status: Set(build_status_to_db(status).to_string()),
+            completed_at: Set(completed_at),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_check_run_status(
+        &self,
+        build: &BuildModel,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            check_run_status: Set(Some(check_run_status_to_db(status).to_string())),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_checks_finished_at(
+        &self,
+        build: &BuildModel,
+        finished_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            checks_finished_at: Set(finished_at.map(|at| at.naive_utc())),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_deployment_id(
+        &self,
+        build: &BuildModel,
+        deployment_id: u64,
+    ) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            deployment_id: Set(Some(deployment_id as i64)),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_deployment_status(
+        &self,
+        build: &BuildModel,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            deployment_status: Set(Some(check_run_status_to_db(status).to_string())),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn restart_build(
+        &self,
+        build: &BuildModel,
+        started_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            created_at: Set(started_at.naive_utc()),
+            checks_finished_at: Set(None),
+            ..Default::default()
+        };
+        model.update(&self.db).await?;
+        Ok(())
+    }
+
+    async fn set_auto_retries(&self, build: &BuildModel, retries: i32) -> anyhow::Result<()> {
+        let model = build::ActiveModel {
+            id: Unchanged(build.id),
+            auto_retries: Set(retries),
             ..Default::default()
         };
         model.update(&self.db).await?;
@@ -165,6 +1200,7 @@ impl DbClient for SeaORMClient {
         name: String,
         url: String,
         run_id: RunId,
+        workflow_id: Option<u64>,
         workflow_type: WorkflowType,
         status: WorkflowStatus,
     ) -> anyhow::Result<()> {
@@ -173,6 +1209,7 @@ impl DbClient for SeaORMClient {
             name: Set(name),
             url: Set(url),
             run_id: Set(run_id.0 as i64),
+            workflow_id: Set(workflow_id.map(|id| id as i64)),
             r#type: Set(workflow_type_to_db(workflow_type).to_string()),
             status: Set(workflow_status_to_db(&status).to_string()),
             ..Default::default()
@@ -181,6 +1218,15 @@ impl DbClient for SeaORMClient {
         Ok(())
     }
 
+    async fn find_workflow(&self, run_id: RunId) -> anyhow::Result<Option<WorkflowModel>> {
+        let workflow = workflow::Entity::find()
+            .filter(workflow::Column::RunId.eq(run_id.0))
+            .find_also_related(build::Entity)
+            .one(&self.db)
+            .await?;
+        Ok(workflow.map(|(workflow, build)| workflow_from_db(workflow, build)))
+    }
+
     async fn update_workflow_status(
         &self,
         run_id: u64,
@@ -234,6 +1280,42 @@ fn workflow_status_from_db(status: String) -> WorkflowStatus {
     }
 }
 
+fn delegation_scope_to_db(scope: DelegationScope) -> &'static str {
+    match scope {
+        DelegationScope::Try => "try",
+        DelegationScope::Review => "review",
+    }
+}
+
+fn delegation_scope_from_db(scope: String) -> anyhow::Result<DelegationScope> {
+    match scope.as_str() {
+        "try" => Ok(DelegationScope::Try),
+        "review" => Ok(DelegationScope::Review),
+        _ => Err(anyhow!(
+            "Encountered unknown delegation scope in DB: {scope}"
+        )),
+    }
+}
+
+fn rollup_mode_to_db(rollup: RollupMode) -> &'static str {
+    match rollup {
+        RollupMode::Always => "always",
+        RollupMode::Maybe => "maybe",
+        RollupMode::Iffy => "iffy",
+        RollupMode::Never => "never",
+    }
+}
+
+fn rollup_mode_from_db(rollup: String) -> RollupMode {
+    match rollup.as_str() {
+        "always" => RollupMode::Always,
+        "maybe" => RollupMode::Maybe,
+        "iffy" => RollupMode::Iffy,
+        "never" => RollupMode::Never,
+        _ => panic!("Encountered unknown rollup mode in DB: {rollup}"),
+    }
+}
+
 fn workflow_type_to_db(workflow_type: WorkflowType) -> &'static str {
     match workflow_type {
         WorkflowType::Github => "github",
@@ -258,6 +1340,7 @@ fn workflow_from_db(workflow: workflow::Model, build: Option<build::Model>) -> W
         name: workflow.name,
         url: workflow.url,
         run_id: RunId(workflow.run_id as u64),
+        workflow_id: workflow.workflow_id.map(|id| id as u64),
         workflow_type: workflow_type_from_db(workflow.r#type),
         status: workflow_status_from_db(workflow.status),
         created_at: datetime_from_db(workflow.created_at),
@@ -285,6 +1368,25 @@ fn build_status_from_db(status: String) -> BuildStatus {
     }
 }
 
+fn check_run_status_to_db(status: CheckRunStatus) -> &'static str {
+    match status {
+        CheckRunStatus::InProgress => "in_progress",
+        CheckRunStatus::Success => "success",
+        CheckRunStatus::Failure => "failure",
+        CheckRunStatus::Cancelled => "cancelled",
+    }
+}
+
+fn check_run_status_from_db(status: String) -> CheckRunStatus {
+    match status.as_str() {
+        "in_progress" => CheckRunStatus::InProgress,
+        "success" => CheckRunStatus::Success,
+        "failure" => CheckRunStatus::Failure,
+        "cancelled" => CheckRunStatus::Cancelled,
+        _ => panic!("Encountered unknown check run status in DB: {status}"),
+    }
+}
+
 fn build_from_db(model: build::Model) -> BuildModel {
     BuildModel {
         id: model.id,
@@ -293,23 +1395,394 @@ fn build_from_db(model: build::Model) -> BuildModel {
         commit_sha: model.commit_sha,
         status: build_status_from_db(model.status),
         created_at: datetime_from_db(model.created_at),
+        triggered_by: model.triggered_by,
+        completed_at: model.completed_at.map(datetime_from_db),
+        check_run_status: model.check_run_status.map(check_run_status_from_db),
+        checks_finished_at: model.checks_finished_at.map(datetime_from_db),
+        deployment_id: model.deployment_id.map(|id| id as u64),
+        deployment_status: model.deployment_status.map(check_run_status_from_db),
+        auto_retries: model.auto_retries,
+        selected_workflows: model
+            .selected_workflows
+            .map(|workflows| workflows.split(',').map(str::to_string).collect())
+            .unwrap_or_default(),
+    }
+}
+
+fn approval_from_db(model: approval::Model) -> ApprovalModel {
+    ApprovalModel {
+        id: model.id,
+        approver: model.approver,
+        commit_sha: model.commit_sha,
+        created_at: datetime_from_db(model.created_at),
+        author: model.author,
+        team: model.team,
+    }
+}
+
+fn delegation_from_db(model: delegation::Model) -> anyhow::Result<DelegationModel> {
+    Ok(DelegationModel {
+        id: model.id,
+        delegatee: model.delegatee,
+        scope: delegation_scope_from_db(model.scope)?,
+        created_at: datetime_from_db(model.created_at),
+    })
+}
+
+fn failed_event_from_db(model: failed_event::Model) -> FailedEventModel {
+    FailedEventModel {
+        id: model.id,
+        repository: model.repository,
+        event: model.event,
+        error: model.error,
+        created_at: datetime_from_db(model.created_at),
+    }
+}
+
+fn confirmation_action_to_db(action: &ConfirmationAction) -> String {
+    match action {
+        ConfirmationAction::Clean => "clean".to_string(),
+        ConfirmationAction::SetBase(branch) => format!("set-base:{branch}"),
+    }
+}
+
+fn confirmation_action_from_db(action: String) -> anyhow::Result<ConfirmationAction> {
+    if let Some(branch) = action.strip_prefix("set-base:") {
+        return Ok(ConfirmationAction::SetBase(branch.to_string()));
+    }
+    match action.as_str() {
+        "clean" => Ok(ConfirmationAction::Clean),
+        _ => Err(anyhow!(
+            "Encountered unknown confirmation action in DB: {action}"
+        )),
+    }
+}
+
+fn confirmation_from_db(model: confirmation::Model) -> anyhow::Result<ConfirmationModel> {
+    Ok(ConfirmationModel {
+        id: model.id,
+        repository: model.repository,
+        pr_number: PullRequestNumber(model.pr_number as u64),
+        token: model.token,
+        action: confirmation_action_from_db(model.action)?,
+        author: model.author,
+        created_at: datetime_from_db(model.created_at),
+    })
+}
+
+fn new_build(
+    pr: &PullRequestModel,
+    branch: String,
+    commit_sha: CommitSha,
+    triggered_by: &str,
+) -> build::ActiveModel {
+    build::ActiveModel {
+        repository: Set(pr.repository.clone()),
+        branch: Set(branch),
+        commit_sha: Set(commit_sha.0),
+        status: Set(build_status_to_db(BuildStatus::Pending).to_string()),
+        pull_request: Set(Some(pr.id)),
+        triggered_by: Set(Some(triggered_by.to_string())),
+        ..Default::default()
     }
 }
 
-fn pr_from_db(pr: pull_request::Model, build: Option<build::Model>) -> PullRequestModel {
+fn pr_from_db(
+    pr: pull_request::Model,
+    try_build: Option<build::Model>,
+    auto_build: Option<build::Model>,
+) -> PullRequestModel {
     PullRequestModel {
         id: pr.id,
         repository: pr.repository,
         number: PullRequestNumber(pr.number as u64),
-        try_build: build.map(build_from_db),
+        try_build: try_build.map(build_from_db),
         created_at: datetime_from_db(pr.created_at),
+        base_branch: pr.base_branch,
+        approved_sha: pr.approved_sha,
+        auto_build: auto_build.map(build_from_db),
+        priority: pr.priority,
+        rollup: pr.rollup.map(rollup_mode_from_db),
+        title: pr.title,
+        approved_at: pr.approved_at.map(datetime_from_db),
+        parked: pr.parked,
+        tree_closed_notified: pr.tree_closed_notified,
+        consecutive_failures: pr.consecutive_failures,
+        consecutive_auto_failures: pr.consecutive_auto_failures,
+        not_before: pr.not_before.map(datetime_from_db),
+        rebase_notified_sha: pr.rebase_notified_sha,
+        rollup_members: pr
+            .rollup_members
+            .map(|members| rollup_members_from_db(&members))
+            .unwrap_or_default(),
+        merged_at: pr.merged_at.map(datetime_from_db),
+        nag_interval: pr
+            .nag_interval
+            .map(|seconds| std::time::Duration::from_secs(seconds.max(0) as u64)),
+        last_nag_at: pr.last_nag_at.map(datetime_from_db),
+        muted: pr.muted,
+        author: pr.author,
+        status_comment_id: pr.status_comment_id.map(|id| id as u64),
     }
 }
 
+fn rollup_members_from_db(members: &str) -> Vec<PullRequestNumber> {
+    members
+        .split(',')
+        .filter_map(|member| member.parse().ok())
+        .map(PullRequestNumber)
+        .collect()
+}
+
 fn datetime_from_db(datetime: NaiveDateTime) -> DateTime<Utc> {
     DateTime::from_utc(datetime, Utc)
 }
 
+fn merge_group_from_db(model: merge_group::Model) -> MergeGroupModel {
+    MergeGroupModel {
+        id: model.id,
+        repository: model.repository,
+        pr_number: PullRequestNumber(model.pr_number as u64),
+        branch: model.branch,
+        commit_sha: CommitSha(model.commit_sha),
+        status: build_status_from_db(model.status),
+        created_at: datetime_from_db(model.created_at),
+    }
+}
+
 fn full_repo_name(repo: &GithubRepoName) -> String {
     format!("{}/{}", repo.owner(), repo.name())
 }
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use chrono::Utc;
+    use entity::{confirmation, delegation};
+    use migration::{Migrator, MigratorTrait};
+    use sea_orm::{ActiveValue::Set, Database, EntityTrait};
+
+    use crate::database::sea_orm_client::full_repo_name;
+    use crate::database::{DbClient, PullRequestModel, SeaORMClient, TreeState};
+    use crate::github::{CommitSha, PullRequestNumber};
+    use crate::tests::database::create_test_db;
+    use crate::tests::state::default_repo_name;
+
+    #[tokio::test]
+    async fn test_run_migrations_fresh_db() {
+        let db = SeaORMClient::new(Database::connect("sqlite::memory:").await.unwrap());
+        assert_eq!(db.get_schema_version().await.unwrap(), None);
+
+        db.run_migrations().await.unwrap();
+        assert_eq!(
+            db.get_schema_version().await.unwrap(),
+            Some(last_migration_name())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_migrations_advance_version() {
+        let connection = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&connection, Some(1)).await.unwrap();
+        let first_migration = Migrator::migrations()[0].name().to_string();
+
+        let db = SeaORMClient::new(connection);
+        assert_eq!(
+            db.get_schema_version().await.unwrap(),
+            Some(first_migration)
+        );
+
+        db.run_migrations().await.unwrap();
+        assert_eq!(
+            db.get_schema_version().await.unwrap(),
+            Some(last_migration_name())
+        );
+
+        // Running migrations again is a no-op
+        db.run_migrations().await.unwrap();
+        assert_eq!(
+            db.get_schema_version().await.unwrap(),
+            Some(last_migration_name())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit() {
+        let db = create_test_db().await;
+        let pr = get_pr(&db).await;
+        let pr_ref = &pr;
+        db.transaction(Box::new(move |db| {
+            Box::pin(async move {
+                db.approve(pr_ref, "user", None, &CommitSha("sha".to_string()))
+                    .await?;
+                db.set_priority(pr_ref, 5).await
+            })
+        }))
+        .await
+        .unwrap();
+
+        assert_eq!(db.get_approvals(&pr).await.unwrap().len(), 1);
+        assert_eq!(get_pr(&db).await.priority, 5);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rollback() {
+        let db = create_test_db().await;
+        let pr = get_pr(&db).await;
+        let pr_ref = &pr;
+        let result = db
+            .transaction(Box::new(move |db| {
+                Box::pin(async move {
+                    db.approve(pr_ref, "user", None, &CommitSha("sha".to_string()))
+                        .await?;
+                    db.set_priority(pr_ref, 5).await?;
+                    Err(anyhow!("Something went wrong"))
+                })
+            }))
+            .await;
+        assert_eq!(result.unwrap_err().to_string(), "Something went wrong");
+
+        assert!(db.get_approvals(&pr).await.unwrap().is_empty());
+        assert_eq!(get_pr(&db).await.priority, 0);
+    }
+
+    #[tokio::test]
+    async fn test_report_queries_use_replica() {
+        let primary = create_test_db().await;
+        let mut replica = create_test_db().await;
+        let pr = get_pr(&replica).await;
+        replica
+            .attach_try_build(
+                pr,
+                "try".to_string(),
+                CommitSha("sha".to_string()),
+                "user",
+                &[],
+            )
+            .await
+            .unwrap();
+
+        let db = primary.clone().with_replica(replica.connection().clone());
+        let pr = get_pr(&db).await;
+        assert_eq!(db.get_builds_for_pr(&pr, 10).await.unwrap().len(), 1);
+        assert_eq!(
+            db.read_only()
+                .get_builds_for_pr(&pr, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        // Other queries go to the primary connection
+        assert!(get_pr(&db).await.try_build.is_none());
+        assert!(primary.get_builds_for_pr(&pr, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_confirmation_action() {
+        let mut db = create_test_db().await;
+        let entry = confirmation::ActiveModel {
+            repository: Set(full_repo_name(&default_repo_name())),
+            pr_number: Set(1),
+            token: Set("token".to_string()),
+            action: Set("reboot".to_string()),
+            author: Set("user".to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        confirmation::Entity::insert(entry)
+            .exec_without_returning(&*db.connection())
+            .await
+            .unwrap();
+
+        let error = db
+            .find_confirmation(&default_repo_name(), PullRequestNumber(1), "token")
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Encountered unknown confirmation action in DB: reboot"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_served_authors() {
+        let db = create_test_db().await;
+        let repo = default_repo_name();
+        for (number, author) in [(1, "alice"), (2, "bob"), (3, "alice"), (4, "carol")] {
+            let pr = db
+                .get_or_create_pull_request(&repo, PullRequestNumber(number))
+                .await
+                .unwrap();
+            let sha = CommitSha(format!("sha-{number}"));
+            db.enqueue(&pr, &sha, "title", author).await.unwrap();
+            // Carol's PR is waiting in the queue
+            if author != "carol" {
+                db.attach_auto_build(&pr, "auto".to_string(), sha, "reviewer")
+                    .await
+                    .unwrap();
+            }
+        }
+        assert_eq!(
+            db.list_served_authors(&repo).await.unwrap(),
+            vec!["bob".to_string(), "alice".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggested_rollup() {
+        let db = create_test_db().await;
+        let repo = default_repo_name();
+        assert!(db.get_suggested_rollup(&repo).await.unwrap().is_empty());
+
+        let closed = TreeState::Closed {
+            priority: None,
+            reason: None,
+            reopen_at: None,
+        };
+        db.set_tree_state(&repo, &closed).await.unwrap();
+        db.set_suggested_rollup(&repo, &[PullRequestNumber(1), PullRequestNumber(2)])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.get_suggested_rollup(&repo).await.unwrap(),
+            vec![PullRequestNumber(1), PullRequestNumber(2)]
+        );
+        // The tree state of the repository is kept
+        assert_eq!(db.get_tree_state(&repo).await.unwrap(), closed);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_delegation_scope() {
+        let mut db = create_test_db().await;
+        let pr = get_pr(&db).await;
+        let entry = delegation::ActiveModel {
+            pull_request: Set(pr.id),
+            delegatee: Set("user".to_string()),
+            scope: Set("merge".to_string()),
+            created_at: Set(Utc::now().naive_utc()),
+            ..Default::default()
+        };
+        delegation::Entity::insert(entry)
+            .exec_without_returning(&*db.connection())
+            .await
+            .unwrap();
+
+        let error = db.get_delegations(&pr).await.err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "Encountered unknown delegation scope in DB: merge"
+        );
+    }
+
+    async fn get_pr(db: &SeaORMClient) -> PullRequestModel {
+        db.get_or_create_pull_request(&default_repo_name(), PullRequestNumber(1))
+            .await
+            .unwrap()
+    }
+
+    fn last_migration_name() -> String {
+        Migrator::migrations().last().unwrap().name().to_string()
+    }
+}
@@ -1,18 +1,36 @@
 use anyhow::Context;
 use axum::async_trait;
-use octocrab::models::{Repository, RunId};
-use octocrab::{Error, Octocrab};
+use octocrab::models::repos::Object;
+use octocrab::models::{IssueState, Repository, RunId};
+use octocrab::params::repos::Reference;
+use octocrab::Octocrab;
+use reqwest::StatusCode;
 use tracing::log;
 
-use crate::bors::{CheckSuite, CheckSuiteStatus, RepositoryClient};
+use crate::bors::{
+    Artifact, BranchProtection, CheckRun, CheckSuite, CheckSuiteStatus, CombinedStatus,
+    CommitComparison, CommitComparisonStatus, RepositoryClient, WorkflowRun,
+};
+use crate::database::{CheckRunStatus, WorkflowStatus};
 use crate::github::api::operations::{merge_branches, set_branch_to_commit, MergeError};
-use crate::github::{Branch, CommitSha, GithubRepoName, PullRequest, PullRequestNumber};
+use crate::github::api::rate_limit::RateLimit;
+use crate::github::api::request::ApiClient;
+use crate::github::api::{api_url, BOT_COMMENT_MARKER};
+use crate::github::{
+    Branch, Commit, CommitSha, GithubRepoName, GithubUser, PullRequest, PullRequestNumber,
+    ResolveRefError,
+};
+
+/// Name of the check run in which bors reports the status of its builds.
+const CHECK_RUN_NAME: &str = "bors";
+
+/// Slug of the app that creates the check suites of GitHub Actions workflows.
+const GITHUB_ACTIONS_APP: &str = "github-actions";
 
 /// Provides access to a single app installation (repository) using the GitHub API.
 pub struct GithubRepositoryClient {
-    /// The client caches the access token for this given repository and refreshes it once it
-    /// expires.
-    pub client: Octocrab,
+    /// Sends all requests of the repository to the GitHub API.
+    pub api: ApiClient,
     // We store the name separately, because repository has an optional owner, but at this point
     // we must always have some owner of the repo.
     pub repo_name: GithubRepoName,
@@ -21,13 +39,23 @@ pub struct GithubRepositoryClient {
 
 impl GithubRepositoryClient {
     pub fn client(&self) -> &Octocrab {
-        &self.client
+        &self.api.client
     }
 
     pub fn name(&self) -> &GithubRepoName {
         &self.repo_name
     }
 
+    /// Prepends the bot comment marker to the text of a comment, unless it is already there, e.g.
+    /// because an existing comment is being edited.
+    fn mark_comment(text: &str) -> String {
+        if text.starts_with(BOT_COMMENT_MARKER) {
+            text.to_string()
+        } else {
+            format!("{BOT_COMMENT_MARKER}\n{text}")
+        }
+    }
+
     fn format_pr(&self, pr: PullRequestNumber) -> String {
         format!("{}/{}/{}", self.name().owner(), self.name().name(), pr)
     }
@@ -39,32 +67,208 @@ impl RepositoryClient for GithubRepositoryClient {
         self.name()
     }
 
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.api.rate_limit.get()
+    }
+
+    async fn get_rate_limit(&mut self) -> anyhow::Result<RateLimit> {
+        #[derive(serde::Deserialize, Debug)]
+        struct RateLimitResponse {
+            rate: RateResponse,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct RateResponse {
+            limit: u64,
+            remaining: u64,
+        }
+
+        let url = self.api.base_url().join("/rate_limit")?;
+        let response: RateLimitResponse = self
+            .api
+            .get(url)
+            .await?
+            .error_for_status()
+            .context("Cannot query the rate limit")?
+            .json()
+            .await?;
+        Ok(RateLimit {
+            remaining: response.rate.remaining,
+            limit: response.rate.limit,
+        })
+    }
+
     async fn get_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<PullRequest> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/pulls/{}",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            pr.0
+        ))?;
         let pr = self
-            .client
-            .pulls(self.repository().owner(), self.repository().name())
-            .get(pr.0)
-            .await
-            .map_err(|error| {
-                anyhow::anyhow!("Could not get PR {}/{}: {error:?}", self.repository(), pr.0)
-            })?;
-        Ok(github_pr_to_pr(pr))
+            .api
+            .get(url)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Could not get PR {}", self.format_pr(pr)))?
+            .json::<octocrab::models::pulls::PullRequest>()
+            .await?;
+        github_pr_to_pr(pr)
     }
 
     /// The comment will be posted as the Github App user of the bot.
     async fn post_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<()> {
-        self.client
-            .issues(&self.name().owner, &self.name().name)
-            .create_comment(pr.0, text)
-            .await
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            pr.0
+        ))?;
+        let body = serde_json::json!({ "body": Self::mark_comment(text) });
+        self.api
+            .post(url, Some(&body))
+            .await?
+            .error_for_status()
             .with_context(|| format!("Cannot post comment to {}", self.format_pr(pr)))?;
         Ok(())
     }
 
+    async fn create_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<u64> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/issues/{}/comments",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            pr.0
+        ))?;
+        let body = serde_json::json!({ "body": Self::mark_comment(text) });
+        let comment: CommentResponse = self
+            .api
+            .post(url, Some(&body))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot post comment to {}", self.format_pr(pr)))?
+            .json()
+            .await?;
+        Ok(comment.id)
+    }
+
+    async fn get_comment_text(&mut self, comment_id: u64) -> anyhow::Result<String> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/issues/comments/{comment_id}",
+            self.repo_name.owner(),
+            self.repo_name.name()
+        ))?;
+        let comment: CommentResponse = self
+            .api
+            .get(url)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot get comment {comment_id}"))?
+            .json()
+            .await?;
+        Ok(comment.body.unwrap_or_default())
+    }
+
+    async fn edit_comment(&mut self, comment_id: u64, text: &str) -> anyhow::Result<()> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/issues/comments/{comment_id}",
+            self.repo_name.owner(),
+            self.repo_name.name()
+        ))?;
+        let body = serde_json::json!({ "body": Self::mark_comment(text) });
+        self.api
+            .patch(url, Some(&body))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot edit comment {comment_id}"))?;
+        Ok(())
+    }
+
+    async fn get_branch_sha(&mut self, branch: &str) -> anyhow::Result<Option<CommitSha>> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/git/ref/{}",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+            Reference::Branch(branch.to_string()).ref_url()
+        ))?;
+        let response = self.api.get(url).await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let reference = response
+            .error_for_status()
+            .with_context(|| format!("Cannot resolve branch {branch}"))?
+            .json::<octocrab::models::repos::Ref>()
+            .await?;
+        match reference.object {
+            Object::Commit { sha, .. } => Ok(Some(CommitSha(sha))),
+            object => Err(anyhow::anyhow!(
+                "Branch {branch} does not point to a commit: {object:?}"
+            )),
+        }
+    }
+
+    async fn list_branches(&mut self, prefix: &str) -> anyhow::Result<Vec<Branch>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct RefObject {
+            sha: String,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct RefResponse {
+            #[serde(rename = "ref")]
+            name: String,
+            object: RefObject,
+        }
+
+        let refs: Vec<RefResponse> = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/git/matching-refs/heads/{prefix}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot list branches starting with {prefix}"))?
+            .json()
+            .await?;
+        Ok(refs
+            .into_iter()
+            .filter_map(|reference| {
+                let name = reference.name.strip_prefix("refs/heads/")?.to_string();
+                Some(Branch {
+                    name,
+                    sha: CommitSha(reference.object.sha),
+                })
+            })
+            .collect())
+    }
+
     async fn set_branch_to_sha(&mut self, branch: &str, sha: &CommitSha) -> anyhow::Result<()> {
         Ok(set_branch_to_commit(self, branch.to_string(), sha).await?)
     }
 
+    async fn delete_branch(&mut self, branch: &str) -> anyhow::Result<()> {
+        let response = self
+            .api
+            .delete(self.api.base_url().join(&format!(
+                "/repos/{}/{}/git/refs/heads/{branch}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+            ))?)
+            .await
+            .with_context(|| format!("Cannot delete branch {branch}"))?;
+        match response.status() {
+            // The branch does not exist (anymore), e.g. because it has been deleted concurrently
+            StatusCode::UNPROCESSABLE_ENTITY | StatusCode::NOT_FOUND => Ok(()),
+            status if status.is_success() => Ok(()),
+            status => Err(anyhow::anyhow!(
+                "Cannot delete branch {branch}: unexpected status {status}"
+            )),
+        }
+    }
+
     async fn merge_branches(
         &mut self,
         base: &str,
@@ -80,22 +284,26 @@ impl RepositoryClient for GithubRepositoryClient {
         sha: &CommitSha,
     ) -> anyhow::Result<Vec<CheckSuite>> {
         let response = self
-            .client
-            ._get(
-                self.client.base_url.join(&format!(
-                    "/repos/{}/{}/commits/{}/check-suites",
-                    self.repo_name.owner(),
-                    self.repo_name.name(),
-                    sha.0
-                ))?,
-                None::<&()>,
-            )
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/commits/{}/check-suites",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                sha.0
+            ))?)
             .await?;
 
         #[derive(serde::Deserialize, Debug)]
         struct CheckSuitePayload<'a> {
             conclusion: Option<&'a str>,
             head_branch: &'a str,
+            #[serde(borrow)]
+            app: Option<CheckSuiteApp<'a>>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct CheckSuiteApp<'a> {
+            slug: &'a str,
         }
 
         #[derive(serde::Deserialize, Debug)]
@@ -112,6 +320,7 @@ impl RepositoryClient for GithubRepositoryClient {
             .into_iter()
             .filter(|suite| suite.head_branch == branch)
             .map(|suite| CheckSuite {
+                github_actions: suite.app.is_some_and(|app| app.slug == GITHUB_ACTIONS_APP),
                 status: match suite.conclusion {
                     Some(status) => match status {
                         "success" => CheckSuiteStatus::Success,
@@ -135,26 +344,383 @@ impl RepositoryClient for GithubRepositoryClient {
         Ok(suites)
     }
 
-    async fn cancel_workflows(&mut self, run_ids: Vec<RunId>) -> anyhow::Result<()> {
-        let actions = self.client.actions();
+    async fn get_commit(&mut self, sha: &CommitSha) -> anyhow::Result<Commit> {
+        let response: CommitResponse = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/commits/{}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                sha.0
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot load commit {sha}"))?
+            .json()
+            .await?;
+        Ok(github_commit_to_commit(response))
+    }
 
+    async fn resolve_ref(&mut self, short_sha: &str) -> Result<CommitSha, ResolveRefError> {
+        #[derive(serde::Deserialize, Debug)]
+        struct ShaResponse {
+            sha: String,
+        }
+
+        let response = self
+            .api
+            .get(
+                self.api
+                    .base_url()
+                    .join(&format!(
+                        "/repos/{}/{}/commits/{short_sha}",
+                        self.repo_name.owner(),
+                        self.repo_name.name(),
+                    ))
+                    .map_err(anyhow::Error::from)?,
+            )
+            .await
+            .with_context(|| format!("Cannot resolve commit {short_sha}"))?;
+        match response.status() {
+            status if status.is_success() => {
+                let response: ShaResponse = response.json().await.map_err(anyhow::Error::from)?;
+                Ok(CommitSha(response.sha))
+            }
+            // GitHub answers with 422 both for unknown and for ambiguous SHAs, and describes the
+            // reason only in the message
+            StatusCode::NOT_FOUND | StatusCode::UNPROCESSABLE_ENTITY => {
+                let text = response.text().await.unwrap_or_default();
+                if text.to_lowercase().contains("ambiguous") {
+                    Err(ResolveRefError::Ambiguous)
+                } else {
+                    Err(ResolveRefError::NotFound)
+                }
+            }
+            status => Err(anyhow::anyhow!(
+                "Cannot resolve commit {short_sha}: unexpected status {status}"
+            )
+            .into()),
+        }
+    }
+
+    async fn list_pr_files(&mut self, pr: PullRequestNumber) -> anyhow::Result<Vec<String>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct FilePayload {
+            filename: String,
+        }
+
+        const PAGE_SIZE: usize = 100;
+        let mut files = vec![];
+        for page in 1.. {
+            let mut url = self.api.base_url().join(&format!(
+                "/repos/{}/{}/pulls/{pr}/files",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+            ))?;
+            url.query_pairs_mut()
+                .append_pair("per_page", &PAGE_SIZE.to_string())
+                .append_pair("page", &page.to_string());
+            let response: Vec<FilePayload> = self
+                .api
+                .get(url)
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Cannot load files of {}", self.format_pr(pr)))?
+                .json()
+                .await?;
+            let last_page = response.len() < PAGE_SIZE;
+            files.extend(response.into_iter().map(|file| file.filename));
+            if last_page {
+                break;
+            }
+        }
+        Ok(files)
+    }
+
+    async fn get_check_runs_for_ref(&mut self, reference: &str) -> anyhow::Result<Vec<CheckRun>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CheckRunPayload {
+            name: String,
+            status: String,
+            conclusion: Option<String>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct CheckRunsResponse {
+            total_count: usize,
+            check_runs: Vec<CheckRunPayload>,
+        }
+
+        let mut check_runs = vec![];
+        for page in 1.. {
+            let mut url = self.api.base_url().join(&format!(
+                "/repos/{}/{}/commits/{reference}/check-runs",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+            ))?;
+            url.query_pairs_mut()
+                .append_pair("per_page", "100")
+                .append_pair("page", &page.to_string());
+            let response: CheckRunsResponse = self
+                .api
+                .get(url)
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Cannot load check runs of {reference}"))?
+                .json()
+                .await?;
+            let last_page = response.check_runs.is_empty()
+                || check_runs.len() + response.check_runs.len() >= response.total_count;
+            check_runs.extend(response.check_runs.into_iter().map(|run| CheckRun {
+                status: match (run.status.as_str(), run.conclusion.as_deref()) {
+                    ("completed", Some("success" | "skipped" | "neutral")) => {
+                        CheckSuiteStatus::Success
+                    }
+                    ("completed", _) => CheckSuiteStatus::Failure,
+                    _ => CheckSuiteStatus::Pending,
+                },
+                name: run.name,
+            }));
+            if last_page {
+                break;
+            }
+        }
+        Ok(check_runs)
+    }
+
+    async fn get_workflow_runs_for_commit(
+        &mut self,
+        branch: &str,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Vec<WorkflowRun>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunPayload {
+            id: u64,
+            status: String,
+            conclusion: Option<String>,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowRunsResponse {
+            workflow_runs: Vec<WorkflowRunPayload>,
+        }
+
+        let mut url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/actions/runs",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("branch", branch)
+            .append_pair("head_sha", &sha.0);
+        let response: WorkflowRunsResponse = self
+            .api
+            .get(url)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot load workflow runs of {sha}"))?
+            .json()
+            .await?;
+        Ok(response
+            .workflow_runs
+            .into_iter()
+            .map(|run| WorkflowRun {
+                run_id: RunId(run.id),
+                status: match (run.status.as_str(), run.conclusion.as_deref()) {
+                    ("completed", Some("success")) => WorkflowStatus::Success,
+                    ("completed", _) => WorkflowStatus::Failure,
+                    _ => WorkflowStatus::Pending,
+                },
+            })
+            .collect())
+    }
+
+    async fn compare_commits(
+        &mut self,
+        base: &str,
+        head: &str,
+    ) -> anyhow::Result<CommitComparison> {
+        #[derive(serde::Deserialize, Debug)]
+        struct ComparisonResponse {
+            status: String,
+            ahead_by: u64,
+            behind_by: u64,
+        }
+
+        let response: ComparisonResponse = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/compare/{base}...{head}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot compare {head} with {base}"))?
+            .json()
+            .await?;
+        let status = match response.status.as_str() {
+            "identical" => CommitComparisonStatus::Identical,
+            "ahead" => CommitComparisonStatus::Ahead,
+            "behind" => CommitComparisonStatus::Behind,
+            "diverged" => CommitComparisonStatus::Diverged,
+            status => {
+                return Err(anyhow::anyhow!(
+                    "Unknown status of comparison of {head} with {base}: {status}"
+                ))
+            }
+        };
+        Ok(CommitComparison {
+            status,
+            ahead_by: response.ahead_by,
+            behind_by: response.behind_by,
+        })
+    }
+
+    async fn is_collaborator(&mut self, username: &str) -> anyhow::Result<bool> {
+        let response = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/collaborators/{}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                username
+            ))?)
+            .await?;
+        // The endpoint responds with 204 if the user is a collaborator and with 404 otherwise
+        match response.status().as_u16() {
+            204 => Ok(true),
+            404 => Ok(false),
+            status => Err(anyhow::anyhow!(
+                "Cannot check if {username} is a collaborator of {}: status {status}",
+                self.repo_name
+            )),
+        }
+    }
+
+    async fn get_branch_protection(
+        &mut self,
+        branch: &str,
+    ) -> anyhow::Result<Option<BranchProtection>> {
+        let response = self
+            .api
+            .get(api_url(
+                self.api.base_url(),
+                &[
+                    "repos",
+                    self.repo_name.owner(),
+                    self.repo_name.name(),
+                    "branches",
+                    branch,
+                    "protection",
+                    "required_status_checks",
+                ],
+            )?)
+            .await?;
+
+        // The branch is not protected, or it does not require any status checks
+        if response.status().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct RequiredStatusChecksResponse {
+            contexts: Vec<String>,
+        }
+
+        let response: RequiredStatusChecksResponse = response
+            .error_for_status()
+            .with_context(|| format!("Cannot load protection of branch {branch}"))?
+            .json()
+            .await?;
+        Ok(Some(BranchProtection {
+            required_checks: response.contexts,
+        }))
+    }
+
+    async fn get_workflows(&mut self) -> anyhow::Result<Vec<String>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowResponse {
+            name: String,
+            state: String,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct WorkflowsResponse {
+            workflows: Vec<WorkflowResponse>,
+        }
+
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/actions/workflows?per_page=100",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+        ))?;
+        let workflows: WorkflowsResponse = self
+            .api
+            .get(url)
+            .await?
+            .error_for_status()
+            .context("Cannot load workflows")?
+            .json()
+            .await?;
+        Ok(workflows
+            .workflows
+            .into_iter()
+            .filter(|workflow| workflow.state == "active")
+            .map(|workflow| workflow.name)
+            .collect())
+    }
+
+    async fn dispatch_workflow(&mut self, run_id: RunId) -> anyhow::Result<()> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/actions/runs/{run_id}/rerun-failed-jobs",
+            self.repo_name.owner(),
+            self.repo_name.name(),
+        ))?;
+        self.api
+            .post(url, None::<&()>)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot rerun workflow run {run_id}"))?;
+        Ok(())
+    }
+
+    async fn cancel_workflows(&mut self, run_ids: Vec<RunId>) -> anyhow::Result<()> {
+        let repo: &Self = self;
         // Cancel all workflows in parallel
-        futures::future::join_all(run_ids.into_iter().map(|run_id| {
-            actions.cancel_workflow_run(self.repo_name.owner(), self.repo_name.name(), run_id)
+        futures::future::join_all(run_ids.into_iter().map(|run_id| async move {
+            let url = repo.api.base_url().join(&format!(
+                "/repos/{}/{}/actions/runs/{run_id}/cancel",
+                repo.repo_name.owner(),
+                repo.repo_name.name(),
+            ))?;
+            repo.api
+                .post(url, None::<&()>)
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Cannot cancel workflow run {run_id}"))?;
+            anyhow::Ok(())
         }))
         .await
         .into_iter()
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<anyhow::Result<Vec<_>>>()?;
 
         Ok(())
     }
 
     async fn add_labels(&mut self, pr: PullRequestNumber, labels: &[String]) -> anyhow::Result<()> {
-        let client = self.client.issues(self.name().owner(), self.name().name());
         if !labels.is_empty() {
-            client
-                .add_labels(pr.0, labels)
-                .await
+            let url = self.api.base_url().join(&format!(
+                "/repos/{}/{}/issues/{}/labels",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                pr.0
+            ))?;
+            let body = serde_json::json!({ "labels": labels });
+            self.api
+                .post(url, Some(&body))
+                .await?
+                .error_for_status()
                 .context("Cannot add label(s) to PR")?;
         }
 
@@ -166,37 +732,298 @@ impl RepositoryClient for GithubRepositoryClient {
         pr: PullRequestNumber,
         labels: &[String],
     ) -> anyhow::Result<()> {
-        let client = self.client.issues(self.name().owner(), self.name().name());
+        let repo: &Self = self;
         // The GitHub API only allows removing labels one by one, so we remove all of them in
         // parallel to speed it up a little.
-        let labels_to_remove_futures = labels.iter().map(|label| client.remove_label(pr.0, label));
+        let labels_to_remove_futures = labels.iter().map(|label| async move {
+            let pr_number = pr.0.to_string();
+            let url = api_url(
+                repo.api.base_url(),
+                &[
+                    "repos",
+                    repo.repo_name.owner(),
+                    repo.repo_name.name(),
+                    "issues",
+                    &pr_number,
+                    "labels",
+                    label,
+                ],
+            )?;
+            let response = repo.api.delete(url).await?;
+            // This status is returned if we try to remove a label that does not exist on the
+            // issue. This should be a no-op, rather than an error, therefore we swallow it.
+            if response.status() == StatusCode::NOT_FOUND {
+                log::trace!("Trying to remove label which does not exist on PR {pr}");
+                return anyhow::Ok(());
+            }
+            response.error_for_status()?;
+            Ok(())
+        });
         futures::future::join_all(labels_to_remove_futures)
             .await
             .into_iter()
-            .filter(|result| match result {
-                Ok(_) => false,
-                Err(error) => match error {
-                    // This error is returned if we try to remove a label that does not exist on the issue.
-                    // This should be a no-op, rather than an error, therefore we swallow this error.
-                    Error::GitHub { source, .. }
-                        if source.message.contains("Label does not exist") =>
-                    {
-                        log::trace!("Trying to remove label which does not exist on PR {pr}");
-                        false
-                    }
-                    _ => true,
-                },
-            })
-            .collect::<Result<Vec<_>, _>>()
+            .collect::<anyhow::Result<Vec<_>>>()
             .context("Cannot remove label(s) from PR")?;
 
         Ok(())
     }
+
+    async fn request_reviewers(
+        &mut self,
+        pr: PullRequestNumber,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> anyhow::Result<()> {
+        if !reviewers.is_empty() || !team_reviewers.is_empty() {
+            let url = self.api.base_url().join(&format!(
+                "/repos/{}/{}/pulls/{}/requested_reviewers",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                pr.0
+            ))?;
+            let body = serde_json::json!({
+                "reviewers": reviewers,
+                "team_reviewers": team_reviewers,
+            });
+            self.api
+                .post(url, Some(&body))
+                .await?
+                .error_for_status()
+                .with_context(|| format!("Cannot request reviews on {}", self.format_pr(pr)))?;
+        }
+        Ok(())
+    }
+
+    async fn close_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<()> {
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/pulls/{pr}",
+            self.repo_name.owner(),
+            self.repo_name.name()
+        ))?;
+        let body = serde_json::json!({ "state": "closed" });
+        self.api
+            .patch(url, Some(&body))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot close {}", self.format_pr(pr)))?;
+        Ok(())
+    }
+
+    async fn get_combined_status(
+        &mut self,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Option<CombinedStatus>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CombinedStatusResponse {
+            state: String,
+            total_count: u64,
+        }
+
+        let response: CombinedStatusResponse = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/commits/{}/status",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                sha.0
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot load combined status of {sha}"))?
+            .json()
+            .await?;
+        // GitHub reports `pending` also for commits without any statuses
+        if response.total_count == 0 {
+            return Ok(None);
+        }
+        Ok(Some(match response.state.as_str() {
+            "success" => CombinedStatus::Success,
+            "failure" | "error" => CombinedStatus::Failure,
+            _ => CombinedStatus::Pending,
+        }))
+    }
+
+    async fn list_artifacts(&mut self, run_id: RunId) -> anyhow::Result<Vec<Artifact>> {
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactResponse {
+            id: u64,
+            name: String,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct ArtifactsResponse {
+            artifacts: Vec<ArtifactResponse>,
+        }
+
+        let response: ArtifactsResponse = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/actions/runs/{}/artifacts",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                run_id.0
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot load artifacts of workflow run {run_id}"))?
+            .json()
+            .await?;
+        // The download URL of the API requires authentication, so link the web page instead
+        Ok(response
+            .artifacts
+            .into_iter()
+            .map(|artifact| Artifact {
+                url: format!(
+                    "https://github.com/{}/{}/actions/runs/{}/artifacts/{}",
+                    self.repo_name.owner(),
+                    self.repo_name.name(),
+                    run_id.0,
+                    artifact.id
+                ),
+                name: artifact.name,
+            })
+            .collect())
+    }
+
+    async fn create_or_update_check_run(
+        &mut self,
+        sha: &CommitSha,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        #[derive(serde::Deserialize, Debug)]
+        struct CheckRunResponse {
+            id: u64,
+        }
+
+        #[derive(serde::Deserialize, Debug)]
+        struct CheckRunsResponse {
+            check_runs: Vec<CheckRunResponse>,
+        }
+
+        let existing: CheckRunsResponse = self
+            .api
+            .get(self.api.base_url().join(&format!(
+                "/repos/{}/{}/commits/{}/check-runs?check_name={CHECK_RUN_NAME}",
+                self.repo_name.owner(),
+                self.repo_name.name(),
+                sha.0
+            ))?)
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot load check runs of {sha}"))?
+            .json()
+            .await?;
+
+        let mut body = match status {
+            CheckRunStatus::InProgress => serde_json::json!({ "status": "in_progress" }),
+            CheckRunStatus::Success => {
+                serde_json::json!({ "status": "completed", "conclusion": "success" })
+            }
+            CheckRunStatus::Failure => {
+                serde_json::json!({ "status": "completed", "conclusion": "failure" })
+            }
+            CheckRunStatus::Cancelled => {
+                serde_json::json!({ "status": "completed", "conclusion": "cancelled" })
+            }
+        };
+        let response = match existing.check_runs.first() {
+            Some(check_run) => {
+                let url = self.api.base_url().join(&format!(
+                    "/repos/{}/{}/check-runs/{}",
+                    self.repo_name.owner(),
+                    self.repo_name.name(),
+                    check_run.id
+                ))?;
+                self.api.patch(url, Some(&body)).await?
+            }
+            None => {
+                body["name"] = CHECK_RUN_NAME.into();
+                body["head_sha"] = sha.0.clone().into();
+                let url = self.api.base_url().join(&format!(
+                    "/repos/{}/{}/check-runs",
+                    self.repo_name.owner(),
+                    self.repo_name.name()
+                ))?;
+                self.api.post(url, Some(&body)).await?
+            }
+        };
+        response
+            .error_for_status()
+            .with_context(|| format!("Cannot report check run status of {sha}"))?;
+        Ok(())
+    }
+
+    async fn create_deployment(
+        &mut self,
+        sha: &CommitSha,
+        environment: &str,
+    ) -> anyhow::Result<u64> {
+        #[derive(serde::Deserialize, Debug)]
+        struct DeploymentResponse {
+            id: u64,
+        }
+
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/deployments",
+            self.repo_name.owner(),
+            self.repo_name.name()
+        ))?;
+        // The build itself is the check, so GitHub must not wait for the status checks of the
+        // commit, and the commit is not merged with the default branch
+        let body = serde_json::json!({
+            "ref": sha.0,
+            "environment": environment,
+            "auto_merge": false,
+            "required_contexts": [],
+        });
+        let deployment: DeploymentResponse = self
+            .api
+            .post(url, Some(&body))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot create deployment of {sha} to {environment}"))?
+            .json()
+            .await?;
+        Ok(deployment.id)
+    }
+
+    async fn update_deployment_status(
+        &mut self,
+        deployment_id: u64,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        let state = match status {
+            CheckRunStatus::InProgress => "in_progress",
+            CheckRunStatus::Success => "success",
+            CheckRunStatus::Failure => "failure",
+            CheckRunStatus::Cancelled => "inactive",
+        };
+        let url = self.api.base_url().join(&format!(
+            "/repos/{}/{}/deployments/{deployment_id}/statuses",
+            self.repo_name.owner(),
+            self.repo_name.name()
+        ))?;
+        let body = serde_json::json!({ "state": state });
+        self.api
+            .post(url, Some(&body))
+            .await?
+            .error_for_status()
+            .with_context(|| format!("Cannot update status of deployment {deployment_id}"))?;
+        Ok(())
+    }
 }
 
-fn github_pr_to_pr(pr: octocrab::models::pulls::PullRequest) -> PullRequest {
-    PullRequest {
+fn github_pr_to_pr(pr: octocrab::models::pulls::PullRequest) -> anyhow::Result<PullRequest> {
+    let author = pr
+        .user
+        .ok_or_else(|| anyhow::anyhow!("PR {} has no author", pr.number))?;
+    Ok(PullRequest {
         number: pr.number.into(),
+        author: GithubUser {
+            id: author.id,
+            username: author.login,
+            html_url: author.html_url,
+        },
         head_label: pr.head.label.unwrap_or_else(|| "<unknown>".to_string()),
         head: Branch {
             name: pr.head.ref_field,
@@ -208,5 +1035,562 @@ fn github_pr_to_pr(pr: octocrab::models::pulls::PullRequest) -> PullRequest {
         },
         title: pr.title.unwrap_or_default(),
         message: pr.body.unwrap_or_default(),
+        labels: pr
+            .labels
+            .unwrap_or_default()
+            .into_iter()
+            .map(|label| label.name)
+            .collect(),
+        requested_reviewers: pr
+            .requested_reviewers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|user| user.login)
+            .collect(),
+        draft: pr.draft.unwrap_or_default(),
+        mergeable: pr.mergeable,
+        closed: pr.state == Some(IssueState::Closed),
+    })
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CommentResponse {
+    id: u64,
+    body: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CommitResponse {
+    sha: String,
+    commit: CommitDetail,
+    parents: Vec<CommitParent>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CommitDetail {
+    message: String,
+    // Missing for commits whose author has been removed
+    author: Option<CommitAuthor>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CommitAuthor {
+    name: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct CommitParent {
+    sha: String,
+}
+
+fn github_commit_to_commit(commit: CommitResponse) -> Commit {
+    Commit {
+        sha: CommitSha(commit.sha),
+        message: commit.commit.message,
+        author: commit
+            .commit
+            .author
+            .map(|author| author.name)
+            .unwrap_or_else(|| "<unknown>".to_string()),
+        parents: commit
+            .parents
+            .into_iter()
+            .map(|parent| CommitSha(parent.sha))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, VecDeque};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::extract::{Path, Query};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::{delete, get, patch, post};
+    use axum::{Json, Router};
+    use octocrab::Octocrab;
+
+    use crate::bors::{CombinedStatus, RepositoryClient};
+    use crate::database::CheckRunStatus;
+    use crate::github::api::client::{
+        github_commit_to_commit, CommitResponse, GithubRepositoryClient,
+    };
+    use crate::github::api::rate_limit::{RateLimit, RateLimitToken, RateLimitTracker};
+    use crate::github::api::request::ApiClient;
+    use crate::github::api::retry::RetryPolicy;
+    use crate::github::api::BOT_COMMENT_MARKER;
+    use crate::github::{CommitSha, PullRequestNumber};
+    use crate::tests::io::load_test_file;
+    use crate::tests::state::default_repo_name;
+
+    /// Creates a client for a local server that answers branch deletions with the status
+    /// encoded in the branch name, e.g. `gone-404`.
+    async fn create_branch_client() -> GithubRepositoryClient {
+        create_client(Router::new().route(
+            "/repos/owner/name/git/refs/heads/:branch",
+            delete(|Path(branch): Path<String>| async move {
+                let status = branch.rsplit('-').next().unwrap().parse::<u16>().unwrap();
+                StatusCode::from_u16(status).unwrap()
+            }),
+        ))
+        .await
+    }
+
+    #[tokio::test]
+    async fn list_branches() {
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/git/matching-refs/heads/automation/bors/",
+            get(|| async {
+                Json(serde_json::json!([
+                    { "ref": "refs/heads/automation/bors/try", "object": { "sha": "sha-1" } },
+                    { "ref": "refs/heads/automation/bors/try-merge", "object": { "sha": "sha-2" } }
+                ]))
+            }),
+        ))
+        .await;
+        let branches = client
+            .list_branches("automation/bors/")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|branch| (branch.name, branch.sha.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            branches,
+            vec![
+                ("automation/bors/try".to_string(), "sha-1".to_string()),
+                ("automation/bors/try-merge".to_string(), "sha-2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_branch_protection_of_nested_branch() {
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/branches/:branch/protection/required_status_checks",
+            get(|Path(branch): Path<String>| async move {
+                assert_eq!(branch, "release/1.0");
+                Json(serde_json::json!({ "contexts": ["ci"] }))
+            }),
+        ))
+        .await;
+        let protection = client
+            .get_branch_protection("release/1.0")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(protection.required_checks, vec!["ci"]);
+    }
+
+    #[tokio::test]
+    async fn get_pull_request() {
+        let event: serde_json::Value =
+            serde_json::from_str(&load_test_file("webhook/pull-request-opened.json")).unwrap();
+        let mut pr = event["pull_request"].clone();
+        pr["draft"] = true.into();
+        pr["mergeable"] = false.into();
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/pulls/6",
+            get(move || async move { Json(pr) }),
+        ))
+        .await;
+
+        let pr = client.get_pull_request(PullRequestNumber(6)).await.unwrap();
+        assert_eq!(pr.base.name, "main");
+        assert_eq!(pr.labels, vec!["bar", "baz", "foobar"]);
+        assert!(pr.draft);
+        assert_eq!(pr.mergeable, Some(false));
+    }
+
+    #[tokio::test]
+    async fn create_deployment() {
+        let states = Arc::new(Mutex::new(vec![]));
+        let recorded = states.clone();
+        let mut client = create_client(
+            Router::new()
+                .route(
+                    "/repos/owner/name/deployments",
+                    post(|Json(body): Json<serde_json::Value>| async move {
+                        assert_eq!(body["ref"], "sha-1");
+                        assert_eq!(body["environment"], "staging");
+                        (StatusCode::CREATED, Json(serde_json::json!({ "id": 42 })))
+                    }),
+                )
+                .route(
+                    "/repos/owner/name/deployments/42/statuses",
+                    post(move |Json(body): Json<serde_json::Value>| async move {
+                        recorded.lock().unwrap().push(body["state"].clone());
+                        StatusCode::CREATED
+                    }),
+                ),
+        )
+        .await;
+
+        let id = client
+            .create_deployment(&CommitSha("sha-1".to_string()), "staging")
+            .await
+            .unwrap();
+        assert_eq!(id, 42);
+        for status in [CheckRunStatus::InProgress, CheckRunStatus::Cancelled] {
+            client.update_deployment_status(id, status).await.unwrap();
+        }
+        assert_eq!(*states.lock().unwrap(), vec!["in_progress", "inactive"]);
+    }
+
+    #[tokio::test]
+    async fn get_rate_limit() {
+        let mut client = create_client(Router::new().route(
+            "/rate_limit",
+            get(|| async {
+                Json(serde_json::json!({
+                    "resources": { "core": { "limit": 5000, "remaining": 120 } },
+                    "rate": { "limit": 5000, "remaining": 120 }
+                }))
+            }),
+        ))
+        .await;
+        assert_eq!(
+            client.get_rate_limit().await.unwrap(),
+            RateLimit {
+                remaining: 120,
+                limit: 5000
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn track_rate_limit_of_label_requests() {
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/issues/1/labels",
+            post(|| async {
+                (
+                    [
+                        ("x-ratelimit-remaining", "42"),
+                        ("x-ratelimit-limit", "5000"),
+                    ],
+                    Json(serde_json::json!([])),
+                )
+            }),
+        ))
+        .await;
+        client
+            .add_labels(PullRequestNumber(1), &["S-waiting".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(
+            client.rate_limit(),
+            Some(RateLimit {
+                remaining: 42,
+                limit: 5000
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_comment() {
+        let body = Arc::new(Mutex::new(String::new()));
+        let (created, read, edited) = (body.clone(), body.clone(), body.clone());
+        let mut client = create_client(
+            Router::new()
+                .route(
+                    "/repos/owner/name/issues/1/comments",
+                    post(move |Json(body): Json<serde_json::Value>| async move {
+                        *created.lock().unwrap() = body["body"].as_str().unwrap().to_string();
+                        (StatusCode::CREATED, Json(serde_json::json!({ "id": 7 })))
+                    }),
+                )
+                .route(
+                    "/repos/owner/name/issues/comments/7",
+                    get(move || async move {
+                        Json(serde_json::json!({ "id": 7, "body": *read.lock().unwrap() }))
+                    })
+                    .patch(
+                        move |Json(body): Json<serde_json::Value>| async move {
+                            *edited.lock().unwrap() = body["body"].as_str().unwrap().to_string();
+                            StatusCode::OK
+                        },
+                    ),
+                ),
+        )
+        .await;
+
+        let id = client
+            .create_comment(PullRequestNumber(1), "first")
+            .await
+            .unwrap();
+        assert_eq!(id, 7);
+        let text = client.get_comment_text(id).await.unwrap();
+        client
+            .edit_comment(id, &format!("{text}\nsecond"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *body.lock().unwrap(),
+            format!("{BOT_COMMENT_MARKER}\nfirst\nsecond")
+        );
+    }
+
+    #[tokio::test]
+    async fn list_pr_files() {
+        let client_files = (0..150)
+            .map(|index| format!("docs/{index}.md"))
+            .collect::<Vec<_>>();
+        let files = client_files.clone();
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/pulls/5/files",
+            get(
+                move |Query(query): Query<HashMap<String, String>>| async move {
+                    let page = query["page"].parse::<usize>().unwrap();
+                    let files = files
+                        .iter()
+                        .skip((page - 1) * 100)
+                        .take(100)
+                        .map(|file| serde_json::json!({ "filename": file }))
+                        .collect::<Vec<_>>();
+                    Json(files)
+                },
+            ),
+        ))
+        .await;
+
+        let files = client.list_pr_files(PullRequestNumber(5)).await.unwrap();
+        assert_eq!(files, client_files);
+    }
+
+    #[tokio::test]
+    async fn close_pull_request() {
+        let state = Arc::new(Mutex::new(String::new()));
+        let updated = state.clone();
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/pulls/5",
+            patch(move |Json(body): Json<serde_json::Value>| async move {
+                *updated.lock().unwrap() = body["state"].as_str().unwrap().to_string();
+                StatusCode::OK
+            }),
+        ))
+        .await;
+
+        client
+            .close_pull_request(PullRequestNumber(5))
+            .await
+            .unwrap();
+        assert_eq!(*state.lock().unwrap(), "closed");
+    }
+
+    /// Creates a client for a local server that answers requests for the combined status of a
+    /// commit with the given HTTP statuses, in order, and then with success.
+    async fn create_status_client(statuses: &[u16]) -> (GithubRepositoryClient, Arc<Mutex<u32>>) {
+        let statuses = Arc::new(Mutex::new(
+            statuses.iter().copied().collect::<VecDeque<_>>(),
+        ));
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let client = create_client(Router::new().route(
+            "/repos/owner/name/commits/:sha/status",
+            get(move || async move {
+                *counter.lock().unwrap() += 1;
+                match statuses.lock().unwrap().pop_front() {
+                    Some(status) => StatusCode::from_u16(status).unwrap().into_response(),
+                    None => Json(serde_json::json!({ "state": "success", "total_count": 1 }))
+                        .into_response(),
+                }
+            }),
+        ))
+        .await;
+        (client, requests)
+    }
+
+    /// Creates a client for a local server that answers the posting of comments with the given
+    /// statuses, and then with success. Returns the number of received requests.
+    async fn create_comment_client(statuses: &[u16]) -> (GithubRepositoryClient, Arc<Mutex<u32>>) {
+        let statuses = Arc::new(Mutex::new(
+            statuses.iter().copied().collect::<VecDeque<_>>(),
+        ));
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let client = create_client(Router::new().route(
+            "/repos/owner/name/issues/:pr/comments",
+            post(move || async move {
+                *counter.lock().unwrap() += 1;
+                let status = statuses.lock().unwrap().pop_front().unwrap_or(201);
+                StatusCode::from_u16(status).unwrap()
+            }),
+        ))
+        .await;
+        (client, requests)
+    }
+
+    #[tokio::test]
+    async fn retry_unavailable_mutation() {
+        let (mut client, requests) = create_comment_client(&[503]).await;
+        client
+            .post_comment(PullRequestNumber(1), "Hello")
+            .await
+            .unwrap();
+        assert_eq!(*requests.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn do_not_retry_mutation_after_gateway_error() {
+        // The comment could have been posted before the gateway failed
+        let (mut client, requests) = create_comment_client(&[502]).await;
+        assert!(client
+            .post_comment(PullRequestNumber(1), "Hello")
+            .await
+            .is_err());
+        assert_eq!(*requests.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn resume_after_secondary_rate_limit() {
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/issues/:pr/comments",
+            post(move || async move {
+                let mut requests = counter.lock().unwrap();
+                *requests += 1;
+                if *requests == 1 {
+                    (
+                        StatusCode::FORBIDDEN,
+                        [("retry-after", "1")],
+                        "You have exceeded a secondary rate limit.",
+                    )
+                        .into_response()
+                } else {
+                    (StatusCode::CREATED, Json(serde_json::json!({}))).into_response()
+                }
+            }),
+        ))
+        .await;
+
+        let start = std::time::Instant::now();
+        client
+            .post_comment(PullRequestNumber(1), "Hello")
+            .await
+            .unwrap();
+        assert_eq!(*requests.lock().unwrap(), 2);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    async fn create_client(app: Router) -> GithubRepositoryClient {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        let event: serde_json::Value =
+            serde_json::from_str(&load_test_file("webhook/issue-comment.json")).unwrap();
+        let octocrab = Octocrab::builder()
+            .base_url(format!("http://{address}"))
+            .unwrap()
+            .build()
+            .unwrap();
+        let mut api = ApiClient::new(
+            octocrab,
+            RateLimitTracker::default().token(RateLimitToken::Personal),
+        );
+        api.retry_policy = RetryPolicy::without_delay(3);
+        GithubRepositoryClient {
+            api,
+            repo_name: default_repo_name(),
+            repository: serde_json::from_value(event["repository"].clone()).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_unavailable() {
+        let (mut client, requests) = create_status_client(&[503]).await;
+        let status = client
+            .get_combined_status(&CommitSha("sha".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(status, Some(CombinedStatus::Success));
+        assert_eq!(*requests.lock().unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_combined_status_without_statuses() {
+        let mut client = create_client(Router::new().route(
+            "/repos/owner/name/commits/:sha/status",
+            get(|| async { Json(serde_json::json!({ "state": "pending", "total_count": 0 })) }),
+        ))
+        .await;
+        let status = client
+            .get_combined_status(&CommitSha("sha".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(status, None);
+    }
+
+    #[tokio::test]
+    async fn retry_until_attempts_run_out() {
+        let (mut client, requests) = create_status_client(&[503, 502, 504]).await;
+        assert!(client
+            .get_combined_status(&CommitSha("sha".to_string()))
+            .await
+            .is_err());
+        assert_eq!(*requests.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn do_not_retry_client_errors() {
+        let (mut client, requests) = create_status_client(&[404]).await;
+        assert!(client
+            .get_combined_status(&CommitSha("sha".to_string()))
+            .await
+            .is_err());
+        assert_eq!(*requests.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn delete_branch() {
+        let mut client = create_branch_client().await;
+        assert!(client.delete_branch("branch-204").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_missing_branch() {
+        let mut client = create_branch_client().await;
+        assert!(client.delete_branch("gone-404").await.is_ok());
+        assert!(client.delete_branch("gone-422").await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn delete_branch_error() {
+        let mut client = create_branch_client().await;
+        assert!(client.delete_branch("branch-500").await.is_err());
+    }
+
+    #[test]
+    fn parse_commit() {
+        let response: CommitResponse = serde_json::from_str(
+            r#"{
+  "sha": "abc",
+  "commit": {
+    "author": { "name": "Ferris", "email": "ferris@rust-lang.org", "date": "2023-06-01T10:00:00Z" },
+    "message": "Fix the build"
+  },
+  "parents": [{ "sha": "parent1", "url": "" }, { "sha": "parent2", "url": "" }]
+}"#,
+        )
+        .unwrap();
+        let commit = github_commit_to_commit(response);
+        assert_eq!(commit.sha, CommitSha("abc".to_string()));
+        assert_eq!(commit.message, "Fix the build");
+        assert_eq!(commit.author, "Ferris");
+        assert_eq!(
+            commit.parents,
+            vec![
+                CommitSha("parent1".to_string()),
+                CommitSha("parent2".to_string())
+            ]
+        );
     }
 }
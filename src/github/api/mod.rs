@@ -4,11 +4,15 @@ use std::pin::Pin;
 
 use anyhow::Context;
 use base64::Engine;
-use octocrab::models::{App, AppId, InstallationRepositories, Repository};
-use octocrab::Octocrab;
-use secrecy::{ExposeSecret, SecretVec};
+use futures::StreamExt;
+use octocrab::models::{AppId, InstallationRepositories, Repository, User, UserId};
+use octocrab::{Octocrab, OctocrabBuilder};
+use secrecy::{ExposeSecret, SecretString, SecretVec};
+use url::Url;
 
 use client::GithubRepositoryClient;
+use rate_limit::{RateLimitToken, RateLimitTracker, TokenRateLimit};
+use request::ApiClient;
 
 use crate::bors::event::PullRequestComment;
 use crate::bors::{BorsState, RepositoryState};
@@ -19,52 +23,196 @@ use crate::permissions::TeamApiPermissionResolver;
 
 pub mod client;
 pub(crate) mod operations;
+pub mod rate_limit;
+pub mod request;
+pub mod retry;
 
 type GHRepositoryState = RepositoryState<GithubRepositoryClient>;
 
+/// Hidden marker that is prepended to all comments posted by bors, so that they can be told apart
+/// from comments that the same GitHub user posts on its own.
+const BOT_COMMENT_MARKER: &str = "<!-- rust-bors -->";
+
 type RepositoryMap = HashMap<GithubRepoName, GHRepositoryState>;
 
+/// Credentials used to authenticate with GitHub.
+pub enum GithubAuth {
+    /// Authenticate as a GitHub App, which manages the repositories it is installed in.
+    App {
+        app_id: AppId,
+        private_key: SecretVec<u8>,
+    },
+    /// Authenticate with a (fine-grained) personal access token, which manages the listed
+    /// repositories. A token can usually access more repositories than bors should manage, so
+    /// they have to be listed explicitly.
+    Token {
+        token: SecretString,
+        repositories: Vec<GithubRepoName>,
+    },
+}
+
+impl GithubAuth {
+    fn mode(&self) -> AuthMode {
+        match self {
+            GithubAuth::App { .. } => AuthMode::App,
+            GithubAuth::Token { repositories, .. } => AuthMode::Token {
+                repositories: repositories.clone(),
+            },
+        }
+    }
+}
+
+enum AuthMode {
+    App,
+    Token { repositories: Vec<GithubRepoName> },
+}
+
 /// Provides access to managed GitHub repositories.
 pub struct GithubAppState {
-    app: App,
+    /// ID of the GitHub user that posts comments on behalf of the app.
+    bot_user_id: UserId,
     client: Octocrab,
+    auth_mode: AuthMode,
     repositories: RepositoryMap,
     db: SeaORMClient,
+    rate_limits: RateLimitTracker,
 }
 
 impl GithubAppState {
-    /// Loads repositories managed by the Bors GitHub app with the given ID.
-    pub async fn load(
-        app_id: AppId,
-        private_key: SecretVec<u8>,
-        db: SeaORMClient,
-    ) -> anyhow::Result<GithubAppState> {
-        let key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key.expose_secret().as_ref())
-            .context("Could not encode private key")?;
+    /// Loads repositories managed by Bors, authenticated with the given credentials.
+    pub async fn load(auth: GithubAuth, db: SeaORMClient) -> anyhow::Result<GithubAppState> {
+        let auth_mode = auth.mode();
+        let client = create_client(Octocrab::builder(), auth)?;
+        let bot_user_id = load_bot_user_id(&client, &auth_mode).await?;
+        tracing::info!("Loaded bot user with ID {bot_user_id}");
 
-        let client = Octocrab::builder()
-            .app(app_id, key)
-            .build()
-            .context("Could not create octocrab builder")?;
-
-        let app = client
-            .current()
-            .app()
-            .await
-            .context("Could not load Github App")?;
-
-        let repositories = load_repositories(&client).await?;
+        let rate_limits = RateLimitTracker::default();
+        let repositories = load_repositories(&client, &auth_mode, &rate_limits).await?;
         Ok(GithubAppState {
-            app,
+            bot_user_id,
             client,
+            auth_mode,
             repositories,
             db,
+            rate_limits,
         })
     }
+
+    /// Returns the tracker of the rate-limit budgets of the access tokens of the managed
+    /// repositories.
+    pub fn rate_limits(&self) -> RateLimitTracker {
+        self.rate_limits.clone()
+    }
+}
+
+/// Creates a GitHub client from `builder` that authenticates with the given credentials.
+fn create_client(builder: OctocrabBuilder, auth: GithubAuth) -> anyhow::Result<Octocrab> {
+    let builder = match auth {
+        GithubAuth::App {
+            app_id,
+            private_key,
+        } => {
+            let key = jsonwebtoken::EncodingKey::from_rsa_pem(private_key.expose_secret().as_ref())
+                .context("Could not encode private key")?;
+            builder.app(app_id, key)
+        }
+        GithubAuth::Token { token, .. } => builder.personal_token(token.expose_secret().clone()),
+    };
+    builder.build().context("Could not create octocrab builder")
+}
+
+/// Loads the ID of the GitHub user that posts comments on behalf of bors.
+///
+/// With a personal access token, this is the owner of the token. A GitHub App posts comments as
+/// its bot user.
+async fn load_bot_user_id(client: &Octocrab, auth_mode: &AuthMode) -> anyhow::Result<UserId> {
+    if let AuthMode::Token { .. } = auth_mode {
+        let user = client
+            .current()
+            .user()
+            .await
+            .context("Could not load the owner of the access token")?;
+        return Ok(user.id);
+    }
+
+    let app = client
+        .current()
+        .app()
+        .await
+        .context("Could not load Github App")?;
+    let slug = app
+        .slug
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Github App {} has no slug", app.id))?;
+
+    // Users can be loaded without authentication, while the app client can only access app
+    // endpoints.
+    let user: User = Octocrab::builder()
+        .build()
+        .context("Could not create octocrab builder")?
+        .get(format!("/users/{slug}[bot]"), None::<&()>)
+        .await
+        .with_context(|| format!("Could not load bot user of Github App {slug}"))?;
+    Ok(user.id)
+}
+
+/// How many repositories are initialized at the same time.
+const REPOSITORY_LOAD_CONCURRENCY: usize = 8;
+
+/// Loads repositories that are managed by the given client: the repositories of the installations
+/// of a GitHub App, or the repositories listed for a personal access token.
+///
+/// Repositories are initialized concurrently. A repository that cannot be loaded is skipped, so that
+/// it does not prevent the other repositories from being managed.
+async fn load_repositories(
+    client: &Octocrab,
+    auth_mode: &AuthMode,
+    rate_limits: &RateLimitTracker,
+) -> anyhow::Result<RepositoryMap> {
+    let repos = match auth_mode {
+        AuthMode::App => load_installation_repositories(client).await?,
+        AuthMode::Token { repositories } => load_token_repositories(client, repositories)
+            .await
+            .into_iter()
+            .map(|repo| (client.clone(), RateLimitToken::Personal, repo))
+            .collect(),
+    };
+
+    let results = load_concurrently(repos, |(repo_client, token, repo)| async move {
+        let full_name = repo.full_name.clone();
+        create_repo_state(repo_client, repo, rate_limits.token(token))
+            .await
+            .map_err(|error| anyhow::anyhow!("Cannot load repository {full_name:?}: {error:?}"))
+    })
+    .await;
+
+    let mut repositories = HashMap::default();
+    for result in results {
+        let mut repo_state = match result {
+            Ok(repo_state) => repo_state,
+            Err(error) => {
+                tracing::error!("{error:?}");
+                continue;
+            }
+        };
+        tracing::info!("Loaded repository {}", repo_state.repository);
+        repo_state.check_rate_limit().await;
+
+        if let Some(existing) = repositories.insert(repo_state.repository.clone(), repo_state) {
+            return Err(anyhow::anyhow!(
+                "Repository {} found in multiple installations!",
+                existing.repository
+            ));
+        }
+    }
+    Ok(repositories)
 }
 
-/// Loads repositories that are connected to the given GitHub App client.
-pub async fn load_repositories(client: &Octocrab) -> anyhow::Result<RepositoryMap> {
+/// Loads the repositories of all installations of a GitHub App, each with a client that
+/// authenticates as its installation and the token whose budget the client uses.
+async fn load_installation_repositories(
+    client: &Octocrab,
+) -> anyhow::Result<Vec<(Octocrab, RateLimitToken, Repository)>> {
     let installations = client
         .apps()
         .installations()
@@ -72,7 +220,7 @@ pub async fn load_repositories(client: &Octocrab) -> anyhow::Result<RepositoryMa
         .await
         .context("Could not load app installations")?;
 
-    let mut repositories = HashMap::default();
+    let mut repos = vec![];
     for installation in installations {
         if let Some(ref repositories_url) = installation.repositories_url {
             let installation_client = client.installation(installation.id);
@@ -81,28 +229,14 @@ pub async fn load_repositories(client: &Octocrab) -> anyhow::Result<RepositoryMa
                 .get::<InstallationRepositories, _, ()>(repositories_url, None)
                 .await
             {
-                Ok(repos) => {
-                    for repo in repos.repositories {
-                        let repo_state =
-                            create_repo_state(installation_client.clone(), repo.clone())
-                                .await
-                                .map_err(|error| {
-                                    anyhow::anyhow!(
-                                        "Cannot load repository {:?}: {error:?}",
-                                        repo.full_name
-                                    )
-                                })?;
-                        tracing::info!("Loaded repository {}", repo_state.repository);
-
-                        if let Some(existing) =
-                            repositories.insert(repo_state.repository.clone(), repo_state)
-                        {
-                            return Err(anyhow::anyhow!(
-                                "Repository {} found in multiple installations!",
-                                existing.repository
-                            ));
-                        }
-                    }
+                Ok(installation_repos) => {
+                    repos.extend(installation_repos.repositories.into_iter().map(|repo| {
+                        (
+                            installation_client.clone(),
+                            RateLimitToken::Installation(installation.id),
+                            repo,
+                        )
+                    }));
                 }
                 Err(error) => {
                     tracing::error!(
@@ -113,12 +247,45 @@ pub async fn load_repositories(client: &Octocrab) -> anyhow::Result<RepositoryMa
             };
         }
     }
-    Ok(repositories)
+    Ok(repos)
+}
+
+/// Loads the given repositories with the personal access token of the client. A repository that
+/// the token cannot access is skipped.
+async fn load_token_repositories(
+    client: &Octocrab,
+    repositories: &[GithubRepoName],
+) -> Vec<Repository> {
+    let mut repos = vec![];
+    for name in repositories {
+        match client.repos(name.owner(), name.name()).get().await {
+            Ok(repo) => repos.push(repo),
+            Err(error) => {
+                tracing::error!("Could not load repository {name} with the access token: {error}");
+            }
+        }
+    }
+    repos
+}
+
+/// Runs `load` for all `items`, with at most [`REPOSITORY_LOAD_CONCURRENCY`] of them running at
+/// the same time. Each item gets its own result, in an unspecified order.
+async fn load_concurrently<T, U, F, Fut>(items: Vec<T>, load: F) -> Vec<anyhow::Result<U>>
+where
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = anyhow::Result<U>>,
+{
+    futures::stream::iter(items)
+        .map(load)
+        .buffer_unordered(REPOSITORY_LOAD_CONCURRENCY)
+        .collect()
+        .await
 }
 
 async fn create_repo_state(
     repo_client: Octocrab,
     repo: Repository,
+    rate_limit: TokenRateLimit,
 ) -> anyhow::Result<GHRepositoryState> {
     let Some(owner) = repo.owner.clone() else {
         return Err(anyhow::anyhow!("Repository {} has no owner", repo.name));
@@ -139,22 +306,25 @@ async fn create_repo_state(
         }
     };
 
-    let permissions_resolver = TeamApiPermissionResolver::load(name.clone())
+    // The permission resolver shares the budget and the pause of mutating requests with the
+    // repository client
+    let api = ApiClient::new(repo_client, rate_limit);
+    let permissions_resolver = TeamApiPermissionResolver::load(name.clone(), api.clone())
         .await
         .map_err(|error| anyhow::anyhow!("Could not load permissions for {name}: {error:?}"))?;
 
     let client = GithubRepositoryClient {
-        client: repo_client,
+        api,
         repo_name: name.clone(),
         repository: repo,
     };
 
-    Ok(RepositoryState {
-        repository: name,
+    Ok(RepositoryState::new(
+        name,
         client,
+        Box::new(permissions_resolver),
         config,
-        permissions_resolver: Box::new(permissions_resolver),
-    })
+    ))
 }
 
 /// Loads repository configuration from a file located at `[CONFIG_FILE_PATH]` in the main
@@ -190,9 +360,34 @@ async fn load_repository_config(
         })
 }
 
+/// Creates the URL of a GitHub API endpoint from its path segments. The segments are
+/// percent-encoded, so that e.g. a branch name with a slash stays a single segment.
+pub(crate) fn api_url(base_url: &Url, segments: &[&str]) -> anyhow::Result<Url> {
+    let mut url = base_url.clone();
+    url.path_segments_mut()
+        .map_err(|_| anyhow::anyhow!("Invalid GitHub API URL {base_url}"))?
+        .pop_if_empty()
+        .extend(segments);
+    Ok(url)
+}
+
+/// Checks if the comment was posted by bors.
+fn is_bot_comment(comment: &PullRequestComment, bot_user_id: UserId, auth_mode: &AuthMode) -> bool {
+    // The ID is compared instead of the login, because it cannot be spoofed or changed
+    if comment.author.id != bot_user_id {
+        return false;
+    }
+    match auth_mode {
+        AuthMode::App => true,
+        // The owner of an access token also comments on their own, only the comments posted by
+        // bors contain the marker
+        AuthMode::Token { .. } => comment.text.contains(BOT_COMMENT_MARKER),
+    }
+}
+
 impl BorsState<GithubRepositoryClient> for GithubAppState {
     fn is_comment_internal(&self, comment: &PullRequestComment) -> bool {
-        comment.author.html_url == self.app.html_url
+        is_bot_comment(comment, self.bot_user_id, &self.auth_mode)
     }
 
     fn get_repo_state_mut(
@@ -219,11 +414,220 @@ impl BorsState<GithubRepositoryClient> for GithubAppState {
         )
     }
 
-    /// Re-download information about repositories connected to this GitHub app.
+    fn remove_repository(&mut self, repo: &GithubRepoName) {
+        self.repositories.remove(repo);
+    }
+
+    fn rename_repository(&mut self, old_name: &GithubRepoName, new_name: &GithubRepoName) {
+        if let Some(mut repo) = self.repositories.remove(old_name) {
+            repo.repository = new_name.clone();
+            repo.client.repo_name = new_name.clone();
+            self.repositories.insert(new_name.clone(), repo);
+        }
+    }
+
+    /// Re-download information about repositories accessible by bors.
     fn reload_repositories(&mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + '_>> {
         Box::pin(async move {
-            self.repositories = load_repositories(&self.client).await?;
+            self.repositories =
+                load_repositories(&self.client, &self.auth_mode, &self.rate_limits).await?;
             Ok(())
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use octocrab::Octocrab;
+
+    use crate::github::api::{
+        api_url, create_client, is_bot_comment, load_bot_user_id, load_concurrently,
+        load_token_repositories, AuthMode, GithubAuth, BOT_COMMENT_MARKER,
+        REPOSITORY_LOAD_CONCURRENCY,
+    };
+    use crate::github::GithubRepoName;
+    use crate::tests::event::{comment, user};
+    use crate::tests::io::load_test_file;
+
+    #[test]
+    fn api_url_encodes_segments() {
+        let base_url = url::Url::parse("https://api.github.com/").unwrap();
+        let url = api_url(
+            &base_url,
+            &[
+                "repos",
+                "foo",
+                "bar",
+                "branches",
+                "release/1.0 #2",
+                "protection",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://api.github.com/repos/foo/bar/branches/release%2F1.0%20%232/protection"
+        );
+    }
+    use crate::tests::state::test_bot_user;
+
+    /// Answers requests authenticated with `token` with data of the `issue-comment` webhook and
+    /// all other requests with 401.
+    fn token_client() -> Octocrab {
+        fn authorized(headers: &HeaderMap) -> Result<(), StatusCode> {
+            match headers.get("authorization") {
+                Some(value) if value == "Bearer token" => Ok(()),
+                _ => Err(StatusCode::UNAUTHORIZED),
+            }
+        }
+
+        let event: serde_json::Value =
+            serde_json::from_str(&load_test_file("webhook/issue-comment.json")).unwrap();
+        let user = event["sender"].clone();
+        let repository = event["repository"].clone();
+        let app = Router::new()
+            .route(
+                "/user",
+                get(|headers: HeaderMap| async move { authorized(&headers).map(|_| Json(user)) }),
+            )
+            .route(
+                "/repos/kobzol/bors-kindergarten",
+                get(|headers: HeaderMap| async move {
+                    authorized(&headers).map(|_| Json(repository))
+                }),
+            );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(app.into_make_service()),
+        );
+
+        let builder = Octocrab::builder()
+            .base_url(format!("http://{address}"))
+            .unwrap();
+        let auth = GithubAuth::Token {
+            token: "token".to_string().into(),
+            repositories: vec![],
+        };
+        create_client(builder, auth).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_token_auth() {
+        let client = token_client();
+        let auth_mode = AuthMode::Token {
+            repositories: vec![],
+        };
+        let user_id = load_bot_user_id(&client, &auth_mode).await.unwrap();
+        assert_eq!(user_id.0, 4539057);
+
+        // Repositories that the token cannot access are skipped
+        let repos = load_token_repositories(
+            &client,
+            &[
+                GithubRepoName::new("Kobzol", "bors-kindergarten"),
+                GithubRepoName::new("Kobzol", "private"),
+            ],
+        )
+        .await;
+        assert_eq!(
+            repos
+                .into_iter()
+                .map(|repo| repo.full_name.unwrap())
+                .collect::<Vec<_>>(),
+            vec!["Kobzol/bors-kindergarten"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_concurrently_skips_failed_repository() {
+        let repos = vec!["repo-1", "repo-2", "broken", "repo-3"];
+        let mut results = load_concurrently(repos, |repo| async move {
+            if repo == "broken" {
+                Err(anyhow::anyhow!("Cannot load config"))
+            } else {
+                Ok(repo)
+            }
+        })
+        .await;
+
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 1);
+        results.retain(|result| result.is_ok());
+        let mut loaded = results
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect::<Vec<_>>();
+        loaded.sort();
+        assert_eq!(loaded, vec!["repo-1", "repo-2", "repo-3"]);
+    }
+
+    #[tokio::test]
+    async fn test_load_concurrently_bounds_concurrency() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let results = load_concurrently((0..20).collect(), |index| {
+            let running = &running;
+            let max_running = &max_running;
+            async move {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(current, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                Ok::<_, anyhow::Error>(index)
+            }
+        })
+        .await;
+
+        assert_eq!(results.len(), 20);
+        assert_eq!(
+            max_running.load(Ordering::SeqCst),
+            REPOSITORY_LOAD_CONCURRENCY
+        );
+    }
+
+    fn token_mode() -> AuthMode {
+        AuthMode::Token {
+            repositories: vec![],
+        }
+    }
+
+    #[test]
+    fn bot_comment_with_marker_is_internal() {
+        let bot = test_bot_user();
+        let comment = comment(&format!(
+            "{BOT_COMMENT_MARKER}\n:sunny: Try build successful"
+        ))
+        .author(bot.clone())
+        .create();
+        assert!(is_bot_comment(&comment, bot.id, &AuthMode::App));
+        assert!(is_bot_comment(&comment, bot.id, &token_mode()));
+    }
+
+    #[test]
+    fn token_owner_comment_without_marker_is_not_internal() {
+        let bot = test_bot_user();
+        let comment = comment("@bors r+").author(bot.clone()).create();
+        assert!(is_bot_comment(&comment, bot.id, &AuthMode::App));
+        assert!(!is_bot_comment(&comment, bot.id, &token_mode()));
+    }
+
+    #[test]
+    fn user_comment_quoting_bot_comment_is_not_internal() {
+        let bot = test_bot_user();
+        let comment = comment(&format!(
+            "> {BOT_COMMENT_MARKER}\n> :broken_heart: Test failed\n\n@bors retry"
+        ))
+        .author(user("user"))
+        .create();
+        assert!(!is_bot_comment(&comment, bot.id, &AuthMode::App));
+        assert!(!is_bot_comment(&comment, bot.id, &token_mode()));
+    }
+}
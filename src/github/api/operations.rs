@@ -19,6 +19,16 @@ pub enum MergeError {
     NetworkError(#[from] octocrab::Error),
 }
 
+#[derive(Error, Debug)]
+pub enum ResolveRefError {
+    #[error("Commit not found")]
+    NotFound,
+    #[error("Commit SHA is ambiguous")]
+    Ambiguous,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
 #[derive(serde::Serialize)]
 struct MergeRequest<'a, 'b, 'c> {
     base: &'a str,
@@ -41,26 +51,23 @@ pub async fn merge_branches(
     commit_message: &str,
 ) -> Result<CommitSha, MergeError> {
     let client = repo.client();
-    let merge_url = repo
-        .repository
-        .merges_url
-        .as_ref()
-        .map(|url| url.to_string())
-        .unwrap_or_else(|| {
-            format!(
-                "{}/repos/{}/{}/merges",
-                client.base_url,
+    let merge_url = repo.repository.merges_url.clone().unwrap_or_else(|| {
+        client
+            .base_url
+            .join(&format!(
+                "/repos/{}/{}/merges",
                 repo.name().owner,
                 repo.name().name
-            )
-        });
+            ))
+            .unwrap()
+    });
 
     let request = MergeRequest {
         base: base_ref,
         head: head_sha.as_ref(),
         commit_message,
     };
-    let response = client._post(merge_url, Some(&request)).await;
+    let response = repo.api.post(merge_url, Some(&request)).await;
 
     match response {
         Ok(response) => {
@@ -124,10 +131,24 @@ async fn create_branch(
     name: String,
     sha: &CommitSha,
 ) -> Result<(), String> {
-    repo.client
-        .repos(repo.repo_name.owner(), repo.repo_name.name())
-        .create_ref(&Reference::Branch(name), sha.as_ref())
+    let url = repo
+        .client()
+        .base_url
+        .join(&format!(
+            "repos/{}/{}/git/refs",
+            repo.name().owner(),
+            repo.name().name(),
+        ))
+        .map_err(|error| format!("Cannot create branch: {error}"))?;
+    let body = serde_json::json!({
+        "ref": Reference::Branch(name).full_ref_url(),
+        "sha": sha.as_ref(),
+    });
+    repo.api
+        .post(url.clone(), Some(&body))
         .await
+        .map_err(|error| format!("Cannot create branch: {error}"))?
+        .error_for_status()
         .map_err(|error| format!("Cannot create branch: {error}"))?;
     Ok(())
 }
@@ -158,16 +179,11 @@ async fn update_branch(
             Reference::Branch(branch_name.clone()).ref_url()
         ))
         .unwrap();
-    let res: reqwest::Response = repo
-        .client
-        ._patch(
-            url,
-            Some(&serde_json::json!({
-                "sha": sha.as_ref(),
-                "force": true
-            })),
-        )
-        .await?;
+    let body = serde_json::json!({
+        "sha": sha.as_ref(),
+        "force": true
+    });
+    let res: reqwest::Response = repo.api.patch(url.clone(), Some(&body)).await?;
 
     let status = res.status();
     tracing::trace!(
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use octocrab::models::InstallationId;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use tokio::time::Instant;
+
+const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const LIMIT_HEADER: &str = "x-ratelimit-limit";
+const RETRY_AFTER_HEADER: &str = "retry-after";
+
+/// How long mutating requests are paused when GitHub does not say for how long to wait.
+const DEFAULT_SECONDARY_PAUSE: Duration = Duration::from_secs(60);
+
+/// Rate-limit budget of the GitHub API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct RateLimit {
+    /// How many requests can still be made in the current rate-limit window.
+    pub remaining: u64,
+    /// How many requests can be made in a single rate-limit window.
+    pub limit: u64,
+}
+
+/// Access token whose requests are counted against a rate-limit budget. GitHub counts the requests
+/// per token, so all repositories that use the same token share its budget.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitToken {
+    /// Token of a GitHub App installation, shared by the repositories of the installation.
+    Installation(InstallationId),
+    /// Personal access token, shared by all managed repositories.
+    Personal,
+}
+
+impl Display for RateLimitToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RateLimitToken::Installation(id) => write!(f, "installation/{id}"),
+            RateLimitToken::Personal => f.write_str("personal"),
+        }
+    }
+}
+
+/// Keeps track of the rate-limit budgets of access tokens, as reported by the headers of GitHub
+/// API responses.
+///
+/// The tracker is cheaply cloneable and all clones share the same budgets, so that the budgets can
+/// be read outside of the Bors process.
+#[derive(Clone, Default)]
+pub struct RateLimitTracker {
+    budgets: Arc<Mutex<HashMap<RateLimitToken, RateLimit>>>,
+}
+
+impl RateLimitTracker {
+    /// Returns a handle that tracks the budget of `token` in this tracker.
+    pub fn token(&self, token: RateLimitToken) -> TokenRateLimit {
+        TokenRateLimit {
+            tracker: self.clone(),
+            token,
+        }
+    }
+
+    /// Updates the budget of `token` from the rate-limit headers of a GitHub API response.
+    /// Responses without these headers are ignored.
+    pub fn update_from_headers(&self, token: &RateLimitToken, headers: &HeaderMap) {
+        let (Some(remaining), Some(limit)) = (
+            parse_header(headers, REMAINING_HEADER),
+            parse_header(headers, LIMIT_HEADER),
+        ) else {
+            return;
+        };
+        self.budgets
+            .lock()
+            .unwrap()
+            .insert(token.clone(), RateLimit { remaining, limit });
+    }
+
+    /// Returns the last known budget of `token`.
+    pub fn get(&self, token: &RateLimitToken) -> Option<RateLimit> {
+        self.budgets.lock().unwrap().get(token).copied()
+    }
+
+    /// Returns the last known budgets of all tokens, ordered by the name of the token.
+    pub fn all(&self) -> Vec<(RateLimitToken, RateLimit)> {
+        let mut budgets = self
+            .budgets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(token, budget)| (token.clone(), *budget))
+            .collect::<Vec<_>>();
+        budgets.sort_by_key(|(token, _)| token.to_string());
+        budgets
+    }
+}
+
+/// Rate-limit budget of a single access token, shared with the tracker that it was created from.
+#[derive(Clone)]
+pub struct TokenRateLimit {
+    tracker: RateLimitTracker,
+    token: RateLimitToken,
+}
+
+impl TokenRateLimit {
+    /// Records the budget reported by a response of a request sent with the token.
+    pub fn track(&self, response: &reqwest::Response) {
+        self.tracker
+            .update_from_headers(&self.token, response.headers());
+    }
+
+    /// Returns the last known budget of the token.
+    pub fn get(&self) -> Option<RateLimit> {
+        self.tracker.get(&self.token)
+    }
+}
+
+/// Pause of the requests that modify repositories, imposed by the secondary rate limit of GitHub.
+///
+/// GitHub limits how quickly content can be created, e.g. comments, and answers requests over
+/// the limit with an abuse detection error. Sending more requests during the pause could get the
+/// app throttled even harder, so the requests wait until the pause ends instead.
+/// The pause is cheaply cloneable and all clones share the same state.
+#[derive(Clone, Default)]
+pub struct MutationPause {
+    until: Arc<Mutex<Option<Instant>>>,
+}
+
+impl MutationPause {
+    /// Pauses mutating requests for the given duration. An already longer pause is kept.
+    pub fn pause_for(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut current = self.until.lock().unwrap();
+        if current.is_none_or(|current| current < until) {
+            *current = Some(until);
+        }
+    }
+
+    /// Waits until mutating requests are not paused.
+    pub async fn wait(&self) {
+        loop {
+            let until = *self.until.lock().unwrap();
+            match until {
+                Some(until) if until > Instant::now() => tokio::time::sleep_until(until).await,
+                _ => return,
+            }
+        }
+    }
+}
+
+/// Returns for how long mutating requests should be paused, if the response reports that the
+/// secondary rate limit of GitHub has been exceeded.
+pub fn secondary_rate_limit_pause(
+    status: StatusCode,
+    headers: &HeaderMap,
+    body: &str,
+) -> Option<Duration> {
+    if !matches!(
+        status,
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+    ) {
+        return None;
+    }
+    // Other 403 responses, e.g. for missing permissions, must not pause the requests
+    let body = body.to_lowercase();
+    if !body.contains("secondary rate limit") && !body.contains("abuse detection") {
+        return None;
+    }
+    Some(
+        parse_header(headers, RETRY_AFTER_HEADER)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_SECONDARY_PAUSE),
+    )
+}
+
+fn parse_header(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::StatusCode;
+
+    use octocrab::models::InstallationId;
+
+    use crate::github::api::rate_limit::{
+        secondary_rate_limit_pause, RateLimit, RateLimitToken, RateLimitTracker,
+    };
+
+    fn headers(remaining: &'static str, limit: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", HeaderValue::from_static(remaining));
+        headers.insert("x-ratelimit-limit", HeaderValue::from_static(limit));
+        headers
+    }
+
+    #[test]
+    fn test_update_from_headers() {
+        let tracker = RateLimitTracker::default();
+        let token = RateLimitToken::Personal;
+        assert_eq!(tracker.get(&token), None);
+
+        tracker.update_from_headers(&token, &headers("4999", "5000"));
+        tracker.update_from_headers(&token, &headers("4998", "5000"));
+        assert_eq!(
+            tracker.get(&token),
+            Some(RateLimit {
+                remaining: 4998,
+                limit: 5000
+            })
+        );
+    }
+
+    #[test]
+    fn test_budgets_are_tracked_per_token() {
+        let tracker = RateLimitTracker::default();
+        let first = RateLimitToken::Installation(InstallationId(1));
+        let second = RateLimitToken::Installation(InstallationId(2));
+        tracker.update_from_headers(&first, &headers("100", "5000"));
+        tracker.update_from_headers(&second, &headers("4000", "5000"));
+        assert_eq!(tracker.get(&first).unwrap().remaining, 100);
+        assert_eq!(tracker.get(&second).unwrap().remaining, 4000);
+        assert_eq!(tracker.get(&RateLimitToken::Personal), None);
+    }
+
+    #[test]
+    fn test_update_from_invalid_headers() {
+        let tracker = RateLimitTracker::default();
+        let token = RateLimitToken::Personal;
+        tracker.update_from_headers(&token, &headers("100", "5000"));
+        tracker.update_from_headers(&token, &headers("unknown", "5000"));
+        tracker.update_from_headers(&token, &HeaderMap::new());
+        assert_eq!(
+            tracker.get(&token),
+            Some(RateLimit {
+                remaining: 100,
+                limit: 5000
+            })
+        );
+    }
+
+    #[test]
+    fn test_secondary_rate_limit_pause() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("30"));
+        let body = r#"{"message": "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."}"#;
+        assert_eq!(
+            secondary_rate_limit_pause(StatusCode::FORBIDDEN, &headers, body),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            secondary_rate_limit_pause(StatusCode::FORBIDDEN, &HeaderMap::new(), body),
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_other_errors_do_not_pause() {
+        let body = r#"{"message": "Resource not accessible by integration"}"#;
+        assert_eq!(
+            secondary_rate_limit_pause(StatusCode::FORBIDDEN, &HeaderMap::new(), body),
+            None
+        );
+        assert_eq!(
+            secondary_rate_limit_pause(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                &HeaderMap::new(),
+                "abuse detection"
+            ),
+            None
+        );
+    }
+}
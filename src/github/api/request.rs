@@ -0,0 +1,128 @@
+use octocrab::Octocrab;
+use reqwest::{Method, StatusCode, Url};
+use serde::Serialize;
+
+use crate::github::api::rate_limit::{secondary_rate_limit_pause, MutationPause, TokenRateLimit};
+use crate::github::api::retry::RetryPolicy;
+
+/// Sends requests to the GitHub API with a single access token.
+///
+/// All requests are sent through [`ApiClient::send`], which retries them according to the retry
+/// policy, records the rate-limit budget of the token and respects the secondary rate limit.
+/// The client is cheaply cloneable and all clones share the budget and the pause of mutating
+/// requests.
+#[derive(Clone)]
+pub struct ApiClient {
+    /// The client caches the access token and refreshes it once it expires.
+    pub client: Octocrab,
+    /// Rate-limit budget of the access token, which can be shared with other repositories.
+    pub rate_limit: TokenRateLimit,
+    /// Decides how requests that fail because of transient errors are retried.
+    pub retry_policy: RetryPolicy,
+    /// Pause of mutating requests imposed by the secondary rate limit of GitHub.
+    pub mutation_pause: MutationPause,
+}
+
+impl ApiClient {
+    pub fn new(client: Octocrab, rate_limit: TokenRateLimit) -> Self {
+        Self {
+            client,
+            rate_limit,
+            retry_policy: RetryPolicy::default(),
+            mutation_pause: MutationPause::default(),
+        }
+    }
+
+    pub fn base_url(&self) -> &Url {
+        &self.client.base_url
+    }
+
+    pub async fn get(&self, url: Url) -> octocrab::Result<reqwest::Response> {
+        self.send(Method::GET, url, None::<&()>).await
+    }
+
+    pub async fn post<B: Serialize + ?Sized>(
+        &self,
+        url: Url,
+        body: Option<&B>,
+    ) -> octocrab::Result<reqwest::Response> {
+        self.send(Method::POST, url, body).await
+    }
+
+    pub async fn patch<B: Serialize + ?Sized>(
+        &self,
+        url: Url,
+        body: Option<&B>,
+    ) -> octocrab::Result<reqwest::Response> {
+        self.send(Method::PATCH, url, body).await
+    }
+
+    pub async fn delete(&self, url: Url) -> octocrab::Result<reqwest::Response> {
+        self.send(Method::DELETE, url, None::<&()>).await
+    }
+
+    /// Sends a request with an optional JSON body, retrying it according to the retry policy.
+    pub async fn send<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<&B>,
+    ) -> octocrab::Result<reqwest::Response> {
+        self.retry_policy
+            .execute(&method, || self.send_once(&method, &url, body))
+            .await
+    }
+
+    /// Sends a single attempt of a request and records the rate-limit budget reported by the
+    /// response.
+    ///
+    /// If GitHub reports that the secondary rate limit has been exceeded by a mutating request,
+    /// all mutating requests of the client are paused for the indicated duration and the request
+    /// is sent again once the pause ends, instead of failing.
+    async fn send_once<B: Serialize + ?Sized>(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: Option<&B>,
+    ) -> octocrab::Result<reqwest::Response> {
+        let mutating = method != Method::GET;
+        loop {
+            if mutating {
+                self.mutation_pause.wait().await;
+            }
+            let mut request = self.client.request_builder(url.clone(), method.clone());
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+            let response = self.client.execute(request).await?;
+            self.rate_limit.track(&response);
+            let status = response.status();
+            if !mutating
+                || !matches!(
+                    status,
+                    StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+                )
+            {
+                return Ok(response);
+            }
+
+            // The body has to be read to recognize the error, so the response is rebuilt from it
+            let headers = response.headers().clone();
+            let body = response.bytes().await.unwrap_or_default();
+            match secondary_rate_limit_pause(status, &headers, &String::from_utf8_lossy(&body)) {
+                Some(pause) => {
+                    tracing::warn!(
+                        "Secondary rate limit exceeded, pausing mutating requests for {pause:?}"
+                    );
+                    self.mutation_pause.pause_for(pause);
+                }
+                None => {
+                    let mut response = axum::http::Response::new(body);
+                    *response.status_mut() = status;
+                    *response.headers_mut() = headers;
+                    return Ok(response.into());
+                }
+            }
+        }
+    }
+}
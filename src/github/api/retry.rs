@@ -0,0 +1,147 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use reqwest::{Method, StatusCode};
+
+/// Decides which requests to the GitHub API are retried and how long to wait between the
+/// attempts.
+///
+/// The delay grows exponentially from `base_delay` with each attempt, and a random jitter of up
+/// to `max_jitter` is added to it, so that retries of concurrent requests are spread out.
+/// The policy is applied to all requests, but requests that are not idempotent (e.g. POST) are
+/// only retried if GitHub has certainly not handled them, as repeating e.g. a merge could apply
+/// it twice.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// How many times a request is sent at most, including the first attempt.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Upper bound of the random delay added to each retry.
+    pub max_jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(1),
+            max_jitter: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that retries requests without waiting in between, e.g. for tests.
+    pub fn without_delay(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay: Duration::ZERO,
+            max_jitter: Duration::ZERO,
+        }
+    }
+
+    /// Sends a request created by `send` until it succeeds, fails with an error that is not
+    /// retryable or the attempts run out. The result of the last attempt is returned.
+    pub async fn execute<F, Fut>(
+        &self,
+        method: &Method,
+        mut send: F,
+    ) -> octocrab::Result<reqwest::Response>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = octocrab::Result<reqwest::Response>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let result = send().await;
+            if attempt >= self.max_attempts || !is_retryable(method, &result) {
+                return result;
+            }
+            let delay = self.delay(attempt);
+            tracing::warn!(
+                "GitHub request failed ({}), retrying in {delay:?}",
+                describe_failure(&result)
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Delay before the retry that follows the given (1-based) attempt.
+    fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt - 1));
+        backoff + random_duration(self.max_jitter)
+    }
+}
+
+/// Transient server errors and connection failures are retried. GitHub answers some requests with
+/// these errors under load, even though they would succeed a moment later.
+///
+/// A gateway error or a failure while the request was sent could hide a handled request, so
+/// requests that are not idempotent are only retried if they could not be delivered, or if
+/// GitHub refused to handle them.
+fn is_retryable(method: &Method, result: &octocrab::Result<reqwest::Response>) -> bool {
+    let idempotent = matches!(*method, Method::GET | Method::PUT | Method::DELETE);
+    match result {
+        Ok(response) => match response.status() {
+            StatusCode::SERVICE_UNAVAILABLE => true,
+            StatusCode::BAD_GATEWAY | StatusCode::GATEWAY_TIMEOUT => idempotent,
+            _ => false,
+        },
+        Err(octocrab::Error::Http { source, .. }) => idempotent || source.is_connect(),
+        Err(_) => false,
+    }
+}
+
+fn describe_failure(result: &octocrab::Result<reqwest::Response>) -> String {
+    match result {
+        Ok(response) => format!("status {}", response.status()),
+        Err(octocrab::Error::Http { source, .. }) => source.to_string(),
+        Err(error) => error.to_string(),
+    }
+}
+
+fn random_duration(max: Duration) -> Duration {
+    if max.is_zero() {
+        return Duration::ZERO;
+    }
+    // Each `RandomState` is seeded randomly, which is good enough for jitter
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % max.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::github::api::retry::RetryPolicy;
+
+    #[test]
+    fn delay_grows_exponentially() {
+        let policy = RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_secs(1),
+            max_jitter: Duration::ZERO,
+        };
+        assert_eq!(policy.delay(1), Duration::from_secs(1));
+        assert_eq!(policy.delay(2), Duration::from_secs(2));
+        assert_eq!(policy.delay(3), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn delay_with_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_secs(1),
+            max_jitter: Duration::from_millis(100),
+        };
+        let delay = policy.delay(1);
+        assert!(delay >= Duration::from_secs(1));
+        assert!(delay < Duration::from_millis(1100));
+    }
+}
@@ -2,6 +2,7 @@
 //! for working with (GitHub) repositories.
 use std::fmt::{Debug, Display, Formatter};
 
+use octocrab::models::UserId;
 use url::Url;
 
 pub mod api;
@@ -9,13 +10,13 @@ mod labels;
 pub mod server;
 mod webhook;
 
-pub use api::operations::MergeError;
-pub use api::GithubAppState;
+pub use api::operations::{MergeError, ResolveRefError};
+pub use api::{GithubAppState, GithubAuth};
 pub use labels::{LabelModification, LabelTrigger};
 pub use webhook::WebhookSecret;
 
 /// Unique identifier of a GitHub repository
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, serde::Serialize, serde::Deserialize)]
 pub struct GithubRepoName {
     owner: String,
     name: String,
@@ -44,13 +45,14 @@ impl Display for GithubRepoName {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct GithubUser {
+    pub id: UserId,
     pub username: String,
     pub html_url: Url,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct CommitSha(pub String);
 
 impl From<String> for CommitSha {
@@ -78,15 +80,38 @@ pub struct Branch {
 #[derive(Clone, Debug)]
 pub struct PullRequest {
     pub number: PullRequestNumber,
+    pub author: GithubUser,
     // <author>:<branch>
     pub head_label: String,
     pub head: Branch,
+    /// Branch that the PR is merged into (`base.ref` on GitHub).
     pub base: Branch,
     pub title: String,
     pub message: String,
+    /// Names of the labels of the PR.
+    pub labels: Vec<String>,
+    /// Logins of the users whose review of the PR has been requested.
+    pub requested_reviewers: Vec<String>,
+    /// Is the PR a draft?
+    pub draft: bool,
+    /// Can the PR be merged into its base without conflicts? `None` if GitHub has not computed
+    /// it yet.
+    pub mergeable: Option<bool>,
+    /// Is the PR closed? Merged PRs are closed as well.
+    pub closed: bool,
+}
+
+/// Metadata of a single commit.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Commit {
+    pub sha: CommitSha,
+    pub message: String,
+    /// Name of the author of the commit.
+    pub author: String,
+    pub parents: Vec<CommitSha>,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct PullRequestNumber(pub u64);
 
 impl From<u64> for PullRequestNumber {
@@ -1,28 +1,61 @@
 use crate::bors::event::BorsEvent;
-use crate::bors::{handle_bors_event, BorsContext};
-use crate::github::api::GithubAppState;
+use crate::bors::{handle_bors_event, BorsContext, BorsState, RepositoryClient};
+use crate::database::{BuildStatus, DbClient, RollupMode, SeaORMClient};
+use crate::github::api::rate_limit::{RateLimit, RateLimitToken, RateLimitTracker};
 use crate::github::webhook::GitHubWebhook;
 use crate::github::webhook::WebhookSecret;
+use crate::github::GithubRepoName;
 use crate::utils::logging::LogError;
-use axum::extract::State;
-use axum::http::StatusCode;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
 use axum::response::IntoResponse;
+use axum::Json;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
 use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::Instrument;
 
+/// Token that authenticates requests to the endpoints of the server that are not webhooks, e.g.
+/// the merge queue. It is separate from the webhook secret, so that the clients of these
+/// endpoints cannot sign webhooks.
+pub struct ApiToken(SecretString);
+
+impl ApiToken {
+    pub fn new(token: String) -> Self {
+        Self(token.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        self.0.expose_secret().as_str()
+    }
+}
+
 /// Shared server state for all axum handlers.
 pub struct ServerState {
     webhook_sender: WebhookSender,
     webhook_secret: WebhookSecret,
+    /// If no token is configured, the endpoints that require it reject all requests.
+    api_token: Option<ApiToken>,
+    db: SeaORMClient,
+    rate_limits: RateLimitTracker,
 }
 
 impl ServerState {
-    pub fn new(webhook_sender: WebhookSender, webhook_secret: WebhookSecret) -> Self {
+    pub fn new(
+        webhook_sender: WebhookSender,
+        webhook_secret: WebhookSecret,
+        api_token: Option<ApiToken>,
+        db: SeaORMClient,
+        rate_limits: RateLimitTracker,
+    ) -> Self {
         Self {
             webhook_sender,
             webhook_secret,
+            api_token,
+            db,
+            rate_limits,
         }
     }
 
@@ -47,18 +80,305 @@ pub async fn github_webhook_handler(
     }
 }
 
+/// Merge queue of a single repository.
+#[derive(serde::Serialize)]
+pub struct QueueResponse {
+    repository: String,
+    tree_open: bool,
+    prs: Vec<QueueEntry>,
+}
+
+/// A single PR in the merge queue.
+#[derive(serde::Serialize)]
+struct QueueEntry {
+    number: u64,
+    title: Option<String>,
+    approver: String,
+    priority: i32,
+    rollup: Option<RollupMode>,
+    state: QueueEntryState,
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum QueueEntryState {
+    /// The PR is waiting for space in the merge queue.
+    Waiting,
+    /// The PR is waiting for its merge build.
+    Approved,
+    /// The merge build of the PR is running.
+    Testing,
+    /// The merge build of the PR did not succeed.
+    Failed,
+}
+
+/// Axum handler that returns the merge queue of a repository, in the order in which the PRs will be
+/// merged. Requires the API token, as the queue contains e.g. titles of PRs of private
+/// repositories.
+pub async fn queue_handler(
+    State(state): State<ServerStateRef>,
+    Path((owner, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_token(&headers, state.api_token.as_ref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let repo = GithubRepoName::new(&owner, &name);
+    match load_queue(&state.db, &repo).await {
+        Ok(queue) => Json(queue).into_response(),
+        Err(error) => {
+            tracing::error!("Could not load merge queue of {repo}: {error:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn load_queue(
+    db: &(dyn DbClient + Sync),
+    repo: &GithubRepoName,
+) -> anyhow::Result<QueueResponse> {
+    let mut prs = vec![];
+    for pr in db.list_queued_prs(repo).await? {
+        let approver = db
+            .get_approvals(&pr)
+            .await?
+            .into_iter()
+            .filter(|approval| Some(&approval.commit_sha) == pr.approved_sha.as_ref())
+            .map(|approval| approval.reviewer().to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let state = match pr.auto_build.map(|build| build.status) {
+            None if pr.parked || pr.not_before.is_some() => QueueEntryState::Waiting,
+            None => QueueEntryState::Approved,
+            Some(BuildStatus::Pending) => QueueEntryState::Testing,
+            Some(_) => QueueEntryState::Failed,
+        };
+        prs.push(QueueEntry {
+            number: pr.number.0,
+            title: pr.title,
+            approver,
+            priority: pr.priority,
+            rollup: pr.rollup,
+            state,
+        });
+    }
+    Ok(QueueResponse {
+        repository: repo.to_string(),
+        tree_open: db.get_tree_state(repo).await?.is_open(),
+        prs,
+    })
+}
+
+/// How many failed events of a repository are returned by the failed events endpoint.
+const FAILED_EVENTS_LIMIT: u64 = 50;
+
+/// An event whose handling has failed.
+#[derive(serde::Serialize)]
+struct FailedEventEntry {
+    id: i32,
+    event: serde_json::Value,
+    error: String,
+    created_at: String,
+}
+
+/// Checks that the request is authenticated by the API token, passed as a bearer token.
+fn has_api_token(headers: &HeaderMap, api_token: Option<&ApiToken>) -> bool {
+    let Some(api_token) = api_token else {
+        return false;
+    };
+    let Some(token) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+    // Hashes are compared instead of the token itself, so that the comparison does not reveal how
+    // much of the token was guessed correctly
+    Sha256::digest(token.as_bytes()) == Sha256::digest(api_token.expose().as_bytes())
+}
+
+/// Axum handler that returns the last events of a repository whose handling has failed.
+/// Requires the API token, as the events can contain e.g. comments of private repositories.
+pub async fn failed_events_handler(
+    State(state): State<ServerStateRef>,
+    Path((owner, name)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_token(&headers, state.api_token.as_ref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    let repo = GithubRepoName::new(&owner, &name);
+    match load_failed_events(&state.db, &repo).await {
+        Ok(events) => Json(events).into_response(),
+        Err(error) => {
+            tracing::error!("Could not load failed events of {repo}: {error:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+async fn load_failed_events(
+    db: &(dyn DbClient + Sync),
+    repo: &GithubRepoName,
+) -> anyhow::Result<Vec<FailedEventEntry>> {
+    db.get_failed_events(repo, FAILED_EVENTS_LIMIT)
+        .await?
+        .into_iter()
+        .map(|event| {
+            Ok(FailedEventEntry {
+                id: event.id,
+                event: serde_json::from_str(&event.event)?,
+                error: event.error,
+                created_at: event.created_at.to_rfc3339(),
+            })
+        })
+        .collect()
+}
+
+/// Axum handler that sends a failed event to the Bors process again. If the event fails again,
+/// it is recorded as a new failed event. Requires the API token.
+pub async fn replay_failed_event_handler(
+    State(state): State<ServerStateRef>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_token(&headers, state.api_token.as_ref()) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    match replay_failed_event(&state.db, &state.webhook_sender, id).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            tracing::error!("Could not replay failed event {id}: {error:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Sends the failed event with the given ID to the Bors process. Returns `false` if the event does
+/// not exist.
+async fn replay_failed_event(
+    db: &(dyn DbClient + Sync),
+    sender: &WebhookSender,
+    id: i32,
+) -> anyhow::Result<bool> {
+    let Some(event) = db.find_failed_event(id).await? else {
+        return Ok(false);
+    };
+    let event: BorsEvent = serde_json::from_str(&event.event)?;
+    tracing::info!("Replaying failed event {id}");
+    sender.send(event).await?;
+    Ok(true)
+}
+
+/// Rate-limit budget of a single access token.
+#[derive(serde::Serialize)]
+pub struct RateLimitEntry {
+    token: String,
+    #[serde(flatten)]
+    budget: RateLimit,
+}
+
+/// Axum handler that returns the last known GitHub API rate-limit budgets of all access tokens.
+/// Requires the API token, as the budgets reveal how the app is used.
+pub async fn rate_limit_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_token(&headers, state.api_token.as_ref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Json(rate_limit_entries(&state.rate_limits)).into_response()
+}
+
+fn rate_limit_entries(rate_limits: &RateLimitTracker) -> Vec<RateLimitEntry> {
+    rate_limits
+        .all()
+        .into_iter()
+        .map(|(token, budget)| RateLimitEntry {
+            token: token.to_string(),
+            budget,
+        })
+        .collect()
+}
+
+/// Axum handler that exposes the rate-limit budgets as metrics in the Prometheus text format.
+/// Requires the API token, like the rate-limit endpoint.
+pub async fn metrics_handler(
+    State(state): State<ServerStateRef>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if !has_api_token(&headers, state.api_token.as_ref()) {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    format_metrics(&state.rate_limits).into_response()
+}
+
+fn format_metrics(rate_limits: &RateLimitTracker) -> String {
+    let budgets = rate_limits.all();
+    let mut metrics = String::new();
+    push_gauge(
+        &mut metrics,
+        "bors_github_rate_limit_remaining",
+        "Remaining GitHub API requests in the current rate-limit window.",
+        budgets
+            .iter()
+            .map(|(token, budget)| (token, budget.remaining)),
+    );
+    push_gauge(
+        &mut metrics,
+        "bors_github_rate_limit_limit",
+        "GitHub API requests allowed in a single rate-limit window.",
+        budgets.iter().map(|(token, budget)| (token, budget.limit)),
+    );
+    metrics
+}
+
+fn push_gauge<'a>(
+    metrics: &mut String,
+    name: &str,
+    help: &str,
+    values: impl Iterator<Item = (&'a RateLimitToken, u64)>,
+) {
+    metrics.push_str(&format!("# HELP {name} {help}\n# TYPE {name} gauge\n"));
+    for (token, value) in values {
+        metrics.push_str(&format!("{name}{{token=\"{token}\"}} {value}\n"));
+    }
+}
+
 type WebhookSender = mpsc::Sender<BorsEvent>;
 
 /// Creates a future with a Bors process that continuously receives webhook events and reacts to
 /// them.
-pub fn create_bors_process(
-    mut state: GithubAppState,
+///
+/// Once `shutdown` resolves, the process stops accepting new events, handles all events that have
+/// already been received and then ends.
+pub fn create_bors_process<Client: RepositoryClient, State: BorsState<Client>>(
+    mut state: State,
     ctx: BorsContext,
+    shutdown: impl Future<Output = ()>,
 ) -> (WebhookSender, impl Future<Output = ()>) {
     let (tx, mut rx) = mpsc::channel::<BorsEvent>(1024);
 
     let service = async move {
-        while let Some(event) = rx.recv().await {
+        tokio::pin!(shutdown);
+        let mut shutting_down = false;
+
+        loop {
+            let event = tokio::select! {
+                event = rx.recv() => event,
+                () = &mut shutdown, if !shutting_down => {
+                    tracing::info!("Shutting down, handling already received events");
+                    shutting_down = true;
+                    rx.close();
+                    continue;
+                }
+            };
+            let Some(event) = event else {
+                break;
+            };
+
             tracing::trace!("Received event: {event:#?}");
 
             let span = tracing::info_span!("Event");
@@ -72,3 +392,207 @@ pub fn create_bors_process(
     };
     (tx, service)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    use axum::http::{header, HeaderMap};
+    use octocrab::models::InstallationId;
+    use sea_orm::EntityTrait;
+    use tokio::sync::{mpsc, oneshot};
+
+    use entity::build;
+
+    use crate::bors::event::BorsEvent;
+    use crate::bors::{BorsContext, CommandParser};
+    use crate::database::{DbClient, RollupMode};
+    use crate::github::api::rate_limit::{RateLimitToken, RateLimitTracker};
+    use crate::github::server::{
+        create_bors_process, format_metrics, has_api_token, load_failed_events, load_queue,
+        rate_limit_entries, replay_failed_event, ApiToken,
+    };
+    use crate::github::{CommitSha, PullRequestNumber};
+    use crate::tests::database::create_test_db;
+    use crate::tests::event::{comment, default_pr_number};
+    use crate::tests::github::PRBuilder;
+    use crate::tests::state::{default_repo_name, ClientBuilder};
+
+    #[tokio::test]
+    async fn test_queue_order() {
+        let db = create_test_db().await;
+        let repo = default_repo_name();
+        for (number, priority) in [(1, 0), (2, 5), (3, 0)] {
+            let pr = db
+                .get_or_create_pull_request(&repo, PullRequestNumber(number))
+                .await
+                .unwrap();
+            let sha = CommitSha(format!("sha-{number}"));
+            db.approve(&pr, "reviewer", None, &sha).await.unwrap();
+            db.set_priority(&pr, priority).await.unwrap();
+            db.enqueue(&pr, &sha, &format!("PR {number}"), "pr-author")
+                .await
+                .unwrap();
+        }
+        let pr = db
+            .get_or_create_pull_request(&repo, PullRequestNumber(3))
+            .await
+            .unwrap();
+        db.set_rollup(&pr, RollupMode::Never).await.unwrap();
+
+        let queue = load_queue(&db, &repo).await.unwrap();
+        insta::assert_snapshot!(serde_json::to_string_pretty(&queue).unwrap(), @r###"
+        {
+          "repository": "owner/name",
+          "tree_open": true,
+          "prs": [
+            {
+              "number": 2,
+              "title": "PR 2",
+              "approver": "reviewer",
+              "priority": 5,
+              "rollup": null,
+              "state": "approved"
+            },
+            {
+              "number": 1,
+              "title": "PR 1",
+              "approver": "reviewer",
+              "priority": 0,
+              "rollup": null,
+              "state": "approved"
+            },
+            {
+              "number": 3,
+              "title": "PR 3",
+              "approver": "reviewer",
+              "priority": 0,
+              "rollup": "never",
+              "state": "approved"
+            }
+          ]
+        }
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_replay_failed_event() {
+        let db = create_test_db().await;
+        let event = BorsEvent::Comment("@bors try".into());
+        db.record_failed_event(
+            &default_repo_name(),
+            &serde_json::to_string(&event).unwrap(),
+            "Cannot load PR",
+        )
+        .await
+        .unwrap();
+
+        let events = load_failed_events(&db, &default_repo_name()).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].error, "Cannot load PR");
+
+        let (tx, mut rx) = mpsc::channel(1);
+        assert!(replay_failed_event(&db, &tx, events[0].id).await.unwrap());
+        let BorsEvent::Comment(comment) = rx.recv().await.unwrap() else {
+            panic!("Unexpected event");
+        };
+        assert_eq!(comment.text, "@bors try");
+        assert!(!replay_failed_event(&db, &tx, events[0].id + 1)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_replayed_comment_is_executed() {
+        let mut state = ClientBuilder::default().create_state().await;
+        state.client().get_pr_fn = Box::new(|pr| Err(anyhow::anyhow!("PR {pr} not found")));
+        state
+            .comment(comment("@bors ping").comment_id(Some(1)))
+            .await;
+        let events = load_failed_events(&state.db, &default_repo_name())
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+
+        state.client().get_pr_fn = Box::new(|pr| Ok(PRBuilder::default().number(pr.0).create()));
+        let (tx, mut rx) = mpsc::channel(1);
+        assert!(replay_failed_event(&state.db, &tx, events[0].id)
+            .await
+            .unwrap());
+        state.event(rx.recv().await.unwrap()).await;
+        insta::assert_snapshot!(
+            state.client().get_last_comment(default_pr_number()),
+            @"Pong 🏓! Queued PRs: 0, events in flight: 0"
+        );
+    }
+
+    #[test]
+    fn test_queue_requires_api_token() {
+        let token = ApiToken::new("token".to_string());
+        let mut headers = HeaderMap::new();
+        assert!(!has_api_token(&headers, Some(&token)));
+        headers.insert(header::AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        assert!(!has_api_token(&headers, Some(&token)));
+        headers.insert(header::AUTHORIZATION, "Bearer token".parse().unwrap());
+        assert!(has_api_token(&headers, Some(&token)));
+        // Without a configured token, no request is authorized
+        assert!(!has_api_token(&headers, None));
+    }
+
+    #[test]
+    fn test_rate_limit_report() {
+        let tracker = RateLimitTracker::default();
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "4321".parse().unwrap());
+        headers.insert("x-ratelimit-limit", "5000".parse().unwrap());
+        tracker.update_from_headers(&RateLimitToken::Installation(InstallationId(1)), &headers);
+
+        insta::assert_snapshot!(serde_json::to_string(&rate_limit_entries(&tracker)).unwrap(), @r###"[{"token":"installation/1","remaining":4321,"limit":5000}]"###);
+        insta::assert_snapshot!(format_metrics(&tracker), @r###"
+        # HELP bors_github_rate_limit_remaining Remaining GitHub API requests in the current rate-limit window.
+        # TYPE bors_github_rate_limit_remaining gauge
+        bors_github_rate_limit_remaining{token="installation/1"} 4321
+        # HELP bors_github_rate_limit_limit GitHub API requests allowed in a single rate-limit window.
+        # TYPE bors_github_rate_limit_limit gauge
+        bors_github_rate_limit_limit{token="installation/1"} 5000
+        "###);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_handles_received_events() {
+        let mut state = ClientBuilder::default().create_state().await;
+        let db = state.db.connection().clone();
+        let ctx = BorsContext::new(CommandParser::new("@bors".to_string()));
+
+        // The shutdown is requested while the first event is being handled
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let shutdown_tx = Mutex::new(Some(shutdown_tx));
+        state.client().get_pr_fn = Box::new(move |pr| {
+            if let Some(shutdown_tx) = shutdown_tx.lock().unwrap().take() {
+                shutdown_tx.send(()).unwrap();
+            }
+            Ok(PRBuilder::default().number(pr.0).create())
+        });
+        let merges = AtomicU64::new(0);
+        state.client().merge_branches_fn = Box::new(move || {
+            let merge = merges.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(CommitSha(format!("merge-{merge}")))
+        });
+        let (tx, process) = create_bors_process(state, ctx, async move {
+            shutdown_rx.await.unwrap();
+        });
+        tx.send(BorsEvent::Comment(comment("@bors try").pr_number(1).into()))
+            .await
+            .unwrap();
+        tx.send(BorsEvent::Comment(comment("@bors try").pr_number(2).into()))
+            .await
+            .unwrap();
+        process.await;
+
+        // The event in progress and the event waiting after it have been fully handled
+        assert_eq!(build::Entity::find().all(&db).await.unwrap().len(), 2);
+        // New events are not accepted anymore
+        assert!(tx.send(BorsEvent::Refresh).await.is_err());
+    }
+}
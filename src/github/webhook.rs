@@ -8,8 +8,8 @@ use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
 use axum::{async_trait, RequestExt};
 use hmac::{Hmac, Mac};
 use octocrab::models::events::payload::{
-    IssueCommentEventAction, IssueCommentEventPayload, PullRequestReviewCommentEventAction,
-    PullRequestReviewCommentEventPayload,
+    IssueCommentEventAction, IssueCommentEventChanges, IssueCommentEventPayload,
+    PullRequestReviewCommentEventAction, PullRequestReviewCommentEventPayload,
 };
 use octocrab::models::pulls::{PullRequest, Review};
 use octocrab::models::{workflows, App, CheckRun, Repository, RunId, User};
@@ -17,7 +17,10 @@ use secrecy::{ExposeSecret, SecretString};
 use sha2::Sha256;
 
 use crate::bors::event::{
-    BorsEvent, CheckSuiteCompleted, PullRequestComment, WorkflowCompleted, WorkflowStarted,
+    BorsEvent, CheckSuiteCompleted, CommentEdit, CommitStatusChanged, MergeGroupCompleted,
+    MergeGroupDestroyed, MergeGroupRequested, PullRequestComment, PullRequestOpened,
+    RepositoriesRemoved, RepositoryRenamed, WorkflowCompleted, WorkflowStarted,
+    MERGE_GROUP_BRANCH_PREFIX,
 };
 use crate::database::{WorkflowStatus, WorkflowType};
 use crate::github::server::ServerStateRef;
@@ -71,6 +74,8 @@ pub struct WebhookCheckRun<'a> {
 pub struct CheckSuiteInner {
     head_branch: String,
     head_sha: String,
+    #[serde(default)]
+    conclusion: Option<String>,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -80,6 +85,69 @@ pub struct WebhookCheckSuite<'a> {
     repository: Repository,
 }
 
+#[derive(serde::Deserialize, Debug)]
+pub struct MergeGroupInner {
+    head_sha: String,
+    head_ref: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct WebhookMergeGroup<'a> {
+    action: &'a str,
+    merge_group: MergeGroupInner,
+    #[serde(default)]
+    reason: Option<&'a str>,
+    repository: Repository,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct StatusBranch {
+    name: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct WebhookStatus<'a> {
+    sha: String,
+    state: &'a str,
+    branches: Vec<StatusBranch>,
+    repository: Repository,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct InstallationRepository {
+    full_name: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct WebhookInstallationRepositories<'a> {
+    action: &'a str,
+    #[serde(default)]
+    repositories_removed: Vec<InstallationRepository>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct WebhookRepositoryEvent<'a> {
+    action: &'a str,
+    #[serde(default)]
+    changes: Option<RepositoryChanges>,
+    repository: Repository,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct RepositoryChanges {
+    repository: Option<RepositoryNameChanges>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct RepositoryNameChanges {
+    name: Option<PreviousValue>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PreviousValue {
+    from: String,
+}
+
 #[derive(Debug, serde::Deserialize)]
 pub struct WebhookPullRequestReviewEvent<'a> {
     action: &'a str,
@@ -89,6 +157,13 @@ pub struct WebhookPullRequestReviewEvent<'a> {
     sender: User,
 }
 
+#[derive(Debug, serde::Deserialize)]
+pub struct WebhookPullRequestEvent<'a> {
+    action: &'a str,
+    pull_request: PullRequest,
+    repository: Repository,
+}
+
 /// axum extractor for GitHub webhook events.
 #[derive(Debug)]
 pub struct GitHubWebhook(pub BorsEvent);
@@ -141,7 +216,7 @@ where
 
 fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<BorsEvent>> {
     let Some(event_type) = request.headers.get("x-github-event") else {
-         return Err(anyhow::anyhow!("x-github-event header not found"));
+        return Err(anyhow::anyhow!("x-github-event header not found"));
     };
 
     tracing::trace!(
@@ -156,13 +231,25 @@ fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<Bor
             let repository_name = parse_repository_name(&repository.repository)?;
 
             let event: IssueCommentEventPayload = serde_json::from_slice(body)?;
-            if event.action == IssueCommentEventAction::Created {
+            if matches!(
+                event.action,
+                IssueCommentEventAction::Created | IssueCommentEventAction::Edited
+            ) {
                 let comment = parse_pr_comment(repository_name, event).map(BorsEvent::Comment);
                 Ok(comment)
             } else {
                 Ok(None)
             }
         }
+        b"pull_request" => {
+            let payload: WebhookPullRequestEvent = serde_json::from_slice(body)?;
+            if payload.action == "opened" {
+                let event = parse_pr_opened(payload)?;
+                Ok(Some(BorsEvent::PullRequestOpened(event)))
+            } else {
+                Ok(None)
+            }
+        }
         b"pull_request_review" => {
             let payload: WebhookPullRequestReviewEvent = serde_json::from_slice(body)?;
             if payload.action == "submitted" {
@@ -184,7 +271,42 @@ fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<Bor
                 Ok(None)
             }
         }
-        b"installation_repositories" | b"installation" => Ok(Some(BorsEvent::InstallationsChanged)),
+        b"installation_repositories" => {
+            let payload: WebhookInstallationRepositories = serde_json::from_slice(body)?;
+            if payload.action == "removed" {
+                let repositories = payload
+                    .repositories_removed
+                    .iter()
+                    .map(|repository| parse_full_repository_name(&repository.full_name))
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(Some(BorsEvent::RepositoriesRemoved(RepositoriesRemoved {
+                    repositories,
+                })))
+            } else {
+                Ok(Some(BorsEvent::InstallationsChanged))
+            }
+        }
+        b"installation" => Ok(Some(BorsEvent::InstallationsChanged)),
+        b"repository" => {
+            let payload: WebhookRepositoryEvent = serde_json::from_slice(body)?;
+            if payload.action != "renamed" {
+                return Ok(None);
+            }
+            let new_name = parse_repository_name(&payload.repository)?;
+            let Some(previous) = payload
+                .changes
+                .and_then(|changes| changes.repository)
+                .and_then(|repository| repository.name)
+            else {
+                return Err(anyhow::anyhow!(
+                    "Previous name of renamed repository {new_name} is missing"
+                ));
+            };
+            Ok(Some(BorsEvent::RepositoryRenamed(RepositoryRenamed {
+                old_name: GithubRepoName::new(new_name.owner(), &previous.from),
+                new_name,
+            })))
+        }
         b"workflow_run" => {
             let payload: WebhookWorkflowRun = serde_json::from_slice(body)?;
             let repository_name = parse_repository_name(&payload.repository)?;
@@ -195,6 +317,7 @@ fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<Bor
                     branch: payload.workflow_run.head_branch,
                     commit_sha: CommitSha(payload.workflow_run.head_sha),
                     run_id: RunId(payload.workflow_run.id.0),
+                    workflow_id: Some(payload.workflow_run.workflow_id.0),
                     workflow_type: WorkflowType::Github,
                     url: payload.workflow_run.html_url.into(),
                 })),
@@ -229,6 +352,7 @@ fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<Bor
                     branch: payload.check_run.check_suite.head_branch,
                     commit_sha: CommitSha(payload.check_run.check_suite.head_sha),
                     run_id: RunId(payload.check_run.check_run.id.map(|v| v.0).unwrap_or(0)),
+                    workflow_id: None,
                     workflow_type: WorkflowType::External,
                     url: payload.check_run.check_run.html_url.unwrap_or_default(),
                 })))
@@ -239,16 +363,71 @@ fn parse_webhook_event(request: Parts, body: &[u8]) -> anyhow::Result<Option<Bor
         b"check_suite" => {
             let payload: WebhookCheckSuite = serde_json::from_slice(body)?;
             let repository_name = parse_repository_name(&payload.repository)?;
-            if payload.action == "completed" {
-                Ok(Some(BorsEvent::CheckSuiteCompleted(CheckSuiteCompleted {
+            if payload.action != "completed" {
+                Ok(None)
+            } else if payload
+                .check_suite
+                .head_branch
+                .starts_with(MERGE_GROUP_BRANCH_PREFIX)
+            {
+                Ok(Some(BorsEvent::MergeGroup(MergeGroupCompleted {
                     repository: repository_name,
                     branch: payload.check_suite.head_branch,
                     commit_sha: CommitSha(payload.check_suite.head_sha),
+                    status: match payload.check_suite.conclusion.as_deref() {
+                        Some("success") => WorkflowStatus::Success,
+                        _ => WorkflowStatus::Failure,
+                    },
                 })))
             } else {
-                Ok(None)
+                Ok(Some(BorsEvent::CheckSuiteCompleted(CheckSuiteCompleted {
+                    repository: repository_name,
+                    branch: payload.check_suite.head_branch,
+                    commit_sha: CommitSha(payload.check_suite.head_sha),
+                })))
             }
         }
+        b"merge_group" => {
+            let payload: WebhookMergeGroup = serde_json::from_slice(body)?;
+            let repository_name = parse_repository_name(&payload.repository)?;
+            let branch = payload
+                .merge_group
+                .head_ref
+                .trim_start_matches("refs/heads/")
+                .to_string();
+            let commit_sha = CommitSha(payload.merge_group.head_sha);
+            match payload.action {
+                "checks_requested" => {
+                    Ok(Some(BorsEvent::MergeGroupRequested(MergeGroupRequested {
+                        repository: repository_name,
+                        branch,
+                        commit_sha,
+                    })))
+                }
+                "destroyed" => Ok(Some(BorsEvent::MergeGroupDestroyed(MergeGroupDestroyed {
+                    repository: repository_name,
+                    commit_sha,
+                    merged: payload.reason == Some("merged"),
+                }))),
+                _ => Ok(None),
+            }
+        }
+        b"status" => {
+            let payload: WebhookStatus = serde_json::from_slice(body)?;
+            if payload.state == "pending" {
+                return Ok(None);
+            }
+            let repository_name = parse_repository_name(&payload.repository)?;
+            Ok(Some(BorsEvent::CommitStatusChanged(CommitStatusChanged {
+                repository: repository_name,
+                commit_sha: CommitSha(payload.sha),
+                branches: payload
+                    .branches
+                    .into_iter()
+                    .map(|branch| branch.name)
+                    .collect(),
+            })))
+        }
         _ => {
             tracing::debug!("Ignoring unknown event type {:?}", event_type.to_str());
             std::fs::write(format!("{}.json", event_type.to_str().unwrap()), body).unwrap();
@@ -267,6 +446,8 @@ fn parse_pr_review_comment(
         author: user,
         pr_number: PullRequestNumber(payload.pull_request.number),
         text: payload.comment.body.unwrap_or_default(),
+        comment_id: None,
+        edit: None,
     }
 }
 
@@ -281,11 +462,31 @@ fn parse_comment_from_pr_review(
         author: user,
         pr_number: PullRequestNumber(payload.pull_request.number),
         text: payload.review.body.unwrap_or_default(),
+        comment_id: None,
+        edit: None,
+    })
+}
+
+fn parse_pr_opened(payload: WebhookPullRequestEvent<'_>) -> anyhow::Result<PullRequestOpened> {
+    let repository_name = parse_repository_name(&payload.repository)?;
+    // The sender of the event is not necessarily the author of the PR
+    let user = payload
+        .pull_request
+        .user
+        .map(|user| parse_user(*user))
+        .ok_or_else(|| anyhow::anyhow!("PR {} has no author", payload.pull_request.number))?;
+
+    Ok(PullRequestOpened {
+        repository: repository_name,
+        author: user,
+        pr_number: PullRequestNumber(payload.pull_request.number),
+        description: payload.pull_request.body.unwrap_or_default(),
     })
 }
 
 fn parse_user(user: User) -> GithubUser {
     GithubUser {
+        id: user.id,
         username: user.login,
         html_url: user.html_url,
     }
@@ -301,25 +502,41 @@ fn parse_pr_comment(
         return None;
     }
 
+    let edit = if payload.action == IssueCommentEventAction::Edited {
+        Some(CommentEdit {
+            previous_text: match payload.changes {
+                Some(IssueCommentEventChanges::Body(body)) => Some(body.from),
+                _ => None,
+            },
+        })
+    } else {
+        None
+    };
     Some(PullRequestComment {
         repository: repo,
         author: parse_user(payload.comment.user),
         text: payload.comment.body.unwrap_or_default(),
         pr_number: PullRequestNumber(payload.issue.number),
+        comment_id: Some(payload.comment.id.0),
+        edit,
     })
 }
 
 fn parse_repository_name(repository: &Repository) -> anyhow::Result<GithubRepoName> {
     let repo_name = &repository.name;
-    let Some(repo_owner) = repository
-        .owner
-        .as_ref()
-        .map(|u| &u.login) else {
+    let Some(repo_owner) = repository.owner.as_ref().map(|u| &u.login) else {
         return Err(anyhow::anyhow!("Owner for repo {repo_name} is missing"));
     };
     Ok(GithubRepoName::new(repo_owner, repo_name))
 }
 
+fn parse_full_repository_name(full_name: &str) -> anyhow::Result<GithubRepoName> {
+    let Some((owner, name)) = full_name.split_once('/') else {
+        return Err(anyhow::anyhow!("Invalid repository name {full_name}"));
+    };
+    Ok(GithubRepoName::new(owner, name))
+}
+
 type HmacSha256 = Hmac<Sha256>;
 
 /// Verifies that the request is properly signed by GitHub with SHA-256 and the passed `secret`.
@@ -331,7 +548,10 @@ fn verify_gh_signature(
     let Some(signature) = headers.get("x-hub-signature-256").map(|v| v.as_bytes()) else {
         return false;
     };
-    let Some(signature) = signature.get(b"sha256=".len()..).and_then(|v| hex::decode(v).ok()) else {
+    let Some(signature) = signature
+        .get(b"sha256=".len()..)
+        .and_then(|v| hex::decode(v).ok())
+    else {
         return false;
     };
 
@@ -353,6 +573,7 @@ mod tests {
     use crate::github::server::{ServerState, ServerStateRef};
     use crate::github::webhook::WebhookSecret;
     use crate::github::webhook::{GitHubWebhook, HmacSha256};
+    use crate::tests::database::create_test_db;
     use crate::tests::io::load_test_file;
 
     #[tokio::test]
@@ -371,6 +592,21 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn test_issue_comment_edited() {
+        let Ok(GitHubWebhook(BorsEvent::Comment(comment))) =
+            check_webhook("webhook/issue-comment-edited.json", "issue_comment").await
+        else {
+            panic!("Edited comment was not parsed");
+        };
+        assert_eq!(comment.text, "hello bors\n@bors ping");
+        assert_eq!(comment.comment_id, Some(1420770715));
+        assert_eq!(
+            comment.edit.and_then(|edit| edit.previous_text).as_deref(),
+            Some("hello bors")
+        );
+    }
+
     #[tokio::test]
     async fn test_issue_comment() {
         insta::assert_debug_snapshot!(
@@ -385,6 +621,9 @@ mod tests {
                             name: "bors-kindergarten",
                         },
                         author: GithubUser {
+                            id: UserId(
+                                4539057,
+                            ),
                             username: "Kobzol",
                             html_url: Url {
                                 scheme: "https",
@@ -406,6 +645,56 @@ mod tests {
                             5,
                         ),
                         text: "hello bors",
+                        comment_id: Some(
+                            1420770715,
+                        ),
+                        edit: None,
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pull_request_opened() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/pull-request-opened.json", "pull_request").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                PullRequestOpened(
+                    PullRequestOpened {
+                        repository: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        author: GithubUser {
+                            id: UserId(
+                                4539057,
+                            ),
+                            username: "Kobzol",
+                            html_url: Url {
+                                scheme: "https",
+                                cannot_be_a_base: false,
+                                username: "",
+                                password: None,
+                                host: Some(
+                                    Domain(
+                                        "github.com",
+                                    ),
+                                ),
+                                port: None,
+                                path: "/Kobzol",
+                                query: None,
+                                fragment: None,
+                            },
+                        },
+                        pr_number: PullRequestNumber(
+                            6,
+                        ),
+                        description: "This PR adds a new feature.\n\n@bors try",
                     },
                 ),
             ),
@@ -428,6 +717,9 @@ mod tests {
                             name: "bors-kindergarten",
                         },
                         author: GithubUser {
+                            id: UserId(
+                                4539057,
+                            ),
                             username: "Kobzol",
                             html_url: Url {
                                 scheme: "https",
@@ -449,6 +741,8 @@ mod tests {
                             6,
                         ),
                         text: "review comment",
+                        comment_id: None,
+                        edit: None,
                     },
                 ),
             ),
@@ -471,6 +765,9 @@ mod tests {
                             name: "bors-kindergarten",
                         },
                         author: GithubUser {
+                            id: UserId(
+                                4539057,
+                            ),
                             username: "Kobzol",
                             html_url: Url {
                                 scheme: "https",
@@ -492,6 +789,8 @@ mod tests {
                             6,
                         ),
                         text: "Foo",
+                        comment_id: None,
+                        edit: None,
                     },
                 ),
             ),
@@ -521,6 +820,9 @@ mod tests {
                         run_id: RunId(
                             4900979074,
                         ),
+                        workflow_id: Some(
+                            56438184,
+                        ),
                         workflow_type: Github,
                         url: "https://github.com/Kobzol/bors-kindergarten/actions/runs/4900979074",
                     },
@@ -560,6 +862,162 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_check_suite_completed_merge_group() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/check-suite-completed-merge-group.json", "check_suite").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                MergeGroup(
+                    MergeGroupCompleted {
+                        repository: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        branch: "gh-readonly-queue/main/pr-12-6e8c0c2a0b9f4b2c9f2d7e6f5a4b3c2d1e0f9a8b",
+                        commit_sha: CommitSha(
+                            "a4f8d3c2b1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6",
+                        ),
+                        status: Failure,
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_checks_requested() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/merge-group-checks-requested.json", "merge_group").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                MergeGroupRequested(
+                    MergeGroupRequested {
+                        repository: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        branch: "gh-readonly-queue/main/pr-12-6e8c0c2a0b9f4b2c9f2d7e6f5a4b3c2d1e0f9a8b",
+                        commit_sha: CommitSha(
+                            "a4f8d3c2b1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6",
+                        ),
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_group_destroyed() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/merge-group-destroyed.json", "merge_group").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                MergeGroupDestroyed(
+                    MergeGroupDestroyed {
+                        repository: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        commit_sha: CommitSha(
+                            "a4f8d3c2b1e0f9a8b7c6d5e4f3a2b1c0d9e8f7a6",
+                        ),
+                        merged: false,
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_installation_repositories_removed() {
+        insta::assert_debug_snapshot!(
+            check_webhook(
+                "webhook/installation-repositories-removed.json",
+                "installation_repositories"
+            )
+            .await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                RepositoriesRemoved(
+                    RepositoriesRemoved {
+                        repositories: [
+                            GithubRepoName {
+                                owner: "kobzol",
+                                name: "bors-kindergarten",
+                            },
+                        ],
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repository_renamed() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/repository-renamed.json", "repository").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                RepositoryRenamed(
+                    RepositoryRenamed {
+                        old_name: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        new_name: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-playground",
+                        },
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_success() {
+        insta::assert_debug_snapshot!(
+            check_webhook("webhook/status-success.json", "status").await,
+            @r###"
+        Ok(
+            GitHubWebhook(
+                CommitStatusChanged(
+                    CommitStatusChanged {
+                        repository: GithubRepoName {
+                            owner: "kobzol",
+                            name: "bors-kindergarten",
+                        },
+                        commit_sha: CommitSha(
+                            "b3f7e1d0c2a94f5e8d6b7a1c0e9f8d7c6b5a4e3d",
+                        ),
+                        branches: [
+                            "automation/bors/try",
+                            "automation/bors/try-merge",
+                        ],
+                    },
+                ),
+            ),
+        )
+        "###
+        );
+    }
+
     #[tokio::test]
     async fn test_check_run_created_external() {
         insta::assert_debug_snapshot!(
@@ -581,6 +1039,7 @@ mod tests {
                         run_id: RunId(
                             13293850093,
                         ),
+                        workflow_id: None,
                         workflow_type: External,
                         url: "https://github.com/Kobzol/bors-kindergarten/runs/13293850093",
                     },
@@ -636,7 +1095,13 @@ mod tests {
         );
 
         let (tx, _) = mpsc::channel(1024);
-        let server_ref = ServerStateRef::new(ServerState::new(tx, WebhookSecret::new(secret)));
+        let server_ref = ServerStateRef::new(ServerState::new(
+            tx,
+            WebhookSecret::new(secret),
+            None,
+            create_test_db().await,
+            Default::default(),
+        ));
         GitHubWebhook::from_request(request, &server_ref).await
     }
 }
@@ -3,36 +3,52 @@ use std::collections::HashSet;
 use std::time::{Duration, SystemTime};
 use tokio::sync::Mutex;
 
+use crate::github::api::api_url;
+use crate::github::api::request::ApiClient;
 use crate::github::GithubRepoName;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PermissionType {
     /// Can perform commands like r+.
     Review,
     /// Can start a try build.
     Try,
+    /// Can retry a failed build.
+    Retry,
+    /// Has at least write access to the repository on GitHub.
+    Write,
+    /// Has admin access to the repository on GitHub.
+    Admin,
 }
 
 /// Decides if a GitHub user can perform various actions using the bot.
 #[async_trait]
 pub trait PermissionResolver {
     async fn has_permission(&self, username: &str, permission: PermissionType) -> bool;
+
+    /// Is the user a member of the given team of the organization that owns the repository?
+    async fn is_team_member(&self, username: &str, team: &str) -> bool;
 }
 
 /// For how long should the permissions be cached.
 const CACHE_DURATION: Duration = Duration::from_secs(60);
 
 /// Loads permission information from the Rust Team API.
+/// Team memberships and access to the repository are resolved using the GitHub API.
 pub struct TeamApiPermissionResolver {
     repo: GithubRepoName,
+    api: ApiClient,
     permissions: Mutex<CachedUserPermissions>,
 }
 
 impl TeamApiPermissionResolver {
-    pub async fn load(repo: GithubRepoName) -> anyhow::Result<Self> {
+    pub async fn load(repo: GithubRepoName, api: ApiClient) -> anyhow::Result<Self> {
         let permissions = load_permissions(&repo).await?;
 
         Ok(Self {
             repo,
+            api,
             permissions: Mutex::new(CachedUserPermissions::new(permissions)),
         })
     }
@@ -51,6 +67,18 @@ impl TeamApiPermissionResolver {
 #[async_trait]
 impl PermissionResolver for TeamApiPermissionResolver {
     async fn has_permission(&self, username: &str, permission: PermissionType) -> bool {
+        if let PermissionType::Write | PermissionType::Admin = permission {
+            return match load_repository_permission(&self.api, &self.repo, username).await {
+                Ok(level) => grants_permission(&level, permission),
+                Err(error) => {
+                    tracing::error!(
+                        "Cannot load permission of {username} in {}: {error:?}",
+                        self.repo
+                    );
+                    false
+                }
+            };
+        }
         if self.permissions.lock().await.is_stale() {
             self.reload_permissions().await;
         }
@@ -61,6 +89,16 @@ impl PermissionResolver for TeamApiPermissionResolver {
             .permissions
             .has_permission(username, permission)
     }
+
+    async fn is_team_member(&self, username: &str, team: &str) -> bool {
+        match load_team_membership(&self.api, self.repo.owner(), team, username).await {
+            Ok(member) => member,
+            Err(error) => {
+                tracing::error!("Cannot load membership of {username} in team {team}: {error:?}");
+                false
+            }
+        }
+    }
 }
 
 pub struct UserPermissions {
@@ -72,7 +110,10 @@ impl UserPermissions {
     fn has_permission(&self, username: &str, permission: PermissionType) -> bool {
         match permission {
             PermissionType::Review => self.review_users.contains(username),
-            PermissionType::Try => self.try_users.contains(username),
+            // Retrying a build requires the same permissions as starting it
+            PermissionType::Try | PermissionType::Retry => self.try_users.contains(username),
+            // Access to the repository is not managed by the team API
+            PermissionType::Write | PermissionType::Admin => false,
         }
     }
 }
@@ -100,11 +141,11 @@ impl CachedUserPermissions {
 async fn load_permissions(repo: &GithubRepoName) -> anyhow::Result<UserPermissions> {
     tracing::info!("Reloading permissions for repository {repo}");
 
-    let review_users = load_users_from_team_api(repo.name(), PermissionType::Review)
+    let review_users = load_users_from_team_api(repo.name(), "review")
         .await
         .map_err(|error| anyhow::anyhow!("Cannot load review users: {error:?}"))?;
 
-    let try_users = load_users_from_team_api(repo.name(), PermissionType::Try)
+    let try_users = load_users_from_team_api(repo.name(), "try")
         .await
         .map_err(|error| anyhow::anyhow!("Cannot load try users: {error:?}"))?;
     Ok(UserPermissions {
@@ -121,13 +162,8 @@ struct UserPermissionsResponse {
 /// Loads users that are allowed to perform try/review from the Rust Team API.
 async fn load_users_from_team_api(
     repository_name: &str,
-    permission: PermissionType,
+    permission: &str,
 ) -> anyhow::Result<HashSet<String>> {
-    let permission = match permission {
-        PermissionType::Review => "review",
-        PermissionType::Try => "try",
-    };
-
     let url = format!("https://team-api.infra.rust-lang.org/v1/permissions/bors.{repository_name}.{permission}.json");
     let users = reqwest::get(url)
         .await
@@ -138,3 +174,88 @@ async fn load_users_from_team_api(
         .map_err(|error| anyhow::anyhow!("Cannot deserialize users from team API: {error:?}"))?;
     Ok(users.github_users)
 }
+
+#[derive(serde::Deserialize)]
+struct TeamMembershipResponse {
+    state: String,
+}
+
+/// Checks if the user is an active member of the team using the GitHub teams API.
+async fn load_team_membership(
+    api: &ApiClient,
+    org: &str,
+    team: &str,
+    username: &str,
+) -> anyhow::Result<bool> {
+    let response = api
+        .get(api_url(
+            api.base_url(),
+            &["orgs", org, "teams", team, "memberships", username],
+        )?)
+        .await?;
+    // The user is not a member of the team, or the team does not exist
+    if response.status().as_u16() == 404 {
+        return Ok(false);
+    }
+    let membership = response
+        .error_for_status()?
+        .json::<TeamMembershipResponse>()
+        .await?;
+    // Pending memberships are invitations that were not accepted yet
+    Ok(membership.state == "active")
+}
+
+#[derive(serde::Deserialize)]
+struct RepositoryPermissionResponse {
+    permission: String,
+}
+
+/// Loads the access level of the user to the repository using the GitHub collaborators API, e.g.
+/// `admin`, `write` or `read`.
+async fn load_repository_permission(
+    api: &ApiClient,
+    repo: &GithubRepoName,
+    username: &str,
+) -> anyhow::Result<String> {
+    let response = api
+        .get(api.base_url().join(&format!(
+            "/repos/{}/{}/collaborators/{username}/permission",
+            repo.owner(),
+            repo.name()
+        ))?)
+        .await?;
+    // The user is not a collaborator of the repository
+    if response.status().as_u16() == 404 {
+        return Ok("none".to_string());
+    }
+    let permission = response
+        .error_for_status()?
+        .json::<RepositoryPermissionResponse>()
+        .await?;
+    Ok(permission.permission)
+}
+
+/// Does the access level of a user to the repository grant the given permission?
+fn grants_permission(level: &str, permission: PermissionType) -> bool {
+    match permission {
+        // The `maintain` role is reported as `write` by the collaborators API
+        PermissionType::Write => matches!(level, "admin" | "write"),
+        PermissionType::Admin => level == "admin",
+        PermissionType::Review | PermissionType::Try | PermissionType::Retry => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::permissions::{grants_permission, PermissionType};
+
+    #[test]
+    fn repository_permission_levels() {
+        assert!(grants_permission("admin", PermissionType::Write));
+        assert!(grants_permission("admin", PermissionType::Admin));
+        assert!(grants_permission("write", PermissionType::Write));
+        assert!(!grants_permission("write", PermissionType::Admin));
+        assert!(!grants_permission("read", PermissionType::Write));
+        assert!(!grants_permission("none", PermissionType::Write));
+    }
+}
@@ -1,11 +1,9 @@
 use sea_orm::Database;
 
-use migration::{Migrator, MigratorTrait};
-
-use crate::database::SeaORMClient;
+use crate::database::{DbClient, SeaORMClient};
 
 pub async fn create_test_db() -> SeaORMClient {
-    let db = Database::connect("sqlite::memory:").await.unwrap();
-    Migrator::up(&db, None).await.unwrap();
-    SeaORMClient::new(db.clone())
+    let db = SeaORMClient::new(Database::connect("sqlite::memory:").await.unwrap());
+    db.run_migrations().await.unwrap();
+    db
 }
@@ -1,19 +1,39 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
 use derive_builder::Builder;
-use octocrab::models::RunId;
+use octocrab::models::{RunId, UserId};
 
-use crate::bors::event::PullRequestComment;
+use crate::bors::event::{CommentEdit, PullRequestComment, PullRequestOpened};
 use crate::bors::{event, CheckSuite, CheckSuiteStatus};
 use crate::database::{WorkflowStatus, WorkflowType};
 use crate::github::{CommitSha, GithubRepoName, GithubUser, PullRequestNumber};
 use crate::tests::state::{default_merge_sha, default_repo_name};
 
-fn default_user() -> GithubUser {
+pub fn default_user() -> GithubUser {
     GithubUser {
+        id: UserId(1),
         username: "<user>".to_string(),
         html_url: "https://user.com".parse().unwrap(),
     }
 }
 
+/// Creates a user with the given name, its ID is derived from the name.
+pub fn user(username: &str) -> GithubUser {
+    let mut hasher = DefaultHasher::new();
+    username.hash(&mut hasher);
+    GithubUser {
+        id: UserId(hasher.finish()),
+        username: username.to_string(),
+        html_url: format!("https://github.com/{username}").parse().unwrap(),
+    }
+}
+
+/// Author of the PRs returned by the test client.
+pub fn default_pr_author() -> GithubUser {
+    user("pr-author")
+}
+
 pub fn default_pr_number() -> u64 {
     1
 }
@@ -28,6 +48,10 @@ pub struct Comment {
     text: String,
     #[builder(default = "default_user()")]
     author: GithubUser,
+    #[builder(default)]
+    comment_id: Option<u64>,
+    #[builder(default)]
+    edit: Option<CommentEdit>,
 }
 
 impl CommentBuilder {
@@ -37,12 +61,16 @@ impl CommentBuilder {
             pr_number,
             text,
             author,
+            comment_id,
+            edit,
         } = self.build().unwrap();
         PullRequestComment {
             repository: repo,
             pr_number: PullRequestNumber(pr_number),
             text,
             author,
+            comment_id,
+            edit,
         }
     }
 }
@@ -54,6 +82,8 @@ impl From<&str> for PullRequestComment {
             author: default_user(),
             pr_number: PullRequestNumber(default_pr_number()),
             text: value.to_string(),
+            comment_id: None,
+            edit: None,
         }
     }
 }
@@ -68,20 +98,41 @@ pub fn comment(text: &str) -> CommentBuilder {
     CommentBuilder::default().text(text.to_string())
 }
 
+/// Creates an edit of the comment with the given ID, whose text was `previous_text` before.
+pub fn edited_comment(comment_id: u64, previous_text: &str, text: &str) -> CommentBuilder {
+    comment(text)
+        .comment_id(Some(comment_id))
+        .edit(Some(CommentEdit {
+            previous_text: Some(previous_text.to_string()),
+        }))
+}
+
+pub fn pr_opened(description: &str) -> PullRequestOpened {
+    PullRequestOpened {
+        repository: default_repo_name(),
+        author: default_user(),
+        pr_number: PullRequestNumber(default_pr_number()),
+        description: description.to_string(),
+    }
+}
+
 pub fn suite_success() -> CheckSuite {
     CheckSuite {
+        github_actions: true,
         status: CheckSuiteStatus::Success,
     }
 }
 
 pub fn suite_failure() -> CheckSuite {
     CheckSuite {
+        github_actions: true,
         status: CheckSuiteStatus::Failure,
     }
 }
 
 pub fn suite_pending() -> CheckSuite {
     CheckSuite {
+        github_actions: true,
         status: CheckSuiteStatus::Pending,
     }
 }
@@ -99,6 +150,8 @@ pub struct WorkflowStarted {
     #[builder(default = "1")]
     run_id: u64,
     #[builder(default)]
+    workflow_id: Option<u64>,
+    #[builder(default)]
     url: Option<String>,
     #[builder(default = "WorkflowType::Github")]
     workflow_type: WorkflowType,
@@ -112,6 +165,7 @@ impl WorkflowStartedBuilder {
             branch,
             commit_sha,
             run_id,
+            workflow_id,
             url,
             workflow_type,
         } = self.build().unwrap();
@@ -123,6 +177,7 @@ impl WorkflowStartedBuilder {
             branch,
             commit_sha: CommitSha(commit_sha),
             run_id: RunId(run_id),
+            workflow_id,
             workflow_type,
             url,
         }
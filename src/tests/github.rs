@@ -1,12 +1,14 @@
 use derive_builder::Builder;
 
-use crate::github::{Branch as GHBranch, PullRequest};
-use crate::tests::event::default_pr_number;
+use crate::github::{Branch as GHBranch, Commit as GHCommit, GithubUser, PullRequest};
+use crate::tests::event::{default_pr_author, default_pr_number};
 
 #[derive(Builder)]
 pub struct PR {
     #[builder(default = "default_pr_number()")]
     number: u64,
+    #[builder(default = "default_pr_author()")]
+    author: GithubUser,
     #[builder(default = "\"head-label\".to_string()")]
     head_label: String,
     #[builder(default = "self.default_head()")]
@@ -17,26 +19,48 @@ pub struct PR {
     title: String,
     #[builder(default = "\"PR message\".to_string()")]
     message: String,
+    #[builder(default)]
+    labels: Vec<String>,
+    #[builder(default)]
+    requested_reviewers: Vec<String>,
+    #[builder(default)]
+    draft: bool,
+    #[builder(default)]
+    mergeable: Option<bool>,
+    #[builder(default)]
+    closed: bool,
 }
 
 impl PRBuilder {
     pub fn create(&mut self) -> PullRequest {
         let PR {
             number,
+            author,
             head_label,
             head,
             base,
             title,
             message,
+            labels,
+            requested_reviewers,
+            draft,
+            mergeable,
+            closed,
         } = self.build().unwrap();
 
         PullRequest {
             number: number.into(),
+            author,
             head_label,
             head,
             base,
             title,
             message,
+            labels,
+            requested_reviewers,
+            draft,
+            mergeable,
+            closed,
         }
     }
 
@@ -75,3 +99,32 @@ impl BranchBuilder {
         }
     }
 }
+
+#[derive(Builder)]
+pub struct Commit {
+    #[builder(default = "\"sha-1\".to_string()")]
+    sha: String,
+    #[builder(default)]
+    message: String,
+    #[builder(default = "\"author\".to_string()")]
+    author: String,
+    #[builder(default)]
+    parents: Vec<String>,
+}
+
+impl CommitBuilder {
+    pub fn create(&mut self) -> GHCommit {
+        let Commit {
+            sha,
+            message,
+            author,
+            parents,
+        } = self.build().unwrap();
+        GHCommit {
+            sha: sha.into(),
+            message,
+            author,
+            parents: parents.into_iter().map(|sha| sha.into()).collect(),
+        }
+    }
+}
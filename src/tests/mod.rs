@@ -2,5 +2,6 @@ pub(crate) mod database;
 pub(crate) mod event;
 pub(crate) mod github;
 pub(crate) mod io;
+pub(crate) mod logs;
 pub(crate) mod permissions;
 pub(crate) mod state;
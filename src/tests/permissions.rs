@@ -1,3 +1,5 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::permissions::{PermissionResolver, PermissionType};
 use axum::async_trait;
 
@@ -8,6 +10,10 @@ impl PermissionResolver for NoPermissions {
     async fn has_permission(&self, _username: &str, _permission: PermissionType) -> bool {
         false
     }
+
+    async fn is_team_member(&self, _username: &str, _team: &str) -> bool {
+        false
+    }
 }
 
 pub struct AllPermissions;
@@ -17,4 +23,62 @@ impl PermissionResolver for AllPermissions {
     async fn has_permission(&self, _username: &str, _permission: PermissionType) -> bool {
         true
     }
+
+    async fn is_team_member(&self, _username: &str, _team: &str) -> bool {
+        true
+    }
+}
+
+/// Grants all permissions to the selected users, and no permissions to anyone else.
+/// Team memberships have to be configured explicitly.
+pub struct PermissionsFor {
+    users: HashSet<String>,
+    permissions: HashMap<PermissionType, HashSet<String>>,
+    teams: HashMap<String, HashSet<String>>,
+}
+
+impl PermissionsFor {
+    pub fn new(users: &[&str]) -> Self {
+        Self {
+            users: users.iter().map(|user| user.to_string()).collect(),
+            permissions: Default::default(),
+            teams: Default::default(),
+        }
+    }
+
+    /// Grants only the given permission to the selected users.
+    pub fn permission(mut self, permission: PermissionType, users: &[&str]) -> Self {
+        self.permissions
+            .entry(permission)
+            .or_default()
+            .extend(users.iter().map(|user| user.to_string()));
+        self
+    }
+
+    pub fn team(mut self, team: &str, members: &[&str]) -> Self {
+        self.teams.insert(
+            team.to_string(),
+            members.iter().map(|user| user.to_string()).collect(),
+        );
+        self
+    }
+}
+
+#[async_trait]
+impl PermissionResolver for PermissionsFor {
+    async fn has_permission(&self, username: &str, permission: PermissionType) -> bool {
+        self.users.contains(username)
+            || self
+                .permissions
+                .get(&permission)
+                .map(|users| users.contains(username))
+                .unwrap_or(false)
+    }
+
+    async fn is_team_member(&self, username: &str, team: &str) -> bool {
+        self.teams
+            .get(team)
+            .map(|members| members.contains(username))
+            .unwrap_or(false)
+    }
 }
@@ -4,31 +4,42 @@ use std::pin::Pin;
 use std::string::ToString;
 use std::time::Duration;
 
-use crate::config::RepositoryConfig;
+use crate::config::{
+    default_audit_log_retention, BuildStatusSource, PermissionedCommand, RepositoryConfig,
+};
 use axum::async_trait;
 use derive_builder::Builder;
-use octocrab::models::RunId;
+use octocrab::models::{RunId, UserId};
 
 use super::permissions::AllPermissions;
 use crate::bors::event::{
-    BorsEvent, CheckSuiteCompleted, PullRequestComment, WorkflowCompleted, WorkflowStarted,
+    BorsEvent, CheckSuiteCompleted, PullRequestComment, PullRequestOpened, WorkflowCompleted,
+    WorkflowStarted,
+};
+use crate::bors::{
+    handle_bors_event, BorsContext, BranchProtection, CheckSuite, CommandParser, RepositoryState,
 };
-use crate::bors::{handle_bors_event, BorsContext, CheckSuite, CommandParser, RepositoryState};
-use crate::bors::{BorsState, RepositoryClient};
-use crate::database::{DbClient, SeaORMClient, WorkflowStatus};
+use crate::bors::{
+    Artifact, BorsState, CheckRun, CheckSuiteStatus, CombinedStatus, CommitComparison,
+    CommitComparisonStatus, RepositoryClient, WorkflowRun,
+};
+use crate::database::{CheckRunStatus, DbClient, SeaORMClient, WorkflowStatus};
+use crate::github::api::rate_limit::RateLimit;
 use crate::github::{
-    CommitSha, GithubRepoName, GithubUser, LabelModification, LabelTrigger, PullRequest,
+    Branch, Commit, CommitSha, GithubRepoName, GithubUser, LabelModification, LabelTrigger,
+    PullRequest,
 };
-use crate::github::{MergeError, PullRequestNumber};
-use crate::permissions::PermissionResolver;
+use crate::github::{MergeError, PullRequestNumber, ResolveRefError};
+use crate::permissions::{PermissionResolver, PermissionType};
 use crate::tests::database::create_test_db;
 use crate::tests::event::{
     CheckSuiteCompletedBuilder, WorkflowCompletedBuilder, WorkflowStartedBuilder,
 };
-use crate::tests::github::PRBuilder;
+use crate::tests::github::{CommitBuilder, PRBuilder};
 
 pub fn test_bot_user() -> GithubUser {
     GithubUser {
+        id: UserId(1000),
         username: "<test-bot>".to_string(),
         html_url: "https://test-bors.bot.com".parse().unwrap(),
     }
@@ -50,7 +61,17 @@ pub struct TestBorsState {
 impl TestBorsState {
     /// Returns the default test client
     pub fn client(&mut self) -> &mut TestRepositoryClient {
-        &mut self.repos.get_mut(&default_repo_name()).unwrap().client
+        self.client_of(&default_repo_name())
+    }
+
+    /// Returns the test client of the given repository
+    pub fn client_of(&mut self, repo: &GithubRepoName) -> &mut TestRepositoryClient {
+        &mut self.repos.get_mut(repo).unwrap().client
+    }
+
+    /// Returns the state of the default test repository
+    pub fn repo(&mut self) -> &mut RepositoryState<TestRepositoryClient> {
+        self.repos.get_mut(&default_repo_name()).unwrap()
     }
 
     /// Execute an event.
@@ -68,6 +89,11 @@ impl TestBorsState {
         self.event(BorsEvent::Comment(comment.into())).await;
     }
 
+    pub async fn pull_request_opened<T: Into<PullRequestOpened>>(&mut self, payload: T) {
+        self.event(BorsEvent::PullRequestOpened(payload.into()))
+            .await;
+    }
+
     pub async fn workflow_started<T: Into<WorkflowStarted>>(&mut self, payload: T) {
         self.event(BorsEvent::WorkflowStarted(payload.into())).await;
     }
@@ -122,7 +148,7 @@ impl TestBorsState {
 
 impl BorsState<TestRepositoryClient> for TestBorsState {
     fn is_comment_internal(&self, comment: &PullRequestComment) -> bool {
-        comment.author == test_bot_user()
+        comment.author.id == test_bot_user().id
     }
 
     fn get_repo_state_mut(
@@ -149,6 +175,18 @@ impl BorsState<TestRepositoryClient> for TestBorsState {
         )
     }
 
+    fn remove_repository(&mut self, repo: &GithubRepoName) {
+        self.repos.remove(repo);
+    }
+
+    fn rename_repository(&mut self, old_name: &GithubRepoName, new_name: &GithubRepoName) {
+        if let Some(mut repo) = self.repos.remove(old_name) {
+            repo.repository = new_name.clone();
+            repo.client.name = new_name.clone();
+            self.repos.insert(new_name.clone(), repo);
+        }
+    }
+
     fn reload_repositories(&mut self) -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + '_>> {
         Box::pin(async move { Ok(()) })
     }
@@ -161,6 +199,68 @@ pub struct RepoConfig {
     timeout: Duration,
     #[builder(field(type = "HashMap<LabelTrigger, Vec<LabelModification>>"))]
     labels: HashMap<LabelTrigger, Vec<LabelModification>>,
+    #[builder(default = "1")]
+    required_approvals: usize,
+    #[builder(default)]
+    commands_in_pr_description: bool,
+    #[builder(default)]
+    comment_footer: Option<String>,
+    #[builder(default = "\"[bors skip]\".to_string()")]
+    skip_marker: String,
+    #[builder(default)]
+    rollup_suggestion_threshold: Option<usize>,
+    #[builder(default)]
+    max_queue_size: Option<usize>,
+    #[builder(default)]
+    notification_webhook: Option<String>,
+    #[builder(default = "3")]
+    max_consecutive_failures: usize,
+    #[builder(default)]
+    rerequest_review_on_dismissal: bool,
+    #[builder(default = "default_audit_log_retention()")]
+    audit_log_retention: Duration,
+    #[builder(default = "500")]
+    rate_limit_reserve: u64,
+    #[builder(default)]
+    ignored_bots: Vec<String>,
+    #[builder(default)]
+    build_status_source: BuildStatusSource,
+    #[builder(field(type = "HashMap<PermissionedCommand, PermissionType>"))]
+    command_permissions: HashMap<PermissionedCommand, PermissionType>,
+    #[builder(default)]
+    rebase_notice_threshold: Option<u64>,
+    #[builder(default = "\"S-do-not-merge\".to_string()")]
+    do_not_merge_label: String,
+    #[builder(
+        default = "\"@{user}: :key: Insufficient privileges: not in {permission} users\".to_string()"
+    )]
+    insufficient_privileges_message: String,
+    #[builder(default)]
+    merge_commit_message: Option<String>,
+    #[builder(default)]
+    round_robin_authors: bool,
+    #[builder(default)]
+    settle_delay: Duration,
+    #[builder(default)]
+    approval_expiry: Option<Duration>,
+    #[builder(default)]
+    try_deployment_environment: Option<String>,
+    #[builder(default)]
+    observed_workflows: Vec<String>,
+    #[builder(default)]
+    flaky_checks: Vec<String>,
+    #[builder(default)]
+    status_log: bool,
+    #[builder(default)]
+    merged_comment: bool,
+    #[builder(default)]
+    close_merged_prs: bool,
+    #[builder(default)]
+    skip_ci_paths: Vec<String>,
+    #[builder(default = "Duration::from_secs(300)")]
+    command_timeout: Duration,
+    #[builder(field(type = "HashMap<String, String>"))]
+    auto_branches: HashMap<String, String>,
 }
 
 impl RepoConfigBuilder {
@@ -180,9 +280,90 @@ impl RepoConfigBuilder {
         self
     }
 
+    pub fn command_permission(
+        mut self,
+        command: PermissionedCommand,
+        permission: PermissionType,
+    ) -> Self {
+        self.command_permissions.insert(command, permission);
+        self
+    }
+
+    pub fn auto_branch(mut self, base: &str, branch: &str) -> Self {
+        self.auto_branches
+            .insert(base.to_string(), branch.to_string());
+        self
+    }
+
     pub fn create(self) -> RepositoryConfig {
-        let RepoConfig { timeout, labels } = self.build().unwrap();
-        RepositoryConfig { timeout, labels }
+        let RepoConfig {
+            timeout,
+            labels,
+            required_approvals,
+            commands_in_pr_description,
+            comment_footer,
+            skip_marker,
+            rollup_suggestion_threshold,
+            max_queue_size,
+            notification_webhook,
+            max_consecutive_failures,
+            rerequest_review_on_dismissal,
+            audit_log_retention,
+            rate_limit_reserve,
+            ignored_bots,
+            build_status_source,
+            command_permissions,
+            rebase_notice_threshold,
+            do_not_merge_label,
+            insufficient_privileges_message,
+            merge_commit_message,
+            round_robin_authors,
+            settle_delay,
+            approval_expiry,
+            try_deployment_environment,
+            observed_workflows,
+            flaky_checks,
+            status_log,
+            merged_comment,
+            close_merged_prs,
+            skip_ci_paths,
+            command_timeout,
+            auto_branches,
+        } = self.build().unwrap();
+        RepositoryConfig {
+            timeout,
+            labels,
+            required_approvals,
+            commands_in_pr_description,
+            comment_footer,
+            skip_marker,
+            rollup_suggestion_threshold,
+            max_queue_size,
+            notification_webhook,
+            max_consecutive_failures,
+            rerequest_review_on_dismissal,
+            audit_log_retention,
+            rate_limit_reserve,
+            ignored_bots,
+            build_status_source,
+            command_permissions,
+            rebase_notice_threshold,
+            do_not_merge_label,
+            insufficient_privileges_message,
+            merge_commit_message,
+            round_robin_authors,
+            settle_delay,
+            approval_expiry,
+            try_deployment_environment,
+            observed_workflows,
+            flaky_checks,
+            status_log,
+            merged_comment,
+            close_merged_prs,
+            skip_ci_paths,
+            command_timeout,
+            auto_branches,
+        }
     }
 }
 
@@ -209,22 +390,45 @@ impl ClientBuilder {
         } = self.build().unwrap();
 
         let name = name.unwrap_or_else(default_repo_name);
-        RepositoryState {
-            repository: name.clone(),
-            client: TestRepositoryClient {
+        RepositoryState::new(
+            name.clone(),
+            TestRepositoryClient {
                 comments: Default::default(),
+                comment_locations: Default::default(),
                 name,
                 merge_branches_fn: Box::new(|| Ok(CommitSha(default_merge_sha()))),
+                merge_commit_messages: Default::default(),
+                stall_merges: false,
+                stall_comparisons: false,
+                fail_check_runs: false,
                 get_pr_fn: Box::new(move |pr| Ok(PRBuilder::default().number(pr.0).create())),
                 check_suites: Default::default(),
+                workflows: Default::default(),
                 cancelled_workflows: Default::default(),
+                dispatched_workflows: Default::default(),
                 added_labels: Default::default(),
+                requested_reviewers: Default::default(),
+                requested_team_reviewers: Default::default(),
+                closed_prs: Default::default(),
+                pr_files: Default::default(),
                 removed_labels: Default::default(),
                 branch_history: Default::default(),
+                deleted_branches: Default::default(),
+                branch_protections: Default::default(),
+                collaborators: Default::default(),
+                commits: Default::default(),
+                workflow_runs: Default::default(),
+                comparisons: Default::default(),
+                rate_limit: None,
+                combined_statuses: Default::default(),
+                artifacts: Default::default(),
+                check_runs: Default::default(),
+                ref_check_runs: Default::default(),
+                deployments: Default::default(),
             },
-            permissions_resolver: permission_resolver,
-            config: config.create(),
-        }
+            permission_resolver,
+            config.create(),
+        )
     }
 }
 
@@ -242,14 +446,50 @@ impl RepositoryState<TestRepositoryClient> {
 pub struct TestRepositoryClient {
     pub name: GithubRepoName,
     comments: HashMap<u64, Vec<String>>,
+    // Comment ID - 1 -> (PR number, index of the comment in the PR)
+    comment_locations: Vec<(u64, usize)>,
     pub merge_branches_fn: Box<dyn Fn() -> Result<CommitSha, MergeError> + Send>,
+    /// Commit messages of all merges performed by `merge_branches`.
+    pub merge_commit_messages: Vec<String>,
+    /// Makes `merge_branches` wait forever, like a request that GitHub never answers.
+    pub stall_merges: bool,
+    pub stall_comparisons: bool,
+    /// Makes `create_or_update_check_run` fail, like with a personal access token.
+    pub fail_check_runs: bool,
     pub get_pr_fn: Box<dyn Fn(PullRequestNumber) -> anyhow::Result<PullRequest> + Send>,
     pub check_suites: HashMap<String, Vec<CheckSuite>>,
+    pub workflows: Vec<String>,
     pub cancelled_workflows: HashSet<u64>,
+    pub dispatched_workflows: Vec<u64>,
     added_labels: HashMap<u64, Vec<String>>,
     removed_labels: HashMap<u64, Vec<String>>,
+    requested_reviewers: HashMap<u64, Vec<String>>,
+    requested_team_reviewers: HashMap<u64, Vec<String>>,
+    closed_prs: HashSet<u64>,
+    // PR number -> changed files
+    pr_files: HashMap<u64, Vec<String>>,
     // Branch name -> history of SHAs
     branch_history: HashMap<String, Vec<CommitSha>>,
+    deleted_branches: HashSet<String>,
+    // Branch name -> required checks
+    branch_protections: HashMap<String, Vec<String>>,
+    pub collaborators: HashSet<String>,
+    // Commit SHA -> commit
+    commits: HashMap<String, Commit>,
+    // Commit SHA -> workflow runs
+    workflow_runs: HashMap<String, Vec<WorkflowRun>>,
+    // (base, head) -> comparison
+    comparisons: HashMap<(String, String), CommitComparison>,
+    pub rate_limit: Option<RateLimit>,
+    // Commit SHA -> combined legacy status
+    combined_statuses: HashMap<String, CombinedStatus>,
+    artifacts: HashMap<u64, Vec<Artifact>>,
+    // Commit SHA -> history of reported check run statuses
+    check_runs: HashMap<String, Vec<CheckRunStatus>>,
+    // Ref -> check runs of all checks
+    ref_check_runs: HashMap<String, Vec<CheckRun>>,
+    // (commit SHA, environment, history of reported statuses) of created deployments
+    deployments: Vec<(String, String, Vec<CheckRunStatus>)>,
 }
 
 impl TestRepositoryClient {
@@ -272,7 +512,103 @@ impl TestRepositoryClient {
             .insert(commit.to_string(), checks.to_vec());
     }
 
+    pub fn set_combined_status(&mut self, commit: &str, status: CombinedStatus) {
+        self.combined_statuses.insert(commit.to_string(), status);
+    }
+
+    /// Sets the artifacts of a workflow run, as pairs of names and links.
+    pub fn set_artifacts(&mut self, run_id: u64, artifacts: &[(&str, &str)]) {
+        self.artifacts.insert(
+            run_id,
+            artifacts
+                .iter()
+                .map(|(name, url)| Artifact {
+                    name: name.to_string(),
+                    url: url.to_string(),
+                })
+                .collect(),
+        );
+    }
+
+    pub fn set_branch(&mut self, branch: &str, sha: &str) {
+        self.add_branch_sha(branch, sha);
+    }
+
+    pub fn set_branch_protection(&mut self, branch: &str, required_checks: &[&str]) {
+        self.branch_protections.insert(
+            branch.to_string(),
+            required_checks.iter().map(|s| s.to_string()).collect(),
+        );
+    }
+
+    pub fn set_check_runs(&mut self, reference: &str, runs: &[(&str, CheckSuiteStatus)]) {
+        self.ref_check_runs.insert(
+            reference.to_string(),
+            runs.iter()
+                .map(|&(name, status)| CheckRun {
+                    name: name.to_string(),
+                    status,
+                })
+                .collect(),
+        );
+    }
+
+    pub fn set_workflow_runs(&mut self, commit: &str, runs: &[(u64, WorkflowStatus)]) {
+        self.workflow_runs.insert(
+            commit.to_string(),
+            runs.iter()
+                .map(|&(run_id, status)| WorkflowRun {
+                    run_id: RunId(run_id),
+                    status,
+                })
+                .collect(),
+        );
+    }
+
+    pub fn set_comparison(
+        &mut self,
+        base: &str,
+        head: &str,
+        status: CommitComparisonStatus,
+        ahead_by: u64,
+        behind_by: u64,
+    ) {
+        self.comparisons.insert(
+            (base.to_string(), head.to_string()),
+            CommitComparison {
+                status,
+                ahead_by,
+                behind_by,
+            },
+        );
+    }
+
+    pub fn add_commit(&mut self, commit: Commit) {
+        self.commits.insert(commit.sha.0.clone(), commit);
+    }
+
     // Checks
+    pub fn check_check_runs(&self, sha: &str, statuses: &[CheckRunStatus]) {
+        assert_eq!(
+            self.check_runs.get(sha).cloned().unwrap_or_default(),
+            statuses
+        );
+    }
+
+    pub fn check_deployments(&self, deployments: &[(&str, &str, &[CheckRunStatus])]) {
+        assert_eq!(
+            self.deployments,
+            deployments
+                .iter()
+                .map(|(sha, environment, statuses)| (
+                    sha.to_string(),
+                    environment.to_string(),
+                    statuses.to_vec()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
     pub fn check_comments(&self, pr_number: u64, comments: &[&str]) {
         assert_eq!(
             self.comments.get(&pr_number).cloned().unwrap_or_default(),
@@ -302,6 +638,38 @@ impl TestRepositoryClient {
         self
     }
 
+    pub fn check_requested_reviewers(&self, pr: u64, reviewers: &[&str]) -> &Self {
+        assert_eq!(
+            self.requested_reviewers
+                .get(&pr)
+                .cloned()
+                .unwrap_or_default(),
+            reviewers
+        );
+        self
+    }
+
+    pub fn check_requested_team_reviewers(&self, pr: u64, teams: &[&str]) -> &Self {
+        assert_eq!(
+            self.requested_team_reviewers
+                .get(&pr)
+                .cloned()
+                .unwrap_or_default(),
+            teams
+        );
+        self
+    }
+
+    pub fn set_pr_files(&mut self, pr: u64, files: &[&str]) {
+        self.pr_files
+            .insert(pr, files.iter().map(|file| file.to_string()).collect());
+    }
+
+    pub fn check_pr_closed(&self, pr: u64, closed: bool) -> &Self {
+        assert_eq!(self.closed_prs.contains(&pr), closed);
+        self
+    }
+
     pub fn check_cancelled_workflows(&self, cancelled: &[u64]) {
         let set = cancelled.iter().copied().collect::<HashSet<_>>();
         assert_eq!(self.cancelled_workflows, set);
@@ -314,13 +682,30 @@ impl TestRepositoryClient {
             .unwrap_or_else(|| panic!("Branch {branch} not found"));
         assert_eq!(
             history,
-            &sha.into_iter()
+            &sha.iter()
                 .map(|s| CommitSha(s.to_string()))
                 .collect::<Vec<_>>()
         );
     }
 
+    pub fn check_branch_deleted(&self, branch: &str) -> &Self {
+        assert!(
+            self.deleted_branches.contains(branch),
+            "Branch {branch} was not deleted"
+        );
+        self
+    }
+
+    pub fn check_branch_not_created(&self, branch: &str) -> &Self {
+        assert!(
+            !self.branch_history.contains_key(branch),
+            "Branch {branch} was created"
+        );
+        self
+    }
+
     fn add_branch_sha(&mut self, branch: &str, sha: &str) {
+        self.deleted_branches.remove(branch);
         self.branch_history
             .entry(branch.to_string())
             .or_default()
@@ -334,8 +719,25 @@ impl RepositoryClient for TestRepositoryClient {
         &self.name
     }
 
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit
+    }
+
+    async fn get_rate_limit(&mut self) -> anyhow::Result<RateLimit> {
+        Ok(self.rate_limit.unwrap_or(RateLimit {
+            remaining: 5000,
+            limit: 5000,
+        }))
+    }
+
     async fn get_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<PullRequest> {
-        (self.get_pr_fn)(pr)
+        let mut pull_request = (self.get_pr_fn)(pr)?;
+        pull_request.closed |= self.closed_prs.contains(&pr.0);
+        Ok(pull_request)
+    }
+
+    async fn list_pr_files(&mut self, pr: PullRequestNumber) -> anyhow::Result<Vec<String>> {
+        Ok(self.pr_files.get(&pr.0).cloned().unwrap_or_default())
     }
 
     async fn post_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<()> {
@@ -346,17 +748,76 @@ impl RepositoryClient for TestRepositoryClient {
         Ok(())
     }
 
+    async fn create_comment(&mut self, pr: PullRequestNumber, text: &str) -> anyhow::Result<u64> {
+        let comments = self.comments.entry(pr.0).or_default();
+        comments.push(text.to_string());
+        self.comment_locations.push((pr.0, comments.len() - 1));
+        Ok(self.comment_locations.len() as u64)
+    }
+
+    async fn get_comment_text(&mut self, comment_id: u64) -> anyhow::Result<String> {
+        let (pr, index) = self
+            .comment_locations
+            .get(comment_id as usize - 1)
+            .ok_or_else(|| anyhow::anyhow!("Comment {comment_id} does not exist"))?;
+        Ok(self.comments[pr][*index].clone())
+    }
+
+    async fn edit_comment(&mut self, comment_id: u64, text: &str) -> anyhow::Result<()> {
+        let (pr, index) = self
+            .comment_locations
+            .get(comment_id as usize - 1)
+            .ok_or_else(|| anyhow::anyhow!("Comment {comment_id} does not exist"))?;
+        self.comments.get_mut(pr).unwrap()[*index] = text.to_string();
+        Ok(())
+    }
+
+    async fn get_branch_sha(&mut self, branch: &str) -> anyhow::Result<Option<CommitSha>> {
+        if self.deleted_branches.contains(branch) {
+            return Ok(None);
+        }
+        Ok(self
+            .branch_history
+            .get(branch)
+            .and_then(|history| history.last().cloned()))
+    }
+
+    async fn list_branches(&mut self, prefix: &str) -> anyhow::Result<Vec<Branch>> {
+        let mut branches = self
+            .branch_history
+            .iter()
+            .filter(|(name, _)| name.starts_with(prefix) && !self.deleted_branches.contains(*name))
+            .filter_map(|(name, history)| {
+                Some(Branch {
+                    name: name.clone(),
+                    sha: history.last()?.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        branches.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(branches)
+    }
+
     async fn set_branch_to_sha(&mut self, branch: &str, sha: &CommitSha) -> anyhow::Result<()> {
         self.add_branch_sha(branch, &sha.0);
         Ok(())
     }
 
+    async fn delete_branch(&mut self, branch: &str) -> anyhow::Result<()> {
+        self.deleted_branches.insert(branch.to_string());
+        Ok(())
+    }
+
     async fn merge_branches(
         &mut self,
         base: &str,
         _head: &CommitSha,
-        _commit_message: &str,
+        commit_message: &str,
     ) -> Result<CommitSha, MergeError> {
+        self.merge_commit_messages.push(commit_message.to_string());
+        if self.stall_merges {
+            std::future::pending::<()>().await;
+        }
         let res = (self.merge_branches_fn)();
         if let Ok(ref sha) = res {
             self.add_branch_sha(base, &sha.0);
@@ -372,6 +833,89 @@ impl RepositoryClient for TestRepositoryClient {
         Ok(self.check_suites.get(&sha.0).cloned().unwrap_or_default())
     }
 
+    async fn get_commit(&mut self, sha: &CommitSha) -> anyhow::Result<Commit> {
+        // Commits that were not added explicitly have no message and no parents
+        Ok(self
+            .commits
+            .get(&sha.0)
+            .cloned()
+            .unwrap_or_else(|| CommitBuilder::default().sha(sha.0.clone()).create()))
+    }
+
+    async fn resolve_ref(&mut self, short_sha: &str) -> Result<CommitSha, ResolveRefError> {
+        let matching = self
+            .commits
+            .keys()
+            .filter(|sha| sha.starts_with(short_sha))
+            .collect::<Vec<_>>();
+        match matching.as_slice() {
+            [] => Err(ResolveRefError::NotFound),
+            [sha] => Ok(CommitSha(sha.to_string())),
+            _ => Err(ResolveRefError::Ambiguous),
+        }
+    }
+
+    async fn get_check_runs_for_ref(&mut self, reference: &str) -> anyhow::Result<Vec<CheckRun>> {
+        Ok(self
+            .ref_check_runs
+            .get(reference)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn get_workflow_runs_for_commit(
+        &mut self,
+        _branch: &str,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Vec<WorkflowRun>> {
+        Ok(self.workflow_runs.get(&sha.0).cloned().unwrap_or_default())
+    }
+
+    async fn compare_commits(
+        &mut self,
+        base: &str,
+        head: &str,
+    ) -> anyhow::Result<CommitComparison> {
+        if self.stall_comparisons {
+            std::future::pending::<()>().await;
+        }
+        // Unless specified otherwise, the head is one commit ahead of the base
+        Ok(self
+            .comparisons
+            .get(&(base.to_string(), head.to_string()))
+            .cloned()
+            .unwrap_or(CommitComparison {
+                status: CommitComparisonStatus::Ahead,
+                ahead_by: 1,
+                behind_by: 0,
+            }))
+    }
+
+    async fn is_collaborator(&mut self, username: &str) -> anyhow::Result<bool> {
+        Ok(self.collaborators.contains(username))
+    }
+
+    async fn get_branch_protection(
+        &mut self,
+        branch: &str,
+    ) -> anyhow::Result<Option<BranchProtection>> {
+        Ok(self
+            .branch_protections
+            .get(branch)
+            .map(|required_checks| BranchProtection {
+                required_checks: required_checks.clone(),
+            }))
+    }
+
+    async fn get_workflows(&mut self) -> anyhow::Result<Vec<String>> {
+        Ok(self.workflows.clone())
+    }
+
+    async fn dispatch_workflow(&mut self, run_id: RunId) -> anyhow::Result<()> {
+        self.dispatched_workflows.push(run_id.0);
+        Ok(())
+    }
+
     async fn cancel_workflows(&mut self, run_ids: Vec<RunId>) -> anyhow::Result<()> {
         self.cancelled_workflows
             .extend(run_ids.into_iter().map(|id| id.0));
@@ -397,4 +941,75 @@ impl RepositoryClient for TestRepositoryClient {
             .extend(labels.to_vec());
         Ok(())
     }
+
+    async fn request_reviewers(
+        &mut self,
+        pr: PullRequestNumber,
+        reviewers: &[String],
+        team_reviewers: &[String],
+    ) -> anyhow::Result<()> {
+        self.requested_reviewers
+            .entry(pr.0)
+            .or_default()
+            .extend(reviewers.to_vec());
+        self.requested_team_reviewers
+            .entry(pr.0)
+            .or_default()
+            .extend(team_reviewers.to_vec());
+        Ok(())
+    }
+
+    async fn close_pull_request(&mut self, pr: PullRequestNumber) -> anyhow::Result<()> {
+        self.closed_prs.insert(pr.0);
+        Ok(())
+    }
+
+    async fn get_combined_status(
+        &mut self,
+        sha: &CommitSha,
+    ) -> anyhow::Result<Option<CombinedStatus>> {
+        Ok(self.combined_statuses.get(&sha.0).copied())
+    }
+
+    async fn list_artifacts(&mut self, run_id: RunId) -> anyhow::Result<Vec<Artifact>> {
+        Ok(self.artifacts.get(&run_id.0).cloned().unwrap_or_default())
+    }
+
+    async fn create_or_update_check_run(
+        &mut self,
+        sha: &CommitSha,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        if self.fail_check_runs {
+            return Err(anyhow::anyhow!("Check runs cannot be created"));
+        }
+        self.check_runs
+            .entry(sha.0.clone())
+            .or_default()
+            .push(status);
+        Ok(())
+    }
+
+    async fn create_deployment(
+        &mut self,
+        sha: &CommitSha,
+        environment: &str,
+    ) -> anyhow::Result<u64> {
+        self.deployments
+            .push((sha.0.clone(), environment.to_string(), vec![]));
+        Ok(self.deployments.len() as u64)
+    }
+
+    async fn update_deployment_status(
+        &mut self,
+        deployment_id: u64,
+        status: CheckRunStatus,
+    ) -> anyhow::Result<()> {
+        let (_, _, statuses) = self
+            .deployments
+            .get_mut(deployment_id as usize - 1)
+            .ok_or_else(|| anyhow::anyhow!("Deployment {deployment_id} does not exist"))?;
+        statuses.push(status);
+        Ok(())
+    }
 }
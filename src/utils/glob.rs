@@ -0,0 +1,72 @@
+/// Checks if a slash-separated path matches the given glob pattern.
+///
+/// `*` matches any part of a single path segment, `?` matches a single character and a `**`
+/// segment matches any number of segments. Patterns are anchored to the root of the repository,
+/// so `*.md` only matches files in the root directory, while `**/*.md` matches them everywhere.
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<_>>();
+    let path = path.split('/').collect::<Vec<_>>();
+    matches_segments(&pattern, &path)
+}
+
+fn matches_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => {
+            (0..=path.len()).any(|skipped| matches_segments(rest, &path[skipped..]))
+        }
+        Some((segment, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                let segment = segment.chars().collect::<Vec<_>>();
+                let name = name.chars().collect::<Vec<_>>();
+                matches_segment(&segment, &name) && matches_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_segment(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => {
+            (0..=name.len()).any(|skipped| matches_segment(rest, &name[skipped..]))
+        }
+        Some(('?', rest)) => !name.is_empty() && matches_segment(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && matches_segment(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::utils::glob::glob_matches;
+
+    #[test]
+    fn match_literal_path() {
+        assert!(glob_matches("README.md", "README.md"));
+        assert!(!glob_matches("README.md", "docs/README.md"));
+    }
+
+    #[test]
+    fn match_star_within_segment() {
+        assert!(glob_matches("*.md", "CHANGELOG.md"));
+        assert!(glob_matches("docs/*.md", "docs/guide.md"));
+        assert!(!glob_matches("*.md", "docs/guide.md"));
+        assert!(!glob_matches("docs/*.md", "docs/guide.txt"));
+    }
+
+    #[test]
+    fn match_question_mark() {
+        assert!(glob_matches("v?.txt", "v1.txt"));
+        assert!(!glob_matches("v?.txt", "v10.txt"));
+    }
+
+    #[test]
+    fn match_double_star() {
+        assert!(glob_matches("docs/**", "docs/guide.md"));
+        assert!(glob_matches("docs/**", "docs/book/src/intro.md"));
+        assert!(glob_matches("**/*.md", "README.md"));
+        assert!(glob_matches("**/*.md", "src/bors/README.md"));
+        assert!(!glob_matches("docs/**", "src/docs/guide.md"));
+    }
+}